@@ -0,0 +1,518 @@
+use super::DEPTH_LIMIT;
+use super::EPSILON;
+use super::MAX_HIT_DISTANCE;
+use crate::camera;
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::MaterialType;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use crate::stats;
+use crate::stats::Counter;
+use glm::Vec3;
+use image::RgbImage;
+use rayon::prelude::*;
+
+/// Auxiliary feature buffers (AOVs) which are rendered alongside the color
+/// and used to guide denoisers.
+#[derive(Clone, Copy)]
+pub struct Features {
+    /// Base color of the first non-specular surface
+    pub albedo: Vec3,
+    /// Shading normal of the first non-specular surface
+    pub normal: Vec3,
+    /// Distance along the camera ray to the first surface of any kind,
+    /// infinite if the ray escapes
+    pub depth: f32,
+    /// 1 if the first non-specular surface, or the sky, was reached through
+    /// a mirror or glass, and 0 if seen directly. Averaged over a pixel's
+    /// samples it gives the fraction seen that way. Reflections and
+    /// refractions are sharp detail which the albedo and normal guides,
+    /// taken on the surface behind, can't explain, so denoisers should
+    /// smooth pixels with a high value gently.
+    pub caustic: f32,
+}
+
+impl Features {
+    /// Features with every channel set to zero and an infinite depth, for
+    /// accumulating samples.
+    pub fn zero() -> Features {
+        Features {
+            albedo: glm::vec3(0.0, 0.0, 0.0),
+            normal: glm::vec3(0.0, 0.0, 0.0),
+            depth: f32::INFINITY,
+            caustic: 0.0,
+        }
+    }
+
+    /// Add another sample's features to this one. Depths aren't summed but
+    /// keep the nearest, since averaging a foreground and background depth
+    /// at an edge gives a depth where there is no surface.
+    pub fn accumulate(&mut self, other: &Features) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth = self.depth.min(other.depth);
+        self.caustic += other.caustic;
+    }
+
+    /// Average the accumulated features over the number of samples taken.
+    pub fn average(&self, sample_count: usize) -> Features {
+        Features {
+            albedo: self.albedo / sample_count as f32,
+            normal: self.normal / sample_count as f32,
+            depth: self.depth,
+            caustic: self.caustic / sample_count as f32,
+        }
+    }
+}
+
+/// Follow a camera ray to the first non-specular surface it sees and record
+/// that surface's albedo and normal. Mirrors are followed along their
+/// reflection, and glass is treated as see-through so the surface behind it
+/// is recorded, which is what denoisers expect of their guide images. Rays
+/// that escape record the sky color as albedo and a zero normal. The depth
+/// is taken at the first surface hit, mirror or glass included, and the
+/// caustic flag is set if a mirror or glass was followed on the way.
+///
+/// # Arguments
+/// - `ray: &Ray` - the camera ray
+/// - `world: &T` - objects that compose our scene
+/// - `sky: &Sky` - background of the scene
+///
+/// # Returns
+/// - `Features` - the albedo, normal and depth seen along the ray
+pub fn trace_features<T: Hittable>(ray: &Ray, world: &T, sky: &Sky) -> Features {
+    let mut ray = *ray;
+    let mut depth = f32::INFINITY;
+    for _ in 0..DEPTH_LIMIT {
+        let hit = match world.hit(&ray, EPSILON, MAX_HIT_DISTANCE) {
+            Some(hit) => hit,
+            None => {
+                return Features {
+                    albedo: sky.color(&ray),
+                    normal: glm::vec3(0.0, 0.0, 0.0),
+                    depth,
+                    caustic: caustic_weight(&ray),
+                }
+            }
+        };
+        if depth.is_infinite() {
+            depth = hit.distance;
+        }
+        match hit.material {
+            Some(MaterialType::Metal(_)) => {
+                let reflected = glm::reflect_vec(&ray.direction, &hit.normal());
+                ray = ray
+                    .continue_path(hit.hit_point, reflected, None)
+                    .after_specular();
+            }
+            Some(MaterialType::Transparent(_)) | Some(MaterialType::Water(_)) => {
+                ray = ray
+                    .continue_path(hit.hit_point, ray.direction, None)
+                    .after_specular();
+            }
+            Some(MaterialType::ShadowCatcher(_)) => {
+                ray = ray.continue_path(hit.hit_point, ray.direction, None);
+            }
+            Some(material) => {
+                return Features {
+                    albedo: material.color_at(&hit),
+                    normal: hit.normal(),
+                    depth,
+                    caustic: caustic_weight(&ray),
+                }
+            }
+            None => {
+                return Features {
+                    depth,
+                    caustic: caustic_weight(&ray),
+                    ..Features::zero()
+                }
+            }
+        }
+    }
+    Features {
+        depth,
+        caustic: caustic_weight(&ray),
+        ..Features::zero()
+    }
+}
+
+/// Turn a ray's caustic flag into a guide value that averages over samples.
+///
+/// # Arguments
+/// - `ray: &Ray` - the last ray of a path
+///
+/// # Returns
+/// - `f32` - 1 if the path went through a mirror or glass, 0 otherwise
+fn caustic_weight(ray: &Ray) -> f32 {
+    if ray.caustic {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Find the direct light on the first surface a camera ray hits, for seeing
+/// where each light reaches. Every surface is shaded as white and diffuse,
+/// with the same light sampling and shadow rays as Lambertian materials,
+/// and nothing bounces, so indirect light and the materials' colors are
+/// left out. Rays that escape see black.
+///
+/// # Arguments
+/// - `ray: &Ray` - the camera ray
+/// - `world: &T` - objects that compose our scene
+/// - `lights: &[Light]` - light sources for the scene
+/// - `sky: &Sky` - background of the scene, whose sun may light the surface
+/// - `context: &ShadingContext` - how many shadow rays to cast per light
+/// - `min_distance: f32` - closest distance along the ray to accept a hit
+/// - `max_distance: f32` - furthest distance along the ray to accept a hit
+///
+/// # Returns
+/// - `Vec3` - the direct light reflected toward the camera
+pub fn trace_direct_lighting<T: Hittable>(
+    ray: &Ray,
+    world: &T,
+    lights: &[Light],
+    sky: &Sky,
+    context: &ShadingContext,
+    min_distance: f32,
+    max_distance: f32,
+) -> Vec3 {
+    let hit = match world.hit(ray, min_distance, max_distance) {
+        Some(hit) => hit,
+        None => return glm::vec3(0.0, 0.0, 0.0),
+    };
+    let irradiance = lights
+        .iter()
+        .fold(glm::vec3(0.0, 0.0, 0.0), |total, light| {
+            total + light.irradiance(&hit, world, context.light_samples_per_hit)
+        })
+        + sky.direct_irradiance(&hit, world);
+    irradiance / std::f32::consts::PI
+}
+
+/// Find the light from the sky on the first surface a camera ray hits,
+/// like ambient occlusion lit by the sky, for quick sky-lit previews. Every
+/// surface is shaded as white and diffuse by one cosine distributed ray,
+/// which sees the sky's color if it escapes and black if anything blocks
+/// it, and nothing else bounces. A daylight sky's sun is sampled directly
+/// as well, as for Lambertian materials, and the lights are left out. Rays
+/// that escape see the sky.
+///
+/// # Arguments
+/// - `ray: &Ray` - the camera ray
+/// - `world: &T` - objects that compose our scene
+/// - `sky: &Sky` - background of the scene, which lights the surface
+/// - `min_distance: f32` - closest distance along the ray to accept a hit
+/// - `max_distance: f32` - furthest distance along the ray to accept a hit
+///
+/// # Returns
+/// - `Vec3` - the sky light reflected toward the camera
+pub fn trace_sky_occlusion<T: Hittable>(
+    ray: &Ray,
+    world: &T,
+    sky: &Sky,
+    min_distance: f32,
+    max_distance: f32,
+) -> Vec3 {
+    let hit = match world.hit(ray, min_distance, max_distance) {
+        Some(hit) => hit,
+        None => return sky.color(ray),
+    };
+    let normal = hit.normal();
+    let mut direction = normal + Lambertian::random_direction();
+    if glm::length2(&direction) < EPSILON * EPSILON {
+        direction = normal;
+    }
+    let sky_ray = Ray::new(hit.hit_point, direction, None)
+        .with_time(ray.time)
+        .with_scatter_pdf(glm::normalize_dot(&normal, &direction).max(0.0) / std::f32::consts::PI);
+    stats::record(Counter::ShadowRays);
+    // a white diffuse surface reflects the sky seen by a cosine distributed
+    // ray as it is, since the cosine and the density cancel
+    let sky_light = if world.any_hit(&sky_ray, EPSILON, MAX_HIT_DISTANCE) {
+        glm::vec3(0.0, 0.0, 0.0)
+    } else {
+        sky.color(&sky_ray)
+    };
+    sky_light + sky.direct_irradiance(&hit, world) / std::f32::consts::PI
+}
+
+/// Follow a camera ray to find how opaque the image is along it, for
+/// compositing the image over another picture. Every surface is opaque
+/// except shadow catchers, which the ray passes through, each adding the
+/// shadow falling on it. A ray which gets through them to the background,
+/// even if the shadows darken it completely, sees the picture beneath, so
+/// the renderer leaves its color black and only the shadows in the alpha.
+///
+/// # Arguments
+/// - `ray: &Ray` - the camera ray
+/// - `world: &T` - objects that compose our scene
+/// - `lights: &[Light]` - light sources for the scene
+/// - `context: &ShadingContext` - how many shadow rays to cast per light
+/// - `min_distance: f32` - closest distance along the ray to accept a hit
+/// - `max_distance: f32` - furthest distance along the ray to accept a hit
+///
+/// # Returns
+/// - `(f32, bool)` - the opacity, from 0 for the bare background to 1, and
+///   whether the ray reached the background
+pub fn trace_alpha<T: Hittable>(
+    ray: &Ray,
+    world: &T,
+    lights: &[Light],
+    context: &ShadingContext,
+    min_distance: f32,
+    max_distance: f32,
+) -> (f32, bool) {
+    let mut ray = *ray;
+    let (mut min_distance, mut max_distance) = (min_distance, max_distance);
+    let mut transparency = 1.0;
+    for _ in 0..DEPTH_LIMIT {
+        let hit = match world.hit(&ray, min_distance, max_distance) {
+            Some(hit) => hit,
+            None => return (1.0 - transparency, true),
+        };
+        match hit.material {
+            Some(MaterialType::ShadowCatcher(catcher)) => {
+                transparency *=
+                    1.0 - catcher.shadow(world, lights, context.light_samples_per_hit, &hit);
+                ray = Ray::new(hit.hit_point, ray.direction, None).with_time(ray.time);
+                min_distance = EPSILON;
+                max_distance = MAX_HIT_DISTANCE;
+            }
+            _ => return (1.0, false),
+        }
+    }
+    (1.0, false)
+}
+
+/// Convert an averaged normal into an 8 bit RGB pixel, mapping each
+/// component from [-1, 1] to [0, 255].
+///
+/// # Arguments
+/// - `normal: &Vec3` - the normal to convert
+pub fn normal_to_rgb(normal: &Vec3) -> image::Rgb<u8> {
+    let mapped = glm::clamp(&(normal * 0.5 + glm::vec3(0.5, 0.5, 0.5)), 0.0, 1.0) * 255.0;
+    image::Rgb([mapped.x as u8, mapped.y as u8, mapped.z as u8])
+}
+
+/// Render the ID of the first object seen through the center of each pixel,
+/// with each ID hashed to its own color and black where nothing is hit.
+/// Pixels aren't anti-aliased, so every pixel belongs to exactly one object
+/// and a mask of any object can be keyed out of the image by its color.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &C` - camera to generate rays from
+/// - `image_width: u32` - width of the image in pixels
+/// - `image_height: u32` - height of the image in pixels
+/// - `min_distance: f32` - closest distance along a camera ray to accept a hit
+/// - `max_distance: f32` - furthest distance along a camera ray to accept a hit
+///
+/// # Returns
+/// - `RgbImage` - the object ID image
+pub fn render_object_ids<T: Hittable + Sync, C: Camera + Sync>(
+    world: &T,
+    camera: &C,
+    image_width: u32,
+    image_height: u32,
+    min_distance: f32,
+    max_distance: f32,
+) -> RgbImage {
+    // the rays of a row are close together, so they are traced as packets
+    let rows: Vec<Vec<image::Rgb<u8>>> = (0..image_height)
+        .into_par_iter()
+        .map(|y| {
+            let rays: Vec<Ray> = (0..image_width)
+                .map(|x| {
+                    let (u, v) =
+                        camera::pixel_to_camera(x, y, (0.5, 0.5), image_width, image_height);
+                    camera.get_ray(u, v)
+                })
+                .collect();
+            world
+                .hit_packet(&rays, min_distance, max_distance)
+                .into_iter()
+                .map(|hit| object_id_to_rgb(hit.and_then(|hit| hit.object_id)))
+                .collect()
+        })
+        .collect();
+    let mut img = RgbImage::new(image_width, image_height);
+    for (pixel, rgb) in img.pixels_mut().zip(rows.into_iter().flatten()) {
+        *pixel = rgb;
+    }
+    img
+}
+
+/// Hash an object ID to a color. Neighboring IDs get unrelated colors, and
+/// every channel is kept away from zero so no object is mistaken for the
+/// black background.
+///
+/// # Arguments
+/// - `object_id: Option<usize>` - the ID, or `None` where nothing was hit
+///
+/// # Returns
+/// - `image::Rgb<u8>` - the color for the ID
+pub fn object_id_to_rgb(object_id: Option<usize>) -> image::Rgb<u8> {
+    let object_id = match object_id {
+        Some(object_id) => object_id as u64,
+        None => return image::Rgb([0, 0, 0]),
+    };
+    // the finalizer of the 64 bit MurmurHash3
+    let mut hash = object_id.wrapping_add(1);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    let channel = |shift: u64| 32 + ((hash >> shift) & 0xff) as u8 % 224;
+    image::Rgb([channel(0), channel(8), channel(16)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::perspective_camera::PerspectiveCamera;
+    use crate::hittable::hittable_list::HittableList;
+
+    /// Gray diffuse material for test objects.
+    fn gray() -> MaterialType {
+        MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        })
+    }
+
+    /// Camera at the origin looking down -z, with a square image.
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, -1.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            60.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn two_spheres_get_distinct_object_ids() {
+        let mut world = HittableList::new();
+        world.add_sphere(glm::vec3(-1.0, 0.0, -4.0), 0.8, gray());
+        world.add_sphere(glm::vec3(1.0, 0.0, -4.0), 0.8, gray());
+        let (width, height) = (32, 32);
+        let image = render_object_ids(&world, &camera(), width, height, EPSILON, MAX_HIT_DISTANCE);
+        let left = *image.get_pixel(width / 2 - 7, height / 2);
+        let right = *image.get_pixel(width / 2 + 7, height / 2);
+        let background = image::Rgb([0, 0, 0]);
+        assert_ne!(left, background);
+        assert_ne!(right, background);
+        assert_ne!(left, right);
+        assert_eq!(*image.get_pixel(0, 0), background);
+        // every pixel is one of the spheres or the background
+        assert!(image
+            .pixels()
+            .all(|pixel| *pixel == left || *pixel == right || *pixel == background));
+    }
+
+    #[test]
+    fn direct_lighting_matches_a_point_light_on_a_white_lambertian() {
+        // a red floor under a bright sky, which only the sun of a daylight
+        // sky would light in this pass
+        let red = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.9, 0.1, 0.1),
+            force_front_face: false,
+        });
+        let mut world = HittableList::new();
+        world.add_plane(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), red);
+        let lights = [Light::point(glm::vec3(0.0, 2.0, 0.0), 20.0)];
+        let sky = Sky::uniform(glm::vec3(1.0, 1.0, 1.0));
+        let context = ShadingContext::default();
+        let down = Ray::new(glm::vec3(1.0, 1.0, 0.0), glm::vec3(0.0, -1.0, 0.0), None);
+
+        // the light is sqrt(5) away from the hit at (1, 0, 0), at a cosine of
+        // 2 / sqrt(5) to the floor's normal
+        let distance_squared: f32 = 5.0;
+        let cosine = 2.0 / distance_squared.sqrt();
+        let expected = 20.0 * cosine / (std::f32::consts::PI * distance_squared);
+        let lit = trace_direct_lighting(
+            &down,
+            &world,
+            &lights,
+            &sky,
+            &context,
+            EPSILON,
+            MAX_HIT_DISTANCE,
+        );
+        for channel in 0..3 {
+            assert!(
+                (lit[channel] - expected).abs() < 1e-4,
+                "{} vs {}",
+                lit,
+                expected
+            );
+        }
+
+        // a ball between the hit and the light shadows it
+        world.add_sphere(glm::vec3(0.5, 1.0, 0.0), 0.2, gray());
+        let shadowed = trace_direct_lighting(
+            &down,
+            &world,
+            &lights,
+            &sky,
+            &context,
+            EPSILON,
+            MAX_HIT_DISTANCE,
+        );
+        assert_eq!(shadowed, glm::vec3(0.0, 0.0, 0.0));
+
+        // and a ray which escapes sees black rather than the sky
+        let up = Ray::new(glm::vec3(1.0, 1.0, 0.0), glm::vec3(0.0, 1.0, 0.0), None);
+        let escaped = trace_direct_lighting(
+            &up,
+            &world,
+            &lights,
+            &sky,
+            &context,
+            EPSILON,
+            MAX_HIT_DISTANCE,
+        );
+        assert_eq!(escaped, glm::vec3(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sky_occlusion_of_an_open_floor_is_the_sky() {
+        let mut world = HittableList::new();
+        world.add_plane(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), gray());
+        let down = Ray::new(glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, -1.0, 0.0), None);
+        let average = |world: &HittableList, sky: &Sky| {
+            let samples = 4000;
+            (0..samples).fold(glm::vec3(0.0, 0.0, 0.0), |sum, _| {
+                sum + trace_sky_occlusion(&down, world, sky, EPSILON, MAX_HIT_DISTANCE)
+            }) / samples as f32
+        };
+
+        // every ray sees the same color
+        let uniform = Sky::uniform(glm::vec3(0.4, 0.6, 0.8));
+        for _ in 0..100 {
+            let color = trace_sky_occlusion(&down, &world, &uniform, EPSILON, MAX_HIT_DISTANCE);
+            assert!(glm::distance(&color, &glm::vec3(0.4, 0.6, 0.8)) < 1e-6);
+        }
+        // a sky from black below to white above blends by (1 + cos) / 2,
+        // whose cosine weighted mean over the hemisphere is 5 / 6
+        let gradient = Sky::Gradient {
+            bottom: glm::vec3(0.0, 0.0, 0.0),
+            top: glm::vec3(1.0, 1.0, 1.0),
+            axis: glm::vec3(0.0, 1.0, 0.0),
+        };
+        assert!((average(&world, &gradient).x - 5.0 / 6.0).abs() < 0.01);
+
+        // a ceiling blocks all of it
+        world.add_plane(glm::vec3(0.0, 2.0, 0.0), glm::vec3(0.0, -1.0, 0.0), gray());
+        assert_eq!(average(&world, &uniform), glm::vec3(0.0, 0.0, 0.0));
+    }
+}
@@ -1,8 +1,188 @@
 pub mod orthographic_camera;
 pub mod perspective_camera;
 
+use crate::random::rng;
 use crate::ray::Ray;
 use glm::Vec3;
+use rand::Rng;
+
+/// Find the orthonormal basis of a camera looking from `position` toward
+/// `lookat`. A camera placed on its look-at point looks down -z instead,
+/// and an up direction along the view direction (or zero) is swapped for
+/// whichever world axis is furthest from it, with a warning either way, so
+/// a bad scene renders something rather than a black image of NaNs.
+///
+/// # Arguments
+/// - `position: Vec3` - position of the camera
+/// - `lookat: Vec3` - look-at point for the camera
+/// - `up_direction: Vec3` - up direction
+///
+/// # Returns
+/// - tuple of
+///     - `Vec3` - unit vector pointing back from the view into the camera
+///     - `Vec3` - unit vector to the right of the image
+///     - `Vec3` - unit vector to the top of the image
+pub fn view_basis(position: Vec3, lookat: Vec3, up_direction: Vec3) -> (Vec3, Vec3, Vec3) {
+    let mut into_camera = glm::normalize(&(position - lookat));
+    if !is_finite(&into_camera) {
+        eprintln!(
+            "warning: camera at {:?} is on its look-at point, looking down -z instead",
+            position.as_slice()
+        );
+        into_camera = glm::vec3(0.0, 0.0, 1.0);
+    }
+
+    let mut sideways = glm::cross(&up_direction, &into_camera);
+    if glm::length(&sideways) <= 1e-6 * glm::length(&up_direction) || !is_finite(&sideways) {
+        let axis = (0..3)
+            .min_by(|a, b| into_camera[*a].abs().total_cmp(&into_camera[*b].abs()))
+            .unwrap_or(1);
+        let mut up = glm::vec3(0.0, 0.0, 0.0);
+        up[axis] = 1.0;
+        eprintln!(
+            "warning: camera up direction {:?} is zero or along the view direction, using {:?} instead",
+            up_direction.as_slice(),
+            up.as_slice()
+        );
+        sideways = glm::cross(&up, &into_camera);
+    }
+    let horizontal_direction = glm::normalize(&sideways);
+    let vertical_direction = glm::cross(&into_camera, &horizontal_direction);
+    (into_camera, horizontal_direction, vertical_direction)
+}
+
+/// Find the camera coordinates of a point in a pixel. Pixel (x, y) covers
+/// the square from x to x + 1 across and y to y + 1 down the image, with y
+/// counted from the top row, and the camera coordinates run from (0, 0) at
+/// the bottom left corner of the image to (1, 1) at the top right. An
+/// offset of (0.5, 0.5) is the center of the pixel, so the center of the
+/// image is at (0.5, 0.5) and every pixel is the same size.
+///
+/// # Arguments
+/// - `x: u32` - column of the pixel, from 0 at the left
+/// - `y: u32` - row of the pixel, from 0 at the top
+/// - `offset: (f32, f32)` - point within the pixel, each from 0 to 1, right
+///   and up from its bottom left corner
+/// - `image_width: u32` - width of the image in pixels
+/// - `image_height: u32` - height of the image in pixels
+///
+/// # Returns
+/// - `(f32, f32)` - the horizontal and vertical camera coordinates
+pub fn pixel_to_camera(
+    x: u32,
+    y: u32,
+    offset: (f32, f32),
+    image_width: u32,
+    image_height: u32,
+) -> (f32, f32) {
+    let row_from_bottom = (image_height - 1 - y) as f32;
+    (
+        (x as f32 + offset.0) / image_width as f32,
+        (row_from_bottom + offset.1) / image_height as f32,
+    )
+}
+
+/// Which way the horizontal axis of the image runs relative to the view
+/// and up directions, which decides whether images come out mirrored
+/// compared to another renderer's.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Handedness {
+    /// The right of the image is the cross product of the up direction with
+    /// the direction back into the camera, so a camera looking down -z with
+    /// +y up has +x on its right, as in OpenGL and most renderers. This is
+    /// the default.
+    Right,
+    /// The image is mirrored left to right, sensor shift included, so a
+    /// camera looking down -z with +y up has +x on its left, as in
+    /// left-handed conventions like Direct3D's when scenes are carried over
+    /// unchanged
+    Left,
+}
+
+/// One eye of a stereo pair of cameras, see
+/// `PerspectiveCamera::stereo_eye`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Eye {
+    /// The eye on the left of the image
+    Left,
+    /// The eye on the right of the image
+    Right,
+}
+
+/// How a shutter weights the moments of the time each row of the image is
+/// exposed for.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ShutterProfile {
+    /// Every moment counts the same, like a shutter which opens and closes
+    /// at once, so motion blurs into hard-edged streaks
+    Box,
+    /// Moments count less toward either end, like a shutter which takes a
+    /// while to open and close, so streaks fade out at their ends
+    Gaussian,
+}
+
+/// A camera shutter, which exposes the image over a frame interval running
+/// from time 0 to time 1. Every camera ray is given a time in the interval,
+/// and moving objects are placed where they are at that time, so they blur
+/// over the part of the interval their pixels see.
+#[derive(Clone, Copy)]
+pub struct Shutter {
+    /// Fraction of the frame interval taken to start exposing the rows from
+    /// the top of the image to the bottom, with each row exposed for the
+    /// rest of the interval. 0 is a global shutter exposing every row over
+    /// the whole interval. Larger values expose lower rows later, skewing
+    /// fast objects, up to 1 where each row sees a single moment.
+    pub readout: f32,
+    /// How the moments each row is exposed for are weighted
+    pub profile: ShutterProfile,
+}
+
+impl Default for Shutter {
+    /// A global shutter with a box profile.
+    fn default() -> Shutter {
+        Shutter {
+            readout: 0.0,
+            profile: ShutterProfile::Box,
+        }
+    }
+}
+
+impl Shutter {
+    /// Pick a random time for a ray through a row of the image, within the
+    /// part of the frame interval the row is exposed for.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `v: f32` - vertical parameter, from 0 at the bottom of the image to
+    ///   1 at the top
+    ///
+    /// # Returns
+    /// - `f32` - the time, from 0 to 1
+    pub fn sample_time(&self, v: f32) -> f32 {
+        let start = self.readout * (1.0 - v.clamp(0.0, 1.0));
+        let length = 1.0 - self.readout;
+        let moment = match self.profile {
+            ShutterProfile::Box => rng().gen::<f32>(),
+            ShutterProfile::Gaussian => loop {
+                // a normal distribution from the Box-Muller transform, with
+                // three standard deviations to either end of the exposure,
+                // and the rare samples beyond them drawn again
+                let radius = (-2.0 * (1.0 - rng().gen::<f32>()).ln()).sqrt();
+                let angle = 2.0 * std::f32::consts::PI * rng().gen::<f32>();
+                let moment = 0.5 + radius * angle.cos() / 6.0;
+                if (0.0..=1.0).contains(&moment) {
+                    break moment;
+                }
+            },
+        };
+        start + length * moment
+    }
+}
+
+/// Whether every component of a vector is a finite number.
+fn is_finite(vector: &Vec3) -> bool {
+    vector.iter().all(|component| component.is_finite())
+}
 
 /// Represent any camera in the scene. Details aside, any camera must be able
 /// to return a ray to be sampled at a specific point on the imaging plane, and
@@ -38,3 +218,70 @@ pub trait Camera {
         aspect_ratio: f32,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check a basis is made of finite, unit and perpendicular vectors, and
+    /// is right handed.
+    fn assert_orthonormal((into_camera, right, up): (Vec3, Vec3, Vec3)) {
+        for vector in [into_camera, right, up].iter() {
+            assert!(is_finite(vector), "{:?}", vector);
+            assert!((glm::length(vector) - 1.0).abs() < 1e-6);
+        }
+        assert!(glm::dot(&into_camera, &right).abs() < 1e-6);
+        assert!(glm::dot(&into_camera, &up).abs() < 1e-6);
+        assert!(glm::dot(&right, &up).abs() < 1e-6);
+        assert!(glm::distance(&glm::cross(&right, &up), &into_camera) < 1e-6);
+    }
+
+    #[test]
+    fn view_basis_of_an_ordinary_camera() {
+        let basis = view_basis(
+            glm::vec3(0.0, 0.0, 5.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 2.0, 0.0),
+        );
+        assert_orthonormal(basis);
+        assert_eq!(basis.0, glm::vec3(0.0, 0.0, 1.0));
+        assert_eq!(basis.1, glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(basis.2, glm::vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn view_basis_with_the_camera_on_its_look_at_point_looks_down_z() {
+        let point = glm::vec3(1.0, 2.0, 3.0);
+        let basis = view_basis(point, point, glm::vec3(0.0, 1.0, 0.0));
+        assert_orthonormal(basis);
+        assert_eq!(basis.0, glm::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn view_basis_with_up_along_the_view_picks_another_up() {
+        let position = glm::vec3(0.0, 5.0, 0.0);
+        let lookat = glm::vec3(0.0, 0.0, 0.0);
+        for up in [
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, -3.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+        ]
+        .iter()
+        {
+            let basis = view_basis(position, lookat, *up);
+            assert_orthonormal(basis);
+            assert_eq!(basis.0, glm::vec3(0.0, 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn pixel_to_camera_puts_the_center_pixel_at_the_image_center() {
+        // the middle of an odd sized image's middle pixel
+        assert_eq!(pixel_to_camera(2, 1, (0.5, 0.5), 5, 3), (0.5, 0.5));
+        // or the corner shared by an even sized image's middle four
+        assert_eq!(pixel_to_camera(2, 2, (0.0, 1.0), 4, 4), (0.5, 0.5));
+        // rows count down from the top, and the image's corners are 0 and 1
+        assert_eq!(pixel_to_camera(0, 3, (0.0, 0.0), 4, 4), (0.0, 0.0));
+        assert_eq!(pixel_to_camera(3, 0, (1.0, 1.0), 4, 4), (1.0, 1.0));
+    }
+}
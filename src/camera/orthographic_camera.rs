@@ -1,8 +1,16 @@
+use crate::camera;
 use crate::camera::Camera;
 use crate::ray::Ray;
 use glm::Vec3;
 
-/// Camera using the orthographic projection.
+/// Camera using the orthographic projection. With a receding scale above
+/// zero it uses an oblique projection instead, like the cavalier and cabinet
+/// views of technical drawings: faces parallel to the image plane keep their
+/// true shape, and edges going back into the scene are drawn at a fixed
+/// angle on the image, their lengths scaled by the receding scale.
+// `Scene` always holds a perspective camera, so this one is only reached
+// when swapped in by hand
+#[allow(dead_code)]
 pub struct OrthographicCamera {
     /// Camera center in world coordinates
     pub origin: Vec3,
@@ -14,9 +22,20 @@ pub struct OrthographicCamera {
     pub lower_left_corner: Vec3,
     /// Private field to keep track of which direction the orthographic rays point
     orthogonal_direction: Vec3,
+    /// Distance from the camera to its look-at point, where an oblique view
+    /// draws the scene in the same place as the plain orthographic view
+    lookat_distance: f32,
+    /// Angle in degrees, counterclockwise from the image's horizontal axis,
+    /// at which edges going back into the scene are drawn by an oblique view
+    pub oblique_angle: f32,
+    /// Length on the image of a unit of depth along receding edges, 0 for
+    /// the plain orthographic view, 1 for a cavalier view and 0.5 for a
+    /// cabinet view
+    pub receding_scale: f32,
 }
 
 /// Methods for the orthographic camera
+#[allow(dead_code)]
 impl OrthographicCamera {
     /// Calculate the new camera parameters for the given configuration
     ///
@@ -33,20 +52,20 @@ impl OrthographicCamera {
     ///     - `Vec3` - new horizontal vector
     ///     - `Vec3` - new vertical vector
     ///     - `Vec3` - new lower left corner of the image plane
+    ///     - `Vec3` - new direction of the rays
     fn calculate_camera_parameters(
         position: Vec3,
         lookat: Vec3,
         up_direction: Vec3,
         vertical_fov: f32,
         aspect_ratio: f32,
-    ) -> (Vec3, Vec3, Vec3, Vec3) {
+    ) -> (Vec3, Vec3, Vec3, Vec3, Vec3) {
         let h = (vertical_fov.to_radians() / 2.0).tan();
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let into_camera = glm::normalize(&(position - lookat));
-        let horizontal_direction = glm::normalize(&glm::cross(&up_direction, &into_camera));
-        let vertical_direction = glm::cross(&into_camera, &horizontal_direction);
+        let (into_camera, horizontal_direction, vertical_direction) =
+            camera::view_basis(position, lookat, up_direction);
 
         (
             position,
@@ -55,6 +74,7 @@ impl OrthographicCamera {
             position
                 - viewport_width * horizontal_direction / 2.0
                 - viewport_height * vertical_direction / 2.0,
+            -into_camera,
         )
     }
 
@@ -76,7 +96,7 @@ impl OrthographicCamera {
         vertical_fov: f32,
         aspect_ratio: f32,
     ) -> OrthographicCamera {
-        let (origin, horizontal, vertical, lower_left_corner) =
+        let (origin, horizontal, vertical, lower_left_corner, orthogonal_direction) =
             OrthographicCamera::calculate_camera_parameters(
                 position,
                 lookat,
@@ -90,9 +110,42 @@ impl OrthographicCamera {
             horizontal,
             vertical,
             lower_left_corner,
-            orthogonal_direction: lookat - origin,
+            orthogonal_direction,
+            lookat_distance: glm::distance(&position, &lookat),
+            oblique_angle: 0.0,
+            receding_scale: 0.0,
         }
     }
+
+    /// Switch to an oblique projection. The rays stay parallel, but lean away
+    /// from the image plane's normal, so a point further back is seen shifted
+    /// along the oblique angle by its depth times the receding scale. Depth
+    /// is measured from the look-at point, which stays where the plain
+    /// orthographic view draws it.
+    ///
+    /// # Arguments
+    /// - `oblique_angle: f32` - angle of receding edges on the image in
+    ///   degrees, counterclockwise from its horizontal axis, commonly 30 or 45
+    /// - `receding_scale: f32` - length on the image of a unit of depth, 1
+    ///   for a cavalier view or 0.5 for a cabinet view
+    ///
+    /// # Returns
+    /// - the camera with the oblique projection
+    pub fn with_oblique(self, oblique_angle: f32, receding_scale: f32) -> OrthographicCamera {
+        OrthographicCamera {
+            oblique_angle,
+            receding_scale,
+            ..self
+        }
+    }
+
+    /// Unit vector on the image plane along which an oblique view draws the
+    /// edges going back into the scene.
+    fn receding_direction(&self) -> Vec3 {
+        let angle = self.oblique_angle.to_radians();
+        angle.cos() * glm::normalize(&self.horizontal)
+            + angle.sin() * glm::normalize(&self.vertical)
+    }
 }
 
 /// Methods for the camera trait
@@ -107,9 +160,19 @@ impl Camera for OrthographicCamera {
     /// # Returns
     /// - the new ray to be traced
     fn get_ray(&self, u: f32, v: f32) -> Ray {
+        let image_point = self.lower_left_corner + u * self.horizontal + v * self.vertical;
+        if self.receding_scale == 0.0 {
+            return Ray::new(image_point, self.orthogonal_direction, None);
+        }
+        // a ray which moves one unit into the scene also moves the receding
+        // scale back against the receding direction, so the point it meets
+        // there is drawn that far along the receding direction from where
+        // it started. Starting the rays shifted by the look-at distance keeps
+        // the look-at point where the plain view draws it.
+        let shear = self.receding_scale * self.receding_direction();
         Ray::new(
-            self.lower_left_corner + u * self.horizontal + v * self.vertical,
-            self.orthogonal_direction,
+            image_point + self.lookat_distance * shear,
+            self.orthogonal_direction - shear,
             None,
         )
     }
@@ -132,7 +195,7 @@ impl Camera for OrthographicCamera {
         vertical_fov: f32,
         aspect_ratio: f32,
     ) {
-        let (origin, horizontal, vertical, lower_left_corner) =
+        let (origin, horizontal, vertical, lower_left_corner, orthogonal_direction) =
             OrthographicCamera::calculate_camera_parameters(
                 position,
                 lookat,
@@ -144,6 +207,76 @@ impl Camera for OrthographicCamera {
         self.horizontal = horizontal;
         self.vertical = vertical;
         self.lower_left_corner = lower_left_corner;
-        self.orthogonal_direction = lookat - origin;
+        self.orthogonal_direction = orthogonal_direction;
+        self.lookat_distance = glm::distance(&position, &lookat);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where on the image, in the image plane's units from its lower left
+    /// corner, a camera draws a point, found by solving for the ray through
+    /// it among the rays at the point's depth.
+    fn image_position(camera: &OrthographicCamera, point: &Vec3) -> (f32, f32) {
+        let depth = glm::dot(&(point - camera.origin), &camera.orthogonal_direction);
+        let at_depth = |u: f32, v: f32| {
+            let ray = camera.get_ray(u, v);
+            let along =
+                depth - glm::dot(&(ray.origin - camera.origin), &camera.orthogonal_direction);
+            ray.origin
+                + along / glm::dot(&ray.direction, &camera.orthogonal_direction) * ray.direction
+        };
+        let corner = at_depth(0.0, 0.0);
+        let across = at_depth(1.0, 0.0) - corner;
+        let up = at_depth(0.0, 1.0) - corner;
+        (
+            glm::dot(&(point - corner), &across) / glm::length(&across),
+            glm::dot(&(point - corner), &up) / glm::length(&up),
+        )
+    }
+
+    #[test]
+    fn oblique_view_draws_receding_edges_at_its_angle_and_scale() {
+        let plain = OrthographicCamera::new(
+            glm::vec3(0.0, 0.0, 5.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+        );
+        let front = glm::vec3(0.2, 0.1, 0.0);
+        let back = front - glm::vec3(0.0, 0.0, 1.0);
+        for (angle, scale) in [(30.0, 0.5), (45.0, 1.0), (-60.0, 0.25)] {
+            let camera = OrthographicCamera::new(
+                glm::vec3(0.0, 0.0, 5.0),
+                glm::vec3(0.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+                90.0,
+                1.0,
+            )
+            .with_oblique(angle, scale);
+            // points at the look-at depth stay where the plain view draws them
+            let (front_x, front_y) = image_position(&camera, &front);
+            let (plain_x, plain_y) = image_position(&plain, &front);
+            assert!((front_x - plain_x).abs() < 1e-4 && (front_y - plain_y).abs() < 1e-4);
+            assert!((front_x - 1.2).abs() < 1e-4 && (front_y - 1.1).abs() < 1e-4);
+
+            // and a unit of depth is drawn `scale` long at `angle`
+            let (back_x, back_y) = image_position(&camera, &back);
+            let (dx, dy) = (back_x - front_x, back_y - front_y);
+            assert!((dx.hypot(dy) - scale).abs() < 1e-4, "{} {}", angle, scale);
+            assert!(
+                (dy.atan2(dx).to_degrees() - angle).abs() < 1e-2,
+                "{} {}",
+                angle,
+                scale
+            );
+        }
+        // the plain view draws the receding edge as a point
+        let (front_x, front_y) = image_position(&plain, &front);
+        let (back_x, back_y) = image_position(&plain, &back);
+        assert!((back_x - front_x).abs() < 1e-5 && (back_y - front_y).abs() < 1e-5);
     }
 }
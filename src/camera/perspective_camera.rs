@@ -1,4 +1,8 @@
+use crate::camera;
 use crate::camera::Camera;
+use crate::camera::Eye;
+use crate::camera::Handedness;
+use crate::camera::Shutter;
 use crate::ray::Ray;
 use glm::Vec3;
 
@@ -12,6 +16,15 @@ pub struct PerspectiveCamera {
     pub vertical: Vec3,
     /// Point at which the image plane starts
     pub lower_left_corner: Vec3,
+    /// Horizontal sensor shift, as a fraction of the viewport width
+    shift_x: f32,
+    /// Vertical sensor shift, as a fraction of the viewport height
+    shift_y: f32,
+    /// Which way the horizontal axis of the image runs
+    handedness: Handedness,
+    /// Shutter giving each ray its time, or `None` for every ray to see the
+    /// scene at time 0 with nothing moving
+    shutter: Option<Shutter>,
 }
 
 /// Methods for the perspective camera
@@ -24,6 +37,8 @@ impl PerspectiveCamera {
     /// - `up_direction: Vec3` - new up direction
     /// - `vertical_fov: f32` - new vertical field of view in degrees
     /// - `aspect_ratio: f32` - new aspect ratio
+    /// - `shift_x: f32` - horizontal sensor shift in viewport widths
+    /// - `shift_y: f32` - vertical sensor shift in viewport heights
     ///
     /// # Returns
     /// - tuple of
@@ -37,22 +52,23 @@ impl PerspectiveCamera {
         up_direction: Vec3,
         vertical_fov: f32,
         aspect_ratio: f32,
+        shift_x: f32,
+        shift_y: f32,
     ) -> (Vec3, Vec3, Vec3, Vec3) {
         let h = (vertical_fov.to_radians() / 2.0).tan();
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let into_camera = glm::normalize(&(position - lookat));
-        let horizontal_direction = glm::normalize(&glm::cross(&up_direction, &into_camera));
-        let vertical_direction = glm::cross(&into_camera, &horizontal_direction);
+        let (into_camera, horizontal_direction, vertical_direction) =
+            camera::view_basis(position, lookat, up_direction);
 
         (
             position,
             viewport_width * horizontal_direction,
             viewport_height * vertical_direction,
             position
-                - viewport_width * horizontal_direction / 2.0
-                - viewport_height * vertical_direction / 2.0
+                - viewport_width * horizontal_direction * (0.5 - shift_x)
+                - viewport_height * vertical_direction * (0.5 - shift_y)
                 - into_camera,
         )
     }
@@ -82,6 +98,8 @@ impl PerspectiveCamera {
                 up_direction,
                 vertical_fov,
                 aspect_ratio,
+                0.0,
+                0.0,
             );
 
         PerspectiveCamera {
@@ -89,8 +107,118 @@ impl PerspectiveCamera {
             horizontal,
             vertical,
             lower_left_corner,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            handedness: Handedness::Right,
+            shutter: None,
         }
     }
+
+    /// Shift the image plane within its own plane, like the shift movement
+    /// of a view camera. The view direction doesn't change, so lines which
+    /// are parallel to the image plane stay parallel in the image, which is
+    /// how tilt-shift lenses keep the verticals of buildings straight.
+    ///
+    /// # Arguments
+    /// - `shift_x: f32` - horizontal shift, as a fraction of the viewport
+    ///   width. Positive values move the framing right, or left in an
+    ///   image mirrored by `Handedness::Left`.
+    /// - `shift_y: f32` - vertical shift, as a fraction of the viewport
+    ///   height. Positive values move the framing up.
+    ///
+    /// # Returns
+    /// - the camera with the shift applied
+    pub fn with_sensor_shift(mut self, shift_x: f32, shift_y: f32) -> PerspectiveCamera {
+        // the shift is along the right-handed horizontal, so a mirrored
+        // image has its framing mirrored too
+        let right = match self.handedness {
+            Handedness::Right => self.horizontal,
+            Handedness::Left => -self.horizontal,
+        };
+        self.lower_left_corner +=
+            (shift_x - self.shift_x) * right + (shift_y - self.shift_y) * self.vertical;
+        self.shift_x = shift_x;
+        self.shift_y = shift_y;
+        self
+    }
+
+    /// Choose which way the horizontal axis of the image runs, mirroring
+    /// the image left to right if it changes, see `Handedness`.
+    ///
+    /// # Arguments
+    /// - `handedness: Handedness` - the convention to follow
+    ///
+    /// # Returns
+    /// - the camera with the handedness applied
+    pub fn with_handedness(mut self, handedness: Handedness) -> PerspectiveCamera {
+        if handedness != self.handedness {
+            self.mirror();
+            self.handedness = handedness;
+        }
+        self
+    }
+
+    /// Read the image plane from its right edge to its left instead.
+    fn mirror(&mut self) {
+        self.lower_left_corner += self.horizontal;
+        self.horizontal = -self.horizontal;
+    }
+
+    /// Open the shutter over a frame interval, so objects moving across it
+    /// blur, see `Shutter`.
+    ///
+    /// # Arguments
+    /// - `shutter: Shutter` - how the image is exposed over the interval
+    ///
+    /// # Returns
+    /// - the camera with the shutter
+    pub fn with_shutter(mut self, shutter: Shutter) -> PerspectiveCamera {
+        self.shutter = Some(shutter);
+        self
+    }
+
+    /// The camera's shutter, or `None` if it sees every ray at time 0.
+    pub fn shutter(&self) -> Option<Shutter> {
+        self.shutter
+    }
+
+    /// The camera seen from one eye of a stereo pair: moved half the
+    /// interpupillary distance toward that side of the image, with the
+    /// image plane moved along with it. The two eyes look in parallel, so
+    /// only things far away line up in both images.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `eye: Eye` - which eye to place the camera at
+    /// - `interpupillary_distance: f32` - distance between the two eyes
+    ///
+    /// # Returns
+    /// - the camera for that eye
+    pub fn stereo_eye(&self, eye: Eye, interpupillary_distance: f32) -> PerspectiveCamera {
+        // the horizontal runs toward the right of the image whichever the
+        // handedness, so the left eye stays on the left of a mirrored image
+        let side = match eye {
+            Eye::Left => -0.5,
+            Eye::Right => 0.5,
+        };
+        let offset = glm::normalize(&self.horizontal) * (side * interpupillary_distance);
+        PerspectiveCamera {
+            origin: self.origin + offset,
+            lower_left_corner: self.lower_left_corner + offset,
+            ..*self
+        }
+    }
+
+    /// Vertical field of view of the camera in degrees. The image plane is
+    /// one unit in front of the origin, so this follows from its height.
+    pub fn vertical_fov(&self) -> f32 {
+        (2.0 * (glm::length(&self.vertical) / 2.0).atan()).to_degrees()
+    }
+
+    /// Ratio of the image plane's width to its height.
+    pub fn aspect_ratio(&self) -> f32 {
+        glm::length(&self.horizontal) / glm::length(&self.vertical)
+    }
 }
 
 /// Methods for the camera trait
@@ -105,11 +233,15 @@ impl Camera for PerspectiveCamera {
     /// # Returns
     /// - the new ray to be traced
     fn get_ray(&self, u: f32, v: f32) -> Ray {
-        Ray::new(
+        let ray = Ray::new(
             self.origin,
             self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin,
             None,
-        )
+        );
+        match self.shutter {
+            Some(shutter) => ray.with_time(shutter.sample_time(v)),
+            None => ray,
+        }
     }
 
     /// Move the camera to a new location and change the fov or aspect ratio of
@@ -140,10 +272,109 @@ impl Camera for PerspectiveCamera {
                 up_direction,
                 vertical_fov,
                 aspect_ratio,
+                self.shift_x,
+                self.shift_y,
             );
         self.origin = origin;
         self.horizontal = horizontal;
         self.vertical = vertical;
         self.lower_left_corner = lower_left_corner;
+        if self.handedness == Handedness::Left {
+            self.mirror();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aov;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::MaterialType;
+
+    /// Camera at the origin looking down -z with +y up.
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, -1.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            60.0,
+            1.5,
+        )
+    }
+
+    #[test]
+    fn left_handed_image_is_the_right_handed_one_mirrored() {
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        // spheres of different sizes, off center and at different heights,
+        // so no mirroring of the image maps it onto itself
+        let mut world = HittableList::new();
+        world.add_sphere(glm::vec3(-1.2, 0.4, -4.0), 0.5, gray);
+        world.add_sphere(glm::vec3(0.9, -0.3, -5.0), 0.9, gray);
+        world.add_sphere(glm::vec3(2.0, 1.0, -6.0), 0.3, gray);
+        let (width, height) = (48, 32);
+        let ids = |camera: &PerspectiveCamera| {
+            aov::render_object_ids(&world, camera, width, height, 1e-4, f32::INFINITY)
+        };
+        let mut moved_right = camera();
+        moved_right.move_camera(
+            glm::vec3(0.5, 0.2, 1.0),
+            glm::vec3(0.0, 0.0, -5.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            50.0,
+            1.5,
+        );
+        let mut moved_left = camera().with_handedness(Handedness::Left);
+        moved_left.move_camera(
+            glm::vec3(0.5, 0.2, 1.0),
+            glm::vec3(0.0, 0.0, -5.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            50.0,
+            1.5,
+        );
+        for (right, left) in [
+            (camera(), camera().with_handedness(Handedness::Left)),
+            // the shift moves the framing right in the right-handed image,
+            // so left in the mirrored one
+            (
+                camera().with_sensor_shift(0.2, 0.1),
+                camera()
+                    .with_handedness(Handedness::Left)
+                    .with_sensor_shift(0.2, 0.1),
+            ),
+            (moved_right, moved_left),
+        ] {
+            let right = ids(&right);
+            let left = ids(&left);
+            assert_ne!(right, left);
+            for (x, y, pixel) in right.enumerate_pixels() {
+                assert_eq!(*pixel, *left.get_pixel(width - 1 - x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn stereo_eyes_see_parallel_rays_one_baseline_apart() {
+        let baseline = 0.065;
+        for handedness in [Handedness::Right, Handedness::Left] {
+            let camera = camera()
+                .with_sensor_shift(0.1, 0.0)
+                .with_handedness(handedness);
+            let left = camera.stereo_eye(Eye::Left, baseline).get_ray(0.5, 0.5);
+            let right = camera.stereo_eye(Eye::Right, baseline).get_ray(0.5, 0.5);
+            // the right eye is toward the right of the image, which is the
+            // world's -x in a mirrored image
+            let image_right = match handedness {
+                Handedness::Right => glm::vec3(1.0, 0.0, 0.0),
+                Handedness::Left => glm::vec3(-1.0, 0.0, 0.0),
+            };
+            assert!(glm::distance(&(right.origin - left.origin), &(baseline * image_right)) < 1e-6);
+            assert!(glm::distance(&left.direction, &right.direction) < 1e-6);
+            assert!((glm::distance(&left.at(10.0), &right.at(10.0)) - baseline).abs() < 1e-5);
+        }
     }
 }
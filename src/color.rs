@@ -1,27 +1,250 @@
 use glm::Vec3;
 
-/// Convert from vector to gamma adjusted and clamped RGB values.
+/// Largest linear value on the straight segment of the sRGB transfer curve,
+/// below which it is linear instead of a power, to keep its slope finite
+const SRGB_LINEAR_LIMIT: f32 = 0.0031308;
+
+/// Slope of the straight segment of the sRGB transfer curve
+const SRGB_LINEAR_SLOPE: f32 = 12.92;
+
+/// Encode a linear value with the sRGB transfer curve, as the output images
+/// store it.
 ///
 /// # Arguments
-/// - `vec: &Vec3` - Vec3 to convert to a RGB pixel
-pub fn vec3_to_rgb(vec: &Vec3, samples_level: usize) -> image::Rgb<u8> {
+/// - `value: f32` - linear value, from 0 to 1
+///
+/// # Returns
+/// - `f32` - the encoded value, from 0 to 1
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= SRGB_LINEAR_LIMIT {
+        SRGB_LINEAR_SLOPE * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode a value stored with the sRGB transfer curve to linear, the
+/// inverse of `linear_to_srgb`.
+///
+/// # Arguments
+/// - `value: f32` - encoded value, from 0 to 1
+///
+/// # Returns
+/// - `f32` - the linear value, from 0 to 1
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= SRGB_LINEAR_SLOPE * SRGB_LINEAR_LIMIT {
+        value / SRGB_LINEAR_SLOPE
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Relative luminance of a linear color, weighting each channel by how
+/// bright it looks, with the Rec. 709 weights.
+///
+/// # Arguments
+/// - `color: &Vec3` - linear color
+///
+/// # Returns
+/// - `f32` - the luminance
+pub fn luminance(color: &Vec3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// Average accumulated samples, encode with the sRGB transfer curve and
+/// clamp to [0, 1], ready to be quantized for an output image.
+///
+/// # Arguments
+/// - `vec: &Vec3` - accumulated linear color
+/// - `sample_count: usize` - number of samples accumulated into `vec`
+///
+/// # Returns
+/// - `Vec3` - display color with components from 0 to 1
+fn tone_map(vec: &Vec3, sample_count: usize) -> Vec3 {
     // scale by samples per pixel
-    let scaled = vec / (samples_level * samples_level) as f32;
+    let scaled = vec / sample_count as f32;
     // gamma correction
-    let g = 1.0 / 2.2;
-    let adjusted = glm::pow(&scaled, &glm::vec3(g, g, g));
-    // clamp and convert to u8 RGB
-    let clamped = glm::clamp(&adjusted, 0.0, 1.0);
-    let converted = clamped * 255.0;
+    let adjusted = scaled.map(linear_to_srgb);
+    glm::clamp(&adjusted, 0.0, 1.0)
+}
+
+/// Convert from vector to gamma adjusted and clamped RGB values.
+///
+/// # Arguments
+/// - `vec: &Vec3` - Vec3 to convert to a RGB pixel
+/// - `sample_count: usize` - number of samples accumulated into `vec`
+pub fn vec3_to_rgb(vec: &Vec3, sample_count: usize) -> image::Rgb<u8> {
+    let converted = glm::round(&(tone_map(vec, sample_count) * 255.0));
     image::Rgb([converted.x as u8, converted.y as u8, converted.z as u8])
 }
 
-/// Utility to convert from 8 bit RGB values to a Vec3
+/// Thresholds of a 4x4 ordered (Bayer) dither, in sixteenths
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Convert from vector to gamma adjusted, clamped and dithered RGB values.
+/// An ordered dither offset of less than one step is added before
+/// rounding, so smooth gradients alternate between neighboring values in a
+/// fine pattern instead of showing bands.
+///
+/// # Arguments
+/// - `vec: &Vec3` - Vec3 to convert to a RGB pixel
+/// - `sample_count: usize` - number of samples accumulated into `vec`
+/// - `x: u32` - column of the pixel
+/// - `y: u32` - row of the pixel
+pub fn vec3_to_rgb_dithered(vec: &Vec3, sample_count: usize, x: u32, y: u32) -> image::Rgb<u8> {
+    let threshold = BAYER_MATRIX[(y % 4) as usize][(x % 4) as usize];
+    let offset = (threshold as f32 + 0.5) / 16.0 - 0.5;
+    let scaled = tone_map(vec, sample_count) * 255.0 + glm::vec3(offset, offset, offset);
+    let converted = glm::round(&glm::clamp(&scaled, 0.0, 255.0));
+    image::Rgb([converted.x as u8, converted.y as u8, converted.z as u8])
+}
+
+/// Convert from vector to gamma adjusted and clamped 16 bit RGB values, for
+/// images with less banding in smooth gradients.
+///
+/// # Arguments
+/// - `vec: &Vec3` - Vec3 to convert to a RGB pixel
+/// - `sample_count: usize` - number of samples accumulated into `vec`
+pub fn vec3_to_rgb16(vec: &Vec3, sample_count: usize) -> image::Rgb<u16> {
+    let converted = glm::round(&(tone_map(vec, sample_count) * 65535.0));
+    image::Rgb([converted.x as u16, converted.y as u16, converted.z as u16])
+}
+
+/// Utility to convert from 8 bit RGB values to a Vec3, taking the values to
+/// be linear already. This is how the existing scenes were authored, so
+/// they keep their look, but a value of 128 comes out brighter than
+/// mid-gray in the image.
 ///
 /// # Arguments
 /// - `r: u8` - red value
 /// - `g: u8` - green value
 /// - `b: u8` - blue value
-pub fn color(r: u8, g: u8, b: u8) -> Vec3 {
+pub fn color_linear(r: u8, g: u8, b: u8) -> Vec3 {
     glm::vec3(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
 }
+
+/// Utility to convert from 8 bit sRGB values, like those in a color picker,
+/// to a linear Vec3. The decoding is the inverse of the sRGB encoding
+/// applied by `vec3_to_rgb`, so a color lit by exactly 1 comes out as the
+/// same 8 bit values it went in as.
+///
+/// # Arguments
+/// - `r: u8` - red value
+/// - `g: u8` - green value
+/// - `b: u8` - blue value
+pub fn color_srgb(r: u8, g: u8, b: u8) -> Vec3 {
+    color_linear(r, g, b).map(srgb_to_linear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// A smooth, dark ramp of gray levels, where banding shows the most
+    fn ramp(steps: usize) -> Vec<Vec3> {
+        (0..steps)
+            .map(|step| {
+                let value = 0.05 * step as f32 / (steps - 1) as f32;
+                glm::vec3(value, value, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sixteen_bit_output_keeps_more_distinct_levels() {
+        let ramp = ramp(1000);
+        let levels8: HashSet<u8> = ramp.iter().map(|color| vec3_to_rgb(color, 1)[0]).collect();
+        let levels16: HashSet<u16> = ramp
+            .iter()
+            .map(|color| vec3_to_rgb16(color, 1)[0])
+            .collect();
+        assert!(
+            levels16.len() > 10 * levels8.len(),
+            "{} vs {}",
+            levels16.len(),
+            levels8.len()
+        );
+        // and both span the full range
+        assert_eq!(vec3_to_rgb16(&glm::vec3(0.0, 0.0, 0.0), 1)[0], 0);
+        assert_eq!(vec3_to_rgb16(&glm::vec3(1.0, 1.0, 1.0), 1)[0], 65535);
+    }
+
+    #[test]
+    fn dithering_breaks_bands_into_alternating_neighbors() {
+        // a row of pixels across a ramp only a few 8 bit steps tall
+        let ramp: Vec<Vec3> = (0..256)
+            .map(|x| {
+                let value = 0.2 + 0.01 * x as f32 / 255.0;
+                glm::vec3(value, value, value)
+            })
+            .collect();
+        let changes = |row: &[u8]| row.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        let plain: Vec<u8> = ramp.iter().map(|color| vec3_to_rgb(color, 1)[0]).collect();
+        let dithered: Vec<u8> = ramp
+            .iter()
+            .enumerate()
+            .map(|(x, color)| vec3_to_rgb_dithered(color, 1, x as u32, 0)[0])
+            .collect();
+        assert!(
+            changes(&dithered) > 4 * changes(&plain),
+            "{} vs {}",
+            changes(&dithered),
+            changes(&plain)
+        );
+        // the pattern averages out to the same brightness
+        let mean =
+            |row: &[u8]| row.iter().map(|value| *value as f32).sum::<f32>() / row.len() as f32;
+        assert!((mean(&dithered) - mean(&plain)).abs() < 0.5);
+    }
+
+    #[test]
+    fn srgb_round_trips_over_the_whole_range() {
+        // both ends, each side of the join between the straight segment
+        // and the power, and a sweep through both
+        let joins = [
+            0.0,
+            1.0,
+            SRGB_LINEAR_LIMIT,
+            0.999 * SRGB_LINEAR_LIMIT,
+            1.001 * SRGB_LINEAR_LIMIT,
+        ];
+        let sweep = (0..=1000).map(|step| step as f32 / 1000.0);
+        let straight = (0..=100).map(|step| SRGB_LINEAR_LIMIT * step as f32 / 100.0);
+        for value in joins.iter().copied().chain(sweep).chain(straight) {
+            let encoded = linear_to_srgb(value);
+            assert!(
+                (0.0..=1.0).contains(&encoded),
+                "{} encodes to {}",
+                value,
+                encoded
+            );
+            let decoded = srgb_to_linear(encoded);
+            assert!(
+                (decoded - value).abs() <= 1.0e-6 + 1.0e-5 * value,
+                "{} comes back as {}",
+                value,
+                decoded
+            );
+        }
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1.0e-6);
+        // the curve is continuous where the segments meet, and the
+        // straight one really is straight
+        let below = linear_to_srgb(SRGB_LINEAR_LIMIT);
+        let above = linear_to_srgb(SRGB_LINEAR_LIMIT + 1.0e-7);
+        assert!((above - below).abs() < 1.0e-5, "{} vs {}", below, above);
+        assert!((linear_to_srgb(0.001) - 0.01292).abs() < 1.0e-7);
+        // and known values of the standard curve
+        assert!((srgb_to_linear(0.5) - 0.2140).abs() < 1.0e-4);
+        assert!((linear_to_srgb(0.18) - 0.4614).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn every_eight_bit_srgb_color_round_trips_through_the_output() {
+        for value in 0..=255 {
+            let linear = color_srgb(value, value, value);
+            assert_eq!(vec3_to_rgb(&linear, 1)[0], value, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use super::EPSILON;
+use super::MAX_HIT_DISTANCE;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light;
+use crate::light::SurfaceResponse;
+use crate::random;
+use crate::random::rng;
+use crate::ray::Ray;
+use crate::stats;
+use crate::stats::Counter;
+use glm::Vec3;
+use rand::Rng;
+
+/// Angular radius of the sun disk in radians, about twice the real sun's so
+/// shadows have visible penumbras
+const SUN_ANGULAR_RADIUS: f32 = 0.01;
+/// Irradiance from the sun on a surface facing it, before the atmosphere
+/// takes its share
+const SUN_IRRADIANCE: f32 = 4.0;
+/// Fraction of each channel taken out per air mass by clear air, which
+/// scatters blue more than red
+const RAYLEIGH_EXTINCTION: [f32; 3] = [0.06, 0.13, 0.28];
+/// Fraction of every channel taken out per air mass by haze, for each unit
+/// of turbidity above a clear sky's
+const HAZE_EXTINCTION: f32 = 0.04;
+/// Asymmetry of the scattering by haze, which throws most light forward
+/// into a glow around the sun
+const HAZE_ASYMMETRY: f32 = 0.8;
+
+/// An analytic daytime sky: a sun disk bright enough to light the scene,
+/// a glow around it, and a gradient from the horizon to the zenith, all
+/// tinted by how much air the sunlight crosses. Below the horizon is a flat
+/// ground lit by the sun and sky. Diffuse surfaces sample the sun directly,
+/// see `direct_irradiance`.
+#[derive(Clone, Copy)]
+pub struct Daylight {
+    /// Unit direction toward the sun
+    pub sun_direction: Vec3,
+    /// Haziness of the air, 2 for a clear day to about 10 for a hazy one.
+    /// Haze whitens the sky, widens the glow around the sun, and reddens
+    /// and dims the sun near the horizon.
+    pub turbidity: f32,
+    /// Albedo of the ground seen below the horizon
+    pub ground_albedo: Vec3,
+}
+
+impl Daylight {
+    /// Create a daylight sky over grey ground.
+    ///
+    /// # Arguments
+    /// - `sun_direction: Vec3` - direction toward the sun, normalized here
+    /// - `turbidity: f32` - haziness of the air, see `Daylight::turbidity`
+    ///
+    /// # Returns
+    /// - the sky
+    pub fn new(sun_direction: Vec3, turbidity: f32) -> Daylight {
+        Daylight {
+            sun_direction: glm::normalize(&sun_direction),
+            turbidity,
+            ground_albedo: glm::vec3(0.3, 0.3, 0.3),
+        }
+    }
+
+    /// Turn the sun about the y axis, counterclockwise when seen from above.
+    ///
+    /// # Arguments
+    /// - `degrees: f32` - angle to turn by
+    ///
+    /// # Returns
+    /// - the rotated sky
+    pub fn with_yaw(self, degrees: f32) -> Daylight {
+        // whole turns are dropped first so they give exactly the same image
+        let angle = degrees.rem_euclid(360.0).to_radians();
+        let yaw = glm::mat4_to_mat3(&glm::rotation(angle, &glm::vec3(0.0, 1.0, 0.0)));
+        Daylight {
+            sun_direction: yaw * self.sun_direction,
+            ..self
+        }
+    }
+
+    /// Light seen along a ray which escaped the scene. A diffuse bounce
+    /// which finds the sun is weighted against sampling the sun directly.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `ray: &Ray` - the escaped ray
+    ///
+    /// # Returns
+    /// - `Vec3` - radiance from the ray's direction
+    pub fn radiance(&self, ray: &Ray) -> Vec3 {
+        let sky = self.sky_radiance(&ray.direction);
+        if ray.direction.y <= 0.0
+            || glm::dot(&ray.direction, &self.sun_direction) < 1.0 - Daylight::sun_one_minus_cos()
+        {
+            return sky;
+        }
+        let weight = match ray.scatter_pdf {
+            Some(scatter_pdf) => light::power_heuristic(scatter_pdf, self.sun_pdf()),
+            None => 1.0,
+        };
+        sky + self.sun_radiance() * weight
+    }
+
+    /// Estimate the sunlight arriving at a diffuse hit, cosine weighted by
+    /// the surface normal, from one shadow ray toward a random point on the
+    /// sun disk.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    ///
+    /// # Returns
+    /// - `Vec3` - irradiance at the hit point
+    pub fn direct_irradiance(&self, hit: &HitRecord, world: &dyn Hittable) -> Vec3 {
+        self.direct_reflected_radiance(hit, world, &light::diffuse_response)
+    }
+
+    /// Estimate the sunlight which a hit reflects toward the viewer, for
+    /// surfaces which aren't Lambertian, from one shadow ray toward the sun
+    /// like `direct_irradiance`, see `Light::reflected_radiance`.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    /// - `response: SurfaceResponse` - how the surface reflects the light
+    ///
+    /// # Returns
+    /// - `Vec3` - radiance reflected toward the viewer
+    pub fn direct_reflected_radiance(
+        &self,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        response: SurfaceResponse,
+    ) -> Vec3 {
+        let direction = random::cone_direction(
+            &self.sun_direction,
+            Daylight::sun_one_minus_cos(),
+            rng().gen(),
+            rng().gen(),
+        );
+        // surfaces reflect nothing from below them, but media scatter
+        // light arriving from every side, so the response decides
+        let cosine = glm::dot(&hit.normal(), &direction).max(0.0);
+        let (reflected, scatter_pdf) = response(&direction, cosine);
+        if reflected == glm::vec3(0.0, 0.0, 0.0) || direction.y <= 0.0 {
+            return glm::vec3(0.0, 0.0, 0.0);
+        }
+        let shadow_ray = Ray::new(hit.hit_point, direction, None).with_time(hit.ray.time);
+        stats::record(Counter::ShadowRays);
+        if world.any_hit(&shadow_ray, EPSILON, MAX_HIT_DISTANCE) {
+            return glm::vec3(0.0, 0.0, 0.0);
+        }
+        let sun_pdf = self.sun_pdf();
+        let weight = light::power_heuristic(sun_pdf, scatter_pdf);
+        glm::matrix_comp_mult(&(self.sun_radiance() * weight), &reflected) / sun_pdf
+    }
+
+    /// Light from the sky in a direction, leaving out the sun disk.
+    fn sky_radiance(&self, direction: &Vec3) -> Vec3 {
+        if direction.y < 0.0 {
+            // flat ground, lit by the sun and about as much sky as an
+            // upward facing surface sees
+            let sunlight = self.sun_irradiance() * self.sun_direction.y.max(0.0);
+            let skylight = self.sky_radiance(&glm::vec3(0.0, 1.0, 0.0)) * std::f32::consts::PI;
+            return glm::matrix_comp_mult(&self.ground_albedo, &(sunlight + skylight))
+                / std::f32::consts::PI;
+        }
+        let haze = ((self.turbidity - 2.0) / 8.0).clamp(0.0, 1.0);
+        let zenith = glm::mix(
+            &glm::vec3(0.08, 0.18, 0.45),
+            &glm::vec3(0.30, 0.34, 0.40),
+            haze,
+        );
+        let horizon = glm::mix(
+            &glm::vec3(0.40, 0.50, 0.65),
+            &glm::vec3(0.55, 0.55, 0.55),
+            haze,
+        );
+        let toward_horizon = (1.0 - direction.y).powi(3);
+        let gradient = glm::mix(&zenith, &horizon, toward_horizon);
+
+        // the sky takes on the sun's color as it sets, and darkens once
+        // the sun is below the horizon
+        let sun_color = self.sun_irradiance() / SUN_IRRADIANCE;
+        let tint = glm::mix(
+            &glm::vec3(1.0, 1.0, 1.0),
+            &(sun_color / sun_color.max().max(1.0e-6)),
+            toward_horizon,
+        );
+        let daylight = (4.0 * self.sun_direction.y + 0.3).clamp(0.0, 1.0);
+
+        // haze scatters sunlight forward into a glow around the sun
+        let cos_gamma = glm::dot(direction, &self.sun_direction);
+        let phase = random::henyey_greenstein_pdf(cos_gamma, HAZE_ASYMMETRY);
+        let glow = self.sun_irradiance() * phase * (0.05 + 0.25 * haze);
+
+        glm::matrix_comp_mult(&gradient, &tint) * daylight + glow
+    }
+
+    /// Irradiance from the sun after crossing the atmosphere, on a surface
+    /// facing it. The air mass crossed grows toward the horizon, following
+    /// Kasten and Young's formula.
+    fn sun_irradiance(&self) -> Vec3 {
+        if self.sun_direction.y < -SUN_ANGULAR_RADIUS {
+            return glm::vec3(0.0, 0.0, 0.0);
+        }
+        let sin_elevation = self.sun_direction.y.max(0.0);
+        let elevation = sin_elevation.asin().to_degrees();
+        let air_mass = 1.0 / (sin_elevation + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+        let haze = HAZE_EXTINCTION * (self.turbidity - 1.0).max(0.0);
+        let mut irradiance = glm::vec3(0.0, 0.0, 0.0);
+        for channel in 0..3 {
+            irradiance[channel] =
+                SUN_IRRADIANCE * (-(RAYLEIGH_EXTINCTION[channel] + haze) * air_mass).exp();
+        }
+        irradiance
+    }
+
+    /// Radiance of the sun disk, which spreads its irradiance over the disk.
+    fn sun_radiance(&self) -> Vec3 {
+        self.sun_irradiance() * self.sun_pdf()
+    }
+
+    /// Density with which `direct_irradiance` picks each direction in the
+    /// sun disk, the inverse of the disk's solid angle.
+    fn sun_pdf(&self) -> f32 {
+        1.0 / (2.0 * std::f32::consts::PI * Daylight::sun_one_minus_cos())
+    }
+
+    /// One minus the cosine of the sun's angular radius, computed without
+    /// the cancellation of subtracting a cosine so close to one.
+    fn sun_one_minus_cos() -> f32 {
+        2.0 * (SUN_ANGULAR_RADIUS / 2.0).sin().powi(2)
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::Result;
+
+/// OpenEXR channel type for 32 bit floats
+const FLOAT_PIXEL_TYPE: i32 = 2;
+
+/// A named image channel holding one value per pixel, in row-major order
+/// from the top left. Channels named `layer.channel` are grouped into layers
+/// by compositing programs, and unprefixed `R`, `G` and `B` channels form the
+/// default layer which viewers show.
+pub struct Channel {
+    /// Full name of the channel, like `albedo.R`
+    pub name: String,
+    /// Value of the channel at each pixel
+    pub values: Vec<f32>,
+}
+
+/// Write channels to an uncompressed, single part, scanline OpenEXR file
+/// with 32 bit float samples.
+///
+/// # Arguments
+/// - `filename: &str` - path of the file to write
+/// - `width: u32` - width of the image in pixels
+/// - `height: u32` - height of the image in pixels
+/// - `channels: Vec<Channel>` - channels to write, each with `width *
+///   height` values
+///
+/// # Returns
+/// - `Result<()>` - any error from writing the file
+pub fn write(filename: &str, width: u32, height: u32, mut channels: Vec<Channel>) -> Result<()> {
+    // the format requires channels in alphabetical order, both in the header
+    // and within each scanline
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    for channel in channels.iter() {
+        assert_eq!(channel.values.len(), (width * height) as usize);
+    }
+
+    // magic number and version 2, with no flags for single part scanlines
+    let mut bytes: Vec<u8> = vec![0x76, 0x2f, 0x31, 0x01];
+    bytes.extend_from_slice(&2i32.to_le_bytes());
+
+    let mut channel_list = Vec::new();
+    for channel in channels.iter() {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&FLOAT_PIXEL_TYPE.to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]); // linear flag and reserved
+        channel_list.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channel_list.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channel_list.push(0);
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    let mut screen_window_center = Vec::new();
+    screen_window_center.extend_from_slice(&0.0f32.to_le_bytes());
+    screen_window_center.extend_from_slice(&0.0f32.to_le_bytes());
+
+    write_attribute(&mut bytes, "channels", "chlist", &channel_list);
+    write_attribute(&mut bytes, "compression", "compression", &[0]);
+    write_attribute(&mut bytes, "dataWindow", "box2i", &window);
+    write_attribute(&mut bytes, "displayWindow", "box2i", &window);
+    write_attribute(&mut bytes, "lineOrder", "lineOrder", &[0]); // increasing y
+    write_attribute(
+        &mut bytes,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    write_attribute(
+        &mut bytes,
+        "screenWindowCenter",
+        "v2f",
+        &screen_window_center,
+    );
+    write_attribute(
+        &mut bytes,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    bytes.push(0); // end of header
+
+    // uncompressed files store one scanline per block, listed in an offset
+    // table before the blocks
+    let line_size = channels.len() * width as usize * 4;
+    let table_end = bytes.len() + height as usize * 8;
+    for y in 0..height as usize {
+        let offset = (table_end + y * (8 + line_size)) as u64;
+        bytes.extend_from_slice(&offset.to_le_bytes());
+    }
+    for y in 0..height as usize {
+        bytes.extend_from_slice(&(y as i32).to_le_bytes());
+        bytes.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in channels.iter() {
+            let row = &channel.values[y * width as usize..(y + 1) * width as usize];
+            for value in row.iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    std::fs::write(filename, bytes)
+}
+
+/// Append a header attribute, which is its name, type name, size and value.
+fn write_attribute(bytes: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(type_name.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
@@ -0,0 +1,1380 @@
+use crate::camera::perspective_camera::PerspectiveCamera;
+use crate::hittable::bvh::BvhSettings;
+use crate::hittable::hittable_list::HittableList;
+use crate::hittable::mesh::Mesh;
+use crate::hittable::mesh::MeshData;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::diffuse_light::DiffuseLight;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::MaterialType;
+use crate::scenes::Scene;
+use crate::scenes::Sky;
+use glm::Mat4;
+use glm::Vec3;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::path::Path;
+
+/// Distance at which directional lights are placed as point lights, far
+/// enough that their light arrives almost parallel across a scene
+const DIRECTIONAL_LIGHT_DISTANCE: f32 = 1.0e4;
+/// Vertical field of view in degrees of the camera made for files which
+/// have none
+const DEFAULT_VERTICAL_FOV: f32 = 40.0;
+/// Identifier of the JSON chunk of a binary glTF file
+const GLB_JSON_CHUNK: u32 = 0x4e4f_534a;
+/// Identifier of the binary buffer chunk of a binary glTF file
+const GLB_BIN_CHUNK: u32 = 0x004e_4942;
+/// Deepest nesting of JSON arrays and objects parsed. glTF files nest only
+/// a few levels, and a limit keeps a malformed file from overflowing the
+/// stack.
+const MAX_JSON_DEPTH: usize = 128;
+
+/// A parsed JSON value. Objects keep their members in file order.
+enum Json {
+    Null,
+    // no property the loader reads is a boolean, so true and false are
+    // parsed but not told apart
+    Bool,
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Member of an object with the given key, or `None` for other values.
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Items of an array, empty for other values.
+    fn items(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// The value if it is a number.
+    fn number(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// The value if it is a whole number which can index an array.
+    fn index(&self) -> Option<usize> {
+        self.number()
+            .filter(|number| *number >= 0.0 && number.fract() == 0.0)
+            .map(|number| number as usize)
+    }
+
+    /// The value if it is a string.
+    fn string(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The value if it is an array of numbers, as `f32`s.
+    fn floats(&self) -> Option<Vec<f32>> {
+        match self {
+            Json::Array(items) => items
+                .iter()
+                .map(|item| item.number().map(|number| number as f32))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a JSON document one value at a time.
+struct JsonParser<'a> {
+    text: &'a [u8],
+    position: usize,
+    /// Number of arrays and objects the parser is inside
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    /// Parse a whole document, which must hold exactly one value.
+    fn parse(text: &'a [u8]) -> Result<Json> {
+        let mut parser = JsonParser {
+            text,
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, message: &str) -> Error {
+        invalid(format!("bad JSON at byte {}: {}", self.position, message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Consume the given byte, which must come next.
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.text.get(self.position) == Some(&byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", byte as char)))
+        }
+    }
+
+    /// Consume the given word, which must come next.
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json> {
+        if self.text[self.position..].starts_with(word.as_bytes()) {
+            self.position += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unknown keyword"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        match self.text.get(self.position) {
+            Some(b'{' | b'[') if self.depth == MAX_JSON_DEPTH => {
+                Err(self.error("nested too deeply"))
+            }
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.keyword("true", Json::Bool),
+            Some(b'f') => self.keyword("false", Json::Bool),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(_) => self.number(),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    /// Parse an array or object one level deeper.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json>) -> Result<Json> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.text.get(self.position) == Some(&b'}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.text.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.text.get(self.position) == Some(&b']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.text.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = *self
+                .text
+                .get(self.position)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self
+                        .text
+                        .get(self.position)
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.position += 1;
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex_code()?;
+                            // characters outside the basic plane are
+                            // written as a pair of surrogates
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.text[self.position..].starts_with(b"\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex_code()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("bad surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error("unknown escape")),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("string isn't UTF-8"))
+    }
+
+    /// Read the four hex digits of a `\u` escape.
+    fn hex_code(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("bad `\\u` escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let start = self.position;
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(|byte| byte.is_ascii_digit() || b"+-.eE".contains(byte))
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("bad number"))
+    }
+}
+
+/// Everything read from a glTF file which the scene is built from.
+struct Document {
+    /// The file's JSON
+    json: Json,
+    /// Contents of each of the file's buffers
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Choices made by the caller that apply to every mesh in the file.
+struct LoadOptions {
+    /// How to build the BVHs of the meshes
+    bvh: BvhSettings,
+    /// Material for every triangle in place of the file's materials
+    material_override: Option<MaterialType>,
+}
+
+/// The scene being gathered while the node tree is walked.
+struct Gathered {
+    world: HittableList,
+    lights: Vec<Light>,
+    camera: Option<PerspectiveCamera>,
+    triangle_count: usize,
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Load a static glTF 2.0 scene, from either a `.gltf` file with its
+/// buffers alongside or in data URIs, or a single binary `.glb` file.
+///
+/// Every triangle primitive of every mesh in the default scene becomes a
+/// mesh with its own BVH, placed by its node's transform. Materials are
+/// approximated from their metallic-roughness factors: mostly metallic
+/// materials become rough `Metal`, the rest `Lambertian`, and emissive ones
+/// `DiffuseLight`. Textures are ignored. The first perspective camera is
+/// used, or one looking at the whole scene if there is none. Point and spot
+/// lights from `KHR_lights_punctual` become point lights, with spot cones
+/// ignored, and directional lights become distant point lights. Skins,
+/// morph targets and animations are ignored.
+///
+/// # Arguments
+/// - `filename: &str` - path to the `.gltf` or `.glb` file
+/// - `image_width: u32` - width of the image in pixels
+/// - `image_height: u32` - height of the image in pixels
+/// - `bvh: BvhSettings` - how to build the BVHs of the meshes
+/// - `material_override: Option<MaterialType>` - if given, the material of
+///   every triangle in place of the file's materials, for checking the
+///   geometry on its own
+///
+/// # Returns
+/// - `Result<Scene>` - the scene, or why the file couldn't be loaded
+pub fn load(
+    filename: &str,
+    image_width: u32,
+    image_height: u32,
+    bvh: BvhSettings,
+    material_override: Option<MaterialType>,
+) -> Result<Scene> {
+    let document = read_document(filename)?;
+    let json = &document.json;
+    let aspect_ratio = image_width as f32 / image_height as f32;
+
+    let scene_index = json.get("scene").and_then(Json::index).unwrap_or(0);
+    let roots: Vec<usize> = match json.get("scenes").map(Json::items) {
+        Some(scenes) if !scenes.is_empty() => scenes
+            .get(scene_index)
+            .ok_or_else(|| invalid(format!("no scene {}", scene_index)))?
+            .get("nodes")
+            .map(Json::items)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(Json::index)
+            .collect(),
+        // without scenes, every node which isn't a child is a root
+        _ => {
+            let nodes = json.get("nodes").map(Json::items).unwrap_or(&[]);
+            let children: Vec<usize> = nodes
+                .iter()
+                .flat_map(|node| node.get("children").map(Json::items).unwrap_or(&[]))
+                .filter_map(Json::index)
+                .collect();
+            (0..nodes.len())
+                .filter(|node| !children.contains(node))
+                .collect()
+        }
+    };
+
+    let options = LoadOptions {
+        bvh,
+        material_override,
+    };
+    let mut gathered = Gathered {
+        world: HittableList::new(),
+        lights: Vec::new(),
+        camera: None,
+        triangle_count: 0,
+    };
+    for root in roots {
+        gather_node(
+            &document,
+            root,
+            &glm::identity(),
+            aspect_ratio,
+            &options,
+            &mut gathered,
+            0,
+        )?;
+    }
+    if gathered.triangle_count == 0 {
+        return Err(invalid(String::from("no triangles in the scene")));
+    }
+
+    let camera = match gathered.camera {
+        Some(camera) => camera,
+        None => framing_camera(&gathered.world, aspect_ratio),
+    };
+    let sky = Sky::Gradient {
+        bottom: glm::vec3(0.05, 0.05, 0.05),
+        top: glm::vec3(0.2, 0.22, 0.25),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+    Ok((gathered.world, camera, gathered.lights, sky, None))
+}
+
+/// Read a file's JSON and the contents of all its buffers.
+fn read_document(filename: &str) -> Result<Document> {
+    let contents = std::fs::read(filename)?;
+    let (json, binary_chunk) = if contents.starts_with(b"glTF") {
+        split_glb(&contents)?
+    } else {
+        (JsonParser::parse(&contents)?, None)
+    };
+
+    let directory = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let mut buffers = Vec::new();
+    for (index, buffer) in json
+        .get("buffers")
+        .map(Json::items)
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+    {
+        let data = match buffer.get("uri").and_then(Json::string) {
+            Some(uri) if uri.starts_with("data:") => {
+                let (_, encoded) = uri
+                    .split_once(";base64,")
+                    .ok_or_else(|| invalid(String::from("only base64 data URIs are supported")))?;
+                decode_base64(encoded)?
+            }
+            Some(uri) => std::fs::read(directory.join(uri))?,
+            // a buffer without a URI is the binary chunk of a .glb file
+            None if index == 0 => binary_chunk
+                .clone()
+                .ok_or_else(|| invalid(String::from("buffer 0 has no data")))?,
+            None => return Err(invalid(format!("buffer {} has no data", index))),
+        };
+        let length = buffer.get("byteLength").and_then(Json::index).unwrap_or(0);
+        if data.len() < length {
+            return Err(invalid(format!(
+                "buffer {} is shorter than its byteLength",
+                index
+            )));
+        }
+        buffers.push(data);
+    }
+    Ok(Document { json, buffers })
+}
+
+/// Split a binary glTF file into its JSON and binary chunks.
+fn split_glb(contents: &[u8]) -> Result<(Json, Option<Vec<u8>>)> {
+    let word = |offset: usize| -> Result<u32> {
+        contents
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(|| invalid(String::from("truncated .glb file")))
+    };
+    if word(4)? != 2 {
+        return Err(invalid(String::from("only glTF 2.0 is supported")));
+    }
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= contents.len() {
+        let length = word(offset)? as usize;
+        let kind = word(offset + 4)?;
+        let data = contents
+            .get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| invalid(String::from("truncated .glb chunk")))?;
+        match kind {
+            GLB_JSON_CHUNK => json = Some(JsonParser::parse(data)?),
+            GLB_BIN_CHUNK => binary = Some(data.to_vec()),
+            _ => (),
+        }
+        // chunks are padded to four bytes
+        offset += 8 + length.div_ceil(4) * 4;
+    }
+    let json = json.ok_or_else(|| invalid(String::from("no JSON chunk in .glb file")))?;
+    Ok((json, binary))
+}
+
+/// Decode standard base64, ignoring padding.
+fn decode_base64(encoded: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for byte in encoded.bytes().filter(|byte| *byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(invalid(String::from("bad base64 data"))),
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(data)
+}
+
+/// Add a node's mesh, camera and light to the scene, then its children's,
+/// each placed by the node's transform after its parents'.
+fn gather_node(
+    document: &Document,
+    index: usize,
+    parent_transform: &Mat4,
+    aspect_ratio: f32,
+    options: &LoadOptions,
+    gathered: &mut Gathered,
+    depth: usize,
+) -> Result<()> {
+    let nodes = document.json.get("nodes").map(Json::items).unwrap_or(&[]);
+    // a node can't be its own ancestor, so a deeper tree has a cycle
+    if depth > nodes.len() {
+        return Err(invalid(String::from("the node tree has a cycle")));
+    }
+    let node = nodes
+        .get(index)
+        .ok_or_else(|| invalid(format!("no node {}", index)))?;
+    let transform = parent_transform * node_transform(node)?;
+
+    if let Some(mesh) = node.get("mesh").and_then(Json::index) {
+        add_mesh(document, mesh, &transform, options, gathered)?;
+    }
+    if let Some(camera) = node.get("camera").and_then(Json::index) {
+        if gathered.camera.is_none() {
+            gathered.camera = node_camera(&document.json, camera, &transform, aspect_ratio);
+        }
+    }
+    let light = node
+        .get("extensions")
+        .and_then(|extensions| extensions.get("KHR_lights_punctual"))
+        .and_then(|extension| extension.get("light"))
+        .and_then(Json::index);
+    if let Some(light) = light {
+        gathered
+            .lights
+            .extend(node_light(&document.json, light, &transform));
+    }
+
+    for child in node.get("children").map(Json::items).unwrap_or(&[]) {
+        if let Some(child) = child.index() {
+            gather_node(
+                document,
+                child,
+                &transform,
+                aspect_ratio,
+                options,
+                gathered,
+                depth + 1,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Local transform of a node, from its matrix or its translation, rotation
+/// and scale.
+fn node_transform(node: &Json) -> Result<Mat4> {
+    if let Some(matrix) = node.get("matrix").and_then(Json::floats) {
+        if matrix.len() != 16 {
+            return Err(invalid(String::from("node matrix needs 16 numbers")));
+        }
+        // glTF matrices are column-major, like glm's
+        return Ok(glm::make_mat4(&matrix));
+    }
+    let vector = |key: &str, default: Vec<f32>| -> Vec<f32> {
+        node.get(key)
+            .and_then(Json::floats)
+            .filter(|values| values.len() == default.len())
+            .unwrap_or(default)
+    };
+    let t = vector("translation", vec![0.0, 0.0, 0.0]);
+    let r = vector("rotation", vec![0.0, 0.0, 0.0, 1.0]);
+    let s = vector("scale", vec![1.0, 1.0, 1.0]);
+    // rotations are unit quaternions stored as (x, y, z, w)
+    let rotation = glm::quat_to_mat4(&glm::quat_normalize(&glm::quat(r[0], r[1], r[2], r[3])));
+    Ok(glm::translation(&glm::vec3(t[0], t[1], t[2]))
+        * rotation
+        * glm::scaling(&glm::vec3(s[0], s[1], s[2])))
+}
+
+/// Add each triangle primitive of a mesh to the world as its own mesh,
+/// with the vertices moved into world space.
+fn add_mesh(
+    document: &Document,
+    index: usize,
+    transform: &Mat4,
+    options: &LoadOptions,
+    gathered: &mut Gathered,
+) -> Result<()> {
+    let json = &document.json;
+    let mesh = json
+        .get("meshes")
+        .map(Json::items)
+        .and_then(|meshes| meshes.get(index))
+        .ok_or_else(|| invalid(format!("no mesh {}", index)))?;
+    // normals move with the inverse transpose, and mirroring transforms
+    // turn faces inside out
+    let normal_transform = glm::transpose(&glm::inverse(&glm::mat4_to_mat3(transform)));
+    let mirrored = glm::determinant(&glm::mat4_to_mat3(transform)) < 0.0;
+
+    for primitive in mesh.get("primitives").map(Json::items).unwrap_or(&[]) {
+        let mode = primitive.get("mode").and_then(Json::index).unwrap_or(4);
+        if mode != 4 {
+            eprintln!(
+                "warning: skipping a primitive of mesh {} which isn't triangles",
+                index
+            );
+            continue;
+        }
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| invalid(format!("primitive of mesh {} has no attributes", index)))?;
+        let position = attributes
+            .get("POSITION")
+            .and_then(Json::index)
+            .ok_or_else(|| invalid(format!("primitive of mesh {} has no positions", index)))?;
+        let mut positions = read_floats(document, position, 3)?;
+        let mut normals = match attributes.get("NORMAL").and_then(Json::index) {
+            Some(normal) => read_floats(document, normal, 3)?,
+            None => Vec::new(),
+        };
+        let texcoords = match attributes.get("TEXCOORD_0").and_then(Json::index) {
+            Some(texcoord) => read_floats(document, texcoord, 2).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let vertex_count = positions.len() / 3;
+        let mut indices = match primitive.get("indices").and_then(Json::index) {
+            Some(accessor) => read_indices(document, accessor)?,
+            None => (0..vertex_count).collect(),
+        };
+        indices.truncate(indices.len() / 3 * 3);
+        if indices.is_empty() {
+            continue;
+        }
+        if let Some(bad) = indices.iter().find(|index| **index >= vertex_count) {
+            return Err(invalid(format!("vertex index {} out of range", bad)));
+        }
+
+        for vertex in positions.chunks_mut(3) {
+            let moved = transform * glm::vec4(vertex[0], vertex[1], vertex[2], 1.0);
+            vertex.copy_from_slice(&[moved.x, moved.y, moved.z]);
+        }
+        for normal in normals.chunks_mut(3) {
+            let moved =
+                glm::normalize(&(normal_transform * glm::vec3(normal[0], normal[1], normal[2])));
+            normal.copy_from_slice(&[moved.x, moved.y, moved.z]);
+        }
+        if mirrored {
+            for triangle in indices.chunks_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        let mut data = MeshData {
+            positions,
+            normals,
+            texcoords,
+            colors: None,
+            smoothing_groups: None,
+            indices,
+        };
+        if data.normals.is_empty() {
+            // glTF asks for flat shading when normals are left out
+            data = flat_shaded(&data);
+        }
+        let material = options
+            .material_override
+            .unwrap_or_else(|| material(json, primitive.get("material").and_then(Json::index)));
+        gathered.triangle_count += data.indices.len() / 3;
+        gathered
+            .world
+            .add_mesh(Mesh::build(&data, material, options.bvh, None));
+    }
+    Ok(())
+}
+
+/// Give every triangle its own three vertices with the face's normal, so
+/// the mesh is shaded flat.
+fn flat_shaded(data: &MeshData) -> MeshData {
+    let mut flat = MeshData {
+        positions: Vec::with_capacity(data.indices.len() * 3),
+        normals: Vec::with_capacity(data.indices.len() * 3),
+        texcoords: Vec::new(),
+        colors: None,
+        smoothing_groups: None,
+        indices: (0..data.indices.len()).collect(),
+    };
+    let has_texcoords = data.texcoords.len() * 3 == data.positions.len() * 2;
+    for triangle in data.indices.chunks(3) {
+        let vertex = |index: usize| {
+            glm::vec3(
+                data.positions[index * 3],
+                data.positions[index * 3 + 1],
+                data.positions[index * 3 + 2],
+            )
+        };
+        let (a, b, c) = (
+            vertex(triangle[0]),
+            vertex(triangle[1]),
+            vertex(triangle[2]),
+        );
+        let cross = glm::cross(&(b - a), &(c - a));
+        let normal = if glm::length2(&cross) > 0.0 {
+            glm::normalize(&cross)
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        };
+        for &index in triangle {
+            flat.positions
+                .extend_from_slice(&data.positions[index * 3..index * 3 + 3]);
+            flat.normals.extend_from_slice(normal.as_slice());
+            if has_texcoords {
+                flat.texcoords
+                    .extend_from_slice(&data.texcoords[index * 2..index * 2 + 2]);
+            }
+        }
+    }
+    flat
+}
+
+/// Where an accessor's elements are in its buffer.
+struct AccessorLayout<'a> {
+    /// The buffer, from the start of the accessor's first element
+    data: &'a [u8],
+    /// Number of elements
+    count: usize,
+    /// Bytes from the start of one element to the next
+    stride: usize,
+    /// glTF component type code
+    component_type: usize,
+    /// Components per element
+    components: usize,
+}
+
+/// Find an accessor's elements and check they lie inside its buffer.
+fn accessor_layout(document: &Document, index: usize) -> Result<AccessorLayout<'_>> {
+    let json = &document.json;
+    let accessor = json
+        .get("accessors")
+        .map(Json::items)
+        .and_then(|accessors| accessors.get(index))
+        .ok_or_else(|| invalid(format!("no accessor {}", index)))?;
+    if accessor.get("sparse").is_some() {
+        return Err(invalid(String::from("sparse accessors aren't supported")));
+    }
+    let count = accessor.get("count").and_then(Json::index).unwrap_or(0);
+    let component_type = accessor
+        .get("componentType")
+        .and_then(Json::index)
+        .unwrap_or(0);
+    let components = match accessor.get("type").and_then(Json::string) {
+        Some("SCALAR") => 1,
+        Some("VEC2") => 2,
+        Some("VEC3") => 3,
+        Some("VEC4") => 4,
+        _ => {
+            return Err(invalid(format!(
+                "accessor {} has an unsupported type",
+                index
+            )))
+        }
+    };
+    let component_size = match component_type {
+        5120 | 5121 => 1,
+        5122 | 5123 => 2,
+        5125 | 5126 => 4,
+        _ => {
+            return Err(invalid(format!(
+                "accessor {} has an unknown component type",
+                index
+            )))
+        }
+    };
+    let view_index = accessor
+        .get("bufferView")
+        .and_then(Json::index)
+        .ok_or_else(|| invalid(format!("accessor {} has no buffer view", index)))?;
+    let view = json
+        .get("bufferViews")
+        .map(Json::items)
+        .and_then(|views| views.get(view_index))
+        .ok_or_else(|| invalid(format!("no buffer view {}", view_index)))?;
+    let buffer = view
+        .get("buffer")
+        .and_then(Json::index)
+        .and_then(|buffer| document.buffers.get(buffer))
+        .ok_or_else(|| invalid(format!("buffer view {} has no buffer", view_index)))?;
+    let element_size = component_size * components;
+    let stride = view
+        .get("byteStride")
+        .and_then(Json::index)
+        .unwrap_or(element_size);
+    // offsets and counts can be anything in a malformed file, so the end is
+    // found without overflowing
+    let offset = |json: &Json| json.get("byteOffset").and_then(Json::index).unwrap_or(0);
+    let start = offset(view).checked_add(offset(accessor));
+    let end = start.and_then(|start| match count {
+        0 => Some(start),
+        _ => stride
+            .checked_mul(count - 1)?
+            .checked_add(element_size)?
+            .checked_add(start),
+    });
+    let data = start
+        .zip(end)
+        .and_then(|(start, end)| buffer.get(start..end))
+        .ok_or_else(|| invalid(format!("accessor {} runs past its buffer", index)))?;
+    Ok(AccessorLayout {
+        data,
+        count,
+        stride,
+        component_type,
+        components,
+    })
+}
+
+/// Read a float accessor with the given number of components per element,
+/// flattened.
+fn read_floats(document: &Document, index: usize, components: usize) -> Result<Vec<f32>> {
+    let layout = accessor_layout(document, index)?;
+    if layout.component_type != 5126 || layout.components != components {
+        return Err(invalid(format!(
+            "accessor {} should hold {} floats per element",
+            index, components
+        )));
+    }
+    let mut values = Vec::with_capacity(layout.count * components);
+    for element in 0..layout.count {
+        for component in 0..components {
+            let offset = element * layout.stride + component * 4;
+            let bytes = &layout.data[offset..offset + 4];
+            values.push(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        }
+    }
+    Ok(values)
+}
+
+/// Read an accessor of unsigned integer vertex indices.
+fn read_indices(document: &Document, index: usize) -> Result<Vec<usize>> {
+    let layout = accessor_layout(document, index)?;
+    if layout.components != 1 {
+        return Err(invalid(format!("index accessor {} isn't scalar", index)));
+    }
+    let mut indices = Vec::with_capacity(layout.count);
+    for element in 0..layout.count {
+        let bytes = &layout.data[element * layout.stride..];
+        indices.push(match layout.component_type {
+            5121 => bytes[0] as usize,
+            5123 => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+            5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+            _ => {
+                return Err(invalid(format!(
+                    "index accessor {} isn't unsigned integers",
+                    index
+                )))
+            }
+        });
+    }
+    Ok(indices)
+}
+
+/// Approximate a glTF metallic-roughness material. Primitives without a
+/// material get glTF's default of white, fully metallic and fully rough.
+fn material(json: &Json, index: Option<usize>) -> MaterialType {
+    let material = index.and_then(|index| {
+        json.get("materials")
+            .map(Json::items)
+            .and_then(|materials| materials.get(index))
+    });
+    let pbr = material.and_then(|material| material.get("pbrMetallicRoughness"));
+    let factor = |key: &str| pbr.and_then(|pbr| pbr.get(key)).and_then(Json::number);
+    let base_color = pbr
+        .and_then(|pbr| pbr.get("baseColorFactor"))
+        .and_then(Json::floats)
+        .filter(|color| color.len() == 4)
+        .map_or(glm::vec3(1.0, 1.0, 1.0), |color| {
+            glm::vec3(color[0], color[1], color[2])
+        });
+    let metallic = factor("metallicFactor").unwrap_or(1.0) as f32;
+    let roughness = factor("roughnessFactor").unwrap_or(1.0) as f32;
+
+    let emission_strength = material
+        .and_then(|material| material.get("extensions"))
+        .and_then(|extensions| extensions.get("KHR_materials_emissive_strength"))
+        .and_then(|extension| extension.get("emissiveStrength"))
+        .and_then(Json::number)
+        .unwrap_or(1.0) as f32;
+    let emission = material
+        .and_then(|material| material.get("emissiveFactor"))
+        .and_then(Json::floats)
+        .filter(|color| color.len() == 3)
+        .map(|color| glm::vec3(color[0], color[1], color[2]) * emission_strength);
+    if let Some(emission) = emission.filter(|emission| emission.max() > 0.0) {
+        return MaterialType::DiffuseLight(DiffuseLight::new(emission));
+    }
+
+    if metallic >= 0.5 {
+        // glTF roughness is perceptual, the square of it is the slope spread
+        let spread = roughness * roughness;
+        MaterialType::Metal(Metal {
+            albedo: base_color,
+            fresnel: true,
+            roughness: (spread, spread),
+        })
+    } else {
+        MaterialType::Lambertian(Lambertian {
+            albedo: base_color,
+            force_front_face: false,
+        })
+    }
+}
+
+/// Make the camera a node places, if it is a perspective camera. glTF
+/// cameras look down their -z axis with +y up.
+fn node_camera(
+    json: &Json,
+    index: usize,
+    transform: &Mat4,
+    aspect_ratio: f32,
+) -> Option<PerspectiveCamera> {
+    let camera = json
+        .get("cameras")
+        .map(Json::items)
+        .and_then(|cameras| cameras.get(index))?;
+    let vertical_fov = match camera
+        .get("perspective")
+        .and_then(|p| p.get("yfov"))
+        .and_then(Json::number)
+    {
+        Some(yfov) => (yfov as f32).to_degrees(),
+        None => {
+            eprintln!(
+                "warning: skipping camera {}, which isn't a perspective camera",
+                index
+            );
+            return None;
+        }
+    };
+    let position = transform * glm::vec4(0.0, 0.0, 0.0, 1.0);
+    let forward = transform * glm::vec4(0.0, 0.0, -1.0, 0.0);
+    let up = transform * glm::vec4(0.0, 1.0, 0.0, 0.0);
+    let position = glm::vec3(position.x, position.y, position.z);
+    Some(PerspectiveCamera::new(
+        position,
+        position + glm::normalize(&glm::vec3(forward.x, forward.y, forward.z)),
+        glm::vec3(up.x, up.y, up.z),
+        vertical_fov,
+        aspect_ratio,
+    ))
+}
+
+/// Make the point light a node places from a `KHR_lights_punctual` light.
+fn node_light(json: &Json, index: usize, transform: &Mat4) -> Option<Light> {
+    let light = json
+        .get("extensions")
+        .and_then(|extensions| extensions.get("KHR_lights_punctual"))
+        .and_then(|extension| extension.get("lights"))
+        .map(Json::items)
+        .and_then(|lights| lights.get(index))?;
+    let intensity = light.get("intensity").and_then(Json::number).unwrap_or(1.0) as f32;
+    let color = light
+        .get("color")
+        .and_then(Json::floats)
+        .filter(|color| color.len() == 3)
+        .map_or(glm::vec3(1.0, 1.0, 1.0), |color| {
+            glm::vec3(color[0], color[1], color[2])
+        });
+    let position = transform * glm::vec4(0.0, 0.0, 0.0, 1.0);
+    let position = glm::vec3(position.x, position.y, position.z);
+    let mut point = match light.get("type").and_then(Json::string) {
+        // point and spot intensities are in candela, which the renderer's
+        // point lights divide by the squared distance just the same
+        Some("point") => Light::point(position, intensity),
+        Some("spot") => {
+            eprintln!(
+                "warning: light {} is a spot light, its cone is ignored",
+                index
+            );
+            Light::point(position, intensity)
+        }
+        // directional intensities are in lux, the light falling on a
+        // surface facing them, which a point light gives from far away
+        // when scaled up by the squared distance
+        Some("directional") => {
+            let direction = transform * glm::vec4(0.0, 0.0, -1.0, 0.0);
+            let direction = glm::normalize(&glm::vec3(direction.x, direction.y, direction.z));
+            Light::point(
+                -direction * DIRECTIONAL_LIGHT_DISTANCE,
+                intensity * DIRECTIONAL_LIGHT_DISTANCE * DIRECTIONAL_LIGHT_DISTANCE,
+            )
+        }
+        _ => {
+            eprintln!("warning: skipping light {} of unknown type", index);
+            return None;
+        }
+    };
+    point.color = color;
+    Some(point)
+}
+
+/// Make a camera looking at the whole world from in front, for files which
+/// have no camera of their own.
+fn framing_camera(world: &HittableList, aspect_ratio: f32) -> PerspectiveCamera {
+    let (center, radius) = match world.bounding_box() {
+        Some(bounds) => (
+            0.5 * (bounds.minimum_point + bounds.maximum_point),
+            0.5 * glm::distance(&bounds.minimum_point, &bounds.maximum_point),
+        ),
+        None => (glm::vec3(0.0, 0.0, 0.0), 1.0),
+    };
+    // the narrower of the two fields of view has to fit the bounding sphere
+    let half_fov = (DEFAULT_VERTICAL_FOV.to_radians() / 2.0)
+        .min(((DEFAULT_VERTICAL_FOV.to_radians() / 2.0).tan() * aspect_ratio).atan());
+    let distance = radius.max(1.0e-3) / half_fov.sin();
+    let direction: Vec3 = glm::normalize(&glm::vec3(0.0, 0.3, 1.0));
+    PerspectiveCamera::new(
+        center + direction * distance,
+        center,
+        glm::vec3(0.0, 1.0, 0.0),
+        DEFAULT_VERTICAL_FOV,
+        aspect_ratio,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    /// Write a minimal binary glTF file: a unit quad of two triangles,
+    /// nested under a node which doubles its size, and a perspective camera
+    /// with a `yfov` of 0.7 radians. The lower right triangle is red and
+    /// diffuse, and the upper left has glTF's default material.
+    ///
+    /// # Arguments
+    /// - `name: &str` - distinguishes the file from other tests' files
+    ///
+    /// # Returns
+    /// - the path of the file, which the caller removes
+    fn write_quad_glb(name: &str) -> std::path::PathBuf {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 2]}],
+            "nodes": [
+                {"children": [1], "scale": [2, 2, 2]},
+                {"mesh": 0, "translation": [0, 0, -1]},
+                {"camera": 0, "translation": [0, 0, 5]}
+            ],
+            "meshes": [{"primitives": [
+                {"attributes": {"POSITION": 0}, "indices": 1, "material": 0},
+                {"attributes": {"POSITION": 0}, "indices": 2}
+            ]}],
+            "materials": [
+                {"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0}}
+            ],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 0.7, "znear": 0.1}}],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"},
+                {"bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 48},
+                {"buffer": 0, "byteOffset": 48, "byteLength": 6},
+                {"buffer": 0, "byteOffset": 54, "byteLength": 6}
+            ],
+            "buffers": [{"byteLength": 60}]
+        }"#;
+        let mut binary = Vec::new();
+        for corner in [
+            [-0.5f32, -0.5, 0.0],
+            [0.5, -0.5, 0.0],
+            [0.5, 0.5, 0.0],
+            [-0.5, 0.5, 0.0],
+        ] {
+            for value in corner.iter() {
+                binary.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for index in [0u16, 1, 2, 0, 2, 3] {
+            binary.extend_from_slice(&index.to_le_bytes());
+        }
+
+        // chunks are padded to four bytes, JSON with spaces
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        binary.resize(binary.len().div_ceil(4) * 4, 0);
+        let total = 12 + 8 + json.len() + 8 + binary.len();
+        let mut contents = Vec::with_capacity(total);
+        contents.extend_from_slice(b"glTF");
+        contents.extend_from_slice(&2u32.to_le_bytes());
+        contents.extend_from_slice(&(total as u32).to_le_bytes());
+        for (kind, chunk) in [(GLB_JSON_CHUNK, &json), (GLB_BIN_CHUNK, &binary)] {
+            contents.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            contents.extend_from_slice(&kind.to_le_bytes());
+            contents.extend_from_slice(chunk);
+        }
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_{}_{}.glb", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn minimal_glb_loads_its_triangles_and_camera() {
+        let path = write_quad_glb("minimal");
+        let scene = load(path.to_str().unwrap(), 64, 64, BvhSettings::default(), None);
+        std::fs::remove_file(&path).unwrap();
+        let (world, camera, lights, _, _) = scene.unwrap();
+
+        assert_eq!(world.geometry_counts().triangles, 2);
+        assert!(lights.is_empty());
+        // the nested nodes double the quad and move it back 2 units
+        let bounds = world.bounding_box().unwrap();
+        assert!(glm::distance(&bounds.minimum_point, &glm::vec3(-1.0, -1.0, -2.0)) < 1e-3);
+        assert!(glm::distance(&bounds.maximum_point, &glm::vec3(1.0, 1.0, -2.0)) < 1e-3);
+
+        // the image plane is one unit in front of the camera, so its height
+        // gives the field of view
+        assert_eq!(camera.origin, glm::vec3(0.0, 0.0, 5.0));
+        let fov = 2.0 * (glm::length(&camera.vertical) / 2.0).atan().to_degrees();
+        assert!((fov - 40.107).abs() < 1e-3, "{}", fov);
+    }
+
+    #[test]
+    fn material_override_replaces_every_material() {
+        let path = write_quad_glb("override");
+        let chrome = MaterialType::Metal(Metal {
+            albedo: glm::vec3(0.9, 0.9, 0.9),
+            fresnel: false,
+            roughness: (0.0, 0.0),
+        });
+        let file = load(path.to_str().unwrap(), 64, 64, BvhSettings::default(), None);
+        let overridden = load(
+            path.to_str().unwrap(),
+            64,
+            64,
+            BvhSettings::default(),
+            Some(chrome),
+        );
+        std::fs::remove_file(&path).unwrap();
+        let (file, _, _, _, _) = file.unwrap();
+        let (overridden, _, _, _, _) = overridden.unwrap();
+
+        // rays at the lower right and upper left triangles
+        let rays = [(0.5, -0.5), (-0.5, 0.5)]
+            .map(|(x, y)| Ray::new(glm::vec3(x, y, 0.0), glm::vec3(0.0, 0.0, -1.0), None));
+        let material_hit = |world: &HittableList, ray: &Ray| {
+            *world
+                .hit(ray, 0.0, f32::INFINITY)
+                .unwrap()
+                .material
+                .unwrap()
+        };
+        match material_hit(&file, &rays[0]) {
+            MaterialType::Lambertian(lambertian) => {
+                assert_eq!(lambertian.albedo, glm::vec3(1.0, 0.0, 0.0))
+            }
+            _ => panic!("the red triangle isn't diffuse"),
+        }
+        match material_hit(&file, &rays[1]) {
+            MaterialType::Metal(metal) => assert_eq!(metal.albedo, glm::vec3(1.0, 1.0, 1.0)),
+            _ => panic!("the default material isn't metal"),
+        }
+        for ray in rays.iter() {
+            match material_hit(&overridden, ray) {
+                MaterialType::Metal(metal) => assert_eq!(metal.albedo, glm::vec3(0.9, 0.9, 0.9)),
+                _ => panic!("the override wasn't used"),
+            }
+        }
+    }
+
+    /// A `.gltf` file of one triangle, with its positions and indices in a
+    /// base64 data URI, for breaking in the malformed file tests
+    const TRIANGLE_GLTF: &str = r#"{
+        "asset": {"version": "2.0"},
+        "nodes": [{"mesh": 0}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [
+            {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+        ],
+        "buffers": [{
+            "byteLength": 42,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA"
+        }]
+    }"#;
+
+    /// Load `TRIANGLE_GLTF` with one piece of it replaced, expecting it to
+    /// fail.
+    ///
+    /// # Arguments
+    /// - `name: &str` - distinguishes the file from other tests' files
+    /// - `from: &str` - text of the file to replace, which must be in it
+    /// - `to: &str` - text to replace it with
+    ///
+    /// # Returns
+    /// - the error's message
+    fn broken_triangle_error(name: &str, from: &str, to: &str) -> String {
+        assert!(TRIANGLE_GLTF.contains(from), "{}", from);
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_{}_{}.gltf", name, std::process::id()));
+        std::fs::write(&path, TRIANGLE_GLTF.replace(from, to)).unwrap();
+        let scene = load(path.to_str().unwrap(), 64, 64, BvhSettings::default(), None);
+        std::fs::remove_file(&path).unwrap();
+        match scene {
+            Ok(_) => panic!("{} loaded after replacing {} with {}", name, from, to),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_an_error() {
+        let error = |text: &str| match JsonParser::parse(text.as_bytes()) {
+            Ok(_) => panic!("{} parsed", text),
+            Err(error) => error.to_string(),
+        };
+        assert!(error(r#"{"name": "quad"#).contains("unterminated string"));
+        assert!(error(r#"{"name": "quad\"#).contains("unterminated string"));
+        assert!(error(r#""\q""#).contains("unknown escape"));
+        for escape in [r#""\u12""#, r#""\u12g4""#, r#""\u+abc""#, r#""\u"#] {
+            assert!(error(escape).contains("bad `\\u` escape"), "{}", escape);
+        }
+        // an escaped high surrogate has to be followed by a low one
+        assert!(error(r#""\ud83d\u0041""#).contains("bad surrogate pair"));
+        assert!(error(r#"{"a" 1}"#).contains("expected `:`"));
+        assert!(error("[1, 2").contains("expected `,` or `]`"));
+        assert!(error("[1] [2]").contains("trailing characters"));
+        assert!(error("[nul]").contains("unknown keyword"));
+        assert!(error("[1.2.3]").contains("bad number"));
+        assert!(error("").contains("unexpected end"));
+        // by the byte it went wrong at
+        assert!(error(r#"{"a": 1,}"#).starts_with("bad JSON at byte 8"));
+        // deep nesting is refused rather than overflowing the stack
+        let deep = "[".repeat(100_000);
+        assert!(error(&deep).contains("nested too deeply"));
+        let nested = format!("{}{}", "[".repeat(100), "]".repeat(100));
+        assert!(JsonParser::parse(nested.as_bytes()).is_ok());
+
+        // and valid escapes still decode, pairs of surrogates included,
+        // with a lone surrogate replaced
+        for (text, expected) in [
+            (r#""a\"\\\/\n\u00e9""#, "a\"\\/\n\u{e9}"),
+            (r#""\ud83d\ude00""#, "\u{1f600}"),
+            (r#""\ud83dA""#, "\u{fffd}A"),
+        ] {
+            match JsonParser::parse(text.as_bytes()).unwrap() {
+                Json::String(string) => assert_eq!(string, expected, "{}", text),
+                _ => panic!("{} isn't a string", text),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_base64_is_an_error() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        for encoded in ["aGV sbG8=", "aGVs*G8=", "aGVsbG8\n", "aGVsbG\u{e9}"] {
+            assert!(decode_base64(encoded).is_err(), "{:?}", encoded);
+        }
+        // and through a file's data URI
+        let error = broken_triangle_error("base64", "AAAAAAAAAAAA", "AAAA!AAAAAAA");
+        assert!(error.contains("bad base64 data"), "{}", error);
+        let error = broken_triangle_error("short_buffer", "AAAAAAAAAAAA", "");
+        assert!(error.contains("shorter than its byteLength"), "{}", error);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        // the triangle loads as written
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_triangle_{}.gltf", std::process::id()));
+        std::fs::write(&path, TRIANGLE_GLTF).unwrap();
+        let scene = load(path.to_str().unwrap(), 64, 64, BvhSettings::default(), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(scene.unwrap().0.geometry_counts().triangles, 1);
+
+        for (name, from, to, message) in [
+            (
+                "position",
+                r#""POSITION": 0"#,
+                r#""POSITION": 7"#,
+                "no accessor 7",
+            ),
+            (
+                "indices",
+                r#""indices": 1"#,
+                r#""indices": 2"#,
+                "no accessor 2",
+            ),
+            (
+                "view",
+                r#""bufferView": 1,"#,
+                r#""bufferView": 5,"#,
+                "no buffer view 5",
+            ),
+            (
+                "buffer",
+                r#""buffer": 0, "byteOffset": 36"#,
+                r#""buffer": 3, "byteOffset": 36"#,
+                "buffer view 1 has no buffer",
+            ),
+            ("mesh", r#""mesh": 0"#, r#""mesh": 4"#, "no mesh 4"),
+            (
+                "count",
+                r#""count": 3, "type": "SCALAR""#,
+                r#""count": 4, "type": "SCALAR""#,
+                "accessor 1 runs past its buffer",
+            ),
+            // counts and offsets too big to add up are past the buffer too
+            (
+                "huge_count",
+                r#""count": 3, "type": "SCALAR""#,
+                r#""count": 1e300, "type": "SCALAR""#,
+                "accessor 1 runs past its buffer",
+            ),
+            (
+                "huge_offset",
+                r#""byteOffset": 36"#,
+                r#""byteOffset": 18446744073709551615"#,
+                "accessor 1 runs past its buffer",
+            ),
+        ] {
+            let error = broken_triangle_error(name, from, to);
+            assert!(error.contains(message), "{}: {}", name, error);
+        }
+        // and vertex indices past the positions
+        let error = broken_triangle_error("vertex", "AAABAAIA", "AAABAAMA");
+        assert!(error.contains("vertex index 3 out of range"), "{}", error);
+    }
+}
@@ -0,0 +1,197 @@
+use super::render;
+use super::tonemapped_image;
+use crate::scenes;
+use crate::settings::RenderSettings;
+use image::RgbImage;
+
+/// Width of the images hashed for each scene, small so every built-in scene
+/// renders in a few seconds
+const GOLDEN_WIDTH: u32 = 64;
+/// Height of the images hashed for each scene
+const GOLDEN_HEIGHT: u32 = 36;
+/// Specular reflections allowed along each path. Glass traces both a
+/// reflection and a refraction at every hit, so without a limit a glass
+/// scene takes minutes even this small.
+const GOLDEN_REFLECTION_DEPTH: u32 = 2;
+/// Refractions allowed along each path
+const GOLDEN_REFRACTION_DEPTH: u32 = 6;
+/// Offset basis of the 64 bit FNV-1a hash
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// Prime of the 64 bit FNV-1a hash
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Hash an 8 bit image, its size and every channel of every pixel, with
+/// 64 bit FNV-1a. Unlike the standard library's hasher its output is fixed,
+/// so hashes written by one build can be compared against another's.
+///
+/// # Arguments
+/// - `image: &RgbImage` - the image
+///
+/// # Returns
+/// - `u64` - the hash
+pub fn image_hash(image: &RgbImage) -> u64 {
+    let size = [image.width().to_le_bytes(), image.height().to_le_bytes()];
+    size.iter()
+        .flatten()
+        .chain(image.as_raw().iter())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Render one built-in scene small, with the default settings apart from
+/// one sample per pixel and limited reflection and refraction depths, and
+/// hash the tone mapped image. The renders are
+/// deterministic, so the hash only changes when the image does.
+///
+/// # Arguments
+/// - `name: &str` - name of the scene, one of `scenes::SCENE_NAMES`
+///
+/// # Returns
+/// - `Result<u64, scenes::SceneError>` - the hash, or why the scene
+///   couldn't be built
+pub fn scene_hash(name: &str) -> Result<u64, scenes::SceneError> {
+    let settings = RenderSettings {
+        image_width: GOLDEN_WIDTH,
+        image_height: GOLDEN_HEIGHT,
+        aa_level: 1,
+        max_reflection_depth: GOLDEN_REFLECTION_DEPTH,
+        max_refraction_depth: GOLDEN_REFRACTION_DEPTH,
+        highlight_missing_material: false,
+        ..RenderSettings::default()
+    };
+
+    let (world, camera, lights, sky, exposure) =
+        scenes::by_name(name, GOLDEN_WIDTH, GOLDEN_HEIGHT, settings.bvh)?;
+    let settings = RenderSettings {
+        exposure,
+        ..settings
+    };
+    let buffers = render(&world, &camera, &lights, &sky, &settings);
+    Ok(image_hash(&tonemapped_image(&buffers, &settings)))
+}
+
+/// Hash every built-in scene and compare the hashes against those stored
+/// in a file, printing the scenes whose images changed, or rewrite the file
+/// with the new hashes. The file has a line with the name and hash of each
+/// scene, and lines starting with `#` are comments.
+///
+/// # Arguments
+/// - `filename: &str` - file of stored hashes
+/// - `update: bool` - write the new hashes to the file instead of comparing
+///
+/// # Returns
+/// - `Result<bool, String>` - whether every hash matched, always true when
+///   updating, or a message if the file couldn't be read or written
+pub fn check(filename: &str, update: bool) -> Result<bool, String> {
+    let stored = if update {
+        Vec::new()
+    } else {
+        let contents = std::fs::read_to_string(filename)
+            .map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+        parse_hashes(&contents).map_err(|message| format!("{}: {}", filename, message))?
+    };
+
+    let mut hashes = Vec::with_capacity(scenes::SCENE_NAMES.len());
+    let mut changed = Vec::new();
+    for name in scenes::SCENE_NAMES.iter() {
+        let hash = scene_hash(name).map_err(|error| error.to_string())?;
+        if !update {
+            match stored.iter().find(|(stored_name, _)| stored_name == name) {
+                Some((_, stored_hash)) if *stored_hash == hash => {}
+                Some(_) => changed.push(format!("{} changed", name)),
+                None => changed.push(format!("{} has no stored hash", name)),
+            }
+        }
+        hashes.push((name.to_string(), hash));
+    }
+
+    if update {
+        let mut contents = String::from(
+            "# hashes of each built-in scene's image, rewritten by\n# cargo run --release -- --golden golden_hashes.txt --update-golden\n",
+        );
+        for (name, hash) in hashes.iter() {
+            contents.push_str(&format!("{} {:016x}\n", name, hash));
+        }
+        std::fs::write(filename, contents)
+            .map_err(|error| format!("couldn't write {}: {}", filename, error))?;
+        println!("wrote {} hashes to {}", hashes.len(), filename);
+        return Ok(true);
+    }
+    for message in changed.iter() {
+        println!("{}", message);
+    }
+    println!(
+        "{} of {} scenes match {}",
+        hashes.len() - changed.len(),
+        hashes.len(),
+        filename
+    );
+    Ok(changed.is_empty())
+}
+
+/// Read the scene names and hashes from the contents of a hash file.
+fn parse_hashes(contents: &str) -> Result<Vec<(String, u64)>, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(hash), None) => u64::from_str_radix(hash, 16)
+                    .map(|hash| (name.to_string(), hash))
+                    .map_err(|_| format!("bad hash in line `{}`", line)),
+                _ => Err(format!("expected a name and a hash in line `{}`", line)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_files_skip_comments_and_reject_bad_lines() {
+        let hashes = parse_hashes("# a comment\n\ncornell 00ff\n  spheres 1a2b  \n").unwrap();
+        assert_eq!(
+            hashes,
+            vec![
+                (String::from("cornell"), 0xff),
+                (String::from("spheres"), 0x1a2b)
+            ]
+        );
+        assert!(parse_hashes("cornell").is_err());
+        assert!(parse_hashes("cornell 00ff extra").is_err());
+        assert!(parse_hashes("cornell 00fg").is_err());
+    }
+
+    #[test]
+    fn teapot_caustic_matches_its_golden_hash_on_any_thread_count() {
+        // rayon shares the pixels out differently with each pool, and each
+        // pixel seeds its own random numbers, so the image never changes
+        let contents = std::fs::read_to_string("golden_hashes.txt").unwrap();
+        let stored = parse_hashes(&contents).unwrap();
+        let (_, golden) = stored
+            .iter()
+            .find(|(name, _)| name == "teapot_caustic")
+            .unwrap();
+        for threads in [1, 3, 8] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let hash = pool.install(|| scene_hash("teapot_caustic")).unwrap();
+            assert_eq!(hash, *golden, "{} threads", threads);
+        }
+    }
+
+    /// Renders every built-in scene, which takes minutes in a debug build,
+    /// so it only runs when asked for with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn every_scene_matches_its_golden_hash() {
+        assert_eq!(check("golden_hashes.txt", false), Ok(true));
+    }
+}
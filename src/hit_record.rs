@@ -1,7 +1,12 @@
+use crate::material::Material;
 use crate::material::MaterialType;
 use crate::ray::Ray;
 use glm::Vec3;
 
+/// Furthest the length of an outward normal may be from one before debug
+/// builds reject the hit
+const NORMAL_LENGTH_TOLERANCE: f32 = 1.0e-3;
+
 /// Record a hit point for a ray and the normal at that hit point.
 pub struct HitRecord<'a> {
     /// Location in space of the ray intersection
@@ -12,20 +17,189 @@ pub struct HitRecord<'a> {
     pub distance: f32,
     /// Normal vector of the geometry hit by the ray
     pub outward_normal: Vec3,
+    /// Normal of the flat face hit, which differs from `outward_normal`
+    /// where normals are interpolated across a face
+    pub geometric_normal: Vec3,
+    /// Texture coordinates (u, v) of the hit point on the surface
+    pub texture_coordinates: (f32, f32),
+    /// Weights of the three vertices at the hit point, for hits on
+    /// triangles. The weights sum to one, and each is one at its own vertex
+    /// and zero along the opposite edge.
+    pub barycentric_coordinates: Option<Vec3>,
+    /// Distances from the hit point to the three edges of the triangle hit,
+    /// each to the edge opposite the vertex of the same barycentric weight
+    pub edge_distances: Option<Vec3>,
+    /// Colors at the three vertices of the triangle hit, in the same order
+    /// as the barycentric coordinates, for meshes which carry vertex colors
+    pub vertex_colors: Option<[Vec3; 3]>,
+    /// Direction along the surface in which the u texture coordinate
+    /// increases, for triangles of meshes with texture coordinates
+    pub tangent: Option<Vec3>,
     /// Material hit
     pub material: Option<&'a MaterialType>,
+    /// Index of the object hit among the world's top-level objects, so a
+    /// whole mesh shares one ID. Primitives leave it `None` and the world's
+    /// `HittableList` fills it in, so IDs follow the order objects were
+    /// added and are the same every time a scene is built.
+    pub object_id: Option<usize>,
+    /// Whether the ray hit the side `outward_normal` points out of, see
+    /// `is_front_face`. Set with `shading_normal` when the record is made,
+    /// from the ray, the normals and the material.
+    front_face: bool,
+    /// Unit normal turned toward the incoming ray, see `normal`
+    shading_normal: Vec3,
 }
 
 impl<'a> HitRecord<'a> {
-    /// Is this face visible to the camera?
+    /// Create a record of a hit at some distance along a ray, working out
+    /// once which side was hit and the normal to shade with. Every other
+    /// field is empty, for primitives to fill in afterward.
+    ///
+    /// # Arguments
+    /// - `ray: &Ray` - the ray which intersected
+    /// - `distance: f32` - position of the hit along the ray
+    /// - `outward_normal: Vec3` - unit normal pointing out of the surface,
+    ///   interpolated across the face for smooth shading
+    /// - `geometric_normal: Vec3` - normal of the flat face hit, pointing to
+    ///   the same side as `outward_normal`
+    /// - `material: Option<&'a MaterialType>` - material hit
+    ///
+    /// # Returns
+    /// - `HitRecord` - the record
+    ///
+    /// # Panics
+    /// In debug builds, if a record with a material has an outward normal
+    /// which isn't finite and unit length. The panic names the line which
+    /// made the record, so the primitive at fault is the one calling this.
+    #[track_caller]
+    pub fn new(
+        ray: &Ray,
+        distance: f32,
+        outward_normal: Vec3,
+        geometric_normal: Vec3,
+        material: Option<&'a MaterialType>,
+    ) -> HitRecord<'a> {
+        let mut hit_record = HitRecord {
+            hit_point: ray.at(distance),
+            ray: *ray,
+            distance,
+            outward_normal,
+            geometric_normal,
+            texture_coordinates: (0.0, 0.0),
+            barycentric_coordinates: None,
+            edge_distances: None,
+            vertex_colors: None,
+            tangent: None,
+            material,
+            object_id: None,
+            front_face: true,
+            shading_normal: outward_normal,
+        };
+        hit_record.debug_check_normal();
+        hit_record.orient();
+        hit_record
+    }
+
+    /// Copy this record with a different outward normal, like the normal of
+    /// a microscopic facet, finding the side hit and shading normal again.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `outward_normal: Vec3` - the new outward normal
+    ///
+    /// # Returns
+    /// - `HitRecord` - the record with the new normal
+    ///
+    /// # Panics
+    /// In debug builds, if the normal isn't finite and unit length, see
+    /// `HitRecord::new`.
+    #[track_caller]
+    pub fn with_outward_normal(&self, outward_normal: Vec3) -> HitRecord<'a> {
+        let mut hit_record = HitRecord {
+            outward_normal,
+            ..*self
+        };
+        hit_record.debug_check_normal();
+        hit_record.orient();
+        hit_record
+    }
+
+    /// Copy this record as if the ray had arrived through the other side of
+    /// the surface, like a path leaving an object from inside it being lit
+    /// from outside, finding the side hit and shading normal again.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `HitRecord` - the record with the ray reversed
+    pub fn seen_from_other_side(&self) -> HitRecord<'a> {
+        let mut hit_record = HitRecord {
+            ray: Ray::new(self.ray.origin, -self.ray.direction, None).with_time(self.ray.time),
+            ..*self
+        };
+        hit_record.orient();
+        hit_record
+    }
+
+    /// Check in debug builds that the outward normal is finite and unit
+    /// length, since a slightly long or short one biases shading and
+    /// Fresnel without looking obviously wrong. Records without a material,
+    /// like a bounding box's, have no surface and aren't checked.
+    #[track_caller]
+    fn debug_check_normal(&self) {
+        let length = glm::length(&self.outward_normal);
+        debug_assert!(
+            self.material.is_none()
+                || (length.is_finite() && (length - 1.0).abs() <= NORMAL_LENGTH_TOLERANCE),
+            "outward normal {:?} has length {} at hit point {:?}",
+            self.outward_normal,
+            length,
+            self.hit_point
+        );
+    }
+
+    /// Work out which side of the surface the ray hit and the normal facing
+    /// the ray from the ray, the normals and the material.
+    fn orient(&mut self) {
+        if self.forces_front_face() {
+            // the face's own normal is reliable even when the winding isn't
+            let facing = if glm::dot(&self.ray.direction, &self.geometric_normal) < 0.0 {
+                self.geometric_normal
+            } else {
+                -self.geometric_normal
+            };
+            self.front_face = true;
+            self.shading_normal = glm::normalize(&facing);
+            return;
+        }
+        self.front_face = glm::dot(&self.ray.direction, &self.outward_normal) < 0.0;
+        self.shading_normal = glm::normalize(
+            &(if self.front_face {
+                self.outward_normal
+            } else {
+                -self.outward_normal
+            }),
+        );
+    }
+
+    /// Is this face visible to the camera? Always true for materials which
+    /// force the front face.
     ///
     /// # Arguments
     /// - self reference
     ///
     /// # Returns
     /// - boolean value indicating if the face is visible to the camera
+    #[allow(dead_code)]
     pub fn is_front_face(&self) -> bool {
-        glm::dot(&self.ray.direction, &self.outward_normal) < 0.0
+        self.front_face
+    }
+
+    /// Whether the material hit treats every hit as a front face hit.
+    fn forces_front_face(&self) -> bool {
+        self.material
+            .is_some_and(|material| material.force_front_face())
     }
 
     /// Normal vector at this intersection point.
@@ -36,12 +210,170 @@ impl<'a> HitRecord<'a> {
     /// # Returns
     /// - the normal vector corrected for the position of the camera
     pub fn normal(&self) -> Vec3 {
-        glm::normalize(
-            &(if self.is_front_face() {
-                self.outward_normal
-            } else {
-                -self.outward_normal
-            }),
-        )
+        self.shading_normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::hittable::triangle::Triangle;
+    use crate::hittable::Hittable;
+    use crate::hittable::HittableItem;
+    use crate::material::lambertian::Lambertian;
+
+    /// Rays toward the origin from every side, from outside and from near
+    /// the origin, for hitting surfaces through it from both sides.
+    fn rays_from_both_sides() -> Vec<Ray> {
+        let mut rays = Vec::new();
+        for i in 0..8 {
+            for j in 1..8 {
+                let (theta, phi) = (i as f32 * 0.8, j as f32 * 0.4);
+                let direction =
+                    glm::vec3(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+                let offset = glm::vec3(0.05, -0.03, 0.02);
+                rays.push(Ray::new(offset - 3.0 * direction, direction, None));
+                rays.push(Ray::new(offset, direction, None));
+            }
+        }
+        rays
+    }
+
+    #[test]
+    fn shading_normal_is_unit_and_faces_the_ray() {
+        for force_front_face in [false, true] {
+            let material = || {
+                MaterialType::Lambertian(Lambertian {
+                    albedo: glm::vec3(0.5, 0.5, 0.5),
+                    force_front_face,
+                })
+            };
+            let mut worlds = Vec::new();
+            for radius in [1.0, -1.0] {
+                let mut world = HittableList::new();
+                world.add_sphere(glm::vec3(0.0, 0.0, 0.0), radius, material());
+                worlds.push(world);
+            }
+            let mut plane = HittableList::new();
+            // tilted, and not normalized as given
+            plane.add_plane(
+                glm::vec3(0.0, 0.0, 0.0),
+                glm::vec3(1.0, 2.0, 0.5),
+                material(),
+            );
+            worlds.push(plane);
+            let mut triangle = HittableList::new();
+            triangle.add_triangle(
+                [
+                    glm::vec3(-2.0, -2.0, 0.0),
+                    glm::vec3(2.0, -2.0, 0.0),
+                    glm::vec3(0.0, 2.0, 0.0),
+                ],
+                material(),
+            );
+            worlds.push(triangle);
+
+            for world in worlds.iter() {
+                let mut front_faces = 0;
+                let mut back_faces = 0;
+                for ray in rays_from_both_sides() {
+                    let hit = match world.hit(&ray, 1e-4, f32::INFINITY) {
+                        Some(hit) => hit,
+                        None => continue,
+                    };
+                    let normal = hit.normal();
+                    assert!((glm::length(&normal) - 1.0).abs() < 1e-5);
+                    assert!(glm::dot(&normal, &ray.direction) < 0.0);
+                    if hit.is_front_face() {
+                        front_faces += 1;
+                    } else {
+                        back_faces += 1;
+                    }
+                }
+                // every surface is hit from both sides, which only counts as
+                // hitting the back when the material allows it
+                assert!(front_faces > 0);
+                assert_eq!(back_faces > 0, !force_front_face);
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_with_long_normals_record_unit_ones() {
+        let material = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        // the face normal of this triangle is the cross product of its
+        // edges, which is 36 long
+        let vertices = [
+            glm::vec3(-3.0, -3.0, 0.0),
+            glm::vec3(3.0, -3.0, 0.0),
+            glm::vec3(0.0, 3.0, 0.0),
+        ];
+        let mut world = HittableList::new();
+        world.add_triangle(vertices, material);
+        let up = glm::vec3(0.0, 0.0, 1.0);
+        for (start, front_face) in [(2.0, true), (-2.0, false)] {
+            let ray = Ray::new(glm::vec3(0.5, -0.5, start), -start.signum() * up, None);
+            let hit = world.hit(&ray, 1e-4, f32::INFINITY).unwrap();
+            assert!((hit.distance - 2.0).abs() < 1e-5);
+            assert!(glm::distance(&hit.hit_point, &glm::vec3(0.5, -0.5, 0.0)) < 1e-5);
+            assert!(glm::distance(&hit.outward_normal, &up) < 1e-6);
+            assert!(glm::distance(&hit.geometric_normal, &(36.0 * up)) < 1e-4);
+            assert_eq!(hit.is_front_face(), front_face);
+            assert!(glm::distance(&hit.normal(), &(start.signum() * up)) < 1e-6);
+        }
+
+        // vertex normals of three lengths, and leaning different ways, are
+        // blended by where the ray lands and then brought to unit length
+        let normals = [
+            glm::vec3(0.1, 0.0, 0.5),
+            glm::vec3(0.0, -1.0, 3.0),
+            glm::vec3(-2.0, 0.0, 7.0),
+        ];
+        let mut world = HittableList::new();
+        world.add(HittableItem::Triangle(Triangle {
+            vertex_normals: normals,
+            ..Triangle::new(vertices, material)
+        }));
+        let ray = Ray::new(glm::vec3(0.5, -0.5, 2.0), -up, None);
+        let hit = world.hit(&ray, 1e-4, f32::INFINITY).unwrap();
+        // (0.5, -0.5) is 0.375 of the way along the first edge and 5 / 12 of
+        // the way along the second
+        let (u, v) = (0.375, 5.0 / 12.0);
+        let expected =
+            glm::normalize(&((1.0 - u - v) * normals[0] + u * normals[1] + v * normals[2]));
+        assert!(
+            glm::distance(&hit.outward_normal, &expected) < 1e-5,
+            "{:?} {:?}",
+            hit.outward_normal,
+            expected
+        );
+        assert!(hit.is_front_face());
+        assert!(glm::distance(&hit.normal(), &expected) < 1e-5);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "has length 2")]
+    fn long_outward_normal_panics_in_debug_builds() {
+        let material = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, 0.0, -1.0), None);
+        let normal = glm::vec3(0.0, 0.0, 2.0);
+        HitRecord::new(&ray, 1.0, normal, normal, Some(&material));
+    }
+
+    #[test]
+    fn long_normal_without_a_material_is_not_checked() {
+        // bounding boxes report hits with no surface to shade
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, 0.0, -1.0), None);
+        let normal = glm::vec3(0.0, 0.0, 2.0);
+        let hit = HitRecord::new(&ray, 1.0, normal, normal, None);
+        assert_eq!(hit.normal(), glm::vec3(0.0, 0.0, 1.0));
     }
 }
@@ -1,14 +1,18 @@
 pub mod aabb;
 pub mod bvh;
 pub mod hittable_list;
+pub mod medium;
 pub mod mesh;
 pub mod plane;
+pub mod ply;
+pub mod polygon;
 pub mod rectangle;
 pub mod sphere;
 pub mod triangle;
 
 use crate::hit_record::HitRecord;
 use crate::hittable::aabb::AABB;
+use crate::hittable::medium::ConstantMedium;
 use crate::hittable::mesh::Mesh;
 use crate::hittable::plane::Plane;
 use crate::hittable::rectangle::Rectangle;
@@ -30,7 +34,46 @@ pub trait Hittable {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>>;
+
+    /// Check whether an object is hit by a ray anywhere in a range, for
+    /// shadow rays which only need to know if something is in the way.
+    /// Unlike `hit`, collections can stop at the first hit they find
+    /// rather than searching for the closest.
+    ///
+    /// # Arguments
+    /// - `ray` the ray to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the ray
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - `bool` - true if `hit` would find a hit, otherwise false
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        self.hit(ray, min_distance, max_distance).is_some()
+    }
+
+    /// Find the closest hit along each of several rays, exactly as `hit`
+    /// would find for each ray alone. Objects which can share work between
+    /// rays close together, like BVHs testing one box against several rays
+    /// at once, trace them in packets; the rest test each ray in turn.
+    ///
+    /// # Arguments
+    /// - `rays` the rays to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the rays
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - Optional `HitRecord` for each ray, in the same order as the rays
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
+        rays.iter()
+            .map(|ray| self.hit(ray, min_distance, max_distance))
+            .collect()
+    }
 
     /// Finite objects fit into bounding boxes. If this is the case, they
     /// can provide their bounding box.
@@ -46,6 +89,7 @@ pub trait Hittable {
 /// Enumerate all possible hittables here. These are the only hittables, so
 /// dynamic dispatch is avoided.
 pub enum HittableItem {
+    ConstantMedium(ConstantMedium),
     Mesh(Mesh),
     Plane(Plane),
     Rectangle(Rectangle),
@@ -66,8 +110,11 @@ impl Hittable for HittableItem {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
         match *self {
+            HittableItem::ConstantMedium(ref hittable) => {
+                hittable.hit(ray, min_distance, max_distance)
+            }
             HittableItem::Mesh(ref hittable) => hittable.hit(ray, min_distance, max_distance),
             HittableItem::Plane(ref hittable) => hittable.hit(ray, min_distance, max_distance),
             HittableItem::Rectangle(ref hittable) => hittable.hit(ray, min_distance, max_distance),
@@ -79,6 +126,60 @@ impl Hittable for HittableItem {
         }
     }
 
+    /// Check whether the child is hit anywhere in a range.
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        match *self {
+            HittableItem::ConstantMedium(ref hittable) => {
+                hittable.any_hit(ray, min_distance, max_distance)
+            }
+            HittableItem::Mesh(ref hittable) => hittable.any_hit(ray, min_distance, max_distance),
+            HittableItem::Plane(ref hittable) => hittable.any_hit(ray, min_distance, max_distance),
+            HittableItem::Rectangle(ref hittable) => {
+                hittable.any_hit(ray, min_distance, max_distance)
+            }
+            HittableItem::Sphere(ref hittable) => hittable.any_hit(ray, min_distance, max_distance),
+            HittableItem::Triangle(ref hittable) => {
+                hittable.any_hit(ray, min_distance, max_distance)
+            }
+            HittableItem::TriangleList(ref hittable) => {
+                hittable.any_hit(ray, min_distance, max_distance)
+            }
+        }
+    }
+
+    /// Find the closest hit along each of several rays with the child,
+    /// which meshes trace in packets through their BVH.
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
+        match *self {
+            HittableItem::ConstantMedium(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::Mesh(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::Plane(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::Rectangle(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::Sphere(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::Triangle(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+            HittableItem::TriangleList(ref hittable) => {
+                hittable.hit_packet(rays, min_distance, max_distance)
+            }
+        }
+    }
+
     /// Finite objects fit into bounding boxes. If this is the case, they
     /// can provide their bounding box.
     ///
@@ -89,6 +190,7 @@ impl Hittable for HittableItem {
     /// - Optional 'AABB' which encloses the hittable.
     fn bounding_box(&self) -> Option<AABB> {
         match *self {
+            HittableItem::ConstantMedium(ref hittable) => hittable.bounding_box(),
             HittableItem::Mesh(ref hittable) => hittable.bounding_box(),
             HittableItem::Plane(ref hittable) => hittable.bounding_box(),
             HittableItem::Rectangle(ref hittable) => hittable.bounding_box(),
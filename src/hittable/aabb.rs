@@ -1,10 +1,20 @@
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::ray::Ray;
+use crate::ray::RayPacket;
+use crate::ray::PACKET_WIDTH;
 use glm::Vec3;
 
+#[cfg(test)]
+thread_local! {
+    /// Number of rays `entry_distance` has tested against boxes on this
+    /// thread, for tests comparing how much work traversals do
+    pub static BOX_TESTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 /// Model an axis-aligned bounding box
 #[derive(Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct AABB {
     /// Minimal point of the bounded space
     pub minimum_point: Vec3,
@@ -23,33 +33,12 @@ impl Hittable for AABB {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        for dimension in 0..3 {
-            let t0: f32 = ((self.minimum_point[dimension] - ray.origin[dimension])
-                / ray.direction[dimension])
-                .min(
-                    (self.maximum_point[dimension] - ray.origin[dimension])
-                        / ray.direction[dimension],
-                );
-            let t1: f32 = ((self.minimum_point[dimension] - ray.origin[dimension])
-                / ray.direction[dimension])
-                .max(
-                    (self.maximum_point[dimension] - ray.origin[dimension])
-                        / ray.direction[dimension],
-                );
-            let t_min = t0.max(min_distance);
-            let t_max = t1.min(max_distance);
-            if t_max <= t_min {
-                return None;
-            }
-        }
-        Some(HitRecord {
-            hit_point: glm::vec3(0.0, 0.0, 0.0),
-            ray: *ray,
-            distance: 0.0,
-            outward_normal: glm::vec3(0.0, 0.0, 0.0),
-            material: None,
-        })
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        self.entry_distance(ray, min_distance, max_distance)?;
+        let zero = glm::vec3(0.0, 0.0, 0.0);
+        let mut hit_record = HitRecord::new(ray, 0.0, zero, zero, None);
+        hit_record.hit_point = zero;
+        Some(hit_record)
     }
 
     /// The bounding box of a bounding box is itself.
@@ -59,6 +48,95 @@ impl Hittable for AABB {
 }
 
 impl AABB {
+    /// Find the distance at which a ray enters this box, clamped to the given
+    /// range. The slab test uses the ray's cached inverse direction, so there
+    /// are no divisions per box.
+    ///
+    /// # Arguments
+    /// - `ray` the ray to test against the box
+    /// - `min_distance` the minimum distance of intersections along the ray
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - Optional entry distance if the ray passes through the box in range
+    pub fn entry_distance(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<f32> {
+        #[cfg(test)]
+        BOX_TESTS.with(|count| count.set(count.get() + 1));
+        // the ray is inside the box where its intervals inside all three
+        // slabs overlap
+        let mut t_min = min_distance;
+        let mut t_max = max_distance;
+        for dimension in 0..3 {
+            // the slab is entered at the near face and left at the far face
+            let (near, far) = if ray.direction_is_negative[dimension] {
+                (self.maximum_point[dimension], self.minimum_point[dimension])
+            } else {
+                (self.minimum_point[dimension], self.maximum_point[dimension])
+            };
+            let t0 = (near - ray.origin[dimension]) * ray.inverse_direction[dimension];
+            let t1 = (far - ray.origin[dimension]) * ray.inverse_direction[dimension];
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            // a flat box, such as around an axis-aligned triangle, is
+            // entered and left at the same distance, which is still a hit
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    /// Find the distances at which each ray of a packet enters this box, the
+    /// same as `entry_distance` gives for each ray alone. Every step of the
+    /// slab test is done across all the lanes at once, which the compiler
+    /// turns into wide operations, and rays which miss are only found after
+    /// all three slabs rather than at the first they miss.
+    ///
+    /// # Arguments
+    /// - `packet` the rays to test against the box
+    /// - `min_distance` the minimum distance of intersections along the rays
+    /// - `max_distances` the maximum distance of intersections along each ray
+    ///
+    /// # Returns
+    /// - Optional entry distance of each lane, `None` for rays which miss
+    ///   and for lanes past the packet's last ray
+    pub fn entry_distances(
+        &self,
+        packet: &RayPacket,
+        min_distance: f32,
+        max_distances: &[f32; PACKET_WIDTH],
+    ) -> [Option<f32>; PACKET_WIDTH] {
+        let mut t_min = [min_distance; PACKET_WIDTH];
+        let mut t_max = *max_distances;
+        for dimension in 0..3 {
+            let minimum = self.minimum_point[dimension];
+            let maximum = self.maximum_point[dimension];
+            for lane in 0..PACKET_WIDTH {
+                let origin = packet.origin[dimension][lane];
+                let inverse_direction = packet.inverse_direction[dimension][lane];
+                let t_minimum = (minimum - origin) * inverse_direction;
+                let t_maximum = (maximum - origin) * inverse_direction;
+                // the slab is entered at the near face and left at the far face
+                let (t0, t1) = if packet.direction_is_negative[dimension][lane] {
+                    (t_maximum, t_minimum)
+                } else {
+                    (t_minimum, t_maximum)
+                };
+                t_min[lane] = t0.max(t_min[lane]);
+                t_max[lane] = t1.min(t_max[lane]);
+            }
+        }
+        let mut entries = [None; PACKET_WIDTH];
+        for lane in 0..packet.rays.len() {
+            // the interval only shrinks, so a ray which missed one slab
+            // still misses here
+            if t_max[lane] >= t_min[lane] {
+                entries[lane] = Some(t_min[lane]);
+            }
+        }
+        entries
+    }
+
     /// Produce a new box which surrounds both of the given boxes.
     ///
     /// # Arguments
@@ -93,4 +171,17 @@ impl AABB {
     pub fn centroid(&self) -> Vec3 {
         (self.minimum_point + self.maximum_point) / 2.0
     }
+
+    /// Find the total area of the six faces of this AABB, which is
+    /// proportional to the chance a random ray hits it.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `f32` - the surface area
+    pub fn surface_area(&self) -> f32 {
+        let extent = self.maximum_point - self.minimum_point;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
 }
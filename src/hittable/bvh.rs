@@ -4,12 +4,38 @@ use crate::hittable::triangle::Triangle;
 use crate::hittable::triangle::TriangleList;
 use crate::hittable::Hittable;
 use crate::ray::Ray;
+use crate::ray::RayPacket;
+use crate::ray::PACKET_WIDTH;
+use crate::stats::GeometryCounts;
 
+/// Options for building BVHs, which trade build time against how fast the
+/// built tree is to trace. Every layout gives the same hits.
+#[derive(Clone, Copy)]
+pub struct BvhSettings {
+    /// Most triangles to put in a leaf
+    pub leaf_max: usize,
+    /// Number of bins to evaluate the surface area heuristic over when
+    /// choosing each split, or zero to split at the mean centroid
+    pub bins: usize,
+}
+
+impl Default for BvhSettings {
+    /// Leaves of up to 32 triangles, split at the mean centroid.
+    fn default() -> BvhSettings {
+        BvhSettings {
+            leaf_max: 32,
+            bins: 0,
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub enum BVHNode {
     BVH(Box<BVH>),
     HittableList(Box<TriangleList>),
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct BVH {
     /// Left subtree
     pub left: BVHNode,
@@ -29,10 +55,33 @@ impl Hittable for BVHNode {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        match *self {
+            BVHNode::BVH(ref node) => node.hit(ray, min_distance, max_distance),
+            BVHNode::HittableList(ref node) => node.hit(ray, min_distance, max_distance),
+        }
+    }
+
+    /// Forward the occlusion test to the appropriate method for each type
+    /// of BVH node.
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        match *self {
+            BVHNode::BVH(ref node) => node.any_hit(ray, min_distance, max_distance),
+            BVHNode::HittableList(ref node) => node.any_hit(ray, min_distance, max_distance),
+        }
+    }
+
+    /// Forward the packet to the appropriate method for each type of BVH
+    /// node.
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
         match *self {
-            BVHNode::BVH(ref node) => node.hit(&ray, min_distance, max_distance),
-            BVHNode::HittableList(ref node) => node.hit(&ray, min_distance, max_distance),
+            BVHNode::BVH(ref node) => node.hit_packet(rays, min_distance, max_distance),
+            BVHNode::HittableList(ref node) => node.hit_packet(rays, min_distance, max_distance),
         }
     }
 
@@ -56,20 +105,66 @@ impl Hittable for BVH {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        if let Some(_hit) = &self.bounding_box.hit(&ray, min_distance, max_distance) {
-            if let Some(hit_left) = self.left.hit(&ray, min_distance, max_distance) {
-                if let Some(hit_right) = self.right.hit(&ray, min_distance, hit_left.distance) {
-                    Some(hit_right)
-                } else {
-                    Some(hit_left)
-                }
-            } else {
-                self.right.hit(&ray, min_distance, max_distance)
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        self.bounding_box
+            .entry_distance(ray, min_distance, max_distance)?;
+        self.hit_children(ray, min_distance, max_distance)
+    }
+
+    /// Check whether any triangle in this BVH is hit by a ray in a range.
+    /// Children are searched without ordering them, and the search stops
+    /// at the first triangle hit, so no hit record has to be kept.
+    ///
+    /// # Arguments
+    /// - `ray` the ray to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the ray
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - `bool` - true if `hit` would find a hit, otherwise false
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        self.bounding_box
+            .entry_distance(ray, min_distance, max_distance)
+            .is_some()
+            && self.any_hit_children(ray, min_distance, max_distance)
+    }
+
+    /// Find the closest hit along each of several rays, tracing them
+    /// through the tree `PACKET_WIDTH` at a time. Each box is tested
+    /// against the whole packet at once, and a packet only goes down into a
+    /// child while at least one of its rays could still hit something
+    /// there, so coherent rays share the work of the boxes and only split
+    /// at the leaves, where each ray is tested against the triangles alone.
+    /// Every ray searches the children in the order `hit` would, so the hits
+    /// are the same as tracing the rays one by one, ties included.
+    ///
+    /// # Arguments
+    /// - `rays` the rays to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the rays
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - Optional `HitRecord` for each ray, in the same order as the rays
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
+        let mut hits = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(PACKET_WIDTH) {
+            let packet = RayPacket::new(chunk);
+            let mut closest = PacketHits::new(max_distance);
+            let entries =
+                self.bounding_box
+                    .entry_distances(&packet, min_distance, &closest.distances);
+            let active = entries.map(|entry| entry.is_some());
+            if active.contains(&true) {
+                self.hit_children_packet(&packet, min_distance, &active, &mut closest);
             }
-        } else {
-            None
+            hits.extend(IntoIterator::into_iter(closest.hits).take(chunk.len()));
         }
+        hits
     }
 
     /// Return the bounding box for this BVH.
@@ -78,23 +173,286 @@ impl Hittable for BVH {
     }
 }
 
+impl BVHNode {
+    /// Distance at which a ray enters this node's bounding box, if it does.
+    fn entry_distance(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<f32> {
+        self.bounding_box()?
+            .entry_distance(ray, min_distance, max_distance)
+    }
+
+    /// Hit the contents of this node, assuming its box has already been hit.
+    fn hit_contents(
+        &self,
+        ray: &Ray,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        match *self {
+            BVHNode::BVH(ref node) => node.hit_children(ray, min_distance, max_distance),
+            BVHNode::HittableList(ref node) => node.hit(ray, min_distance, max_distance),
+        }
+    }
+
+    /// Distances at which each ray of a packet enters this node's bounding
+    /// box, see `AABB::entry_distances`.
+    fn entry_distances(
+        &self,
+        packet: &RayPacket,
+        min_distance: f32,
+        max_distances: &[f32; PACKET_WIDTH],
+    ) -> [Option<f32>; PACKET_WIDTH] {
+        match self.bounding_box() {
+            Some(bounding_box) => bounding_box.entry_distances(packet, min_distance, max_distances),
+            None => [None; PACKET_WIDTH],
+        }
+    }
+
+    /// Hit the contents of this node with the active rays of a packet,
+    /// assuming its box has already been hit by them, keeping any hit
+    /// closer than the closest so far. Leaves test each active ray against
+    /// their triangles alone.
+    fn hit_contents_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        min_distance: f32,
+        active: &[bool; PACKET_WIDTH],
+        closest: &mut PacketHits<'a>,
+    ) {
+        match *self {
+            BVHNode::BVH(ref node) => {
+                node.hit_children_packet(packet, min_distance, active, closest)
+            }
+            BVHNode::HittableList(ref node) => {
+                for (lane, ray) in packet.rays.iter().enumerate() {
+                    if !active[lane] {
+                        continue;
+                    }
+                    if let Some(hit) = node.hit(ray, min_distance, closest.distances[lane]) {
+                        closest.keep(lane, hit);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check the contents of this node for any hit, assuming its box has
+    /// already been hit.
+    fn any_hit_contents(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        match *self {
+            BVHNode::BVH(ref node) => node.any_hit_children(ray, min_distance, max_distance),
+            BVHNode::HittableList(ref node) => node.any_hit(ray, min_distance, max_distance),
+        }
+    }
+
+    /// Number of triangles in the leaves under this node whose boxes a ray
+    /// enters, see `BVH::count_candidates`.
+    fn count_candidates(&self, ray: &Ray) -> usize {
+        if self.entry_distance(ray, 0.0, f32::INFINITY).is_none() {
+            return 0;
+        }
+        match *self {
+            BVHNode::BVH(ref node) => node.count_candidates(ray),
+            BVHNode::HittableList(ref node) => node.triangle_count(),
+        }
+    }
+}
+
 impl BVH {
-    pub fn build(objects: Vec<Triangle>, max_at_leaf: usize) -> BVH {
-        // compute all bounding boxes and centroids
-        let mut bounding_boxes: Vec<AABB> = Vec::new();
-        let mut centroids: Vec<glm::Vec3> = Vec::new();
-        for object in objects.iter() {
-            if let Some(bbox) = object.bounding_box() {
-                bounding_boxes.push(bbox);
-                centroids.push(bbox.centroid());
+    /// Count the triangles and nodes in this BVH, including its leaves.
+    pub fn geometry_counts(&self) -> GeometryCounts {
+        let mut counts = GeometryCounts {
+            triangles: 0,
+            bvh_nodes: 1,
+        };
+        for child in [&self.left, &self.right].iter() {
+            match child {
+                BVHNode::BVH(bvh) => {
+                    let child_counts = bvh.geometry_counts();
+                    counts.triangles += child_counts.triangles;
+                    counts.bvh_nodes += child_counts.bvh_nodes;
+                }
+                BVHNode::HittableList(list) => {
+                    counts.triangles += list.triangle_count();
+                    counts.bvh_nodes += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Call a function on every triangle in this BVH, leaf by leaf.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `visit: &mut F` - the function to call
+    pub fn for_each_triangle<F: FnMut(&Triangle)>(&self, visit: &mut F) {
+        for child in [&self.left, &self.right].iter() {
+            match child {
+                BVHNode::BVH(bvh) => bvh.for_each_triangle(visit),
+                BVHNode::HittableList(list) => list.triangles().iter().for_each(&mut *visit),
             }
         }
+    }
+
+    /// Count the triangles a ray would be tested against: those in every
+    /// leaf whose bounding box the ray enters anywhere ahead of its origin.
+    /// `hit` tests fewer once it has a hit to skip further boxes by, so
+    /// this is a bound on its work, for judging how well a BVH fits a mesh.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `ray: &Ray` - the ray to trace
+    ///
+    /// # Returns
+    /// - `usize` - the number of candidate triangles
+    pub fn count_candidates(&self, ray: &Ray) -> usize {
+        if self
+            .bounding_box
+            .entry_distance(ray, 0.0, f32::INFINITY)
+            .is_none()
+        {
+            return 0;
+        }
+        self.left.count_candidates(ray) + self.right.count_candidates(ray)
+    }
+
+    /// Check the children of this BVH for any hit, assuming its box has
+    /// already been hit.
+    fn any_hit_children(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        [&self.left, &self.right].iter().any(|child| {
+            child
+                .entry_distance(ray, min_distance, max_distance)
+                .is_some()
+                && child.any_hit_contents(ray, min_distance, max_distance)
+        })
+    }
+
+    /// Hit the children of this BVH front to back. The child whose box the
+    /// ray enters first is searched first, and the other child is skipped
+    /// when the closest hit so far is in front of its box. The nearest hit
+    /// in either child is returned even when their boxes overlap in depth,
+    /// and of hits at the same distance, the one found first is kept.
+    ///
+    /// # Arguments
+    /// - `ray` the ray to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the ray
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - Optional `HitRecord` if there was a hit, otherwise `None`.
+    fn hit_children(
+        &self,
+        ray: &Ray,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'_>> {
+        let left_entry = self.left.entry_distance(ray, min_distance, max_distance);
+        let right_entry = self.right.entry_distance(ray, min_distance, max_distance);
+        let ordered = match (left_entry, right_entry) {
+            (Some(left), Some(right)) if right < left => {
+                [(right_entry, &self.right), (left_entry, &self.left)]
+            }
+            _ => [(left_entry, &self.left), (right_entry, &self.right)],
+        };
+
+        let mut closest_hit: Option<HitRecord> = None;
+        let mut closest_distance = max_distance;
+        for (entry, child) in ordered.iter() {
+            match entry {
+                Some(entry) if *entry < closest_distance => {
+                    if let Some(hit) = child.hit_contents(ray, min_distance, closest_distance) {
+                        // the bound already keeps out further hits, but a
+                        // farther or tied hit must never replace the one
+                        // found first
+                        if hit.distance < closest_distance {
+                            closest_distance = hit.distance;
+                            closest_hit = Some(hit);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        closest_hit
+    }
+
+    /// Hit the children of this BVH with the active rays of a packet,
+    /// assuming its box has already been hit by them, giving each ray the
+    /// hit `hit_children` would. Each ray searches first the child whose
+    /// box it enters first, so rays which disagree are split: the left
+    /// child is searched by the rays which start there, then the right by
+    /// every ray, then the left again by the rest. Rays which won't search
+    /// a child, or whose closest hit is already in front of its box, are
+    /// masked off, and a child none of the rays search is skipped.
+    ///
+    /// # Arguments
+    /// - `packet` the rays to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the rays
+    /// - `active` which lanes to search
+    /// - `closest` the closest hit of each lane so far, whose distances
+    ///   bound the search and which closer hits replace
+    fn hit_children_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        min_distance: f32,
+        active: &[bool; PACKET_WIDTH],
+        closest: &mut PacketHits<'a>,
+    ) {
+        let left_entries = self
+            .left
+            .entry_distances(packet, min_distance, &closest.distances);
+        let right_entries = self
+            .right
+            .entry_distances(packet, min_distance, &closest.distances);
+        let right_first: [bool; PACKET_WIDTH] =
+            std::array::from_fn(|lane| match (left_entries[lane], right_entries[lane]) {
+                (Some(left), Some(right)) => right < left,
+                _ => false,
+            });
+        let left_first = right_first.map(|right_first| !right_first);
+        let searches = [
+            (&self.left, &left_entries, left_first),
+            (&self.right, &right_entries, [true; PACKET_WIDTH]),
+            (&self.left, &left_entries, right_first),
+        ];
+        for (child, entries, lanes) in searches.iter() {
+            let searching: [bool; PACKET_WIDTH] = std::array::from_fn(|lane| {
+                active[lane]
+                    && lanes[lane]
+                    && entries[lane].is_some_and(|entry| entry < closest.distances[lane])
+            });
+            if searching.contains(&true) {
+                child.hit_contents_packet(packet, min_distance, &searching, closest);
+            }
+        }
+    }
+
+    /// Build a BVH over triangles, splitting each node along the axis its
+    /// centroids are most spread over until the leaves are small enough.
+    /// Only triangles go in, and every triangle has a box, so every leaf's
+    /// box is the union of its triangles' boxes and every node's box is the
+    /// union of its children's. Objects without a box, like planes, stay in
+    /// the scene's `HittableList` beside the meshes.
+    ///
+    /// # Arguments
+    /// - `objects: Vec<Triangle>` - the triangles, at least one
+    /// - `settings: BvhSettings` - leaf size and how to choose each split
+    ///
+    /// # Returns
+    /// - the root of the BVH
+    pub fn build(objects: Vec<Triangle>, settings: BvhSettings) -> BVH {
+        // compute all bounding boxes and centroids, one for each triangle
+        let bounding_boxes: Vec<AABB> = objects
+            .iter()
+            .map(|triangle| triangle.bounding_box().unwrap())
+            .collect();
+        let centroids: Vec<glm::Vec3> = bounding_boxes.iter().map(AABB::centroid).collect();
 
         // compute the bounding box of this BVH (sub)tree
         let bounding_box = bounding_boxes
             .iter()
             .fold(bounding_boxes[0], |expanding, next| {
-                AABB::surrounding_box(&expanding, &*next)
+                AABB::surrounding_box(&expanding, next)
             });
 
         // find the axis with the greatest spread
@@ -117,14 +475,29 @@ impl BVH {
         let projected_centroids: Vec<f32> = centroids.iter().map(|c| c[split_axis]).collect();
         let midpoint = projected_centroids.iter().sum::<f32>() / (projected_centroids.len() as f32);
 
-        // partition the objects to the 'left' and 'right' of the midpoint
+        // with bins, split at the bin boundary with the lowest surface area
+        // heuristic cost instead of at the midpoint
+        let minimum = projected_centroids
+            .iter()
+            .fold(f32::INFINITY, |min, c| min.min(*c));
+        let bin_of = |position: f32| {
+            (((position - minimum) / maximal_spread * settings.bins as f32) as usize)
+                .min(settings.bins - 1)
+        };
+        let split_bin = if settings.bins > 1 && maximal_spread > 0.0 {
+            cheapest_split(&bounding_boxes, &projected_centroids, bin_of, settings.bins)
+        } else {
+            None
+        };
+
+        // partition the objects to the 'left' and 'right' of the split
         let mut lefts: Vec<Triangle> = Vec::new();
         let mut rights: Vec<Triangle> = Vec::new();
-        for obj in objects.into_iter() {
-            let mut which = true;
-            if let Some(bbox) = obj.bounding_box() {
-                which = bbox.centroid()[split_axis] < midpoint;
-            }
+        for (obj, position) in objects.into_iter().zip(projected_centroids.iter()) {
+            let which = match split_bin {
+                Some(split_bin) => bin_of(*position) < split_bin,
+                None => *position < midpoint,
+            };
             if which {
                 lefts.push(obj);
             } else {
@@ -132,18 +505,24 @@ impl BVH {
             }
         }
 
-        let left: BVHNode;
-        if lefts.len() > max_at_leaf {
-            left = BVHNode::BVH(Box::new(BVH::build(lefts, max_at_leaf)));
-        } else {
-            left = BVHNode::HittableList(Box::new(TriangleList::new(lefts)));
+        // centroids in one spot all land on one side, which would recurse
+        // forever, so split those in half instead
+        if lefts.is_empty() && rights.len() > settings.leaf_max {
+            lefts = rights.split_off(rights.len() / 2);
+        } else if rights.is_empty() && lefts.len() > settings.leaf_max {
+            rights = lefts.split_off(lefts.len() / 2);
         }
-        let right: BVHNode;
-        if rights.len() > max_at_leaf {
-            right = BVHNode::BVH(Box::new(BVH::build(rights, max_at_leaf)));
+
+        let left = if lefts.len() > settings.leaf_max {
+            BVHNode::BVH(Box::new(BVH::build(lefts, settings)))
         } else {
-            right = BVHNode::HittableList(Box::new(TriangleList::new(rights)));
-        }
+            BVHNode::HittableList(Box::new(TriangleList::new(lefts)))
+        };
+        let right = if rights.len() > settings.leaf_max {
+            BVHNode::BVH(Box::new(BVH::build(rights, settings)))
+        } else {
+            BVHNode::HittableList(Box::new(TriangleList::new(rights)))
+        };
 
         BVH {
             left,
@@ -152,3 +531,595 @@ impl BVH {
         }
     }
 }
+
+/// Closest hit found so far along each lane of a packet, for tracing
+/// packets through a BVH.
+struct PacketHits<'a> {
+    /// Distance of each lane's closest hit, or the furthest distance to
+    /// search if it has none yet
+    distances: [f32; PACKET_WIDTH],
+    /// Each lane's closest hit
+    hits: [Option<HitRecord<'a>>; PACKET_WIDTH],
+}
+
+impl<'a> PacketHits<'a> {
+    /// No hits yet, searching every lane up to a distance.
+    fn new(max_distance: f32) -> PacketHits<'a> {
+        PacketHits {
+            distances: [max_distance; PACKET_WIDTH],
+            hits: std::array::from_fn(|_| None),
+        }
+    }
+
+    /// Keep a lane's hit if it is closer than the lane's closest so far. As
+    /// in `BVH::hit_children`, a farther or tied hit never replaces the one
+    /// found first.
+    fn keep(&mut self, lane: usize, hit: HitRecord<'a>) {
+        if hit.distance < self.distances[lane] {
+            self.distances[lane] = hit.distance;
+            self.hits[lane] = Some(hit);
+        }
+    }
+}
+
+/// Find the bin boundary to split a node at with the lowest surface area
+/// heuristic cost, the number of triangles on each side weighted by the
+/// area of that side's bounding box.
+///
+/// # Arguments
+/// - `bounding_boxes: &[AABB]` - bounding box of each triangle
+/// - `positions: &[f32]` - centroid of each triangle along the split axis
+/// - `bin_of: F` - gives the bin a position falls in
+/// - `bins: usize` - number of bins
+///
+/// # Returns
+/// - `Option<usize>` - the first bin on the right of the split, or `None`
+///   if every triangle falls in one bin
+fn cheapest_split<F: Fn(f32) -> usize>(
+    bounding_boxes: &[AABB],
+    positions: &[f32],
+    bin_of: F,
+    bins: usize,
+) -> Option<usize> {
+    let mut counts = vec![0usize; bins];
+    let mut boxes: Vec<Option<AABB>> = vec![None; bins];
+    for (bbox, position) in bounding_boxes.iter().zip(positions.iter()) {
+        let bin = bin_of(*position);
+        counts[bin] += 1;
+        boxes[bin] = Some(match boxes[bin] {
+            Some(expanding) => AABB::surrounding_box(&expanding, bbox),
+            None => *bbox,
+        });
+    }
+
+    // sweep from the right to find the box and count right of each boundary
+    let mut right_costs = vec![0.0; bins];
+    let mut right_box: Option<AABB> = None;
+    let mut right_count = 0;
+    for bin in (1..bins).rev() {
+        right_box = merge(right_box, boxes[bin]);
+        right_count += counts[bin];
+        right_costs[bin] = right_box.map_or(0.0, |bbox| bbox.surface_area()) * right_count as f32;
+    }
+
+    // then from the left, keeping the cheapest boundary with triangles on
+    // both sides
+    let mut cheapest: Option<(usize, f32)> = None;
+    let mut left_box: Option<AABB> = None;
+    let mut left_count = 0;
+    for bin in 1..bins {
+        left_box = merge(left_box, boxes[bin - 1]);
+        left_count += counts[bin - 1];
+        if left_count == 0 || left_count == positions.len() {
+            continue;
+        }
+        let cost =
+            left_box.map_or(0.0, |bbox| bbox.surface_area()) * left_count as f32 + right_costs[bin];
+        if cheapest.is_none_or(|(_, lowest)| cost < lowest) {
+            cheapest = Some((bin, cost));
+        }
+    }
+    cheapest.map(|(bin, _)| bin)
+}
+
+/// Box around two optional boxes, either of which may be missing.
+fn merge(first: Option<AABB>, second: Option<AABB>) -> Option<AABB> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(AABB::surrounding_box(&first, &second)),
+        (first, None) => first,
+        (None, second) => second,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::mesh::read_obj;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::Material;
+    use crate::material::MaterialType;
+    use crate::random::rng;
+    use crate::EPSILON;
+    use rand::Rng;
+
+    /// The color torus's triangles with a gray diffuse material.
+    fn torus_triangles() -> Vec<Triangle> {
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        read_obj("assets/color_torus.obj")
+            .unwrap()
+            .triangles(gray, None)
+    }
+
+    /// Rays slanting down through the torus from a grid above it.
+    fn grid_rays() -> Vec<Ray> {
+        let mut rays = Vec::new();
+        for i in 0..40 {
+            for j in 0..40 {
+                let origin = glm::vec3(i as f32 / 10.0 - 2.0, 3.0, j as f32 / 10.0 - 2.0);
+                rays.push(Ray::new(origin, glm::vec3(0.1, -1.0, 0.2), None));
+            }
+        }
+        rays
+    }
+
+    /// Closest hit in a tree searched left child first, then right, the
+    /// way `hit_children` searched before it ordered the children by
+    /// distance, testing the same boxes.
+    fn hit_left_then_right<'a>(
+        node: &'a BVHNode,
+        ray: &Ray,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Option<HitRecord<'a>> {
+        node.entry_distance(ray, min_distance, max_distance)?;
+        match node {
+            BVHNode::BVH(bvh) => {
+                let left = hit_left_then_right(&bvh.left, ray, min_distance, max_distance);
+                let bound = left.as_ref().map_or(max_distance, |hit| hit.distance);
+                hit_left_then_right(&bvh.right, ray, min_distance, bound).or(left)
+            }
+            BVHNode::HittableList(list) => list.hit(ray, min_distance, max_distance),
+        }
+    }
+
+    #[test]
+    fn front_to_back_traversal_tests_fewer_boxes_with_the_same_hits() {
+        use crate::hittable::aabb::BOX_TESTS;
+        // sixteen stacked layers of small quads, so every ray along z
+        // passes through all of them and only the first layer it meets
+        // matters
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let mut triangles = Vec::new();
+        for layer in 0..16 {
+            let z = -0.5 * layer as f32;
+            for i in 0..8 {
+                for j in 0..8 {
+                    let (x, y) = (i as f32 * 0.25 - 1.0, j as f32 * 0.25 - 1.0);
+                    let corner = |dx: f32, dy: f32| glm::vec3(x + dx, y + dy, z);
+                    triangles.push(Triangle::new(
+                        [corner(0.0, 0.0), corner(0.25, 0.0), corner(0.25, 0.25)],
+                        gray,
+                    ));
+                    triangles.push(Triangle::new(
+                        [corner(0.0, 0.0), corner(0.25, 0.25), corner(0.0, 0.25)],
+                        gray,
+                    ));
+                }
+            }
+        }
+        let root = BVHNode::BVH(Box::new(BVH::build(
+            triangles,
+            BvhSettings {
+                leaf_max: 4,
+                bins: 0,
+            },
+        )));
+
+        // rays from in front and from behind, so neither child order the
+        // build left suits every ray
+        let mut rays = Vec::new();
+        for i in 0..12 {
+            for j in 0..12 {
+                let (x, y) = (i as f32 * 0.15 - 0.83, j as f32 * 0.15 - 0.81);
+                rays.push(Ray::new(
+                    glm::vec3(x, y, 5.0),
+                    glm::vec3(0.02, 0.01, -1.0),
+                    None,
+                ));
+                rays.push(Ray::new(
+                    glm::vec3(x, y, -13.0),
+                    glm::vec3(-0.01, 0.02, 1.0),
+                    None,
+                ));
+            }
+        }
+
+        let box_tests = |trace: &dyn Fn(&Ray) -> Option<(u32, glm::Vec3)>| {
+            BOX_TESTS.with(|count| count.set(0));
+            let hits: Vec<Option<(u32, glm::Vec3)>> = rays.iter().map(trace).collect();
+            (hits, BOX_TESTS.with(|count| count.get()))
+        };
+        let (ordered_hits, ordered_tests) = box_tests(&|ray| {
+            root.hit(ray, EPSILON, f32::INFINITY)
+                .map(|hit| (hit.distance.to_bits(), hit.hit_point))
+        });
+        let (unordered_hits, unordered_tests) = box_tests(&|ray| {
+            hit_left_then_right(&root, ray, EPSILON, f32::INFINITY)
+                .map(|hit| (hit.distance.to_bits(), hit.hit_point))
+        });
+
+        assert!(ordered_hits == unordered_hits);
+        assert!(ordered_hits.iter().all(Option::is_some));
+        // both search the layer each ray meets first, but the unordered
+        // search goes on through every layer behind it for the rays the
+        // tree's order doesn't suit
+        assert!(
+            2 * ordered_tests < unordered_tests,
+            "{} box tests front to back, {} left then right",
+            ordered_tests,
+            unordered_tests
+        );
+    }
+
+    /// A square of two triangles facing +z, centered on (x, y) at depth z,
+    /// with a diffuse material of the given gray.
+    fn square(x: f32, y: f32, z: f32, half_size: f32, gray: f32) -> Vec<Triangle> {
+        let material = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(gray, gray, gray),
+            force_front_face: false,
+        });
+        let corner = |dx: f32, dy: f32| glm::vec3(x + dx * half_size, y + dy * half_size, z);
+        vec![
+            Triangle::new(
+                [corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0)],
+                material,
+            ),
+            Triangle::new(
+                [corner(-1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)],
+                material,
+            ),
+        ]
+    }
+
+    /// A BVH of two leaves holding the given triangles.
+    fn two_leaves(left: Vec<Triangle>, right: Vec<Triangle>) -> BVH {
+        let left = TriangleList::new(left);
+        let right = TriangleList::new(right);
+        BVH {
+            bounding_box: AABB::surrounding_box(
+                &left.bounding_box().unwrap(),
+                &right.bounding_box().unwrap(),
+            ),
+            left: BVHNode::HittableList(Box::new(left)),
+            right: BVHNode::HittableList(Box::new(right)),
+        }
+    }
+
+    /// Distance and gray of the closest hit along a ray down -z from z = 5
+    /// through the origin.
+    fn closest_down_z(bvh: &BVH) -> (f32, f32) {
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 5.0), glm::vec3(0.0, 0.0, -1.0), None);
+        let hit = bvh.hit(&ray, EPSILON, f32::INFINITY).unwrap();
+        (hit.distance, hit.material.unwrap().color().x)
+    }
+
+    #[test]
+    fn nearer_hit_in_the_child_entered_second_is_found() {
+        // the far child's box reaches up to z = 0 with a square off to the
+        // side, so the ray enters it first though it only hits at z = -8,
+        // and the child entered second holds the nearer square at z = -3
+        let mut far = square(0.0, 0.0, -8.0, 0.5, 0.2);
+        far.extend(square(4.0, 0.0, 0.0, 0.5, 0.2));
+        let near = square(0.0, 0.0, -3.0, 0.5, 0.7);
+        for bvh in [
+            two_leaves(far.clone(), near.clone()),
+            two_leaves(near.clone(), far.clone()),
+        ] {
+            let ray = Ray::new(glm::vec3(0.0, 0.0, 5.0), glm::vec3(0.0, 0.0, -1.0), None);
+            // the boxes overlap in depth from z = -3 to z = -8
+            let left_entry = bvh.left.entry_distance(&ray, EPSILON, f32::INFINITY);
+            let right_entry = bvh.right.entry_distance(&ray, EPSILON, f32::INFINITY);
+            let mut entries = [left_entry.unwrap(), right_entry.unwrap()];
+            entries.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(entries, [5.0, 8.0]);
+            assert_eq!(closest_down_z(&bvh), (8.0, 0.7));
+        }
+    }
+
+    #[test]
+    fn tied_entry_distances_still_give_the_closest_hit() {
+        // both children's boxes reach up to z = 0 with a square off to the
+        // side, so the ray enters both at the same distance
+        let with_decoy = |z: f32, gray: f32| {
+            let mut triangles = square(0.0, 0.0, z, 0.5, gray);
+            triangles.extend(square(4.0, 0.0, 0.0, 0.5, gray));
+            triangles
+        };
+        let far = with_decoy(-8.0, 0.2);
+        let near = with_decoy(-3.0, 0.7);
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 5.0), glm::vec3(0.0, 0.0, -1.0), None);
+        for bvh in [
+            two_leaves(far.clone(), near.clone()),
+            two_leaves(near.clone(), far.clone()),
+        ] {
+            assert_eq!(
+                bvh.left.entry_distance(&ray, EPSILON, f32::INFINITY),
+                bvh.right.entry_distance(&ray, EPSILON, f32::INFINITY)
+            );
+            assert_eq!(closest_down_z(&bvh), (8.0, 0.7));
+        }
+
+        // and of two hits at the same distance too, the left child's,
+        // searched first, is kept
+        let bvh = two_leaves(with_decoy(-3.0, 0.4), with_decoy(-3.0, 0.7));
+        assert_eq!(closest_down_z(&bvh), (8.0, 0.4));
+    }
+
+    /// Every leaf under a node.
+    fn leaves<'a>(node: &'a BVHNode, found: &mut Vec<&'a TriangleList>) {
+        match node {
+            BVHNode::BVH(bvh) => {
+                leaves(&bvh.left, found);
+                leaves(&bvh.right, found);
+            }
+            BVHNode::HittableList(list) => found.push(list),
+        }
+    }
+
+    #[test]
+    fn count_candidates_matches_a_count_over_every_leaf() {
+        let bvh = BVH::build(
+            torus_triangles(),
+            BvhSettings {
+                leaf_max: 4,
+                bins: 0,
+            },
+        );
+        let mut all_leaves = Vec::new();
+        leaves(&bvh.left, &mut all_leaves);
+        leaves(&bvh.right, &mut all_leaves);
+        let mut triangles = Vec::new();
+        bvh.for_each_triangle(&mut |triangle| triangles.push(*triangle));
+
+        let mut rays = grid_rays();
+        for _ in 0..500 {
+            let point = || {
+                glm::vec3(
+                    rng().gen_range(-3.0..3.0),
+                    rng().gen_range(-3.0..3.0),
+                    rng().gen_range(-3.0..3.0),
+                )
+            };
+            rays.push(Ray::new(point(), point(), None));
+        }
+        let mut entered = 0;
+        for ray in rays.iter() {
+            // boxes nest, so a ray enters every box above a leaf it enters
+            let brute_force: usize = all_leaves
+                .iter()
+                .filter(|leaf| {
+                    leaf.bounding_box()
+                        .unwrap()
+                        .entry_distance(ray, 0.0, f32::INFINITY)
+                        .is_some()
+                })
+                .map(|leaf| leaf.triangle_count())
+                .sum();
+            let candidates = bvh.count_candidates(ray);
+            assert_eq!(candidates, brute_force);
+            // and every triangle the ray hits is among the candidates
+            let hit = triangles
+                .iter()
+                .filter(|triangle| triangle.hit(ray, 0.0, f32::INFINITY).is_some())
+                .count();
+            assert!(candidates >= hit);
+            entered += (candidates > 0) as usize;
+        }
+        assert!(entered > 500, "{} rays entered a leaf", entered);
+    }
+
+    #[test]
+    fn leaf_sizes_give_identical_hits() {
+        let single = BVH::build(
+            torus_triangles(),
+            BvhSettings {
+                leaf_max: 1,
+                bins: 0,
+            },
+        );
+        let default = BVH::build(torus_triangles(), BvhSettings::default());
+        let mut hits = 0;
+        for ray in grid_rays().iter() {
+            let first = single
+                .hit(ray, EPSILON, f32::INFINITY)
+                .map(|hit| hit.distance);
+            let second = default
+                .hit(ray, EPSILON, f32::INFINITY)
+                .map(|hit| hit.distance);
+            assert_eq!(first, second);
+            hits += first.is_some() as usize;
+        }
+        assert!(hits > 100, "{} hits", hits);
+    }
+
+    #[test]
+    fn any_hit_agrees_with_hit() {
+        let bvh = BVH::build(torus_triangles(), BvhSettings::default());
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let point = || {
+                glm::vec3(
+                    rng().gen_range(-3.0..3.0),
+                    rng().gen_range(-3.0..3.0),
+                    rng().gen_range(-3.0..3.0),
+                )
+            };
+            // from around the torus toward points near its middle
+            let origin = point();
+            let ray = Ray::new(origin, point() / 2.0 - origin, None);
+            // unbounded, and cut short inside the torus's box
+            for max_distance in [f32::INFINITY, rng().gen_range(0.0..3.0)] {
+                let hit = bvh.hit(&ray, EPSILON, max_distance).is_some();
+                assert_eq!(bvh.any_hit(&ray, EPSILON, max_distance), hit);
+                hits += hit as usize;
+            }
+        }
+        assert!(hits > 1000, "{} hits", hits);
+    }
+
+    #[test]
+    fn packet_hits_match_single_ray_hits() {
+        // camera rays fanning out from one point, which mostly share boxes,
+        // and random rays which mostly don't
+        let mut coherent = Vec::new();
+        for i in 0..41 {
+            for j in 0..41 {
+                let target = glm::vec3(i as f32 / 10.0 - 2.0, 0.0, j as f32 / 10.0 - 2.0);
+                let origin = glm::vec3(0.3, 4.0, 0.2);
+                coherent.push(Ray::new(origin, target - origin, None));
+            }
+        }
+        let point = || {
+            glm::vec3(
+                rng().gen_range(-3.0..3.0),
+                rng().gen_range(-3.0..3.0),
+                rng().gen_range(-3.0..3.0),
+            )
+        };
+        let incoherent: Vec<Ray> = (0..1681)
+            .map(|_| {
+                let origin = point();
+                Ray::new(origin, point() / 2.0 - origin, None)
+            })
+            .collect();
+
+        for leaf_max in [4, 32] {
+            let bvh = BVH::build(torus_triangles(), BvhSettings { leaf_max, bins: 0 });
+            for rays in [&coherent, &incoherent] {
+                let mut hits = 0;
+                // 1681 rays, so the last packet is one ray short of full
+                for max_distance in [f32::INFINITY, 4.0] {
+                    let packet = bvh.hit_packet(rays, EPSILON, max_distance);
+                    assert_eq!(packet.len(), rays.len());
+                    for (ray, packet_hit) in rays.iter().zip(packet.iter()) {
+                        let single = bvh.hit(ray, EPSILON, max_distance);
+                        match (single, packet_hit) {
+                            (Some(single), Some(packet_hit)) => {
+                                assert_eq!(
+                                    single.distance.to_bits(),
+                                    packet_hit.distance.to_bits()
+                                );
+                                assert_eq!(single.hit_point, packet_hit.hit_point);
+                                assert_eq!(single.outward_normal, packet_hit.outward_normal);
+                                assert_eq!(
+                                    single.barycentric_coordinates,
+                                    packet_hit.barycentric_coordinates
+                                );
+                                hits += 1;
+                            }
+                            (None, None) => {}
+                            _ => panic!("packet and single ray disagree on a hit"),
+                        }
+                    }
+                }
+                assert!(hits > 300, "{} hits", hits);
+            }
+        }
+    }
+
+    /// Check that a node's stored box is the union of its children's, all
+    /// the way down to the leaves, which must hold every triangle's box.
+    fn assert_boxes_nest(node: &BVHNode) -> AABB {
+        match node {
+            BVHNode::BVH(bvh) => {
+                let union = AABB::surrounding_box(
+                    &assert_boxes_nest(&bvh.left),
+                    &assert_boxes_nest(&bvh.right),
+                );
+                assert_eq!(union.minimum_point, bvh.bounding_box.minimum_point);
+                assert_eq!(union.maximum_point, bvh.bounding_box.maximum_point);
+                bvh.bounding_box
+            }
+            BVHNode::HittableList(list) => {
+                let stored = list.bounding_box().unwrap();
+                for triangle in list.triangles() {
+                    let bbox = triangle.bounding_box().unwrap();
+                    assert_eq!(
+                        AABB::surrounding_box(&stored, &bbox).minimum_point,
+                        stored.minimum_point
+                    );
+                    assert_eq!(
+                        AABB::surrounding_box(&stored, &bbox).maximum_point,
+                        stored.maximum_point
+                    );
+                }
+                stored
+            }
+        }
+    }
+
+    #[test]
+    fn node_boxes_are_the_union_of_their_children_for_a_mixed_build() {
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let point = || {
+            glm::vec3(
+                rng().gen_range(-3.0..3.0),
+                rng().gen_range(-3.0..3.0),
+                rng().gen_range(-3.0..3.0),
+            )
+        };
+        let mut triangles: Vec<Triangle> = (0..200)
+            .map(|_| {
+                let corner = point();
+                let spread = |_| corner + point() / 4.0;
+                Triangle::new([corner, spread(0), spread(1)], gray)
+            })
+            .collect();
+        // a triangle shrunk to a point, a sliver, and a stack of copies
+        // whose centroids all coincide
+        let spot = glm::vec3(0.5, 0.5, 0.5);
+        triangles.push(Triangle::new([spot, spot, spot], gray));
+        triangles.push(Triangle::new(
+            [spot, glm::vec3(2.5, 0.5, 0.5), glm::vec3(2.5, 0.5, 0.5001)],
+            gray,
+        ));
+        let stacked = [
+            glm::vec3(-1.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+        ];
+        for _ in 0..40 {
+            triangles.push(Triangle::new(stacked, gray));
+        }
+        let everything = TriangleList::new(triangles.clone());
+
+        for leaf_max in [1, 4, 32] {
+            for bins in [0, 8] {
+                let bvh = BVH::build(triangles.clone(), BvhSettings { leaf_max, bins });
+                let root = BVHNode::BVH(Box::new(bvh));
+                assert_boxes_nest(&root);
+                // the tree still finds the same closest hits as the list
+                for _ in 0..500 {
+                    let origin = point();
+                    let ray = Ray::new(origin, point() / 2.0 - origin, None);
+                    let expected = everything
+                        .hit(&ray, EPSILON, f32::INFINITY)
+                        .map(|hit| hit.distance);
+                    let found = root
+                        .hit(&ray, EPSILON, f32::INFINITY)
+                        .map(|hit| hit.distance);
+                    assert_eq!(found, expected, "leaf_max {} bins {}", leaf_max, bins);
+                }
+            }
+        }
+    }
+}
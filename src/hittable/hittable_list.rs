@@ -1,8 +1,23 @@
 use crate::hit_record::HitRecord;
 use crate::hittable::aabb::AABB;
+use crate::hittable::bvh::BvhSettings;
+use crate::hittable::mesh::read_obj;
+use crate::hittable::mesh::Mesh;
+use crate::hittable::mesh::MeshReport;
+use crate::hittable::plane::Plane;
+use crate::hittable::polygon;
+use crate::hittable::rectangle::Rectangle;
+use crate::hittable::sphere::Sphere;
+use crate::hittable::triangle::Triangle;
+use crate::hittable::triangle::TriangleList;
 use crate::hittable::Hittable;
 use crate::hittable::HittableItem;
+use crate::material::MaterialType;
 use crate::ray::Ray;
+use crate::stats::GeometryCounts;
+use glm::Vec3;
+use std::path::Path;
+use std::path::PathBuf;
 
 /// Represent a list of hittable objects
 pub struct HittableList {
@@ -37,7 +52,7 @@ impl HittableList {
     /// - `hittable: Box<dyn Hittable>` - new hittable to add to the collection
     pub fn add(&mut self, hittable: HittableItem) {
         self.objects.push(hittable);
-        let last_box = (&self.objects.last().unwrap()).bounding_box();
+        let last_box = self.objects.last().unwrap().bounding_box();
         self.bounding_box = if let Some(new_box) = last_box {
             AABB::surrounding_box(&self.bounding_box, &new_box)
         } else {
@@ -45,10 +60,241 @@ impl HittableList {
         }
     }
 
+    /// Check every mesh in the list, see `Mesh::validate`.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec<MeshReport>` - a report for each mesh, in the order they
+    ///   were added
+    pub fn mesh_reports(&self) -> Vec<MeshReport> {
+        self.objects
+            .iter()
+            .filter_map(|object| match object {
+                HittableItem::Mesh(mesh) => Some(mesh.validate()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Count the triangles and BVH nodes of everything in the list, for the
+    /// render statistics.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `GeometryCounts` - totals over all the objects
+    pub fn geometry_counts(&self) -> GeometryCounts {
+        let mut counts = GeometryCounts {
+            triangles: 0,
+            bvh_nodes: 0,
+        };
+        for object in self.objects.iter() {
+            match object {
+                HittableItem::Mesh(mesh) => {
+                    let mesh_counts = mesh.geometry_counts();
+                    counts.triangles += mesh_counts.triangles;
+                    counts.bvh_nodes += mesh_counts.bvh_nodes;
+                }
+                HittableItem::Rectangle(rectangle) => {
+                    counts.triangles += rectangle.triangle_count()
+                }
+                HittableItem::Triangle(_) => counts.triangles += 1,
+                HittableItem::TriangleList(list) => counts.triangles += list.triangle_count(),
+                HittableItem::ConstantMedium(_)
+                | HittableItem::Plane(_)
+                | HittableItem::Sphere(_) => {}
+            }
+        }
+        counts
+    }
+
+    /// Add a sphere to the list.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `center: Vec3` - center of the sphere
+    /// - `radius: f32` - radius of the sphere, negative for a hollow shell
+    /// - `material: MaterialType` - material of the sphere
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_sphere(&mut self, center: Vec3, radius: f32, material: MaterialType) -> &mut Self {
+        self.add(HittableItem::Sphere(Sphere {
+            center,
+            radius,
+            material,
+            velocity: glm::vec3(0.0, 0.0, 0.0),
+        }));
+        self
+    }
+
+    /// Add an infinite plane to the list.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `center: Vec3` - a point on the plane
+    /// - `normal: Vec3` - normal of the plane
+    /// - `material: MaterialType` - material of the plane
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_plane(&mut self, center: Vec3, normal: Vec3, material: MaterialType) -> &mut Self {
+        self.add(HittableItem::Plane(Plane::new(center, normal, material)));
+        self
+    }
+
+    /// Add a plane cut to a square around its center. Unlike an infinite
+    /// plane, it counts toward the list's bounding box.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `center: Vec3` - center of the square
+    /// - `normal: Vec3` - normal of the plane
+    /// - `half_size: f32` - half the side length of the square
+    /// - `material: MaterialType` - material of the plane
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    #[allow(dead_code)]
+    pub fn add_finite_plane(
+        &mut self,
+        center: Vec3,
+        normal: Vec3,
+        half_size: f32,
+        material: MaterialType,
+    ) -> &mut Self {
+        self.add(HittableItem::Plane(Plane::with_extent(
+            center, normal, half_size, material,
+        )));
+        self
+    }
+
+    /// Add a rectangle to the list.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `points: [Vec3; 4]` - four corners of the rectangle in counter-
+    ///   clockwise order
+    /// - `material: MaterialType` - material of the rectangle
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_rectangle(&mut self, points: [Vec3; 4], material: MaterialType) -> &mut Self {
+        self.add(HittableItem::Rectangle(Rectangle::new(points, material)));
+        self
+    }
+
+    /// Add a flat-shaded triangle to the list.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `vertices: [Vec3; 3]` - corners of the triangle
+    /// - `material: MaterialType` - material of the triangle
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_triangle(&mut self, vertices: [Vec3; 3], material: MaterialType) -> &mut Self {
+        self.add(HittableItem::Triangle(Triangle::new(vertices, material)));
+        self
+    }
+
+    /// Add a flat polygon to the list, which may be concave, split into
+    /// triangles by `polygon::triangulate`.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `vertices: &[Vec3]` - outline of the polygon in order, all in one
+    ///   plane, and not crossing itself
+    /// - `material: MaterialType` - material of the polygon
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_polygon(&mut self, vertices: &[Vec3], material: MaterialType) -> &mut Self {
+        let triangles = polygon::triangulate(vertices, material);
+        if !triangles.is_empty() {
+            self.add(HittableItem::TriangleList(TriangleList::new(triangles)));
+        }
+        self
+    }
+
+    /// Add a loaded mesh to the list.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `mesh: Mesh` - the mesh
+    ///
+    /// # Returns
+    /// - the list, so more objects can be added
+    pub fn add_mesh(&mut self, mesh: Mesh) -> &mut Self {
+        self.add(HittableItem::Mesh(mesh));
+        self
+    }
+
+    /// Load every OBJ file in a directory as a mesh and add them to the
+    /// list. Files are loaded in name order, and the triangle count of each
+    /// is printed. Files which fail to load are skipped with a warning, so
+    /// one bad file doesn't stop the rest.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `directory: &str` - path to the directory of OBJ files
+    /// - `material: F` - gives the material of each file from its path, so
+    ///   files can share one material or each have their own
+    /// - `bvh: BvhSettings` - how to build the BVH
+    /// - `single_bvh: bool` - build one BVH over the triangles of all the
+    ///   files, instead of one per file
+    ///
+    /// # Returns
+    /// - `std::io::Result<&mut Self>` - self reference for chaining, or the
+    ///   error if the directory itself couldn't be read
+    pub fn add_obj_directory<F: Fn(&Path) -> MaterialType>(
+        &mut self,
+        directory: &str,
+        material: F,
+        bvh: BvhSettings,
+        single_bvh: bool,
+    ) -> std::io::Result<&mut Self> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("obj"))
+            })
+            .collect();
+        paths.sort();
+
+        let mut all_triangles = Vec::new();
+        for path in paths.iter() {
+            let filename = path.to_string_lossy();
+            let data = match read_obj(&filename) {
+                Ok(data) => data,
+                Err(error) => {
+                    eprintln!("warning: skipping {}: {}", filename, error);
+                    continue;
+                }
+            };
+            let triangles = data.triangles(material(path), None);
+            println!("loaded {} triangles from {}", triangles.len(), filename);
+            if single_bvh {
+                all_triangles.extend(triangles);
+            } else {
+                self.add_mesh(Mesh::from_triangles(triangles, bvh));
+            }
+        }
+        if !all_triangles.is_empty() {
+            self.add_mesh(Mesh::from_triangles(all_triangles, bvh));
+        }
+        Ok(self)
+    }
+
     /// Create a HittableList from a vector of boxed hittables.
     ///
     /// # Arguments
     /// - vec, a vector of boxed hittables
+    #[allow(dead_code)]
     pub fn from_vec(vec: Vec<HittableItem>) -> HittableList {
         let mut list = HittableList::new();
         for object in vec.into_iter() {
@@ -73,14 +319,15 @@ impl Hittable for HittableList {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        let mut current_min = std::f32::INFINITY;
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        let mut current_min = f32::INFINITY;
         let mut closest_hit: Option<HitRecord> = None;
 
-        for object in self.objects.iter() {
-            if let Some(hit) = object.hit(&ray, min_distance, max_distance) {
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(mut hit) = object.hit(ray, min_distance, max_distance) {
                 if hit.distance < current_min {
                     current_min = hit.distance;
+                    hit.object_id = Some(index);
                     closest_hit = Some(hit);
                 }
             }
@@ -88,8 +335,117 @@ impl Hittable for HittableList {
         closest_hit
     }
 
+    /// Find the closest hit along each of several rays. Meshes are handed
+    /// all the rays at once to trace in packets through their BVHs, and
+    /// other objects are tested against each ray in turn.
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
+        let mut closest_hits: Vec<Option<HitRecord>> = rays.iter().map(|_| None).collect();
+        for (index, object) in self.objects.iter().enumerate() {
+            if let HittableItem::Mesh(mesh) = object {
+                let hits = mesh.hit_packet(rays, min_distance, max_distance);
+                for (closest_hit, hit) in closest_hits.iter_mut().zip(hits) {
+                    keep_closer(closest_hit, hit, index);
+                }
+            } else {
+                for (closest_hit, ray) in closest_hits.iter_mut().zip(rays) {
+                    keep_closer(
+                        closest_hit,
+                        object.hit(ray, min_distance, max_distance),
+                        index,
+                    );
+                }
+            }
+        }
+        closest_hits
+    }
+
+    /// Check the objects in order and stop at the first one hit.
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.any_hit(ray, min_distance, max_distance))
+    }
+
     /// Find the box which bounds all objects in the hittable list.
     fn bounding_box(&self) -> Option<AABB> {
         Some(self.bounding_box)
     }
 }
+
+/// Replace the closest hit found so far along a ray with another object's
+/// hit if it is closer, as `HittableList::hit` does.
+///
+/// # Arguments
+/// - `closest_hit` - the closest hit so far
+/// - `hit` - the object's hit, if any
+/// - `index` - position of the object in the list, its ID
+fn keep_closer<'a>(
+    closest_hit: &mut Option<HitRecord<'a>>,
+    hit: Option<HitRecord<'a>>,
+    index: usize,
+) {
+    if let Some(mut hit) = hit {
+        if closest_hit
+            .as_ref()
+            .is_none_or(|closest| hit.distance < closest.distance)
+        {
+            hit.object_id = Some(index);
+            *closest_hit = Some(hit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    #[test]
+    fn finite_plane_bounds_the_list_and_is_only_hit_inside_its_square() {
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let center = glm::vec3(1.0, -1.0, 2.0);
+        let up = glm::vec3(0.0, 1.0, 0.0);
+        let mut infinite = HittableList::new();
+        infinite.add_plane(center, up, gray);
+        let mut finite = HittableList::new();
+        finite.add_finite_plane(center, up, 3.0, gray);
+
+        // only the finite plane gives the list a size, the square's
+        let bounds = finite.bounding_box().unwrap();
+        assert!(bounds.minimum_point.x.is_finite() && bounds.maximum_point.z.is_finite());
+        assert!((bounds.minimum_point.x + 2.0).abs() < 1e-3);
+        assert!((bounds.maximum_point.z - 5.0).abs() < 1e-3);
+        assert!((bounds.maximum_point.y - bounds.minimum_point.y) < 1e-2);
+        assert!(!infinite.bounding_box().unwrap().minimum_point.x.is_finite());
+
+        let down = glm::vec3(0.0, -1.0, 0.0);
+        for (offset, inside) in [
+            (glm::vec3(0.0, 4.0, 0.0), true),
+            (glm::vec3(2.9, 4.0, -2.9), true),
+            (glm::vec3(3.1, 4.0, 0.0), false),
+            (glm::vec3(0.0, 4.0, -3.1), false),
+            (glm::vec3(10.0, 4.0, 10.0), false),
+        ] {
+            let ray = Ray::new(center + offset, down, None);
+            match finite.hit(&ray, 1e-4, f32::INFINITY) {
+                Some(hit) => {
+                    assert!(inside, "{:?} hit outside the square", offset);
+                    assert!((hit.distance - 4.0).abs() < 1e-5);
+                    assert_eq!(hit.object_id, Some(0));
+                }
+                None => assert!(!inside, "{:?} missed inside the square", offset),
+            }
+            assert_eq!(finite.any_hit(&ray, 1e-4, f32::INFINITY), inside);
+            // the infinite plane is hit everywhere
+            assert!(infinite.hit(&ray, 1e-4, f32::INFINITY).is_some());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::hit_record::HitRecord;
+use crate::hittable::aabb::AABB;
+use crate::hittable::Hittable;
+use crate::hittable::HittableItem;
+use crate::material::isotropic::Isotropic;
+use crate::material::MaterialType;
+use crate::random::rng;
+use crate::ray::Ray;
+use rand::Rng;
+
+/// Smallest distance between the boundary hits taken as the entry and the
+/// exit, so a ray grazing the boundary isn't taken to enter and leave at
+/// the same point
+const HIT_SEPARATION: f32 = 1.0e-4;
+
+/// Represent a participating medium of constant density, like fog or smoke,
+/// filling the inside of a closed boundary. A ray crossing it scatters at a
+/// random distance, with the chance of scattering per unit length given by
+/// the density, so a thin medium lets most rays through and a dense one
+/// looks almost solid. Scattering hits are shaded by the medium's
+/// `Isotropic` material. Shadow rays through the medium are stopped by it
+/// the same way, so the light getting through falls off with the distance
+/// crossed.
+///
+/// The boundary must be closed, like a sphere or a closed mesh, since its
+/// first two hits along a ray are taken as where the ray enters and leaves.
+pub struct ConstantMedium {
+    /// Closed surface the medium fills, whose own material is never shaded
+    pub boundary: Box<HittableItem>,
+    /// Chance of scattering per unit distance travelled inside, above 0
+    pub density: f32,
+    /// Material shading each scattering hit, an `Isotropic` material
+    pub phase_function: MaterialType,
+}
+
+/// Methods from the hittable trait
+impl Hittable for ConstantMedium {
+    /// If a ray scatters inside the medium in a certain range, return a
+    /// hit record where it scatters. Otherwise, return `None`, and the ray
+    /// passes through.
+    ///
+    /// # Arguments
+    /// - `ray` the ray to search for intersections along
+    /// - `min_distance` the minimum distance of intersections along the ray
+    /// - `max_distance` the maximum distance of intersections
+    ///
+    /// # Returns
+    /// - Optional `HitRecord` if there was a hit, otherwise `None`.
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        // the boundary is searched along the whole line, so a ray starting
+        // inside the medium still finds where it entered
+        let entry = self
+            .boundary
+            .hit(ray, f32::NEG_INFINITY, f32::INFINITY)?
+            .distance;
+        let exit = self
+            .boundary
+            .hit(ray, entry + HIT_SEPARATION, f32::INFINITY)?
+            .distance;
+        let entry = entry.max(min_distance).max(0.0);
+        let exit = exit.min(max_distance);
+        if entry >= exit {
+            return None;
+        }
+        // distances to scatter at are exponentially distributed, and the
+        // ray is a unit direction so they are along it as they are
+        let scatter_distance = -(1.0 - rng().gen::<f32>()).ln() / self.density;
+        if scatter_distance >= exit - entry {
+            return None;
+        }
+        // the direction a scattering point is seen from is as good a
+        // normal as any, since the phase function doesn't use one
+        let normal = -ray.direction;
+        Some(HitRecord::new(
+            ray,
+            entry + scatter_distance,
+            normal,
+            normal,
+            Some(&self.phase_function),
+        ))
+    }
+
+    /// The medium fills its boundary, so it has the boundary's box.
+    fn bounding_box(&self) -> Option<AABB> {
+        self.boundary.bounding_box()
+    }
+}
+
+impl ConstantMedium {
+    /// Fill a closed boundary with a medium scattering light with an
+    /// `Isotropic` phase function.
+    ///
+    /// # Arguments
+    /// - `boundary: HittableItem` - closed surface the medium fills
+    /// - `density: f32` - chance of scattering per unit distance, above 0
+    /// - `phase_function: Isotropic` - how the medium scatters light
+    ///
+    /// # Returns
+    /// - `ConstantMedium` - the medium
+    pub fn new(boundary: HittableItem, density: f32, phase_function: Isotropic) -> ConstantMedium {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            phase_function: MaterialType::Isotropic(phase_function),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::sphere::Sphere;
+    use crate::material::lambertian::Lambertian;
+    use glm::Vec3;
+
+    /// A unit ball of fog of density 0.7 at the origin.
+    fn fog_ball() -> ConstantMedium {
+        let boundary = HittableItem::Sphere(Sphere {
+            center: glm::vec3(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(0.0, 0.0, 0.0),
+                force_front_face: false,
+            }),
+            velocity: glm::vec3(0.0, 0.0, 0.0),
+        });
+        let phase_function = Isotropic {
+            albedo: glm::vec3(1.0, 1.0, 1.0),
+            asymmetry: 0.0,
+        };
+        ConstantMedium::new(boundary, 0.7, phase_function)
+    }
+
+    /// Fraction of rays along +z from `origin` which pass through the fog
+    /// up to `max_distance` without scattering, checking every scattering
+    /// point is inside the fog and in range.
+    fn transmittance(origin: Vec3, max_distance: f32) -> f32 {
+        let fog = fog_ball();
+        let ray = Ray::new(origin, glm::vec3(0.0, 0.0, 1.0), None);
+        let samples = 40_000;
+        let passed = (0..samples)
+            .filter(|_| match fog.hit(&ray, 0.001, max_distance) {
+                Some(hit) => {
+                    assert!(glm::length(&hit.hit_point) <= 1.0 + 1.0e-4);
+                    assert!(hit.distance > 0.001 && hit.distance < max_distance);
+                    assert!(hit.material.is_some());
+                    false
+                }
+                None => true,
+            })
+            .count();
+        passed as f32 / samples as f32
+    }
+
+    #[test]
+    fn light_through_fog_falls_off_with_the_distance_crossed() {
+        // straight through the middle crosses 2 units of fog
+        let through = transmittance(glm::vec3(0.0, 0.0, -5.0), f32::INFINITY);
+        assert!((through - (-1.4f32).exp()).abs() < 0.01, "{}", through);
+        // from the middle, or stopping there, crosses 1
+        let from_inside = transmittance(glm::vec3(0.0, 0.0, 0.0), f32::INFINITY);
+        assert!(
+            (from_inside - (-0.7f32).exp()).abs() < 0.01,
+            "{}",
+            from_inside
+        );
+        let stopping_inside = transmittance(glm::vec3(0.0, 0.0, -5.0), 5.0);
+        assert!(
+            (stopping_inside - (-0.7f32).exp()).abs() < 0.01,
+            "{}",
+            stopping_inside
+        );
+        // missing the fog, or heading away from it, crosses none
+        assert_eq!(transmittance(glm::vec3(0.0, 1.5, -5.0), f32::INFINITY), 1.0);
+        assert_eq!(transmittance(glm::vec3(0.0, 0.0, 2.0), f32::INFINITY), 1.0);
+    }
+}
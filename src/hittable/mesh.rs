@@ -1,14 +1,25 @@
 use crate::hit_record::HitRecord;
 use crate::hittable::aabb::AABB;
+use crate::hittable::bvh::BvhSettings;
 use crate::hittable::bvh::BVH;
+use crate::hittable::ply;
 use crate::hittable::triangle::Triangle;
 use crate::hittable::Hittable;
 use crate::material::MaterialType;
 use crate::ray::Ray;
+use crate::stats::GeometryCounts;
 use glm::Vec3;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::collections::HashSet;
 
 pub struct Mesh {
     triangles: BVH,
+    /// Box around every triangle, kept from when the BVH was built
+    bounding_box: AABB,
+    /// Scale and offset the vertices were fitted into a unit cube with as
+    /// the mesh was loaded, if they were
+    normalization: Option<Normalization>,
 }
 
 impl Hittable for Mesh {
@@ -24,39 +35,522 @@ impl Hittable for Mesh {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        self.triangles.hit(&ray, min_distance, max_distance)
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        self.triangles.hit(ray, min_distance, max_distance)
     }
 
-    /// Compute the bounding box of this mesh.
+    /// Trace several rays through the BVH in packets, see `BVH::hit_packet`.
+    fn hit_packet(
+        &self,
+        rays: &[Ray],
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Vec<Option<HitRecord<'_>>> {
+        self.triangles.hit_packet(rays, min_distance, max_distance)
+    }
+
+    /// Check the BVH for any triangle hit, see `BVH::any_hit`.
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        self.triangles.any_hit(ray, min_distance, max_distance)
+    }
+
+    /// The bounding box of this mesh, which was found when it was built.
     fn bounding_box(&self) -> Option<AABB> {
-        self.triangles.bounding_box()
+        Some(self.bounding_box)
+    }
+}
+
+/// Vertex and face buffers read from a mesh file, before they are turned into
+/// triangles. Attributes a file doesn't have are left empty.
+pub struct MeshData {
+    /// Flattened (x, y, z) vertex positions
+    pub positions: Vec<f32>,
+    /// Flattened (x, y, z) vertex normals, or empty to compute smooth normals
+    pub normals: Vec<f32>,
+    /// Flattened (u, v) texture coordinates
+    pub texcoords: Vec<f32>,
+    /// Color of each vertex, if every vertex has one
+    pub colors: Option<Vec<Vec3>>,
+    /// Smoothing group of each triangle, which only shares smooth normals
+    /// with triangles of its own group, and zero for triangles shaded flat.
+    /// `None` smooths every triangle together.
+    pub smoothing_groups: Option<Vec<u32>>,
+    /// Vertex indices, three per triangle
+    pub indices: Vec<usize>,
+}
+
+/// Axis which points up in a mesh file. The renderer's world is Y-up.
+#[derive(Clone, Copy, PartialEq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+/// Fixed rotation and mirror applied to a mesh file's vertices and normals
+/// as it is loaded. Exporters disagree on which axis is up and on
+/// handedness, so without this some models load lying on their side or
+/// mirrored.
+#[derive(Clone, Copy)]
+pub struct ImportTransform {
+    /// Axis which points up in the file, rotated onto +Y
+    pub up_axis: UpAxis,
+    /// Mirror the model across the YZ plane, after the rotation
+    pub flip_x: bool,
+    /// Scale and move the model, after the rotation and mirror, so its
+    /// bounding box fits in a unit cube centered at the origin, whatever
+    /// size it was modelled at. See `Normalization` for undoing it.
+    pub normalize: bool,
+}
+
+impl Default for ImportTransform {
+    /// Load files as they are, Y-up, unmirrored and at their own size.
+    fn default() -> ImportTransform {
+        ImportTransform {
+            up_axis: UpAxis::Y,
+            flip_x: false,
+            normalize: false,
+        }
+    }
+}
+
+/// Uniform scale and offset which fit a mesh into a unit cube centered at
+/// the origin, see `ImportTransform::normalize`. The longest side of the
+/// mesh's bounding box becomes 1 and the others keep their proportion to it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Normalization {
+    /// Center of the mesh's bounding box before it was normalized, which is
+    /// moved to the origin
+    pub center: Vec3,
+    /// Length of the longest side of the box before it was normalized,
+    /// which the mesh is scaled down by about its center
+    pub size: f32,
+}
+
+impl Normalization {
+    /// Move a point from the mesh as it was loaded into the unit cube.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `point: &Vec3` - point in the file's coordinates, after the
+    ///   rotation and mirror
+    ///
+    /// # Returns
+    /// - `Vec3` - the point in the normalized mesh's coordinates
+    pub fn apply(&self, point: &Vec3) -> Vec3 {
+        (point - self.center) / self.size
+    }
+
+    /// Move a point from the normalized mesh back to where it was in the
+    /// mesh as loaded, undoing `apply`.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `point: &Vec3` - point in the normalized mesh's coordinates
+    ///
+    /// # Returns
+    /// - `Vec3` - the point in the file's coordinates, after the rotation
+    ///   and mirror
+    #[allow(dead_code)]
+    pub fn undo(&self, point: &Vec3) -> Vec3 {
+        point * self.size + self.center
+    }
+}
+
+/// Sanity checks of a loaded mesh, see `Mesh::validate`. Triangles meet at
+/// an edge where they share both of its vertices' positions, so vertices
+/// split along texture seams still join up.
+pub struct MeshReport {
+    /// Number of triangles in the mesh
+    pub triangle_count: usize,
+    /// Box around every triangle
+    pub bounding_box: AABB,
+    /// Triangles with no area, whose vertices are repeated or in a line
+    pub degenerate_triangles: usize,
+    /// Triangles with the same three vertices as an earlier one, in any
+    /// order
+    pub duplicate_triangles: usize,
+    /// Edges on only one triangle, which border a hole or the rim of an
+    /// open surface
+    pub open_edges: usize,
+    /// Edges on more than two triangles, like where two closed shapes touch
+    /// along an edge
+    pub non_manifold_edges: usize,
+    /// Edges whose two triangles are wound the same way around it, so one
+    /// of them faces the other way to its neighbor
+    pub flipped_edges: usize,
+    /// Share of the mesh's area facing away from the center of its bounding
+    /// box, which is most of it for an object whose faces point outward.
+    /// `None` if no triangle faces toward or away from the center.
+    pub outward_area: Option<f32>,
+    /// Average number of triangles a ray through the mesh is tested
+    /// against at most, over a grid of rays along each axis, see
+    /// `BVH::count_candidates`. A BVH which fits the mesh well keeps this
+    /// to a few leaves' worth however many triangles there are.
+    pub candidates_per_ray: f32,
+}
+
+impl MeshReport {
+    /// Whether every edge is on exactly two triangles, so the mesh closes
+    /// up with no holes. Refracted rays can escape through the holes in a
+    /// mesh which isn't, which shows up as light leaking through glass.
+    pub fn is_watertight(&self) -> bool {
+        self.open_edges == 0 && self.non_manifold_edges == 0
+    }
+}
+
+impl std::fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (minimum, maximum) = (
+            self.bounding_box.minimum_point,
+            self.bounding_box.maximum_point,
+        );
+        writeln!(f, "    triangles          {}", self.triangle_count)?;
+        writeln!(
+            f,
+            "    bounding box       ({}, {}, {}) to ({}, {}, {})",
+            minimum.x, minimum.y, minimum.z, maximum.x, maximum.y, maximum.z
+        )?;
+        writeln!(f, "    degenerate         {}", self.degenerate_triangles)?;
+        writeln!(f, "    duplicates         {}", self.duplicate_triangles)?;
+        writeln!(f, "    open edges         {}", self.open_edges)?;
+        writeln!(f, "    non-manifold edges {}", self.non_manifold_edges)?;
+        writeln!(f, "    flipped edges      {}", self.flipped_edges)?;
+        writeln!(f, "    candidates/ray     {:.1}", self.candidates_per_ray)?;
+        match self.outward_area {
+            Some(share) => writeln!(f, "    facing outward     {:.1}%", 100.0 * share)?,
+            None => writeln!(f, "    facing outward     unknown")?,
+        }
+        write!(
+            f,
+            "    watertight         {}",
+            if self.is_watertight() { "yes" } else { "no" }
+        )
     }
 }
 
 impl Mesh {
-    pub fn create(filename: &str, material: MaterialType, bvh_leaf_max: usize) -> Mesh {
-        // load obj from file, triangulate faces
-        let obj = tobj::load_obj(filename, true);
-        assert!(obj.is_ok());
-        let (models, _) = obj.unwrap();
-        let model = &models[0];
+    /// Count the triangles and BVH nodes of this mesh.
+    pub fn geometry_counts(&self) -> GeometryCounts {
+        self.triangles.geometry_counts()
+    }
 
-        // collect all triangles
-        let mut triangles: Vec<[Vec3; 3]> = Vec::new();
+    /// Check the mesh for problems which show up in renders: degenerate and
+    /// duplicate triangles, holes, triangles wound against their neighbors,
+    /// and faces pointing inward.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `MeshReport` - the counts found
+    pub fn validate(&self) -> MeshReport {
+        // vertices are told apart by the bits of their positions, with -0
+        // made +0 so they match
+        let mut vertex_ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut vertex_id = |position: &Vec3| {
+            let key = [0, 1, 2].map(|axis| (position[axis] + 0.0).to_bits());
+            let next_id = vertex_ids.len();
+            *vertex_ids.entry(key).or_insert(next_id)
+        };
+        let center = (self.bounding_box.minimum_point + self.bounding_box.maximum_point) / 2.0;
+
+        let mut triangle_count = 0;
+        let mut degenerate_triangles = 0;
+        let mut duplicate_triangles = 0;
+        let mut seen_triangles = HashSet::new();
+        // for each edge, the number of triangles on it and how many more
+        // run along it from its lower vertex id than from its higher one
+        let mut edges: HashMap<(usize, usize), (usize, i32)> = HashMap::new();
+        let (mut outward, mut inward) = (0.0, 0.0);
+        self.triangles.for_each_triangle(&mut |triangle| {
+            triangle_count += 1;
+            let ids = [0, 1, 2].map(|corner| vertex_id(&triangle.vertices[corner]));
+            let mut sorted = ids;
+            sorted.sort_unstable();
+            if !seen_triangles.insert(sorted) {
+                duplicate_triangles += 1;
+            }
+
+            let [a, b, c] = triangle.vertices;
+            let area_vector = glm::cross(&(b - a), &(c - a)) / 2.0;
+            let longest = glm::length2(&(b - a))
+                .max(glm::length2(&(c - b)))
+                .max(glm::length2(&(a - c)));
+            let area = glm::length(&area_vector);
+            if area.is_nan() || area <= f32::EPSILON * longest {
+                degenerate_triangles += 1;
+            } else {
+                let facing = glm::dot(&area_vector, &((a + b + c) / 3.0 - center));
+                if facing > 0.0 {
+                    outward += area;
+                } else if facing < 0.0 {
+                    inward += area;
+                }
+            }
+
+            // a triangle with a repeated vertex runs both ways along its one
+            // real edge, which would make a sound edge look non-manifold
+            if sorted[0] == sorted[1] || sorted[1] == sorted[2] {
+                return;
+            }
+            for corner in 0..3 {
+                let (from, to) = (ids[corner], ids[(corner + 1) % 3]);
+                let edge = edges.entry((from.min(to), from.max(to))).or_insert((0, 0));
+                edge.0 += 1;
+                edge.1 += if from < to { 1 } else { -1 };
+            }
+        });
+
+        let count_edges =
+            |test: &dyn Fn(&(usize, i32)) -> bool| edges.values().filter(|edge| test(edge)).count();
+        MeshReport {
+            triangle_count,
+            bounding_box: self.bounding_box,
+            degenerate_triangles,
+            duplicate_triangles,
+            open_edges: count_edges(&|&(faces, _)| faces == 1),
+            non_manifold_edges: count_edges(&|&(faces, _)| faces > 2),
+            flipped_edges: count_edges(&|&(faces, direction)| faces == 2 && direction != 0),
+            outward_area: if outward + inward > 0.0 {
+                Some(outward / (outward + inward))
+            } else {
+                None
+            },
+            candidates_per_ray: self.candidates_per_ray(),
+        }
+    }
+
+    /// Average number of triangles rays through the mesh are tested against
+    /// at most, over a grid of rays through its bounding box along each of
+    /// the three axes.
+    fn candidates_per_ray(&self) -> f32 {
+        let grid = 16;
+        let minimum = self.bounding_box.minimum_point;
+        let extent = self.bounding_box.maximum_point - minimum;
+        let mut total = 0;
+        for axis in 0..3 {
+            let (across, up) = ((axis + 1) % 3, (axis + 2) % 3);
+            for i in 0..grid {
+                for j in 0..grid {
+                    // from the middle of each cell of the grid, starting
+                    // outside the box
+                    let mut origin = minimum;
+                    origin[axis] -= 1.0 + extent[axis];
+                    origin[across] += extent[across] * (i as f32 + 0.5) / grid as f32;
+                    origin[up] += extent[up] * (j as f32 + 0.5) / grid as f32;
+                    let mut direction = glm::vec3(0.0, 0.0, 0.0);
+                    direction[axis] = 1.0;
+                    total += self
+                        .triangles
+                        .count_candidates(&Ray::new(origin, direction, None));
+                }
+            }
+        }
+        total as f32 / (3 * grid * grid) as f32
+    }
+
+    /// Load a triangle mesh from an OBJ file and build a BVH over it.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - path to the OBJ file
+    /// - `material: MaterialType` - material for every triangle
+    /// - `bvh: BvhSettings` - how to build the BVH
+    /// - `weld_epsilon: Option<f32>` - if given, vertices closer together
+    ///   than this share their smooth normal. Some exporters split vertices
+    ///   along seams, which otherwise shows up as faceting.
+    /// - `import: ImportTransform` - rotation, mirror and normalization to
+    ///   apply to the file's vertices, see `ImportTransform`
+    ///
+    /// # Returns
+    /// - the loaded `Mesh`
+    pub fn create(
+        filename: &str,
+        material: MaterialType,
+        bvh: BvhSettings,
+        weld_epsilon: Option<f32>,
+        import: ImportTransform,
+    ) -> Mesh {
+        let mut data = match read_obj(filename) {
+            Ok(data) => data,
+            Err(error) => panic!("couldn't load {}: {}", filename, error),
+        };
+        let normalization = data.apply_import_transform(import);
+        Mesh {
+            normalization,
+            ..Mesh::build(&data, material, bvh, weld_epsilon)
+        }
+    }
+
+    /// Load a triangle mesh from an ASCII or binary PLY file and build a BVH
+    /// over it. Normals and vertex colors are read if the file has them,
+    /// otherwise smooth normals are computed like for OBJ files.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - path to the PLY file
+    /// - `material: MaterialType` - material for every triangle
+    /// - `bvh: BvhSettings` - how to build the BVH
+    ///
+    /// # Returns
+    /// - the loaded `Mesh`
+    pub fn create_ply(filename: &str, material: MaterialType, bvh: BvhSettings) -> Mesh {
+        let data = match ply::read(filename) {
+            Ok(data) => data,
+            Err(error) => panic!("couldn't load {}: {}", filename, error),
+        };
+        Mesh::build(&data, material, bvh, None)
+    }
+
+    /// Make a triangle for each face of a loaded mesh and build a BVH over
+    /// them.
+    ///
+    /// # Arguments
+    /// - `data: &MeshData` - the mesh's vertex and face buffers
+    /// - `material: MaterialType` - material for every triangle
+    /// - `bvh: BvhSettings` - how to build the BVH
+    /// - `weld_epsilon: Option<f32>` - see `Mesh::create`
+    ///
+    /// # Returns
+    /// - the built `Mesh`
+    pub fn build(
+        data: &MeshData,
+        material: MaterialType,
+        bvh: BvhSettings,
+        weld_epsilon: Option<f32>,
+    ) -> Mesh {
+        Mesh::from_triangles(data.triangles(material, weld_epsilon), bvh)
+    }
+
+    /// Build a BVH over triangles which are already made, which may come
+    /// from several files.
+    ///
+    /// # Arguments
+    /// - `triangles: Vec<Triangle>` - the triangles, at least one
+    /// - `bvh: BvhSettings` - how to build the BVH
+    ///
+    /// # Returns
+    /// - the built `Mesh`
+    pub fn from_triangles(triangles: Vec<Triangle>, bvh: BvhSettings) -> Mesh {
+        let triangles = BVH::build(triangles, bvh);
+        // a BVH always has a box, so it only needs fetching once
+        let bounding_box = triangles.bounding_box().unwrap();
+        Mesh {
+            triangles,
+            bounding_box,
+            normalization: None,
+        }
+    }
+
+    /// Scale and offset the mesh was fitted into a unit cube with when it
+    /// was loaded, for mapping points between the normalized mesh and the
+    /// file, see `ImportTransform::normalize`.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - the normalization, or `None` if the mesh wasn't normalized
+    #[allow(dead_code)]
+    pub fn normalization(&self) -> Option<Normalization> {
+        self.normalization
+    }
+}
+
+impl MeshData {
+    /// Rotate and mirror the vertices and normals in place, and then fit
+    /// the vertices into a unit cube if the transform asks for it. Mirroring
+    /// turns the faces inside out, so the winding of every triangle is
+    /// reversed to keep them facing outward.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    /// - `import: ImportTransform` - the rotation, mirror and normalization
+    ///   to apply
+    ///
+    /// # Returns
+    /// - the normalization applied, if one was asked for and the mesh has
+    ///   a size to normalize, see `MeshData::normalize`
+    pub fn apply_import_transform(&mut self, import: ImportTransform) -> Option<Normalization> {
+        for buffer in [&mut self.positions, &mut self.normals].iter_mut() {
+            for vector in buffer.chunks_mut(3) {
+                if import.up_axis == UpAxis::Z {
+                    // rotate -90 degrees about x, taking +z to +y
+                    let (y, z) = (vector[1], vector[2]);
+                    vector[1] = z;
+                    vector[2] = -y;
+                }
+                if import.flip_x {
+                    vector[0] = -vector[0];
+                }
+            }
+        }
+        if import.flip_x {
+            for triangle in self.indices.chunks_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        if import.normalize {
+            self.normalize()
+        } else {
+            None
+        }
+    }
+
+    /// Scale and move the vertices in place so the bounding box of the
+    /// triangles fits in a unit cube centered at the origin, keeping their
+    /// proportions. Normals are unchanged, since the scale is the same
+    /// along every axis. Vertices which no triangle uses don't count
+    /// toward the box.
+    ///
+    /// # Arguments
+    /// - *mutable* self reference
+    ///
+    /// # Returns
+    /// - the normalization applied, or `None`, leaving the vertices as they
+    ///   are, if the mesh has no triangles or they are all at one point
+    pub fn normalize(&mut self) -> Option<Normalization> {
+        let mut minimum = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut maximum = -minimum;
+        for &index in self.indices.iter() {
+            let vertex = glm::make_vec3(&self.positions[3 * index..3 * index + 3]);
+            minimum = glm::min2(&minimum, &vertex);
+            maximum = glm::max2(&maximum, &vertex);
+        }
+        let size = glm::comp_max(&(maximum - minimum));
+        if !(size.is_finite() && size > 0.0) {
+            return None;
+        }
+        let normalization = Normalization {
+            center: (minimum + maximum) / 2.0,
+            size,
+        };
+        for vertex in self.positions.chunks_mut(3) {
+            let normalized = normalization.apply(&glm::make_vec3(vertex));
+            vertex.copy_from_slice(normalized.as_slice());
+        }
+        Some(normalization)
+    }
 
-        // get position and index buffers
-        let positions = &model.mesh.positions;
-        let indices: Vec<usize> = (&model.mesh.indices).iter().map(|i| *i as usize).collect();
+    /// Make a triangle for each face of the mesh.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `material: MaterialType` - material for every triangle
+    /// - `weld_epsilon: Option<f32>` - see `Mesh::create`
+    ///
+    /// # Returns
+    /// - `Vec<Triangle>` - the mesh's triangles
+    pub fn triangles(&self, material: MaterialType, weld_epsilon: Option<f32>) -> Vec<Triangle> {
+        let data = self;
+        let positions = &data.positions;
+        let indices = &data.indices;
         // make sure there are a whole number of triangles
-        assert!(indices.len() % 3 == 0);
+        assert!(indices.len().is_multiple_of(3));
+
+        // collect all triangles
+        let mut triangles: Vec<[Vec3; 3]> = Vec::new();
 
         // create a triangle for each face
         let triangle_count = indices.len() / 3;
-        // println!(
-        //     "loading {} triangles and building bvh for {} . . . ",
-        //     triangle_count, filename
-        // );
         for tri_index in 0..triangle_count {
             let index_one = tri_index * 3;
             let index_two = tri_index * 3 + 1;
@@ -80,43 +574,798 @@ impl Mesh {
             triangles.push([vertex_one, vertex_two, vertex_three]);
         }
 
-        // calculate per-vertex normals
-        let mut normals: Vec<Vec3> = Vec::new();
-        let vertex_count = positions.len() / 3;
-        for _ in 0..vertex_count {
-            normals.push(glm::vec3(0.0, 0.0, 0.0)); // initialize all to zero
-        }
-        for (index, triangle) in triangles.iter().enumerate() {
-            let edge_one = triangle[1] - triangle[0];
-            let edge_two = triangle[2] - triangle[0];
-            let face_normal = glm::cross(&edge_one, &edge_two);
-
-            let index_one = indices[index * 3];
-            let index_two = indices[index * 3 + 1];
-            let index_three = indices[index * 3 + 2];
-
-            normals[index_one] += face_normal;
-            normals[index_two] += face_normal;
-            normals[index_three] += face_normal;
-        }
+        // use the file's normals if it has one for every vertex, giving
+        // each triangle corner its vertex's normal
+        let normals: Vec<Vec3> =
+            if !data.normals.is_empty() && data.normals.len() == positions.len() {
+                indices
+                    .iter()
+                    .map(|&index| {
+                        glm::vec3(
+                            data.normals[index * 3],
+                            data.normals[index * 3 + 1],
+                            data.normals[index * 3 + 2],
+                        )
+                    })
+                    .collect()
+            } else {
+                smooth_normals(
+                    positions,
+                    indices,
+                    &triangles,
+                    data.smoothing_groups.as_deref(),
+                    weld_epsilon,
+                )
+            };
 
         // assign the normals to triangles and make hittables
         let mut hittables: Vec<Triangle> = Vec::new();
         for (index, triangle) in triangles.iter().enumerate() {
+            let texture_coordinates = [
+                texture_coordinate(&data.texcoords, indices[index * 3]),
+                texture_coordinate(&data.texcoords, indices[index * 3 + 1]),
+                texture_coordinate(&data.texcoords, indices[index * 3 + 2]),
+            ];
             hittables.push(Triangle {
                 vertices: *triangle,
                 edges: [triangle[1] - triangle[0], triangle[2] - triangle[0]],
                 vertex_normals: [
-                    glm::normalize(&normals[indices[index * 3]]),
-                    glm::normalize(&normals[indices[index * 3 + 1]]),
-                    glm::normalize(&normals[indices[index * 3 + 2]]),
+                    glm::normalize(&normals[index * 3]),
+                    glm::normalize(&normals[index * 3 + 1]),
+                    glm::normalize(&normals[index * 3 + 2]),
                 ],
+                texture_coordinates,
+                vertex_colors: data.colors.as_ref().map(|colors| {
+                    [
+                        colors[indices[index * 3]],
+                        colors[indices[index * 3 + 1]],
+                        colors[indices[index * 3 + 2]],
+                    ]
+                }),
+                tangent: if data.texcoords.is_empty() {
+                    None
+                } else {
+                    uv_tangent(triangle, &texture_coordinates)
+                },
                 material,
             });
         }
+        hittables
+    }
+}
 
-        Mesh {
-            triangles: BVH::build(hittables, bvh_leaf_max),
+/// Find the direction in which the u texture coordinate increases across a
+/// triangle, from how the coordinates change along its edges.
+///
+/// # Arguments
+/// - `triangle: &[Vec3; 3]` - vertices of the triangle
+/// - `texture_coordinates: &[(f32, f32); 3]` - (u, v) at each vertex
+///
+/// # Returns
+/// - `Option<Vec3>` - the unit tangent, or `None` if the texture
+///   coordinates don't span an area
+fn uv_tangent(triangle: &[Vec3; 3], texture_coordinates: &[(f32, f32); 3]) -> Option<Vec3> {
+    let edge_one = triangle[1] - triangle[0];
+    let edge_two = triangle[2] - triangle[0];
+    let (du_one, dv_one) = (
+        texture_coordinates[1].0 - texture_coordinates[0].0,
+        texture_coordinates[1].1 - texture_coordinates[0].1,
+    );
+    let (du_two, dv_two) = (
+        texture_coordinates[2].0 - texture_coordinates[0].0,
+        texture_coordinates[2].1 - texture_coordinates[0].1,
+    );
+    let determinant = du_one * dv_two - du_two * dv_one;
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    // solve edge = du * tangent + dv * bitangent for the tangent
+    let tangent = (edge_one * dv_two - edge_two * dv_one) / determinant;
+    if glm::length(&tangent) > 0.0 {
+        Some(glm::normalize(&tangent))
+    } else {
+        None
+    }
+}
+
+/// Calculate a smooth normal at every triangle corner by summing the
+/// normals of the faces around its vertex, weighted by face area. With
+/// smoothing groups, only faces of the corner's own group are summed, so
+/// the edges between groups stay sharp, and corners of faces in group zero
+/// take their face's normal.
+///
+/// # Arguments
+/// - `positions: &[f32]` - flattened (x, y, z) vertex positions
+/// - `indices: &[usize]` - vertex indices, three per triangle
+/// - `triangles: &[[Vec3; 3]]` - vertices of each triangle
+/// - `smoothing_groups: Option<&[u32]>` - group of each triangle, see
+///   `MeshData::smoothing_groups`
+/// - `weld_epsilon: Option<f32>` - see `Mesh::create`
+///
+/// # Returns
+/// - `Vec<Vec3>` - the unnormalized normal of each corner, three per
+///   triangle
+fn smooth_normals(
+    positions: &[f32],
+    indices: &[usize],
+    triangles: &[[Vec3; 3]],
+    smoothing_groups: Option<&[u32]>,
+    weld_epsilon: Option<f32>,
+) -> Vec<Vec3> {
+    // find which vertex shares its normal with each vertex
+    let vertex_count = positions.len() / 3;
+    let welded: Vec<usize> = match weld_epsilon {
+        Some(epsilon) => weld_vertices(positions, epsilon),
+        None => (0..vertex_count).collect(),
+    };
+    // every triangle is in one group when the mesh has no groups
+    let group = |triangle: usize| smoothing_groups.map_or(1, |groups| groups[triangle]);
+    let face_normal = |triangle: &[Vec3; 3]| {
+        let edge_one = triangle[1] - triangle[0];
+        let edge_two = triangle[2] - triangle[0];
+        glm::cross(&edge_one, &edge_two)
+    };
+
+    // sum the face normals around each vertex in each group, in parallel
+    // but always in triangle order, since float sums depend on their order
+    // and the same mesh should load to the same bits on any number of
+    // threads. The stable sort keeps each vertex's corners in triangle
+    // order, and each vertex's run is summed by one thread.
+    let face_normals: Vec<Vec3> = triangles.par_iter().map(face_normal).collect();
+    let mut corners: Vec<((usize, u32), usize)> = (0..indices.len())
+        .into_par_iter()
+        .filter(|corner| group(corner / 3) != 0)
+        .map(|corner| ((welded[indices[corner]], group(corner / 3)), corner / 3))
+        .collect();
+    corners.par_sort_by_key(|&(key, _)| key);
+    let normals: HashMap<(usize, u32), Vec3> = corners
+        .par_chunk_by(|(one, _), (other, _)| one == other)
+        .map(|run| {
+            let sum = run
+                .iter()
+                .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &(_, triangle)| {
+                    sum + face_normals[triangle]
+                });
+            (run[0].0, sum)
+        })
+        .collect();
+
+    // welded vertices take the normal of the vertex they were merged into
+    let mut corner_normals = Vec::with_capacity(indices.len());
+    for (index, triangle) in triangles.iter().enumerate() {
+        for corner in index * 3..index * 3 + 3 {
+            corner_normals.push(match group(index) {
+                0 => face_normal(triangle),
+                group => normals[&(welded[indices[corner]], group)],
+            });
+        }
+    }
+    corner_normals
+}
+
+/// Read the first model of an OBJ file. Faces are triangulated as fans, and
+/// vertex colors are read if the file has them.
+///
+/// # Arguments
+/// - `filename: &str` - path to the OBJ file
+///
+/// # Returns
+/// - `Result<MeshData>` - the mesh's buffers, or why they couldn't be read
+pub fn read_obj(filename: &str) -> std::io::Result<MeshData> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let (mut models, _) =
+        tobj::load_obj(filename, false).map_err(|error| invalid(error.to_string()))?;
+    if models.is_empty() {
+        return Err(invalid(String::from("no models in the file")));
+    }
+    let mesh = models.swap_remove(0).mesh;
+
+    let indices = fan_triangulate(&mesh.indices, &mesh.num_face_indices);
+    if indices.is_empty() {
+        return Err(invalid(String::from("no faces in the file")));
+    }
+    // vertex colors and smoothing groups, if the OBJ has them
+    let colors = obj_vertex_colors(filename, &mesh.positions);
+    let smoothing_groups = obj_smoothing_groups(filename, &mesh.num_face_indices)
+        .filter(|groups| groups.len() * 3 == indices.len());
+    Ok(MeshData {
+        positions: mesh.positions,
+        normals: Vec::new(),
+        texcoords: mesh.texcoords,
+        colors,
+        smoothing_groups,
+        indices,
+    })
+}
+
+/// Split every face of a mesh into a fan of triangles around its first
+/// vertex, which is correct for convex polygons. Faces with fewer than three
+/// vertices, like lines, are dropped.
+///
+/// # Arguments
+/// - `indices: &[u32]` - vertex indices of all faces, one after another
+/// - `face_sizes: &[u32]` - number of vertices in each face. If this is
+///   empty every face is taken to be a triangle.
+///
+/// # Returns
+/// - `Vec<usize>` - vertex indices of the triangles, three per triangle
+pub fn fan_triangulate(indices: &[u32], face_sizes: &[u32]) -> Vec<usize> {
+    if face_sizes.is_empty() {
+        return indices.iter().map(|i| *i as usize).collect();
+    }
+    let mut triangles = Vec::with_capacity(indices.len() * 3);
+    let mut start = 0;
+    for &size in face_sizes.iter() {
+        let face = &indices[start..start + size as usize];
+        for corner in 2..face.len() {
+            triangles.push(face[0] as usize);
+            triangles.push(face[corner - 1] as usize);
+            triangles.push(face[corner] as usize);
+        }
+        start += size as usize;
+    }
+    triangles
+}
+
+/// Look up the texture coordinates of a vertex, if the OBJ has any.
+///
+/// # Arguments
+/// - `texcoords: &[f32]` - flattened (u, v) pairs from the OBJ
+/// - `index: usize` - index of the vertex
+///
+/// # Returns
+/// - `(f32, f32)` - the vertex's texture coordinates, or (0, 0) if there are
+///   none
+fn texture_coordinate(texcoords: &[f32], index: usize) -> (f32, f32) {
+    if texcoords.len() >= (index + 1) * 2 {
+        (texcoords[index * 2], texcoords[index * 2 + 1])
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Read vertex colors from an OBJ file. Colors aren't part of the OBJ
+/// format, but many exporters write them as three extra components after the
+/// position on each `v` line, which `tobj` skips over. `tobj` also reorders
+/// the vertices as it reads the faces, so the colors are matched to the
+/// loaded positions by value: the exact bits of the `f32` each coordinate
+/// parses to, the same parse `tobj` does. Vertices which share a position
+/// therefore share a color too, the last one given for it in the file, so
+/// a mesh with a color seam, where split vertices at one place have
+/// different colors, loads with one color on both sides.
+///
+/// # Arguments
+/// - `filename: &str` - path to the OBJ file
+/// - `positions: &[f32]` - flattened (x, y, z) positions loaded by `tobj`
+///
+/// # Returns
+/// - `Option<Vec<Vec3>>` - the color of each loaded vertex, or `None` if the
+///   file doesn't give every vertex a color
+fn obj_vertex_colors(filename: &str, positions: &[f32]) -> Option<Vec<Vec3>> {
+    let contents = std::fs::read_to_string(filename).ok()?;
+    let mut colors_by_position: HashMap<[u32; 3], Vec3> = HashMap::new();
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("v") {
+            continue;
+        }
+        let values: Vec<f32> = words.filter_map(|word| word.parse().ok()).collect();
+        if values.len() == 6 {
+            colors_by_position.insert(
+                [
+                    values[0].to_bits(),
+                    values[1].to_bits(),
+                    values[2].to_bits(),
+                ],
+                glm::vec3(values[3], values[4], values[5]),
+            );
+        }
+    }
+    if colors_by_position.is_empty() {
+        return None;
+    }
+    positions
+        .chunks(3)
+        .map(|position| {
+            colors_by_position
+                .get(&[
+                    position[0].to_bits(),
+                    position[1].to_bits(),
+                    position[2].to_bits(),
+                ])
+                .copied()
+        })
+        .collect()
+}
+
+/// Read the smoothing group of each triangle of an OBJ file's first model,
+/// which `tobj` skips over. An `s` line puts the faces after it into a
+/// group, with `s 0` or `s off` shading them flat. The first model ends
+/// where `tobj` ends it, at an `o` or `g` line or a change of material
+/// after its first face.
+///
+/// # Arguments
+/// - `filename: &str` - path to the OBJ file
+/// - `face_sizes: &[u32]` - number of vertices in each face loaded by
+///   `tobj`, to split the groups of faces among their triangles
+///
+/// # Returns
+/// - `Option<Vec<u32>>` - the group of each triangle, or `None` if the file
+///   has no smoothing groups
+fn obj_smoothing_groups(filename: &str, face_sizes: &[u32]) -> Option<Vec<u32>> {
+    let contents = std::fs::read_to_string(filename).ok()?;
+    let mut has_groups = false;
+    let mut group = 0;
+    let mut material: Option<&str> = None;
+    let mut face_groups: Vec<u32> = Vec::new();
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("s") => {
+                has_groups = true;
+                // "off" doesn't parse, and is the same as group zero
+                group = words.next().and_then(|word| word.parse().ok()).unwrap_or(0);
+            }
+            Some("f") | Some("l") => face_groups.push(group),
+            Some("o") | Some("g") if !face_groups.is_empty() => break,
+            Some("usemtl") => {
+                let name = words.next();
+                if material != name && !face_groups.is_empty() {
+                    break;
+                }
+                material = name;
+            }
+            _ => {}
+        }
+    }
+    if !has_groups {
+        return None;
+    }
+    if face_sizes.is_empty() {
+        return Some(face_groups);
+    }
+    // each face becomes one triangle fewer than its number of sides
+    let triangle_groups = face_sizes
+        .iter()
+        .zip(face_groups)
+        .flat_map(|(&size, group)| std::iter::repeat_n(group, (size as usize).saturating_sub(2)))
+        .collect();
+    Some(triangle_groups)
+}
+
+/// Merge vertices which are within `epsilon` of each other. Vertices are
+/// bucketed into a grid with cells `epsilon` wide, so only the neighboring
+/// cells need to be searched for a match.
+///
+/// # Arguments
+/// - `positions: &[f32]` - flattened (x, y, z) vertex positions
+/// - `epsilon: f32` - distance within which vertices are merged
+///
+/// # Returns
+/// - `Vec<usize>` - for each vertex, the index of the vertex it was merged
+///   into. Vertices which weren't merged map to themselves.
+fn weld_vertices(positions: &[f32], epsilon: f32) -> Vec<usize> {
+    let vertex = |index: usize| {
+        glm::vec3(
+            positions[index * 3],
+            positions[index * 3 + 1],
+            positions[index * 3 + 2],
+        )
+    };
+    let cell_size = epsilon.max(f32::MIN_POSITIVE);
+    let cell = |point: &Vec3| {
+        (
+            (point.x / cell_size).floor() as i64,
+            (point.y / cell_size).floor() as i64,
+            (point.z / cell_size).floor() as i64,
+        )
+    };
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut welded = Vec::with_capacity(positions.len() / 3);
+    for index in 0..positions.len() / 3 {
+        let point = vertex(index);
+        let (x, y, z) = cell(&point);
+        let mut target = index;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(candidates) = grid.get(&(x + dx, y + dy, z + dz)) {
+                        for &candidate in candidates.iter() {
+                            if glm::distance(&point, &vertex(candidate)) <= epsilon {
+                                target = candidate;
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if target == index {
+            grid.entry((x, y, z)).or_default().push(index);
+        }
+        welded.push(target);
+    }
+    welded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    /// Gray diffuse material for test meshes.
+    fn gray() -> MaterialType {
+        MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        })
+    }
+
+    #[test]
+    fn welding_a_split_cube_shares_area_weighted_normals() {
+        let data = read_obj("assets/split_cube.obj").unwrap();
+        assert_eq!(data.positions.len() / 3, 24);
+        let welded = weld_vertices(&data.positions, 1.0e-4);
+        let distinct: HashSet<usize> = welded.iter().copied().collect();
+        assert_eq!(distinct.len(), 8);
+
+        // each corner's normal is the sum of the (area weighted) normals of
+        // every triangle touching a vertex at the same place
+        let triangles = data.triangles(gray(), Some(1.0e-4));
+        for triangle in triangles.iter() {
+            for (vertex, normal) in triangle.vertices.iter().zip(triangle.vertex_normals.iter()) {
+                let expected = triangles
+                    .iter()
+                    .filter(|other| {
+                        other
+                            .vertices
+                            .iter()
+                            .any(|corner| glm::distance(corner, vertex) < 1.0e-4)
+                    })
+                    .fold(glm::vec3(0.0, 0.0, 0.0), |sum, other| {
+                        sum + glm::cross(&other.edges[0], &other.edges[1])
+                    });
+                assert!(glm::distance(normal, &glm::normalize(&expected)) < 1.0e-5);
+                // so every corner normal leans out of all three faces
+                for axis in 0..3 {
+                    assert!(normal[axis] * vertex[axis] > 0.0);
+                }
+            }
+        }
+
+        // without welding each face keeps its own flat normal
+        for triangle in data.triangles(gray(), None).iter() {
+            let face = glm::normalize(&glm::cross(&triangle.edges[0], &triangle.edges[1]));
+            for normal in triangle.vertex_normals.iter() {
+                assert!(glm::distance(normal, &face) < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn fan_triangulate_splits_quads_and_ngons_around_the_first_vertex() {
+        // a quad, a line, a pentagon and a triangle, one after another
+        let indices = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13];
+        let triangles = fan_triangulate(&indices, &[4, 2, 5, 3]);
+        assert_eq!(
+            triangles,
+            vec![0, 1, 2, 0, 2, 3, 6, 7, 8, 6, 8, 9, 6, 9, 10, 11, 12, 13]
+        );
+        // with no sizes every face is already a triangle
+        assert_eq!(fan_triangulate(&[3, 1, 2], &[]), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn z_up_house_loads_upright_and_faces_outward_when_mirrored() {
+        for flip_x in [false, true] {
+            let mut data = read_obj("assets/z_up_house.obj").unwrap();
+            data.apply_import_transform(ImportTransform {
+                up_axis: UpAxis::Z,
+                flip_x,
+                normalize: false,
+            });
+            // the roof's peak is the highest point, straight above the floor
+            let peak = data
+                .positions
+                .chunks(3)
+                .max_by(|a, b| a[1].partial_cmp(&b[1]).unwrap())
+                .unwrap();
+            assert!(glm::distance(&glm::make_vec3(peak), &glm::vec3(0.0, 1.6, 0.0)) < 1e-6);
+            let floor = data
+                .positions
+                .chunks(3)
+                .map(|vertex| vertex[1])
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!(floor, 0.0);
+
+            // every face points away from the middle of the house
+            let center = glm::vec3(0.0, 0.8, 0.0);
+            for triangle in data.triangles(gray(), None).iter() {
+                let centroid =
+                    (triangle.vertices[0] + triangle.vertices[1] + triangle.vertices[2]) / 3.0;
+                let normal = glm::cross(&triangle.edges[0], &triangle.edges[1]);
+                assert!(
+                    glm::dot(&normal, &(centroid - center)) > 0.0,
+                    "flip_x {}",
+                    flip_x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smoothing_groups_keep_cube_edges_sharp_and_smooth_a_cylinder() {
+        // a cube with a group per face, a hexagonal prism's sides in one
+        // group, and a flat triangle on a cube corner
+        let mut obj = String::from(
+            "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+             v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n",
+        );
+        for (group, face) in [
+            "1 4 3 2", "5 6 7 8", "1 2 6 5", "2 3 7 6", "3 4 8 7", "4 1 5 8",
+        ]
+        .iter()
+        .enumerate()
+        {
+            obj += &format!("s {}\nf {}\n", group + 1, face);
+        }
+        for height in [0.0f32, 2.0].iter() {
+            for corner in 0..6 {
+                let angle = corner as f32 * std::f32::consts::PI / 3.0;
+                obj += &format!("v {} {} {}\n", 4.0 + angle.cos(), height, angle.sin());
+            }
+        }
+        obj += "s 7\n";
+        for corner in 0..6 {
+            let next = (corner + 1) % 6;
+            obj += &format!(
+                "f {} {} {} {}\n",
+                9 + corner,
+                15 + corner,
+                15 + next,
+                9 + next
+            );
+        }
+        obj += "v 1 1 3\nv 0 1 3\ns off\nf 7 21 22\n";
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_smoothing_{}.obj", std::process::id()));
+        std::fs::write(&path, obj).unwrap();
+        let data = read_obj(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let data = data.unwrap();
+
+        let triangles = data.triangles(gray(), None);
+        assert_eq!(triangles.len(), 12 + 12 + 1);
+        let face_normal = |triangle: &Triangle| {
+            glm::normalize(&glm::cross(&triangle.edges[0], &triangle.edges[1]))
+        };
+        // the cube's faces and the flat triangle keep their own normals
+        for triangle in triangles[..12].iter().chain(triangles[24..].iter()) {
+            for normal in triangle.vertex_normals.iter() {
+                assert!(glm::distance(normal, &face_normal(triangle)) < 1e-6);
+            }
+        }
+        // the prism's corners blend the faces around them, so they point
+        // out from its axis, leaning toward the side with more triangle
+        // area at the corner
+        for triangle in triangles[12..24].iter() {
+            for (vertex, normal) in triangle.vertices.iter().zip(triangle.vertex_normals.iter()) {
+                let radial = glm::normalize(&glm::vec3(vertex.x - 4.0, 0.0, vertex.z));
+                assert!(glm::dot(normal, &radial) > 0.95, "{:?}", normal);
+                assert!(glm::distance(normal, &face_normal(triangle)) > 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn cached_box_is_the_union_of_the_triangle_boxes() {
+        for filename in ["assets/teapot.obj", "assets/cow.obj", "assets/sphere.obj"] {
+            let triangles = read_obj(filename).unwrap().triangles(gray(), None);
+            let union = triangles
+                .iter()
+                .map(|triangle| triangle.bounding_box().unwrap())
+                .reduce(|union, next| AABB::surrounding_box(&union, &next))
+                .unwrap();
+            let mesh = Mesh::from_triangles(triangles, BvhSettings::default());
+            let cached = mesh.bounding_box().unwrap();
+            assert_eq!(cached.minimum_point, union.minimum_point, "{}", filename);
+            assert_eq!(cached.maximum_point, union.maximum_point, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn validate_finds_a_closed_cube_watertight_and_a_plane_open() {
+        let cube = read_obj("assets/split_cube.obj")
+            .unwrap()
+            .triangles(gray(), None);
+        let report = Mesh::from_triangles(cube.clone(), BvhSettings::default()).validate();
+        assert_eq!(report.triangle_count, 12);
+        assert!(report.is_watertight());
+        assert_eq!(
+            (
+                report.degenerate_triangles,
+                report.duplicate_triangles,
+                report.flipped_edges
+            ),
+            (0, 0, 0)
+        );
+        assert_eq!(report.outward_area, Some(1.0));
+        // every ray through the cube crosses two of its faces
+        assert!(
+            (2.0..=12.0).contains(&report.candidates_per_ray),
+            "{}",
+            report.candidates_per_ray
+        );
+
+        let corners = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 1.0),
+            glm::vec3(0.0, 0.0, 1.0),
+        ];
+        let plane = vec![
+            Triangle::new([corners[0], corners[1], corners[2]], gray()),
+            Triangle::new([corners[0], corners[2], corners[3]], gray()),
+        ];
+        let report = Mesh::from_triangles(plane, BvhSettings::default()).validate();
+        assert!(!report.is_watertight());
+        assert_eq!((report.open_edges, report.non_manifold_edges), (4, 0));
+
+        // one triangle wound backward, one repeated, and one collapsed
+        let mut broken = cube;
+        let [a, b, c] = broken[0].vertices;
+        broken[0] = Triangle::new([a, c, b], gray());
+        // repeating one on the opposite face, so the edges it adds don't
+        // meet the backward one's
+        let opposite = broken
+            .iter()
+            .position(|triangle| {
+                triangle
+                    .vertices
+                    .iter()
+                    .all(|vertex| ![a, b, c].contains(vertex))
+            })
+            .unwrap();
+        broken.push(Triangle::new(broken[opposite].vertices, gray()));
+        broken.push(Triangle::new([a, a, b], gray()));
+        let report = Mesh::from_triangles(broken, BvhSettings::default()).validate();
+        assert_eq!(report.flipped_edges, 3);
+        assert_eq!(report.duplicate_triangles, 1);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.non_manifold_edges, 3);
+        assert!(!report.is_watertight());
+    }
+
+    #[test]
+    fn smooth_normals_match_a_serial_sum_on_any_thread_count() {
+        let data = read_obj("assets/cow.obj").unwrap();
+        let triangles: Vec<[Vec3; 3]> = data
+            .indices
+            .chunks(3)
+            .map(|corners| {
+                [0, 1, 2].map(|corner| {
+                    glm::make_vec3(&data.positions[corners[corner] * 3..corners[corner] * 3 + 3])
+                })
+            })
+            .collect();
+        let epsilon = Some(1.0e-4);
+
+        // the serial sum, in triangle order, which the parallel one follows
+        let welded = weld_vertices(&data.positions, 1.0e-4);
+        let mut sums: HashMap<usize, Vec3> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            let face = glm::cross(&(triangle[1] - triangle[0]), &(triangle[2] - triangle[0]));
+            for corner in index * 3..index * 3 + 3 {
+                *sums
+                    .entry(welded[data.indices[corner]])
+                    .or_insert(glm::vec3(0.0, 0.0, 0.0)) += face;
+            }
+        }
+        let serial: Vec<Vec3> = data
+            .indices
+            .iter()
+            .map(|vertex| sums[&welded[*vertex]])
+            .collect();
+
+        let bits = |normals: &[Vec3]| -> Vec<[u32; 3]> {
+            normals
+                .iter()
+                .map(|normal| [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()])
+                .collect()
+        };
+        for threads in [1, 3, 8] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let parallel = pool.install(|| {
+                smooth_normals(&data.positions, &data.indices, &triangles, None, epsilon)
+            });
+            assert_eq!(bits(&parallel), bits(&serial), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn normalized_mesh_fits_a_centered_unit_cube() {
+        for (filename, up_axis) in [
+            ("assets/teapot.obj", UpAxis::Y),
+            ("assets/z_up_house.obj", UpAxis::Z),
+        ] {
+            let load = |normalize: bool| {
+                Mesh::create(
+                    filename,
+                    gray(),
+                    BvhSettings::default(),
+                    None,
+                    ImportTransform {
+                        up_axis,
+                        flip_x: false,
+                        normalize,
+                    },
+                )
+            };
+            let as_loaded = load(false);
+            assert!(as_loaded.normalization().is_none());
+            let original = as_loaded.bounding_box().unwrap();
+
+            let normalized = load(true);
+            let normalization = normalized.normalization().unwrap();
+            let bounds = normalized.bounding_box().unwrap();
+            let extent = bounds.maximum_point - bounds.minimum_point;
+            assert!((glm::comp_max(&extent) - 1.0).abs() < 1e-6, "{}", filename);
+            let center = (bounds.minimum_point + bounds.maximum_point) / 2.0;
+            assert!(glm::length(&center) < 1e-6, "{} {:?}", filename, center);
+            // the other sides keep their proportions to the longest
+            let original_extent = original.maximum_point - original.minimum_point;
+            assert!(glm::distance(&(original_extent / normalization.size), &extent) < 1e-5);
+            // and undoing the normalization gives back the file's corner
+            assert!(
+                glm::distance(
+                    &normalization.undo(&bounds.minimum_point),
+                    &original.minimum_point
+                ) < 1e-5
+            );
+        }
+    }
+
+    #[test]
+    fn undoing_the_normalization_gives_back_every_vertex() {
+        for (filename, up_axis) in [
+            ("assets/teapot.obj", UpAxis::Y),
+            ("assets/z_up_house.obj", UpAxis::Z),
+        ] {
+            let import = |normalize: bool| ImportTransform {
+                up_axis,
+                flip_x: false,
+                normalize,
+            };
+            // the vertices as loaded, after the rotation onto +Y
+            let mut original = read_obj(filename).unwrap();
+            assert!(original.apply_import_transform(import(false)).is_none());
+            let mut normalized = read_obj(filename).unwrap();
+            let normalization = normalized.apply_import_transform(import(true)).unwrap();
+            // the mesh keeps the same normalization it was fitted with
+            let mesh = Mesh::create(filename, gray(), BvhSettings::default(), None, import(true));
+            assert_eq!(mesh.normalization(), Some(normalization));
+
+            let tolerance = 1e-6 * normalization.size.max(1.0);
+            for (before, after) in original
+                .positions
+                .chunks(3)
+                .zip(normalized.positions.chunks(3))
+            {
+                let (before, after) = (glm::make_vec3(before), glm::make_vec3(after));
+                let undone = normalization.undo(&after);
+                assert!(
+                    glm::distance(&undone, &before) < tolerance,
+                    "{} {:?} {:?}",
+                    filename,
+                    undone,
+                    before
+                );
+                assert!(glm::distance(&normalization.apply(&before), &after) < 1e-6);
+            }
         }
     }
 }
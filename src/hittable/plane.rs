@@ -5,12 +5,16 @@ use crate::material::MaterialType;
 use crate::ray::Ray;
 use glm::Vec3;
 
-/// Represent a plane in space
+/// Represent a plane in space, either infinite or a square around its
+/// center
 pub struct Plane {
     /// center point of the plane
     pub center: Vec3,
     /// normal vector of the plane from the center point
     pub normal: Vec3,
+    /// half the side length of the square the plane is cut to, or `None`
+    /// for an infinite plane
+    pub half_size: Option<f32>,
     /// material of the plane
     pub material: MaterialType,
 }
@@ -27,18 +31,13 @@ impl Hittable for Plane {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
         let angle = glm::dot(&self.normal, &ray.direction);
         if angle.abs() > min_distance {
             let t = glm::dot(&(self.center - ray.origin), &self.normal) / angle;
-            if t > min_distance && t < max_distance {
-                Some(HitRecord {
-                    hit_point: ray.at(t),
-                    ray: *ray,
-                    distance: t,
-                    outward_normal: self.normal,
-                    material: Some(&self.material),
-                })
+            if t > min_distance && t < max_distance && self.contains(ray.at(t)) {
+                let normal = glm::normalize(&self.normal);
+                Some(HitRecord::new(ray, t, normal, normal, Some(&self.material)))
             } else {
                 None
             }
@@ -47,8 +46,158 @@ impl Hittable for Plane {
         }
     }
 
-    /// A plane is infinite, so it has no bounding box.
+    /// An infinite plane has no bounding box. A finite plane's box is padded
+    /// slightly, so axis-aligned planes don't give a box with no thickness.
     fn bounding_box(&self) -> Option<AABB> {
-        None
+        let half_size = self.half_size?;
+        let (u, v) = self.axes();
+        let reach =
+            (glm::abs(&u) + glm::abs(&v)) * half_size + glm::vec3(PADDING, PADDING, PADDING);
+        Some(AABB {
+            minimum_point: self.center - reach,
+            maximum_point: self.center + reach,
+        })
+    }
+}
+
+/// Thickness added to each side of a finite plane's bounding box
+const PADDING: f32 = 0.0001;
+
+/// Methods specific to planes
+impl Plane {
+    /// Create an infinite plane.
+    ///
+    /// # Arguments
+    /// - `center: Vec3` - a point on the plane
+    /// - `normal: Vec3` - normal of the plane
+    /// - `material: MaterialType` - material of the plane
+    ///
+    /// # Returns
+    /// - `Plane` - the plane, which has no bounding box
+    pub fn new(center: Vec3, normal: Vec3, material: MaterialType) -> Plane {
+        Plane {
+            center,
+            normal,
+            half_size: None,
+            material,
+        }
+    }
+
+    /// Create a plane cut to a square around its center, which has a
+    /// bounding box. The square's sides run along the axes from `axes`.
+    ///
+    /// # Arguments
+    /// - `center: Vec3` - center of the square
+    /// - `normal: Vec3` - normal of the plane
+    /// - `half_size: f32` - half the side length of the square
+    /// - `material: MaterialType` - material of the plane
+    ///
+    /// # Returns
+    /// - `Plane` - the finite plane
+    pub fn with_extent(
+        center: Vec3,
+        normal: Vec3,
+        half_size: f32,
+        material: MaterialType,
+    ) -> Plane {
+        Plane {
+            center,
+            normal,
+            half_size: Some(half_size),
+            material,
+        }
+    }
+
+    /// Two perpendicular unit vectors lying in the plane.
+    fn axes(&self) -> (Vec3, Vec3) {
+        let normal = glm::normalize(&self.normal);
+        let helper = if normal.x.abs() < 0.9 {
+            glm::vec3(1.0, 0.0, 0.0)
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        };
+        let u = glm::normalize(&glm::cross(&helper, &normal));
+        let v = glm::cross(&normal, &u);
+        (u, v)
+    }
+
+    /// Whether a point on the plane is within its extent, always true for
+    /// infinite planes.
+    fn contains(&self, point: Vec3) -> bool {
+        match self.half_size {
+            Some(half_size) => {
+                let (u, v) = self.axes();
+                let offset = point - self.center;
+                glm::dot(&offset, &u).abs() <= half_size && glm::dot(&offset, &v).abs() <= half_size
+            }
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    /// Gray diffuse material for test planes.
+    fn gray() -> MaterialType {
+        MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        })
+    }
+
+    #[test]
+    fn finite_plane_has_a_padded_box_and_is_only_hit_inside_it() {
+        let center = glm::vec3(1.0, 2.0, 3.0);
+        let up = glm::vec3(0.0, 1.0, 0.0);
+        let plane = Plane::with_extent(center, up, 2.0, gray());
+        let bounds = plane.bounding_box().unwrap();
+        let padding = glm::vec3(PADDING, PADDING, PADDING);
+        assert!(
+            glm::distance(
+                &bounds.minimum_point,
+                &(glm::vec3(-1.0, 2.0, 1.0) - padding)
+            ) < 1e-6
+        );
+        assert!(glm::distance(&bounds.maximum_point, &(glm::vec3(3.0, 2.0, 5.0) + padding)) < 1e-6);
+        assert!(Plane::new(center, up, gray()).bounding_box().is_none());
+
+        let down = glm::vec3(0.0, -1.0, 0.0);
+        let inside = Ray::new(center + glm::vec3(1.5, 3.0, -1.5), down, None);
+        let outside = Ray::new(center + glm::vec3(2.5, 3.0, 0.0), down, None);
+        let hit = plane.hit(&inside, 1e-4, f32::INFINITY).unwrap();
+        assert!((hit.distance - 3.0).abs() < 1e-5);
+        assert!(plane.hit(&outside, 1e-4, f32::INFINITY).is_none());
+        // the infinite plane is hit either way
+        let infinite = Plane::new(center, up, gray());
+        assert!(infinite.hit(&outside, 1e-4, f32::INFINITY).is_some());
+    }
+
+    #[test]
+    fn tilted_finite_plane_hits_lie_in_its_box() {
+        let plane = Plane::with_extent(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 1.0, 0.5),
+            1.0,
+            gray(),
+        );
+        let bounds = plane.bounding_box().unwrap();
+        let mut hits = 0;
+        for i in 0..21 {
+            for j in 0..21 {
+                let origin = glm::vec3(i as f32 / 5.0 - 2.0, j as f32 / 5.0 - 2.0, 5.0);
+                let ray = Ray::new(origin, glm::vec3(0.0, 0.0, -1.0), None);
+                if let Some(hit) = plane.hit(&ray, 1e-4, f32::INFINITY) {
+                    hits += 1;
+                    for axis in 0..3 {
+                        assert!(hit.hit_point[axis] >= bounds.minimum_point[axis]);
+                        assert!(hit.hit_point[axis] <= bounds.maximum_point[axis]);
+                    }
+                }
+            }
+        }
+        assert!(hits > 0 && hits < 21 * 21);
     }
 }
@@ -0,0 +1,481 @@
+use crate::hittable::mesh::fan_triangulate;
+use crate::hittable::mesh::MeshData;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+
+/// Text or binary encodings of the data after a PLY header
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Numeric types a PLY property can have
+#[derive(Clone, Copy)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    /// Parse a type name from a PLY header, in either the old or new
+    /// spelling.
+    fn parse(name: &str) -> Result<Scalar> {
+        match name {
+            "char" | "int8" => Ok(Scalar::Int8),
+            "uchar" | "uint8" => Ok(Scalar::UInt8),
+            "short" | "int16" => Ok(Scalar::Int16),
+            "ushort" | "uint16" => Ok(Scalar::UInt16),
+            "int" | "int32" => Ok(Scalar::Int32),
+            "uint" | "uint32" => Ok(Scalar::UInt32),
+            "float" | "float32" => Ok(Scalar::Float32),
+            "double" | "float64" => Ok(Scalar::Float64),
+            _ => Err(invalid(format!("unknown property type `{}`", name))),
+        }
+    }
+
+    /// Number of bytes the type takes up in a binary file.
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    /// Largest value of integer types, which color channels are scaled by.
+    /// Floating point colors are already in [0, 1].
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::Int8 => i8::MAX as f64,
+            Scalar::UInt8 => u8::MAX as f64,
+            Scalar::Int16 => i16::MAX as f64,
+            Scalar::UInt16 => u16::MAX as f64,
+            Scalar::Int32 => i32::MAX as f64,
+            Scalar::UInt32 => u32::MAX as f64,
+            Scalar::Float32 | Scalar::Float64 => 1.0,
+        }
+    }
+}
+
+/// A property of an element, either one value or a list of values preceded
+/// by their count
+enum Property {
+    Value(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+/// A group of items in a PLY file, like the vertices or the faces
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads the values after the header one at a time
+struct Reader<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    offset: usize,
+    words: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Reader<'a> {
+    /// Read the next value of the given type.
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        if self.format == Format::Ascii {
+            let word = self
+                .words
+                .next()
+                .ok_or_else(|| invalid(String::from("file ends before its last element")))?;
+            return word
+                .parse()
+                .map_err(|_| invalid(format!("`{}` isn't a number", word)));
+        }
+
+        let size = scalar.size();
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| invalid(String::from("file ends before its last element")))?;
+        self.offset += size;
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::Int8 => buffer[0] as i8 as f64,
+            Scalar::UInt8 => buffer[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            Scalar::UInt16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            Scalar::Int32 => {
+                i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+            }
+            Scalar::UInt32 => {
+                u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+            }
+            Scalar::Float32 => {
+                f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+            }
+            Scalar::Float64 => f64::from_le_bytes(buffer),
+        })
+    }
+}
+
+/// Make an error for a malformed file.
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Read the vertices and faces of a PLY file. Vertices need `x`, `y` and `z`
+/// properties, and may have normals (`nx`, `ny`, `nz`), colors (`red`,
+/// `green`, `blue`) and texture coordinates (`s` and `t`, or `u` and `v`).
+/// Faces are lists of vertex indices, which are triangulated as fans. Other
+/// elements are skipped.
+///
+/// # Arguments
+/// - `filename: &str` - path to the PLY file
+///
+/// # Returns
+/// - `Result<MeshData>` - the mesh's buffers, or why they couldn't be read
+pub fn read(filename: &str) -> Result<MeshData> {
+    let contents = std::fs::read(filename)?;
+
+    // the header is always text, ending at the `end_header` line
+    let marker = b"end_header";
+    let header_end = contents
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .ok_or_else(|| invalid(String::from("missing `end_header`")))?;
+    let header = std::str::from_utf8(&contents[..header_end])
+        .map_err(|_| invalid(String::from("header isn't text")))?;
+    let mut body_start = header_end + marker.len();
+    while body_start < contents.len() && contents[body_start] != b'\n' {
+        body_start += 1;
+    }
+    body_start += 1;
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid(String::from("not a PLY file")));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown format `{}`", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: String::from(*name),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("bad element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid(String::from("property before any element")))?
+                .properties
+                .push(Property::List(
+                    String::from(*name),
+                    Scalar::parse(count_type)?,
+                    Scalar::parse(item_type)?,
+                )),
+            ["property", value_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid(String::from("property before any element")))?
+                .properties
+                .push(Property::Value(
+                    String::from(*name),
+                    Scalar::parse(value_type)?,
+                )),
+            _ => {} // comments, obj_info and blank lines
+        }
+    }
+    let format = format.ok_or_else(|| invalid(String::from("missing `format`")))?;
+
+    let body = contents.get(body_start..).unwrap_or(&[]);
+    let mut reader = Reader {
+        format,
+        bytes: body,
+        offset: 0,
+        words: match format {
+            Format::Ascii => std::str::from_utf8(body)
+                .map_err(|_| invalid(String::from("ASCII data isn't text")))?
+                .split_ascii_whitespace(),
+            _ => "".split_ascii_whitespace(),
+        },
+    };
+
+    let mut data = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        colors: None,
+        smoothing_groups: None,
+        indices: Vec::new(),
+    };
+    let mut colors = Vec::new();
+    let mut face_indices: Vec<u32> = Vec::new();
+    let mut face_sizes: Vec<u32> = Vec::new();
+    for element in elements.iter() {
+        for _ in 0..element.count {
+            // values of this item, by property name
+            let mut position = [0.0f32; 3];
+            let mut normal = [0.0f32; 3];
+            let mut color = [1.0f32; 3];
+            let mut texcoord = [0.0f32; 2];
+            let (mut has_normal, mut has_color, mut has_texcoord) = (false, false, false);
+            for property in element.properties.iter() {
+                match property {
+                    Property::Value(name, scalar) => {
+                        let value = reader.read(*scalar)?;
+                        match name.as_str() {
+                            "x" => position[0] = value as f32,
+                            "y" => position[1] = value as f32,
+                            "z" => position[2] = value as f32,
+                            "nx" | "ny" | "nz" => {
+                                normal[(name.as_bytes()[1] - b'x') as usize] = value as f32;
+                                has_normal = true;
+                            }
+                            "red" | "green" | "blue" => {
+                                let channel = match name.as_str() {
+                                    "red" => 0,
+                                    "green" => 1,
+                                    _ => 2,
+                                };
+                                color[channel] = (value / scalar.color_scale()) as f32;
+                                has_color = true;
+                            }
+                            "s" | "u" | "texture_u" => {
+                                texcoord[0] = value as f32;
+                                has_texcoord = true;
+                            }
+                            "t" | "v" | "texture_v" => {
+                                texcoord[1] = value as f32;
+                                has_texcoord = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                    Property::List(name, count_type, item_type) => {
+                        let count = reader.read(*count_type)? as usize;
+                        let is_face_indices = element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index");
+                        for _ in 0..count {
+                            let index = reader.read(*item_type)?;
+                            if is_face_indices {
+                                face_indices.push(index as u32);
+                            }
+                        }
+                        if is_face_indices {
+                            face_sizes.push(count as u32);
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                data.positions.extend_from_slice(&position);
+                if has_normal {
+                    data.normals.extend_from_slice(&normal);
+                }
+                if has_color {
+                    colors.push(glm::vec3(color[0], color[1], color[2]));
+                }
+                if has_texcoord {
+                    data.texcoords.extend_from_slice(&texcoord);
+                }
+            }
+        }
+    }
+
+    let vertex_count = data.positions.len() / 3;
+    if face_indices
+        .iter()
+        .any(|index| *index as usize >= vertex_count)
+    {
+        return Err(invalid(String::from("face refers to a missing vertex")));
+    }
+    if colors.len() == vertex_count && vertex_count > 0 {
+        data.colors = Some(colors);
+    }
+    data.indices = fan_triangulate(&face_indices, &face_sizes);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::bvh::BvhSettings;
+    use crate::hittable::mesh::Mesh;
+    use crate::hittable::Hittable;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::MaterialType;
+    use crate::ray::Ray;
+
+    /// Positions and colors of the fixture's vertices
+    const VERTICES: [([f32; 3], [u8; 3]); 5] = [
+        ([0.0, 0.0, 0.0], [255, 0, 0]),
+        ([1.0, 0.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0, 0.0], [0, 0, 255]),
+        ([0.0, 1.0, 0.0], [255, 255, 0]),
+        ([0.5, 2.25, -1.5], [0, 51, 255]),
+    ];
+    /// Faces of the fixture, a quad and a triangle
+    const FACES: [&[i32]; 2] = [&[0, 1, 2, 3], &[3, 2, 4]];
+
+    /// Write the fixture in the given format to a temporary file named for
+    /// the test writing it, which the caller removes.
+    fn write_fixture(format: &str, test: &str) -> std::path::PathBuf {
+        let mut contents = format!(
+            "ply\nformat {} 1.0\ncomment test fixture\nelement vertex {}\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face {}\nproperty list uchar int vertex_indices\nend_header\n",
+            format,
+            VERTICES.len(),
+            FACES.len()
+        )
+        .into_bytes();
+        let big_endian = format == "binary_big_endian";
+        for (position, color) in VERTICES.iter() {
+            if format == "ascii" {
+                let line = format!(
+                    "{} {} {} {} {} {}\n",
+                    position[0], position[1], position[2], color[0], color[1], color[2]
+                );
+                contents.extend_from_slice(line.as_bytes());
+            } else {
+                for value in position.iter() {
+                    contents.extend_from_slice(&if big_endian {
+                        value.to_be_bytes()
+                    } else {
+                        value.to_le_bytes()
+                    });
+                }
+                contents.extend_from_slice(color);
+            }
+        }
+        for face in FACES.iter() {
+            if format == "ascii" {
+                let indices: Vec<String> = face.iter().map(|index| index.to_string()).collect();
+                let line = format!("{} {}\n", face.len(), indices.join(" "));
+                contents.extend_from_slice(line.as_bytes());
+            } else {
+                contents.push(face.len() as u8);
+                for index in face.iter() {
+                    contents.extend_from_slice(&if big_endian {
+                        index.to_be_bytes()
+                    } else {
+                        index.to_le_bytes()
+                    });
+                }
+            }
+        }
+        let path = std::env::temp_dir().join(format!(
+            "ray_tracer_{}_{}_{}.ply",
+            test,
+            format,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Write the fixture in the given format and read it back.
+    fn read_fixture(format: &str) -> MeshData {
+        let path = write_fixture(format, "read");
+        let data = read(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        data.unwrap()
+    }
+
+    #[test]
+    fn ascii_and_binary_files_give_identical_geometry() {
+        let ascii = read_fixture("ascii");
+        let positions: Vec<f32> = VERTICES
+            .iter()
+            .flat_map(|(position, _)| *position)
+            .collect();
+        assert_eq!(ascii.positions, positions);
+        assert_eq!(ascii.indices, vec![0, 1, 2, 0, 2, 3, 3, 2, 4]);
+        let colors = ascii.colors.clone().unwrap();
+        assert_eq!(colors[4], glm::vec3(0.0, 0.2, 1.0));
+
+        for format in ["binary_little_endian", "binary_big_endian"] {
+            let binary = read_fixture(format);
+            assert_eq!(binary.positions, ascii.positions, "{}", format);
+            assert_eq!(binary.indices, ascii.indices, "{}", format);
+            assert_eq!(binary.colors, ascii.colors, "{}", format);
+        }
+    }
+
+    #[test]
+    fn meshes_loaded_from_ascii_and_binary_files_are_hit_the_same() {
+        let material = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let mut meshes = Vec::new();
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            let path = write_fixture(format, "mesh");
+            meshes.push(Mesh::create_ply(
+                path.to_str().unwrap(),
+                material,
+                BvhSettings::default(),
+            ));
+            std::fs::remove_file(&path).unwrap();
+        }
+        // straight down onto the quad, which lies in the z = 0 plane, and
+        // across the triangle leaning back from its top edge
+        let mut quad_hits = 0;
+        for i in 0..10 {
+            for j in 0..20 {
+                let origin = glm::vec3(0.05 + 0.1 * i as f32, 0.05 + 0.1 * j as f32, 5.0);
+                let ray = Ray::new(origin, glm::vec3(0.0, 0.0, -1.0), None);
+                let hits: Vec<_> = meshes
+                    .iter()
+                    .map(|mesh| mesh.hit(&ray, 1e-4, f32::INFINITY))
+                    .collect();
+                let ascii = match &hits[0] {
+                    Some(hit) => hit,
+                    None => {
+                        assert!(hits.iter().all(Option::is_none), "{:?}", origin);
+                        continue;
+                    }
+                };
+                if origin.y < 1.0 {
+                    assert!((ascii.distance - 5.0).abs() < 1e-5);
+                    quad_hits += 1;
+                }
+                for hit in hits[1..].iter() {
+                    let hit = hit.as_ref().unwrap();
+                    assert_eq!(hit.distance, ascii.distance, "{:?}", origin);
+                    assert_eq!(hit.outward_normal, ascii.outward_normal);
+                    assert_eq!(hit.vertex_colors, ascii.vertex_colors);
+                }
+            }
+        }
+        assert_eq!(quad_hits, 100);
+        let bounding_box = meshes[0].bounding_box().unwrap();
+        assert_eq!(bounding_box.minimum_point, glm::vec3(0.0, 0.0, -1.5));
+        assert_eq!(bounding_box.maximum_point, glm::vec3(1.0, 2.25, 0.0));
+    }
+}
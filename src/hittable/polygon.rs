@@ -0,0 +1,143 @@
+use crate::hittable::triangle::Triangle;
+use crate::material::MaterialType;
+use glm::Vec3;
+
+/// Split a flat polygon into triangles by ear clipping. The polygon may be
+/// concave, but must not cross itself. Its vertices are given in order
+/// around the outline, and the triangles wind the same way, so they face
+/// the same side as the polygon.
+///
+/// # Arguments
+/// - `vertices: &[Vec3]` - outline of the polygon, all in one plane
+/// - `material: MaterialType` - material for every triangle
+///
+/// # Returns
+/// - `Vec<Triangle>` - the triangles, `vertices.len() - 2` of them unless
+///   some vertices lie on a straight edge
+pub fn triangulate(vertices: &[Vec3], material: MaterialType) -> Vec<Triangle> {
+    ear_clip(vertices)
+        .iter()
+        .map(|[a, b, c]| Triangle::new([vertices[*a], vertices[*b], vertices[*c]], material))
+        .collect()
+}
+
+/// Find the triangles of a flat polygon by ear clipping, as indices into its
+/// vertices. An ear is a convex corner whose triangle contains no other
+/// vertex; cutting it off leaves a smaller polygon, which always has
+/// another ear.
+///
+/// # Arguments
+/// - `vertices: &[Vec3]` - outline of the polygon, all in one plane
+///
+/// # Returns
+/// - `Vec<[usize; 3]>` - vertex indices of the triangles
+pub fn ear_clip(vertices: &[Vec3]) -> Vec<[usize; 3]> {
+    let normal = newell_normal(vertices);
+    let mut remaining: Vec<usize> = (0..vertices.len()).collect();
+    let mut triangles = Vec::new();
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let mut clipped = false;
+        for i in 0..count {
+            let previous = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+            let (a, b, c) = (vertices[previous], vertices[current], vertices[next]);
+            let turn = glm::dot(&glm::cross(&(b - a), &(c - b)), &normal);
+
+            // a vertex on a straight edge adds nothing, so drop it
+            if turn.abs() <= f32::EPSILON * glm::length2(&(c - a)) {
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+            if turn < 0.0 {
+                continue; // reflex corner
+            }
+            let blocked = remaining.iter().any(|&other| {
+                other != previous
+                    && other != current
+                    && other != next
+                    && inside_triangle(vertices[other], [a, b, c], &normal)
+            });
+            if !blocked {
+                triangles.push([previous, current, next]);
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+        if !clipped {
+            // only a self-intersecting outline has no ears, so give up on
+            // the rest rather than loop forever
+            return triangles;
+        }
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// Normal of a polygon by Newell's method, pointing to the side its
+/// vertices wind counterclockwise around. Unlike the cross product of two
+/// edges, this is right for concave polygons.
+fn newell_normal(vertices: &[Vec3]) -> Vec3 {
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    for (index, current) in vertices.iter().enumerate() {
+        let next = vertices[(index + 1) % vertices.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+    normal
+}
+
+/// Whether a point in the plane of a counterclockwise triangle is inside it
+/// or on its edges.
+fn inside_triangle(point: Vec3, triangle: [Vec3; 3], normal: &Vec3) -> bool {
+    (0..3).all(|edge| {
+        let start = triangle[edge];
+        let end = triangle[(edge + 1) % 3];
+        glm::dot(&glm::cross(&(end - start), &(point - start)), normal) >= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    #[test]
+    fn l_shaped_polygon_splits_into_triangles_covering_its_area() {
+        // an L of three unit squares, concave at (1, 1)
+        let outline = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(2.0, 0.0, 0.0),
+            glm::vec3(2.0, 1.0, 0.0),
+            glm::vec3(1.0, 1.0, 0.0),
+            glm::vec3(1.0, 2.0, 0.0),
+            glm::vec3(0.0, 2.0, 0.0),
+        ];
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let triangles = triangulate(&outline, gray);
+        assert_eq!(triangles.len(), outline.len() - 2);
+        // every triangle winds the same way as the outline, so none of them
+        // cancels another out and their areas add up to the L's
+        let mut area = 0.0;
+        for triangle in triangles.iter() {
+            let cross = glm::cross(&triangle.edges[0], &triangle.edges[1]);
+            assert!(cross.z > 0.0);
+            area += 0.5 * cross.z;
+            // and none reaches into the notch
+            let centroid =
+                (triangle.vertices[0] + triangle.vertices[1] + triangle.vertices[2]) / 3.0;
+            assert!(centroid.x < 1.0 || centroid.y < 1.0);
+        }
+        assert!((area - 3.0).abs() < 1e-6, "{}", area);
+    }
+}
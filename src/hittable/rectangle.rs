@@ -9,12 +9,16 @@ use glm::Vec3;
 
 /// Represent a rectangular object as two triangles.
 pub struct Rectangle {
-    pub material: MaterialType,
     triangles: TriangleList,
 }
 
 /// Methods for Rectangle
 impl Rectangle {
+    /// Number of triangles making up the rectangle.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.triangle_count()
+    }
+
     /// Create a new rectangle.
     ///
     /// # Arguments
@@ -22,11 +26,20 @@ impl Rectangle {
     ///   clockwise order.
     /// - `material: MaterialType` - material of the rectangle
     pub fn new(points: [Vec3; 4], material: MaterialType) -> Rectangle {
-        let triangle_one = Triangle::new([points[0], points[1], points[2]], material);
-        let triangle_two = Triangle::new([points[2], points[3], points[0]], material);
+        // texture coordinates run from (0, 0) at the first corner to (1, 1)
+        // at the third
+        let triangle_one = Triangle::with_texture_coordinates(
+            [points[0], points[1], points[2]],
+            [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            material,
+        );
+        let triangle_two = Triangle::with_texture_coordinates(
+            [points[2], points[3], points[0]],
+            [(1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            material,
+        );
 
         Rectangle {
-            material,
             triangles: TriangleList::new(vec![triangle_one, triangle_two]),
         }
     }
@@ -44,7 +57,7 @@ impl Hittable for Rectangle {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
         self.triangles.hit(ray, min_distance, max_distance)
     }
 
@@ -1,18 +1,33 @@
 use crate::hit_record::HitRecord;
 use crate::hittable::aabb::AABB;
 use crate::hittable::Hittable;
+use crate::material::Material;
 use crate::material::MaterialType;
+use crate::random;
 use crate::ray::Ray;
 use glm::Vec3;
 
+/// Distance from a sphere's surface, relative to the size of the radius and
+/// the coordinates, within which a ray's origin is taken to be on it. It is
+/// a few hundred times the rounding of a hit point, so a ray leaving the
+/// surface is never mistaken for one starting just inside or outside.
+const SURFACE_TOLERANCE: f32 = 1.0e-5;
+
 /// Represent a sphere in space
+#[derive(Clone, Copy)]
 pub struct Sphere {
     /// center point of the sphere
     pub center: Vec3,
-    /// radius of the sphere
+    /// radius of the sphere. A negative radius keeps the same surface but
+    /// flips the normals to point inward, which makes a hollow shell when
+    /// placed inside a larger sphere of the same material.
     pub radius: f32,
     /// material to use for the sphere
     pub material: MaterialType,
+    /// Distance the center moves over the camera's frame interval, from
+    /// `center` at time 0, or zero for a sphere standing still. Sampling
+    /// the sphere as a light uses where it is at time 0.
+    pub velocity: Vec3,
 }
 
 /// Methods from the hittable trait
@@ -27,49 +42,400 @@ impl Hittable for Sphere {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        // calculate the discriminant
-        let oc = ray.origin - self.center;
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        // calculate the discriminant from the ray's closest approach to the
+        // center rather than as b^2 - 4ac, which cancels away most of its
+        // precision when the ray starts far from a small sphere
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
         let a = glm::dot(&ray.direction, &ray.direction);
         let half_b = glm::dot(&oc, &ray.direction);
         let c = glm::dot(&oc, &oc) - self.radius * self.radius;
-        let discriminant = half_b.powi(2) - a * c;
+        let closest = oc - (half_b / a) * ray.direction;
+        let discriminant = a * (self.radius * self.radius - glm::dot(&closest, &closest));
 
         if discriminant > 0.0 {
-            let root = discriminant.sqrt();
-            let mut x = (-half_b - root) / a;
-            if x < max_distance && x > min_distance {
-                Some(HitRecord {
-                    hit_point: ray.at(x),
-                    ray: *ray,
-                    distance: x,
-                    outward_normal: (ray.at(x) - self.center) / self.radius,
-                    material: Some(&self.material),
-                })
+            // the root further from zero doesn't cancel, and the other
+            // follows from the product of the roots being c / a
+            let q = -half_b - half_b.signum() * discriminant.sqrt();
+            let (near, far) = if self.starts_on_surface(ray, c) {
+                // one root is the origin itself, which rounding can put on
+                // either side of zero, so it is pinned there and the ray only
+                // hits the sphere again if it heads inside
+                (0.0, -2.0 * half_b / a)
             } else {
-                x = (-half_b + root) / a;
+                (c / q, q / a)
+            };
+            let roots = if near < far { [near, far] } else { [far, near] };
+            // try the near root first, then the far one
+            for &x in roots.iter() {
                 if x < max_distance && x > min_distance {
-                    Some(HitRecord {
-                        hit_point: ray.at(x),
-                        ray: *ray,
-                        distance: x,
-                        outward_normal: (ray.at(x) - self.center) / self.radius,
-                        material: Some(&self.material),
-                    })
-                } else {
-                    None
+                    // normalized rather than divided by the radius, since
+                    // a ray from far away lands a little off the surface
+                    let outward_normal =
+                        glm::normalize(&(ray.at(x) - center)) * self.radius.signum();
+                    let texture_coordinates = Sphere::texture_coordinates(&outward_normal);
+                    if !self.material.cuts_out(texture_coordinates) {
+                        let mut hit_record = HitRecord::new(
+                            ray,
+                            x,
+                            outward_normal,
+                            outward_normal,
+                            Some(&self.material),
+                        );
+                        // and the hit point is put back on it
+                        hit_record.hit_point = center + self.radius * outward_normal;
+                        hit_record.texture_coordinates = texture_coordinates;
+                        return Some(hit_record);
+                    }
                 }
             }
+            None
         } else {
             None
         }
     }
 
-    /// Calculate the bounding box for this sphere.
+    /// Calculate the bounding box for this sphere, around everywhere it
+    /// moves over the frame interval.
     fn bounding_box(&self) -> Option<AABB> {
+        let extent = glm::vec3(1.0, 1.0, 1.0) * self.radius.abs();
+        let end = self.center + self.velocity;
         Some(AABB {
-            minimum_point: self.center - glm::vec3(self.radius, self.radius, self.radius),
-            maximum_point: self.center + glm::vec3(self.radius, self.radius, self.radius),
+            minimum_point: glm::min2(&self.center, &end) - extent,
+            maximum_point: glm::max2(&self.center, &end) + extent,
         })
     }
 }
+
+impl Sphere {
+    /// Center of the sphere at a time in the frame interval.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `time: f32` - moment in the frame interval, from 0 to 1
+    ///
+    /// # Returns
+    /// - `Vec3` - the center at that time
+    pub fn center_at(&self, time: f32) -> Vec3 {
+        self.center + time * self.velocity
+    }
+
+    /// Whether a ray starts on the surface of the sphere, as rays leaving a
+    /// hit on it do, rather than clearly inside or outside it. Such a ray
+    /// could otherwise hit the surface it starts on, when rounding puts its
+    /// origin a little inside and it heads out, and be taken for a ray still
+    /// inside the sphere.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `ray: &Ray` - the ray
+    /// - `c: f32` - squared distance from the center to the ray's origin,
+    ///   less the squared radius
+    ///
+    /// # Returns
+    /// - `bool` - true if the origin is within rounding of the surface
+    fn starts_on_surface(&self, ray: &Ray, c: f32) -> bool {
+        // c is about twice the radius times the distance from the surface
+        let tolerance =
+            SURFACE_TOLERANCE * (self.radius.abs() + glm::comp_max(&glm::abs(&ray.origin)));
+        c.abs() <= 2.0 * self.radius.abs() * tolerance
+    }
+
+    /// Map a point on the unit sphere to texture coordinates. `u` wraps
+    /// around the y axis starting from -x, and `v` runs from the bottom pole
+    /// to the top.
+    ///
+    /// # Arguments
+    /// - `normal: &Vec3` - unit vector from the center to the surface point
+    ///
+    /// # Returns
+    /// - `(f32, f32)` - the (u, v) texture coordinates
+    fn texture_coordinates(normal: &Vec3) -> (f32, f32) {
+        let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+        let phi = (-normal.z).atan2(normal.x) + std::f32::consts::PI;
+        (
+            phi / (2.0 * std::f32::consts::PI),
+            theta / std::f32::consts::PI,
+        )
+    }
+
+    /// Pick a direction from a point toward this sphere, uniformly over the
+    /// cone of directions in which the sphere is seen. Unlike picking a point
+    /// on the surface, every direction reaches the near side of the sphere,
+    /// so none are wasted on its hidden half.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `point: Vec3` - point the sphere is seen from
+    /// - `u: f32` - random number in [0, 1) choosing the angle from the
+    ///   cone's axis
+    /// - `v: f32` - random number in [0, 1) choosing the angle about the axis
+    ///
+    /// # Returns
+    /// - `Option<(Vec3, f32)>` - unit direction toward the sphere and the
+    ///   solid angle density it was picked with, or `None` if the point is
+    ///   inside the sphere
+    pub fn sample_toward(&self, point: Vec3, u: f32, v: f32) -> Option<(Vec3, f32)> {
+        let (one_minus_cos_theta_max, pdf) = self.cone(point)?;
+        let axis = glm::normalize(&(self.center - point));
+        let direction = random::cone_direction(&axis, one_minus_cos_theta_max, u, v);
+        Some((direction, pdf))
+    }
+
+    /// Density with which `sample_toward` picks any one direction toward
+    /// this sphere from a point, which is the same for every direction in
+    /// its cone.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `point: Vec3` - point the sphere is seen from
+    ///
+    /// # Returns
+    /// - `Option<f32>` - the solid angle density, or `None` if the point is
+    ///   inside the sphere
+    pub fn solid_angle_pdf(&self, point: Vec3) -> Option<f32> {
+        self.cone(point).map(|(_, pdf)| pdf)
+    }
+
+    /// One minus the cosine of the half angle of the cone in which this
+    /// sphere is seen from a point, and the inverse of the cone's solid
+    /// angle.
+    fn cone(&self, point: Vec3) -> Option<(f32, f32)> {
+        let sin_theta_max_squared =
+            self.radius * self.radius / glm::distance2(&point, &self.center);
+        if sin_theta_max_squared >= 1.0 {
+            return None;
+        }
+        let cos_theta_max = (1.0 - sin_theta_max_squared).sqrt();
+        // the cap is so thin for distant spheres that the float cosine
+        // rounds to one, so find its area from the sine instead
+        let one_minus_cos = sin_theta_max_squared / (1.0 + cos_theta_max);
+        Some((
+            one_minus_cos,
+            1.0 / (2.0 * std::f32::consts::PI * one_minus_cos),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::transparent::Transparent;
+    use crate::material::ShadingContext;
+    use crate::scenes::Sky;
+    use crate::trace_ray;
+    use crate::DEPTH_LIMIT;
+    use crate::EPSILON;
+
+    /// Sphere of radius one, or minus one, at the origin.
+    fn unit_sphere(radius: f32) -> Sphere {
+        Sphere {
+            center: glm::vec3(0.0, 0.0, 0.0),
+            radius,
+            material: MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(0.5, 0.5, 0.5),
+                force_front_face: false,
+            }),
+            velocity: glm::vec3(0.0, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn negative_radius_hit_from_inside_has_inward_normal() {
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), None);
+        let inside_out = unit_sphere(-1.0);
+        let hit = inside_out.hit(&ray, 1.0e-4, f32::INFINITY).unwrap();
+        assert!((hit.distance - 1.0).abs() < 1.0e-5);
+        // the outward normal of an inside-out sphere points at its center,
+        // so a ray from inside hits its front face
+        assert!(glm::distance(&hit.outward_normal, &glm::vec3(-1.0, 0.0, 0.0)) < 1.0e-5);
+        assert!(hit.is_front_face());
+        assert!(glm::distance(&hit.normal(), &glm::vec3(-1.0, 0.0, 0.0)) < 1.0e-5);
+
+        // the same ray inside a normal sphere hits the back of the face
+        let sphere = unit_sphere(1.0);
+        let hit = sphere.hit(&ray, 1.0e-4, f32::INFINITY).unwrap();
+        assert!(glm::distance(&hit.outward_normal, &glm::vec3(1.0, 0.0, 0.0)) < 1.0e-5);
+        assert!(!hit.is_front_face());
+    }
+
+    #[test]
+    fn negative_radius_bounding_box_is_not_inside_out() {
+        let bbox = unit_sphere(-1.0).bounding_box().unwrap();
+        assert_eq!(bbox.minimum_point, glm::vec3(-1.0, -1.0, -1.0));
+        assert_eq!(bbox.maximum_point, glm::vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn ray_from_inside_a_glass_sphere_exits_along_snells_law() {
+        let glass = Sphere {
+            material: MaterialType::Transparent(Transparent {
+                albedo: glm::vec3(1.0, 1.0, 1.0),
+                reflectance: 0.0,
+                transmittance: 1.0,
+                refractive_index: 1.5,
+                abbe_number: None,
+                roughness: 0.0,
+                reflect_bias: None,
+                branch_depth: None,
+            }),
+            ..unit_sphere(1.0)
+        };
+        // a sky showing the direction it is seen in, in units of its last
+        // channel so the refraction's weight divides out
+        fn direction_sky(ray: &Ray) -> Vec3 {
+            glm::vec3(ray.direction.x, -ray.direction.y, 1.0)
+        }
+        let sky = Sky::Custom(direction_sky);
+        let context = ShadingContext::default();
+        for height in [0.3f32, 0.6] {
+            // without reflections, only the refraction out of the sphere
+            let ray = Ray::new(glm::vec3(0.0, height, 0.0), glm::vec3(1.0, 0.0, 0.0), None)
+                .with_depth_budgets(0, 4);
+            let color = trace_ray(&ray, &glass, &[], &sky, &context, DEPTH_LIMIT);
+            let seen = glm::vec2(color.x / color.z, -color.y / color.z);
+            // the exit point's normal is height above the ray, and the ray
+            // bends away from it
+            let incidence = height.asin();
+            let transmission = (1.5 * height).asin();
+            let angle = incidence - transmission;
+            let expected = glm::vec2(angle.cos(), angle.sin());
+            assert!(
+                glm::distance(&seen, &expected) < 1e-5,
+                "{:?} {:?}",
+                seen,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ray_through_an_air_bubble_in_glass_bends_at_the_relative_indices() {
+        // a glass ball of radius 2 holding a bubble of radius 1, made by an
+        // inside-out sphere of the same glass, which a ray leaving the glass
+        // sees the back of
+        let material = MaterialType::Transparent(Transparent {
+            albedo: glm::vec3(1.0, 1.0, 1.0),
+            reflectance: 0.0,
+            transmittance: 1.0,
+            refractive_index: 1.5,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
+        });
+        let mut world = HittableList::new();
+        world.add_sphere(glm::vec3(0.0, 0.0, 0.0), 2.0, material);
+        world.add_sphere(glm::vec3(0.0, 0.0, 0.0), -1.0, material);
+        fn direction_sky(ray: &Ray) -> Vec3 {
+            glm::vec3(ray.direction.x, -ray.direction.y, 1.0)
+        }
+        let sky = Sky::Custom(direction_sky);
+        let context = ShadingContext::default();
+
+        // the same path followed by hand: distance along a unit direction
+        // to a sphere at the origin, from outside it or from inside
+        let to_sphere = |origin: &Vec3, direction: &Vec3, radius: f32, outside: bool| {
+            let half_b = glm::dot(origin, direction);
+            let root = (half_b * half_b - glm::dot(origin, origin) + radius * radius).sqrt();
+            if outside {
+                -half_b - root
+            } else {
+                -half_b + root
+            }
+        };
+        // and Snell's law for a normal facing the incoming direction, with
+        // the index on the incoming side over the one beyond
+        let snell = |direction: &Vec3, normal: &Vec3, ratio: f32| {
+            let cos_incidence = -glm::dot(direction, normal);
+            let sin_squared = ratio * ratio * (1.0 - cos_incidence * cos_incidence);
+            ratio * direction + (ratio * cos_incidence - (1.0 - sin_squared).sqrt()) * normal
+        };
+
+        for height in [0.2f32, 0.5] {
+            // start in the glass, heading at the bubble off its center
+            let origin = glm::vec3(-1.5, height, 0.0);
+            let ray = Ray::new(origin, glm::vec3(1.0, 0.0, 0.0), None).with_depth_budgets(0, 8);
+            let color = trace_ray(&ray, &world, &[], &sky, &context, DEPTH_LIMIT);
+            let seen = glm::vec3(color.x / color.z, -color.y / color.z, 0.0);
+
+            // glass into air, beyond the critical angle of neither
+            let mut point = origin;
+            let mut direction = glm::vec3(1.0, 0.0, 0.0);
+            point += to_sphere(&point, &direction, 1.0, true) * direction;
+            direction = snell(&direction, &point, 1.5);
+            // air back into glass
+            point += to_sphere(&point, &direction, 1.0, false) * direction;
+            direction = snell(&direction, &-point, 1.0 / 1.5);
+            // and out of the ball into air
+            point += to_sphere(&point, &direction, 2.0, false) * direction;
+            direction = snell(&direction, &(-point / 2.0), 1.5);
+
+            assert!(
+                glm::distance(&seen, &direction) < 1e-4,
+                "{:?} {:?}",
+                seen,
+                direction
+            );
+            // the bubble turns the ray, rather than it passing straight
+            // through the solid part of the ball
+            let solid_exit = origin
+                + to_sphere(&origin, &glm::vec3(1.0, 0.0, 0.0), 2.0, false)
+                    * glm::vec3(1.0, 0.0, 0.0);
+            let solid = snell(&glm::vec3(1.0, 0.0, 0.0), &(-solid_exit / 2.0), 1.5);
+            assert!(
+                glm::distance(&seen, &solid) > 1e-2,
+                "{:?} {:?}",
+                seen,
+                solid
+            );
+        }
+    }
+
+    #[test]
+    fn ray_from_the_surface_does_not_hit_where_it_starts() {
+        // coordinates far from the world's origin round the start points
+        // off the surface, inside or out
+        let mut sphere = unit_sphere(1.0);
+        sphere.center = glm::vec3(10.0, -3.0, 7.0);
+        let steps = 24;
+        for i in 0..steps {
+            for j in 0..steps {
+                let (u, v) = (
+                    (i as f32 + 0.5) / steps as f32,
+                    (j as f32 + 0.5) / steps as f32,
+                );
+                let normal = random::cone_direction(&glm::vec3(0.0, 1.0, 0.0), 2.0, u, v);
+                let point = sphere.center + normal;
+                let tangent = glm::normalize(&glm::cross(&normal, &glm::vec3(0.3, 1.0, 0.2)));
+                // from leaving straight out to grazing the surface, and the
+                // same angles heading in
+                for lean in [1.0, 0.3, 0.01, 0.001] {
+                    let out = Ray::new(point, lean * normal + tangent, None);
+                    assert!(
+                        sphere.hit(&out, EPSILON, f32::INFINITY).is_none(),
+                        "{:?}",
+                        out.direction
+                    );
+
+                    let inward = Ray::new(point, -lean * normal + tangent, None);
+                    let chord = 2.0 * glm::dot(&-normal, &inward.direction);
+                    let hit = sphere.hit(&inward, EPSILON, f32::INFINITY);
+                    if chord > 1e-2 {
+                        let hit = hit.unwrap();
+                        assert!(
+                            (hit.distance - chord).abs() < 1e-4,
+                            "{} {}",
+                            hit.distance,
+                            chord
+                        );
+                        assert!(!hit.is_front_face());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -2,8 +2,11 @@ use super::super::EPSILON;
 use crate::hit_record::HitRecord;
 use crate::hittable::aabb::AABB;
 use crate::hittable::Hittable;
+use crate::material::Material;
 use crate::material::MaterialType;
 use crate::ray::Ray;
+use crate::stats;
+use crate::stats::Counter;
 use glm::Vec3;
 
 /// Represent a triangle in space
@@ -15,6 +18,13 @@ pub struct Triangle {
     pub edges: [Vec3; 2],
     /// Normals at each vertex
     pub vertex_normals: [Vec3; 3],
+    /// Texture coordinates (u, v) at each vertex
+    pub texture_coordinates: [(f32, f32); 3],
+    /// Colors at each vertex, if the triangle came from a mesh with them
+    pub vertex_colors: Option<[Vec3; 3]>,
+    /// Direction in which the u texture coordinate increases across the
+    /// face, if the triangle came from a mesh with texture coordinates
+    pub tangent: Option<Vec3>,
     /// Material of the triangle
     pub material: MaterialType,
 }
@@ -35,11 +45,12 @@ impl Hittable for Triangle {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        stats::record(Counter::TriangleTests);
         let edge_one = &self.edges[0];
         let edge_two = &self.edges[1];
-        let perpendicular = glm::cross(&ray.direction, &edge_two);
-        let elevation_angle = glm::dot(&edge_one, &perpendicular);
+        let perpendicular = glm::cross(&ray.direction, edge_two);
+        let elevation_angle = glm::dot(edge_one, &perpendicular);
         if (-EPSILON..EPSILON).contains(&elevation_angle) {
             None // This ray is parallel to this triangle.
         } else {
@@ -49,22 +60,33 @@ impl Hittable for Triangle {
             if !(0.0..=1.0).contains(&u) {
                 None
             } else {
-                let q = glm::cross(&distance, &edge_one);
+                let q = glm::cross(&distance, edge_one);
                 let v = angle_inv * glm::dot(&ray.direction, &q);
                 if v < 0.0 || u + v > 1.0 {
                     None
                 } else {
                     // compute location of intersection
-                    let t = angle_inv * glm::dot(&edge_two, &q);
-                    if t > min_distance && t < max_distance {
+                    let t = angle_inv * glm::dot(edge_two, &q);
+                    let texture_coordinates = self.interpolate_texture_coordinates(u, v);
+                    if t > min_distance
+                        && t < max_distance
+                        && !self.material.cuts_out(texture_coordinates)
+                    {
                         // intersection!
-                        Some(HitRecord {
-                            hit_point: ray.at(t),
-                            ray: *ray,
-                            distance: t,
-                            outward_normal: self.interpolate_normal(ray.at(t)),
-                            material: Some(&self.material),
-                        })
+                        let weights = glm::vec3(1.0 - u - v, u, v);
+                        let mut hit_record = HitRecord::new(
+                            ray,
+                            t,
+                            self.interpolate_normal(ray.at(t)),
+                            glm::cross(edge_one, edge_two),
+                            Some(&self.material),
+                        );
+                        hit_record.texture_coordinates = texture_coordinates;
+                        hit_record.barycentric_coordinates = Some(weights);
+                        hit_record.edge_distances = Some(self.edge_distances(&weights));
+                        hit_record.vertex_colors = self.vertex_colors;
+                        hit_record.tangent = self.tangent;
+                        Some(hit_record)
                     } else {
                         None
                     }
@@ -97,6 +119,16 @@ impl Hittable for Triangle {
 impl Triangle {
     /// Create a new triangle with vertex normals set to the face normal.
     pub fn new(vertices: [Vec3; 3], material: MaterialType) -> Triangle {
+        Triangle::with_texture_coordinates(vertices, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], material)
+    }
+
+    /// Create a new triangle with the given texture coordinates at each
+    /// vertex, and vertex normals set to the face normal.
+    pub fn with_texture_coordinates(
+        vertices: [Vec3; 3],
+        texture_coordinates: [(f32, f32); 3],
+        material: MaterialType,
+    ) -> Triangle {
         let edge_one = vertices[1] - vertices[0];
         let edge_two = vertices[2] - vertices[0];
         let normal = glm::cross(&edge_one, &edge_two);
@@ -104,10 +136,25 @@ impl Triangle {
             vertices,
             edges: [edge_one, edge_two],
             vertex_normals: [normal; 3],
+            texture_coordinates,
+            vertex_colors: None,
+            tangent: None,
             material,
         }
     }
 
+    /// Interpolate the vertex texture coordinates at a point given by the
+    /// Möller–Trumbore parameters, where the point is
+    /// `(1 - u - v) * v0 + u * v1 + v * v2`.
+    fn interpolate_texture_coordinates(&self, u: f32, v: f32) -> (f32, f32) {
+        let w = 1.0 - u - v;
+        let [t0, t1, t2] = self.texture_coordinates;
+        (
+            w * t0.0 + u * t1.0 + v * t2.0,
+            w * t0.1 + u * t1.1 + v * t2.1,
+        )
+    }
+
     /// Given a hit location, interpolate the vertex normals to get the normal
     /// at the hit point.
     ///
@@ -122,11 +169,11 @@ impl Triangle {
         let edge_two = &self.edges[1];
         let point_to_hit = hit_location - self.vertices[0];
 
-        let d00 = glm::dot(&edge_one, &edge_one);
-        let d01 = glm::dot(&edge_one, &edge_two);
-        let d11 = glm::dot(&edge_two, &edge_two);
-        let d20 = glm::dot(&point_to_hit, &edge_one);
-        let d21 = glm::dot(&point_to_hit, &edge_two);
+        let d00 = glm::dot(edge_one, edge_one);
+        let d01 = glm::dot(edge_one, edge_two);
+        let d11 = glm::dot(edge_two, edge_two);
+        let d20 = glm::dot(&point_to_hit, edge_one);
+        let d21 = glm::dot(&point_to_hit, edge_two);
         let denom = (d00 * d11) - d01.powi(2);
         let v = (d11 * d20 - d01 * d21) / denom;
         let w = (d00 * d21 - d01 * d20) / denom;
@@ -136,6 +183,31 @@ impl Triangle {
             &(u * self.vertex_normals[0] + v * self.vertex_normals[1] + w * self.vertex_normals[2]),
         )
     }
+
+    /// Find how far a point is from each edge of the triangle. Each
+    /// barycentric weight falls linearly from one at its vertex to zero at
+    /// the opposite edge, so scaling it by the triangle's height over that
+    /// edge gives the distance.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `weights: &Vec3` - barycentric coordinates of the point
+    ///
+    /// # Returns
+    /// - `Vec3` - distance to the edge opposite each vertex
+    fn edge_distances(&self, weights: &Vec3) -> Vec3 {
+        let [edge_one, edge_two] = &self.edges;
+        let doubled_area = glm::length(&glm::cross(edge_one, edge_two));
+        let opposite_lengths = glm::vec3(
+            glm::distance(edge_one, edge_two),
+            glm::length(edge_two),
+            glm::length(edge_one),
+        );
+        glm::matrix_comp_mult(
+            weights,
+            &opposite_lengths.map(|length| doubled_area / length),
+        )
+    }
 }
 
 pub struct TriangleList {
@@ -144,12 +216,27 @@ pub struct TriangleList {
 }
 
 impl TriangleList {
+    /// Number of triangles in the list.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// The triangles in the list.
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
     pub fn new(triangles: Vec<Triangle>) -> TriangleList {
-        let bounding_box: AABB = triangles
-            .iter()
-            .fold(triangles[0].bounding_box().unwrap(), |bbox, tri| {
-                AABB::surrounding_box(&bbox, &tri.bounding_box().unwrap())
-            });
+        // a BVH split can leave one side with no triangles, such as when
+        // their boxes all share a centroid, and an inside-out box is one no
+        // ray enters
+        let empty = AABB {
+            minimum_point: glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            maximum_point: glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        };
+        let bounding_box: AABB = triangles.iter().fold(empty, |bbox, tri| {
+            AABB::surrounding_box(&bbox, &tri.bounding_box().unwrap())
+        });
         TriangleList {
             triangles,
             bounding_box,
@@ -172,12 +259,12 @@ impl Hittable for TriangleList {
     ///
     /// # Returns
     /// - Optional `HitRecord` if there was a hit, otherwise `None`.
-    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord> {
-        let mut current_min = std::f32::INFINITY;
+    fn hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<HitRecord<'_>> {
+        let mut current_min = f32::INFINITY;
         let mut closest_hit: Option<HitRecord> = None;
 
         for object in self.triangles.iter() {
-            if let Some(hit) = object.hit(&ray, min_distance, max_distance) {
+            if let Some(hit) = object.hit(ray, min_distance, max_distance) {
                 if hit.distance < current_min {
                     current_min = hit.distance;
                     closest_hit = Some(hit);
@@ -187,8 +274,70 @@ impl Hittable for TriangleList {
         closest_hit
     }
 
+    /// Check the triangles in order and stop at the first one hit.
+    fn any_hit(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> bool {
+        self.triangles
+            .iter()
+            .any(|triangle| triangle.hit(ray, min_distance, max_distance).is_some())
+    }
+
     /// Get the bounding box for this list of triangles.
     fn bounding_box(&self) -> Option<AABB> {
         Some(self.bounding_box)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    /// Right triangle in the z = 0 plane with a gray diffuse material.
+    fn corner_triangle() -> Triangle {
+        Triangle::new(
+            [
+                glm::vec3(0.0, 0.0, 0.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ],
+            MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(0.5, 0.5, 0.5),
+                force_front_face: false,
+            }),
+        )
+    }
+
+    #[test]
+    fn barycentric_coordinates_at_each_vertex_are_the_unit_basis() {
+        let triangle = corner_triangle();
+        for (vertex, expected) in triangle.vertices.iter().zip(
+            [
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+                glm::vec3(0.0, 0.0, 1.0),
+            ]
+            .iter(),
+        ) {
+            let ray = Ray::new(
+                vertex + glm::vec3(0.0, 0.0, 1.0),
+                glm::vec3(0.0, 0.0, -1.0),
+                None,
+            );
+            let hit = triangle.hit(&ray, 0.0, f32::INFINITY).unwrap();
+            let weights = hit.barycentric_coordinates.unwrap();
+            assert!(glm::distance(&weights, expected) < 1.0e-6, "{:?}", weights);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outward normal")]
+    fn hit_with_zeroed_vertex_normals_panics_in_debug_builds() {
+        let triangle = Triangle {
+            vertex_normals: [glm::vec3(0.0, 0.0, 0.0); 3],
+            ..corner_triangle()
+        };
+        let ray = Ray::new(glm::vec3(0.2, 0.2, 1.0), glm::vec3(0.0, 0.0, -1.0), None);
+        triangle.hit(&ray, 0.0, f32::INFINITY);
+    }
+}
@@ -1,47 +1,370 @@
-use super::DIFFUSE_WEIGHT;
 use super::EPSILON;
 use super::MAX_HIT_DISTANCE;
-use super::SPECULAR_COEFFICIENT;
-use super::SPECULAR_WEIGHT;
 use crate::hit_record::HitRecord;
+use crate::hittable::sphere::Sphere;
 use crate::hittable::Hittable;
-use crate::material::Material;
+use crate::random::rng;
 use crate::ray::Ray;
+use crate::stats;
+use crate::stats::Counter;
 use glm::Vec3;
+use rand::seq::SliceRandom;
+use rand::Rng;
 
-/// Represent a point light source
+/// Combine two sampling strategies with the power heuristic, giving the
+/// weight of a sample drawn with density `pdf` when `other_pdf` is the
+/// density the other strategy would have drawn it with. Degenerate
+/// densities, like a light seen exactly edge-on, are clamped so the weight
+/// stays in [0, 1].
+///
+/// # Arguments
+/// - `pdf: f32` - density of the strategy which drew the sample
+/// - `other_pdf: f32` - density of the other strategy
+///
+/// # Returns
+/// - `f32` - weight of the sample
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    if !pdf.is_finite() {
+        return 1.0;
+    }
+    if !other_pdf.is_finite() {
+        return 0.0;
+    }
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// How a surface reflects light arriving from a direction, for sampling
+/// lights at a hit. It is given the direction toward the light, which need
+/// not be of unit length, and the cosine between it and the normal, and
+/// returns the factor the arriving radiance is scaled by on its way to the
+/// viewer, cosine included, with the solid angle density with which the
+/// surface's own bounces would pick the direction.
+pub type SurfaceResponse<'a> = &'a dyn Fn(&Vec3, f32) -> (Vec3, f32);
+
+/// Response of a Lambertian surface, leaving out its albedo and the factor
+/// of pi, so the light it reflects is the cosine weighted irradiance. Its
+/// bounces are cosine distributed.
+///
+/// # Arguments
+/// - `_direction: &Vec3` - direction toward the light
+/// - `cosine: f32` - cosine between the direction and the normal
+///
+/// # Returns
+/// - `(Vec3, f32)` - the cosine in every channel, and the bounces' density
+pub fn diffuse_response(_direction: &Vec3, cosine: f32) -> (Vec3, f32) {
+    (
+        glm::vec3(cosine, cosine, cosine),
+        cosine / std::f32::consts::PI,
+    )
+}
+
+/// Represent a light source which is sampled directly at every diffuse hit.
+/// A light with zero edges is a point light, otherwise it is a rectangle
+/// which emits from both faces, unless it is a sphere. Lights aren't part
+/// of the world, so cameras and mirrors don't see them, only the surfaces
+/// they light, unless the same rectangle is also added to the world with a
+/// `DiffuseLight` material.
 pub struct Light {
-    /// position of the light source in world coordinates
+    /// position of the light source in world coordinates, or one corner of
+    /// the rectangle for area lights
     pub position: Vec3,
-    /// relative strength of the light source
+    /// relative strength of the light source. A negative weight makes a
+    /// "negative light", which takes light away from the surfaces it would
+    /// have lit, for carving out shadows by hand. Nothing real does that, so
+    /// it breaks the physics of the render: the light it takes away bounces
+    /// on as negative light, and shadowed surfaces keep their light since the
+    /// negative light can't reach them. Pixels darkened below black are
+    /// clamped to black.
     pub weight: f32,
+    /// Edges of the rectangle from `position`, zero for a point light
+    pub edges: [Vec3; 2],
+    /// Sphere which emits the light, sampled over the cone of directions
+    /// it is seen in. `position` and `edges` are unused for spheres.
+    pub sphere: Option<Sphere>,
+    /// Color of the emitted light
+    pub color: Vec3,
+    /// Whether this rectangle is also in the world as a `DiffuseLight` with
+    /// no falloff and the same emission, so diffuse bounces can hit it too.
+    /// Both ways of finding the light are then weighted against each other.
+    pub emitter_in_world: bool,
 }
 
-/// Point light implementation
+/// Light implementation
 impl Light {
-    /// Shade the given hit point according to the Blinn-Phong model. The
-    /// global component is done by the ray tracer, so this method just
-    /// calculates the component of shading from diffuse and specular
-    /// reflections.
-    pub fn shade(&self, hit: &HitRecord, world: &dyn Hittable) -> Vec3 {
-        // calculate ray from hit point to light source
-        let point_to_light_vector = self.position - hit.hit_point;
-        let point_to_light = Ray::new(hit.hit_point, point_to_light_vector, None);
-        // cast a new ray to the light to see if it hits anything
-        if let Some(_shadow_hit) = &world.hit(&point_to_light, EPSILON, MAX_HIT_DISTANCE) {
-            // shadow => no diffuse or specular components
-            glm::vec3(0.0, 0.0, 0.0)
-        } else {
-            let material_color = &hit.material.unwrap().color();
-            let normal_vector = &hit.normal();
-            let diffuse_light_weighting =
-                glm::normalize_dot(&point_to_light.direction, &normal_vector).max(0.0);
-            let halfway_vector = point_to_light.direction - hit.ray.direction;
-            let specular_light_weighting = glm::normalize_dot(&normal_vector, &halfway_vector);
-            material_color * diffuse_light_weighting * DIFFUSE_WEIGHT
-                + glm::vec3(1.0, 1.0, 1.0)
-                    * specular_light_weighting.powf(SPECULAR_COEFFICIENT)
-                    * SPECULAR_WEIGHT
+    /// Create a white point light.
+    ///
+    /// # Arguments
+    /// - `position: Vec3` - position of the light source
+    /// - `weight: f32` - relative strength of the light source
+    pub fn point(position: Vec3, weight: f32) -> Light {
+        Light {
+            position,
+            weight,
+            edges: [glm::vec3(0.0, 0.0, 0.0); 2],
+            sphere: None,
+            color: glm::vec3(1.0, 1.0, 1.0),
+            emitter_in_world: false,
+        }
+    }
+
+    /// Create a spherical light. The same sphere should also be in the
+    /// world with a `DiffuseLight` material of no falloff emitting
+    /// `weight * color`, so diffuse bounces which hit it are weighted
+    /// against sampling it.
+    ///
+    /// # Arguments
+    /// - `sphere: Sphere` - the emitting sphere
+    /// - `weight: f32` - relative strength of the light source
+    /// - `color: Vec3` - color of the emitted light
+    pub fn sphere(sphere: Sphere, weight: f32, color: Vec3) -> Light {
+        Light {
+            position: sphere.center,
+            weight,
+            edges: [glm::vec3(0.0, 0.0, 0.0); 2],
+            sphere: Some(sphere),
+            color,
+            emitter_in_world: true,
+        }
+    }
+
+    /// Density, per solid angle, with which this light's sampling would pick
+    /// the direction of a ray which has hit its emitter in the world.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `ray: &Ray` - the ray which hit the emitter
+    /// - `hit: &HitRecord` - the hit on the emitter
+    ///
+    /// # Returns
+    /// - the density, or `None` if the hit isn't on this light
+    pub fn emitter_pdf(&self, ray: &Ray, hit: &HitRecord) -> Option<f32> {
+        if let Some(sphere) = &self.sphere {
+            let height = glm::distance(&hit.hit_point, &sphere.center) - sphere.radius.abs();
+            if !self.emitter_in_world || height.abs() > 1.0e-3 * sphere.radius.abs().max(1.0) {
+                return None;
+            }
+            return sphere.solid_angle_pdf(ray.origin);
+        }
+        let light_normal = glm::cross(&self.edges[0], &self.edges[1]);
+        let area = glm::length(&light_normal);
+        if !self.emitter_in_world || area == 0.0 {
+            return None;
+        }
+        // find the hit in the rectangle's own coordinates
+        let offset = hit.hit_point - self.position;
+        let s = glm::dot(&offset, &self.edges[0]) / glm::length2(&self.edges[0]);
+        let t = glm::dot(&offset, &self.edges[1]) / glm::length2(&self.edges[1]);
+        let height = glm::dot(&offset, &light_normal) / area;
+        let tolerance = 1.0e-3;
+        if height.abs() > tolerance
+            || !(-tolerance..=1.0 + tolerance).contains(&s)
+            || !(-tolerance..=1.0 + tolerance).contains(&t)
+        {
+            return None;
+        }
+        let cosine = glm::normalize_dot(&light_normal, &ray.direction).abs();
+        Some(hit.distance * hit.distance / (cosine * area))
+    }
+
+    /// Estimate the light arriving at a diffuse hit from this light, cosine
+    /// weighted by the surface normal. Area lights are sampled at several
+    /// stratified points, and the visible samples are averaged. More
+    /// samples give smoother penumbras. The estimate assumes a Lambertian
+    /// surface, whose bounces are cosine distributed, when weighing samples
+    /// against bounces which hit the emitter.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    /// - `samples: usize` - number of points to sample an area light at, at
+    ///   least 1
+    ///
+    /// # Returns
+    /// - `Vec3` - irradiance at the hit point
+    pub fn irradiance(&self, hit: &HitRecord, world: &dyn Hittable, samples: usize) -> Vec3 {
+        self.shadowed_irradiance(hit, world, samples).0
+    }
+
+    /// Estimate the light arriving at a diffuse hit like `irradiance`, and
+    /// from the same samples the light which would arrive if nothing cast a
+    /// shadow, so their ratio says how much of the light is blocked.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    /// - `samples: usize` - number of points to sample an area light at
+    ///
+    /// # Returns
+    /// - `(Vec3, Vec3)` - irradiance at the hit point, and the irradiance
+    ///   without shadows
+    pub fn shadowed_irradiance(
+        &self,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        samples: usize,
+    ) -> (Vec3, Vec3) {
+        self.shadowed_reflected_radiance(hit, world, samples, &diffuse_response)
+    }
+
+    /// Estimate the light from this light which a hit reflects toward the
+    /// viewer, for surfaces which aren't Lambertian. The light is sampled
+    /// like in `irradiance`, and each sample is scaled by the surface's
+    /// response to light from its direction and weighed against the
+    /// surface's own bounces hitting the emitter.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    /// - `samples: usize` - number of points to sample an area light at
+    /// - `response: SurfaceResponse` - how the surface reflects the light
+    ///
+    /// # Returns
+    /// - `Vec3` - radiance reflected toward the viewer
+    pub fn reflected_radiance(
+        &self,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        samples: usize,
+        response: SurfaceResponse,
+    ) -> Vec3 {
+        self.shadowed_reflected_radiance(hit, world, samples, response)
+            .0
+    }
+
+    /// Estimate the light reflected by a hit like `reflected_radiance`,
+    /// together with the light which would be reflected if nothing cast a
+    /// shadow.
+    fn shadowed_reflected_radiance(
+        &self,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        samples: usize,
+        response: SurfaceResponse,
+    ) -> (Vec3, Vec3) {
+        let samples = samples.max(1);
+        if let Some(sphere) = &self.sphere {
+            return self.sphere_reflected_radiance(sphere, hit, world, samples, response);
+        }
+        let light_normal = glm::cross(&self.edges[0], &self.edges[1]);
+        let area = glm::length(&light_normal);
+        // a point light looks the same from every sample
+        let samples = if area > 0.0 { samples } else { 1 };
+
+        // stratify both edges with a random pairing of the strata, so any
+        // sample count covers the rectangle evenly
+        let mut strata: Vec<usize> = (0..samples).collect();
+        strata.shuffle(&mut rng());
+
+        let normal = hit.normal();
+        let mut total = glm::vec3(0.0, 0.0, 0.0);
+        let mut unshadowed = glm::vec3(0.0, 0.0, 0.0);
+        for (i, &j) in strata.iter().enumerate() {
+            let s = (i as f32 + rng().gen::<f32>()) / samples as f32;
+            let t = (j as f32 + rng().gen::<f32>()) / samples as f32;
+            let sample_point = self.position + s * self.edges[0] + t * self.edges[1];
+
+            let to_light = sample_point - hit.hit_point;
+            let distance_squared = glm::length2(&to_light);
+            let cosine = glm::normalize_dot(&normal, &to_light).max(0.0);
+            let (reflected, scatter_pdf) = response(&to_light, cosine);
+            let contribution = if area > 0.0 {
+                let light_cosine = glm::normalize_dot(&light_normal, &to_light).abs();
+                let light_pdf = distance_squared / (light_cosine * area);
+                // weigh against the surface's bounce hitting the emitter
+                let weight = if self.emitter_in_world {
+                    power_heuristic(light_pdf, scatter_pdf)
+                } else {
+                    1.0
+                };
+                if light_pdf.is_finite() {
+                    reflected * weight / light_pdf
+                } else {
+                    glm::vec3(0.0, 0.0, 0.0)
+                }
+            } else {
+                reflected / distance_squared
+            };
+            unshadowed += contribution;
+
+            // only objects between the hit and the light cast a shadow, and
+            // the ray stops short of the light, which may also be in the world
+            let shadow_ray = Ray::new(hit.hit_point, to_light, None).with_time(hit.ray.time);
+            let shadow_distance = distance_squared.sqrt() * (1.0 - 1.0e-4);
+            stats::record(Counter::ShadowRays);
+            if !world.any_hit(&shadow_ray, EPSILON, shadow_distance) {
+                total += contribution;
+            }
+        }
+        let emitted = self.color * self.weight;
+        (
+            glm::matrix_comp_mult(&emitted, &total) / samples as f32,
+            glm::matrix_comp_mult(&emitted, &unshadowed) / samples as f32,
+        )
+    }
+
+    /// Estimate the light from a spherical light which a hit reflects, like
+    /// `reflected_radiance`, with the samples stratified over the cone of
+    /// directions the sphere is seen in, together with the light reflected
+    /// without shadows.
+    fn sphere_reflected_radiance(
+        &self,
+        sphere: &Sphere,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        samples: usize,
+        response: SurfaceResponse,
+    ) -> (Vec3, Vec3) {
+        let mut strata: Vec<usize> = (0..samples).collect();
+        strata.shuffle(&mut rng());
+
+        let normal = hit.normal();
+        let mut total = glm::vec3(0.0, 0.0, 0.0);
+        let mut unshadowed = glm::vec3(0.0, 0.0, 0.0);
+        for (i, &j) in strata.iter().enumerate() {
+            let u = (i as f32 + rng().gen::<f32>()) / samples as f32;
+            let v = (j as f32 + rng().gen::<f32>()) / samples as f32;
+            let (direction, light_pdf) = match sphere.sample_toward(hit.hit_point, u, v) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            // surfaces reflect nothing from below them, but media scatter
+            // light arriving from every side, so the response decides
+            let cosine = glm::dot(&normal, &direction).max(0.0);
+            let (reflected, scatter_pdf) = response(&direction, cosine);
+            if reflected == glm::vec3(0.0, 0.0, 0.0) {
+                continue;
+            }
+
+            // only objects in front of the sphere's surface cast a shadow
+            let shadow_ray = Ray::new(hit.hit_point, direction, None).with_time(hit.ray.time);
+            let surface_distance = match sphere.hit(&shadow_ray, EPSILON, MAX_HIT_DISTANCE) {
+                Some(surface) => surface.distance,
+                None => continue,
+            };
+            let weight = if self.emitter_in_world {
+                power_heuristic(light_pdf, scatter_pdf)
+            } else {
+                1.0
+            };
+            let contribution = reflected * weight / light_pdf;
+            unshadowed += contribution;
+            stats::record(Counter::ShadowRays);
+            if !world.any_hit(&shadow_ray, EPSILON, surface_distance * (1.0 - 1.0e-4)) {
+                total += contribution;
+            }
         }
+        let emitted = self.color * self.weight;
+        (
+            glm::matrix_comp_mult(&emitted, &total) / samples as f32,
+            glm::matrix_comp_mult(&emitted, &unshadowed) / samples as f32,
+        )
     }
 }
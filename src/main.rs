@@ -1,118 +1,712 @@
 extern crate nalgebra_glm as glm;
 
+mod aov;
 mod camera;
 mod color;
+mod daylight;
+mod exr;
+mod gltf_loader;
+mod golden;
 mod hit_record;
 mod hittable;
 mod light;
 mod material;
+mod post;
+mod preview;
+mod random;
 mod ray;
+mod renderer;
 mod scenes;
+mod settings;
+mod stats;
+mod stereo;
+mod texture;
+mod tiles;
+mod turntable;
+mod wireframe;
 
+use aov::Features;
 use atomic_counter::AtomicCounter;
 use atomic_counter::RelaxedCounter;
 use camera::Camera;
+use camera::Shutter;
 use glm::Vec3;
 use hittable::Hittable;
+use image::GrayImage;
+use image::ImageBuffer;
+use image::Luma;
+use image::Rgb;
 use image::RgbImage;
+use image::Rgba;
+use image::RgbaImage;
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
 use light::Light;
 use material::Material;
-use rand::prelude::thread_rng as rng;
+use material::ShadingContext;
+use post::DenoiseParameters;
+use preview::Preview;
 use rand::Rng;
+use random::rng;
 use ray::Ray;
 use rayon::prelude::*;
+use scenes::Scene;
 use scenes::Sky;
+use settings::DebugPass;
+use settings::RenderSettings;
+use tiles::TileCallback;
+use tiles::TileTracker;
+use wireframe::WireframeOverlay;
 
 // constants for image specifications
 // Change these to change the image!
 const IMAGE_WIDTH: u32 = 1920 / 2;
 const IMAGE_HEIGHT: u32 = 1080 / 2;
-const SAMPLES_LEVEL: usize = 128; // samples per pixel
+const SAMPLES_LEVEL: usize = 128; // anti-aliasing positions per pixel axis
 const DEPTH_LIMIT: u32 = 64;
 const EPSILON: f32 = 0.000008;
 const MAX_HIT_DISTANCE: f32 = f32::INFINITY;
-const AMBIENT_WEIGHT: f32 = 0.05;
-const DIFFUSE_WEIGHT: f32 = 0.8;
-const SPECULAR_WEIGHT: f32 = 0.5;
-const SPECULAR_COEFFICIENT: f32 = 120.0;
 
 fn main() {
+    let settings = match RenderSettings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("{}\n{}", message, settings::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if settings.stats {
+        stats::enable();
+    }
+    if let Some(filename) = &settings.golden {
+        match golden::check(filename, settings.update_golden) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // check every name up front, so a typo doesn't stop a batch part way
+    for name in settings.scenes.iter().filter(|_| settings.gltf.is_none()) {
+        if !scenes::SCENE_NAMES.contains(&name.as_str()) {
+            eprintln!("{}", scenes::SceneError::UnknownScene(name.clone()));
+            std::process::exit(2);
+        }
+    }
+
+    // the thread pool is shared by every scene in a batch
+    let scene_count = settings.scenes.len();
+    let start = std::time::Instant::now();
+    for (index, name) in settings.scenes.iter().enumerate() {
+        if scene_count > 1 {
+            println!("scene {} of {}: {}", index + 1, scene_count, name);
+        }
+        let mut scene_settings = settings.clone();
+        scene_settings.output = settings.output_for(name);
+        let scene = match &settings.gltf {
+            Some(filename) => gltf_loader::load(
+                filename,
+                settings.image_width,
+                settings.image_height,
+                settings.bvh,
+                settings.material_override,
+            )
+            .unwrap_or_else(|error| {
+                eprintln!("couldn't load {}: {}", filename, error);
+                std::process::exit(2);
+            }),
+            None => scenes::by_name(
+                name,
+                settings.image_width,
+                settings.image_height,
+                settings.bvh,
+            )
+            .unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                std::process::exit(2);
+            }),
+        };
+        render_scene(scene, &scene_settings);
+    }
+    if scene_count > 1 {
+        println!(
+            "rendered {} scenes in {:.1}s",
+            scene_count,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    warn_non_finite_colors();
+    println!("done!");
+}
+
+/// Render a built scene and write its image and feature buffers.
+///
+/// # Arguments
+/// - `scene: Scene` - the scene to render
+/// - `settings: &RenderSettings` - render settings, with the output file
+///   name for this scene
+fn render_scene(scene: Scene, settings: &RenderSettings) {
+    let image_width = settings.image_width;
+    let image_height = settings.image_height;
+    stats::reset();
+
+    let (world, mut camera, lights, sky, scene_exposure) = scene;
+    // the scene's own exposure stands in when none was asked for
+    let settings = &RenderSettings {
+        exposure: settings.exposure.or(scene_exposure),
+        ..settings.clone()
+    };
+
+    let sky = sky.with_yaw(settings.sky_rotation);
+    if let Some(handedness) = settings.handedness {
+        camera = camera.with_handedness(handedness);
+    }
+    if settings.shutter_readout.is_some() || settings.shutter_profile.is_some() {
+        let shutter = camera.shutter().unwrap_or_default();
+        camera = camera.with_shutter(Shutter {
+            readout: settings.shutter_readout.unwrap_or(shutter.readout),
+            profile: settings.shutter_profile.unwrap_or(shutter.profile),
+        });
+    }
+    if settings.verbose {
+        let reports = world.mesh_reports();
+        for (index, report) in reports.iter().enumerate() {
+            println!("mesh {} of {}:\n{}", index + 1, reports.len(), report);
+        }
+    }
+    let start = std::time::Instant::now();
+
+    if let Some(frame_count) = settings.turntable {
+        turntable::render_turntable(&world, &mut camera, &lights, &sky, settings, frame_count);
+        if settings.stats {
+            println!(
+                "{}",
+                stats::report(&world.geometry_counts(), start.elapsed())
+            );
+        }
+        return;
+    }
+
+    if settings.debug == Some(DebugPass::ObjectId) {
+        let mut img = aov::render_object_ids(
+            &world,
+            &camera,
+            image_width,
+            image_height,
+            settings.camera_min_distance(&world),
+            settings.max_distance,
+        );
+        if settings.flip_y {
+            image::imageops::flip_vertical_in_place(&mut img);
+        }
+        img.save(&settings.output).unwrap();
+        return;
+    }
+
+    if let Some(pass_count) = settings.progressive {
+        let geometry_counts = world.geometry_counts();
+        renderer::render_progressive(
+            (world, camera, lights, sky, scene_exposure),
+            settings,
+            pass_count,
+        );
+        if settings.stats {
+            println!("{}", stats::report(&geometry_counts, start.elapsed()));
+        }
+        return;
+    }
+
+    if let Some(layout) = settings.stereo {
+        stereo::render_stereo(&world, &camera, &lights, &sky, settings, layout)
+            .save(&settings.output)
+            .unwrap();
+        if settings.stats {
+            println!(
+                "{}",
+                stats::report(&world.geometry_counts(), start.elapsed())
+            );
+        }
+        return;
+    }
+
+    let buffers = render(&world, &camera, &lights, &sky, settings);
+
+    if settings.writes_alpha() {
+        save_with_alpha(&buffers, settings).unwrap();
+    } else if settings.bit_depth == 16 {
+        let mut deep_img: ImageBuffer<Rgb<u16>, Vec<u16>> =
+            ImageBuffer::new(image_width, image_height);
+        for (x, y, pixel) in deep_img.enumerate_pixels_mut() {
+            *pixel = color::vec3_to_rgb16(&buffers.color[(y * image_width + x) as usize], 1);
+        }
+        deep_img.save(&settings.output).unwrap();
+    } else {
+        tonemapped_image(&buffers, settings)
+            .save(&settings.output)
+            .unwrap();
+    }
+    if settings.aov {
+        let mut albedo_img = RgbImage::new(image_width, image_height);
+        let mut normal_img = RgbImage::new(image_width, image_height);
+        let mut caustic_img = GrayImage::new(image_width, image_height);
+        for (x, y, pixel) in albedo_img.enumerate_pixels_mut() {
+            let index = (y * image_width + x) as usize;
+            *pixel = color::vec3_to_rgb(&buffers.albedo[index], 1);
+            normal_img.put_pixel(x, y, aov::normal_to_rgb(&buffers.normal[index]));
+            let caustic = (buffers.caustic[index].clamp(0.0, 1.0) * 255.0).round() as u8;
+            caustic_img.put_pixel(x, y, Luma([caustic]));
+        }
+        albedo_img.save("albedo.png").unwrap();
+        normal_img.save("normal.png").unwrap();
+        caustic_img.save("caustic.png").unwrap();
+    }
+    if let Some(filename) = &settings.exr_aov {
+        write_exr_aov(filename, &buffers, settings).unwrap();
+    }
+    if settings.stats {
+        println!(
+            "{}",
+            stats::report(&world.geometry_counts(), start.elapsed())
+        );
+    }
+}
+
+/// Convert a render's colors into an 8 bit RGB image, dithered if the
+/// settings ask for it. The colors are already exposed.
+///
+/// # Arguments
+/// - `buffers: &RenderBuffers` - the render's buffers
+/// - `settings: &RenderSettings` - image size and dithering
+///
+/// # Returns
+/// - `RgbImage` - the tone mapped image
+fn tonemapped_image(buffers: &RenderBuffers, settings: &RenderSettings) -> RgbImage {
+    let image_width = settings.image_width;
+    let mut img = RgbImage::new(image_width, settings.image_height);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let index = (y * image_width + x) as usize;
+        *pixel = if settings.dither {
+            color::vec3_to_rgb_dithered(&buffers.color[index], 1, x, y)
+        } else {
+            color::vec3_to_rgb(&buffers.color[index], 1)
+        };
+    }
+    img
+}
+
+/// Warn if any shaded colors were infinite or NaN and had to be replaced,
+/// since those samples were darker than they should have been.
+fn warn_non_finite_colors() {
+    let replaced = material::transparent::non_finite_count();
+    if replaced > 0 {
+        eprintln!(
+            "warning: replaced {} non-finite colors from transparent surfaces",
+            replaced
+        );
+    }
+}
+
+/// Write a render's color and alpha to the output file as an RGBA image.
+/// The background was left black, so the color is premultiplied by the
+/// alpha, and is divided back out for the straight alpha of PNG files.
+///
+/// # Arguments
+/// - `buffers: &RenderBuffers` - the render's buffers
+/// - `settings: &RenderSettings` - image size, output name, and bit depth
+///
+/// # Returns
+/// - `image::ImageResult<()>` - any error from writing the file
+fn save_with_alpha(buffers: &RenderBuffers, settings: &RenderSettings) -> image::ImageResult<()> {
+    let image_width = settings.image_width;
+    let straight_color = |index: usize| {
+        let alpha = buffers.alpha[index];
+        if alpha > 0.0 {
+            buffers.color[index] / alpha
+        } else {
+            glm::vec3(0.0, 0.0, 0.0)
+        }
+    };
+    if settings.bit_depth == 16 {
+        let mut img: ImageBuffer<Rgba<u16>, Vec<u16>> =
+            ImageBuffer::new(image_width, settings.image_height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let index = (y * image_width + x) as usize;
+            let Rgb([red, green, blue]) = color::vec3_to_rgb16(&straight_color(index), 1);
+            let alpha = (buffers.alpha[index].clamp(0.0, 1.0) * 65535.0).round() as u16;
+            *pixel = Rgba([red, green, blue, alpha]);
+        }
+        img.save(&settings.output)
+    } else {
+        let mut img = RgbaImage::new(image_width, settings.image_height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let index = (y * image_width + x) as usize;
+            let Rgb([red, green, blue]) = if settings.dither {
+                color::vec3_to_rgb_dithered(&straight_color(index), 1, x, y)
+            } else {
+                color::vec3_to_rgb(&straight_color(index), 1)
+            };
+            let alpha = (buffers.alpha[index].clamp(0.0, 1.0) * 255.0).round() as u8;
+            *pixel = Rgba([red, green, blue, alpha]);
+        }
+        img.save(&settings.output)
+    }
+}
+
+/// Row-major image buffers produced by a render.
+pub struct RenderBuffers {
+    /// Linear color of each pixel, averaged over its samples
+    pub color: Vec<Vec3>,
+    /// Albedo feature of each pixel
+    pub albedo: Vec<Vec3>,
+    /// Normal feature of each pixel
+    pub normal: Vec<Vec3>,
+    /// Depth feature of each pixel
+    pub depth: Vec<f32>,
+    /// Caustic feature of each pixel, the fraction of its samples seen
+    /// through a mirror or glass, see `aov::Features::caustic`
+    pub caustic: Vec<f32>,
+    /// Opacity of each pixel, from 0 where the background shows to 1, see
+    /// `aov::trace_alpha`. Every rendered pixel is opaque unless the
+    /// settings ask for an alpha channel, and the rows outside a band given
+    /// by `--rows` are transparent.
+    pub alpha: Vec<f32>,
+}
+
+impl RenderBuffers {
+    /// Mirror every buffer top to bottom in place, for `--flip-y`.
+    ///
+    /// # Arguments
+    /// - mutable self reference
+    /// - `image_width: u32` - width of the image in pixels
+    fn flip_rows(&mut self, image_width: u32) {
+        let width = image_width as usize;
+        post::flip_rows(&mut self.color, width);
+        post::flip_rows(&mut self.albedo, width);
+        post::flip_rows(&mut self.normal, width);
+        post::flip_rows(&mut self.depth, width);
+        post::flip_rows(&mut self.caustic, width);
+        post::flip_rows(&mut self.alpha, width);
+    }
+}
+
+/// Write the color and feature buffers of a render as layers of an OpenEXR
+/// file. The color is the default layer, with the albedo, normal, depth and
+/// caustic features in layers of the same names. With an alpha channel, the
+/// default layer also has the alpha, which the color is already
+/// premultiplied by as EXR files expect.
+///
+/// # Arguments
+/// - `filename: &str` - path of the file to write
+/// - `buffers: &RenderBuffers` - the render's buffers
+/// - `settings: &RenderSettings` - image size, and whether to write alpha
+///
+/// # Returns
+/// - `std::io::Result<()>` - any error from writing the file
+fn write_exr_aov(
+    filename: &str,
+    buffers: &RenderBuffers,
+    settings: &RenderSettings,
+) -> std::io::Result<()> {
+    let mut channels = Vec::new();
+    let layers = [
+        ("", ["R", "G", "B"], &buffers.color),
+        ("albedo.", ["R", "G", "B"], &buffers.albedo),
+        ("normal.", ["X", "Y", "Z"], &buffers.normal),
+    ];
+    for (layer, names, buffer) in layers.iter() {
+        for (component, name) in names.iter().enumerate() {
+            channels.push(exr::Channel {
+                name: format!("{}{}", layer, name),
+                values: buffer.iter().map(|value| value[component]).collect(),
+            });
+        }
+    }
+    channels.push(exr::Channel {
+        name: String::from("depth.Z"),
+        values: buffers.depth.clone(),
+    });
+    channels.push(exr::Channel {
+        name: String::from("caustic.Y"),
+        values: buffers.caustic.clone(),
+    });
+    if settings.writes_alpha() {
+        channels.push(exr::Channel {
+            name: String::from("A"),
+            values: buffers.alpha.clone(),
+        });
+    }
+    exr::write(
+        filename,
+        settings.image_width,
+        settings.image_height,
+        channels,
+    )
+}
+
+/// Render the scene as seen through a camera, denoising the result if the
+/// settings ask for it.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &C` - camera to generate rays from
+/// - `lights: &[Light]` - light sources for the scene
+/// - `sky: &Sky` - background of the scene
+/// - `settings: &RenderSettings` - image size, sample counts, and clipping
+///
+/// # Returns
+/// - `RenderBuffers` - the color and feature buffers of the image
+fn render<T: Hittable + Sync, C: Camera + Sync>(
+    world: &T,
+    camera: &C,
+    lights: &[Light],
+    sky: &Sky,
+    settings: &RenderSettings,
+) -> RenderBuffers {
+    render_with_tiles(world, camera, lights, sky, settings, None)
+}
+
+/// Render the scene as seen through a camera like `render`, handing each
+/// tile of the image to a callback as soon as its pixels are finished, for
+/// streaming the image to a viewer or over a pipe while it renders. The
+/// tiles hold the averaged linear colors, clamped to black, before any
+/// denoising, exposure or white balance.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &C` - camera to generate rays from
+/// - `lights: &[Light]` - light sources for the scene
+/// - `sky: &Sky` - background of the scene
+/// - `settings: &RenderSettings` - image size, sample counts, and clipping
+/// - `on_tile_complete: Option<TileCallback>` - called once for each tile,
+///   see `tiles::TileTracker`
+///
+/// # Returns
+/// - `RenderBuffers` - the color and feature buffers of the image
+fn render_with_tiles<T: Hittable + Sync, C: Camera + Sync>(
+    world: &T,
+    camera: &C,
+    lights: &[Light],
+    sky: &Sky,
+    settings: &RenderSettings,
+    on_tile_complete: Option<TileCallback>,
+) -> RenderBuffers {
+    let image_width = settings.image_width;
+    let image_height = settings.image_height;
+    let aa_level = settings.aa_level;
+    let path_samples = settings.path_samples;
+    let min_distance = settings.camera_min_distance(world);
+    let context = settings.shading_context();
+    let wireframe = settings
+        .wireframe
+        .map(|line_width| WireframeOverlay::new(line_width, image_width, image_height));
+
+    // every pixel is its own item for rayon, which splits the list finer as
+    // idle threads steal from busy ones, so an expensive stretch of glass or
+    // mesh silhouette gets shared out down to single pixels near the end.
+    // Coarser tiles would leave threads waiting on the last expensive one.
     let mut pixel_coordinates: Vec<(u32, u32)> = Vec::new();
-    for x in 0..IMAGE_WIDTH {
-        for y in 0..IMAGE_HEIGHT {
+    for x in 0..image_width {
+        for y in settings.rendered_rows() {
             pixel_coordinates.push((x, y));
         }
     }
 
-    // set up scene
-    let (world, camera, lights, sky) = scenes::infinite_mirror_hallway(IMAGE_WIDTH, IMAGE_HEIGHT);
-
     println!("tracing rays . . .");
     let counter = RelaxedCounter::new(0);
     let progress_block_size: usize = 100;
-    let progress_bar =
-        ProgressBar::new(IMAGE_HEIGHT as u64 * IMAGE_WIDTH as u64 / progress_block_size as u64);
+    let progress_bar = ProgressBar::new((pixel_coordinates.len() / progress_block_size) as u64);
     progress_bar.set_style(ProgressStyle::default_bar().template(
         "Elapsed: [{elapsed_precise}]\nRemaining: [{eta_precise}]\n{bar:60.cyan.blue} {pos:}/{len:} {msg}",
     ));
-    let pixels: Vec<((u32, u32), Vec3)> = pixel_coordinates
+    let preview = settings.preview_interval.map(|interval| {
+        Preview::new(&settings.output, image_width, image_height, interval)
+            .with_flip_y(settings.flip_y)
+    });
+    let tiles = on_tile_complete.map(|on_tile_complete| {
+        TileTracker::new(image_width, settings.rendered_rows(), on_tile_complete)
+    });
+    let pixels: Vec<((u32, u32), Vec3, Features, f32)> = pixel_coordinates
         .par_iter()
-        .map(|(x, y)| {
-            // preallocate an array for the multi-jittered sampling
-            let mut jitter_boxes: [[(f32, f32); SAMPLES_LEVEL]; SAMPLES_LEVEL] =
-                [[(0.0, 0.0); SAMPLES_LEVEL]; SAMPLES_LEVEL];
-            // initialize the canonical arrangement for multi-jittered sampling
-            for j in 0..SAMPLES_LEVEL {
-                for i in 0..SAMPLES_LEVEL {
-                    let j_float = j as f32;
-                    let i_float = i as f32;
-                    let n_float = SAMPLES_LEVEL as f32;
-                    jitter_boxes[j][i].0 =
-                        (i_float + (j_float + rng().gen::<f32>()) / n_float) / n_float;
-                    jitter_boxes[j][i].1 =
-                        (j_float + (i_float + rng().gen::<f32>()) / n_float) / n_float;
+        .map_init(
+            // rayon makes one array for the multi-jittered sampling per
+            // batch of pixels a thread takes, rather than one per pixel,
+            // and every entry is overwritten before each pixel reads it
+            || vec![vec![(0.0, 0.0); aa_level]; aa_level],
+            |jitter_boxes: &mut Vec<Vec<(f32, f32)>>, (x, y)| {
+                let started = stats::start_pixel();
+                random::seed_pixel(*x, *y, settings.seed);
+
+                // initialize the canonical arrangement for multi-jittered sampling
+                for (j, row) in jitter_boxes.iter_mut().enumerate() {
+                    for (i, jitter_box) in row.iter_mut().enumerate() {
+                        let j_float = j as f32;
+                        let i_float = i as f32;
+                        let n_float = aa_level as f32;
+                        jitter_box.0 =
+                            (i_float + (j_float + rng().gen::<f32>()) / n_float) / n_float;
+                        jitter_box.1 =
+                            (j_float + (i_float + rng().gen::<f32>()) / n_float) / n_float;
+                    }
                 }
-            }
 
-            let image_width = IMAGE_WIDTH as f32 - 1.0;
-            let image_height = IMAGE_HEIGHT as f32 - 1.0;
-            let mut pixel_color = glm::vec3(0.0, 0.0, 0.0);
-            let jitter_boxes = shuffle_jittered_sampling(&mut jitter_boxes);
-            let x_float = *x as f32;
-            let y_float = image_height - *y as f32;
-            for j in 0..SAMPLES_LEVEL {
-                for i in 0..SAMPLES_LEVEL {
-                    let u = (x_float + jitter_boxes[j][i].0) / image_width;
-                    let v = (y_float + jitter_boxes[j][i].1) / image_height;
-                    let r = camera.get_ray(u, v);
-                    pixel_color += trace_ray(&r, &world, &lights, &sky, DEPTH_LIMIT);
+                let mut pixel_color = glm::vec3(0.0, 0.0, 0.0);
+                let mut pixel_features = Features::zero();
+                let mut pixel_alpha = 0.0;
+                let jitter_boxes = shuffle_jittered_sampling(jitter_boxes);
+                for row in jitter_boxes.iter() {
+                    for jitter_box in row.iter() {
+                        let (u, v) =
+                            camera::pixel_to_camera(*x, *y, *jitter_box, image_width, image_height);
+                        let r = camera.get_ray(u, v).with_depth_budgets(
+                            settings.max_reflection_depth,
+                            settings.max_refraction_depth,
+                        );
+                        if settings.needs_features() {
+                            pixel_features.accumulate(&aov::trace_features(&r, world, sky));
+                        }
+                        if wireframe.as_ref().is_some_and(|wireframe| {
+                            wireframe.covers(
+                                world,
+                                camera,
+                                u,
+                                v,
+                                min_distance,
+                                settings.max_distance,
+                            )
+                        }) {
+                            // lines are black, so they add nothing to the pixel
+                            pixel_alpha += path_samples as f32;
+                            continue;
+                        }
+                        for _ in 0..path_samples {
+                            stats::record(stats::Counter::PrimaryRays);
+                            let (alpha, background) = if settings.alpha {
+                                aov::trace_alpha(
+                                    &r,
+                                    world,
+                                    lights,
+                                    &context,
+                                    min_distance,
+                                    settings.max_distance,
+                                )
+                            } else {
+                                (1.0, false)
+                            };
+                            pixel_alpha += alpha;
+                            if background {
+                                // the picture the image goes over shows here,
+                                // darkened only by the alpha of the shadows
+                                continue;
+                            }
+                            pixel_color += match settings.debug {
+                                Some(DebugPass::Lighting) => aov::trace_direct_lighting(
+                                    &r,
+                                    world,
+                                    lights,
+                                    sky,
+                                    &context,
+                                    min_distance,
+                                    settings.max_distance,
+                                ),
+                                Some(DebugPass::SkyOcclusion) => aov::trace_sky_occlusion(
+                                    &r,
+                                    world,
+                                    sky,
+                                    min_distance,
+                                    settings.max_distance,
+                                ),
+                                _ => trace_ray_within(
+                                    &r,
+                                    world,
+                                    lights,
+                                    sky,
+                                    &context,
+                                    DEPTH_LIMIT,
+                                    min_distance,
+                                    settings.max_distance,
+                                ),
+                            };
+                        }
+                    }
                 }
-            }
 
-            // give a progress update
-            counter.inc();
-            let count = counter.get();
-            if count % progress_block_size == 0 {
-                progress_bar.inc(1);
-            }
+                if let Some(preview) = &preview {
+                    preview.update(*x, *y, &pixel_color, settings.samples_per_pixel());
+                }
+                if let Some(tiles) = &tiles {
+                    let average = pixel_color / settings.samples_per_pixel() as f32;
+                    tiles.finish_pixel(*x, *y, &glm::max(&average, 0.0));
+                }
+
+                stats::record_pixel_time(started);
+
+                // give a progress update
+                counter.inc();
+                let count = counter.get();
+                if count.is_multiple_of(progress_block_size) {
+                    progress_bar.inc(1);
+                }
 
-            ((*x, *y), pixel_color)
-        })
+                (
+                    (*x, *y),
+                    pixel_color,
+                    pixel_features.average(aa_level * aa_level),
+                    pixel_alpha,
+                )
+            },
+        )
         .collect();
 
-    // convert pixel colors into 8 bit RGB pixels and place them in an image buffer
-    let mut img = RgbImage::new(IMAGE_WIDTH, IMAGE_HEIGHT);
-    for pixel in pixels.into_iter() {
-        img.put_pixel(
-            pixel.0 .0,
-            pixel.0 .1,
-            color::vec3_to_rgb(&pixel.1, SAMPLES_LEVEL),
+    // gather the averaged pixel colors and features into row-major buffers
+    let pixel_count = (image_width * image_height) as usize;
+    let mut color_buffer = vec![glm::vec3(0.0, 0.0, 0.0); pixel_count];
+    let mut albedo_buffer = vec![glm::vec3(0.0, 0.0, 0.0); pixel_count];
+    let mut normal_buffer = vec![glm::vec3(0.0, 0.0, 0.0); pixel_count];
+    let mut depth_buffer = vec![f32::INFINITY; pixel_count];
+    let mut caustic_buffer = vec![0.0; pixel_count];
+    // rows outside the band being rendered are left transparent
+    let mut alpha_buffer = vec![0.0; pixel_count];
+    for ((x, y), pixel_color, features, pixel_alpha) in pixels.into_iter() {
+        let index = (y * image_width + x) as usize;
+        // lights with negative weights can take a pixel below black, which
+        // would be smeared by the denoiser and written to deep images
+        color_buffer[index] = glm::max(&(pixel_color / settings.samples_per_pixel() as f32), 0.0);
+        alpha_buffer[index] = pixel_alpha / settings.samples_per_pixel() as f32;
+        albedo_buffer[index] = features.albedo;
+        normal_buffer[index] = features.normal;
+        depth_buffer[index] = features.depth;
+        caustic_buffer[index] = features.caustic;
+    }
+    if settings.denoise {
+        color_buffer = post::denoise(
+            &color_buffer,
+            &albedo_buffer,
+            &normal_buffer,
+            image_width as usize,
+            image_height as usize,
+            &DenoiseParameters::default(),
         );
     }
-    img.save("out.png").unwrap();
-    println!("done!");
+    let exposure = settings.exposure.unwrap_or(0.0);
+    if exposure != 0.0 || settings.white_balance != [1.0, 1.0, 1.0] {
+        let [red, green, blue] = settings.white_balance;
+        post::expose(&mut color_buffer, exposure, glm::vec3(red, green, blue));
+    }
+    let mut buffers = RenderBuffers {
+        color: color_buffer,
+        albedo: albedo_buffer,
+        normal: normal_buffer,
+        depth: depth_buffer,
+        caustic: caustic_buffer,
+        alpha: alpha_buffer,
+    };
+    if settings.flip_y {
+        buffers.flip_rows(image_width);
+    }
+    buffers
 }
 
 /// Given a ray from the camera, figure out what color that ray sees.
@@ -121,24 +715,88 @@ fn main() {
 /// - `ray: &Ray` - ray along which we are sampling the scene
 /// - `world: &HittableList` - objects that compose our scene
 /// - `lights: &Vec<Light>` - light sources for the scene
+/// - `context: &ShadingContext` - render settings used to shade each hit
 ///
 ///
 /// # Returns
 /// - `Vec3` - the color that this ray contributes to the pixel
-fn trace_ray<T: Hittable>(ray: &Ray, world: &T, lights: &[Light], sky: &Sky, depth: u32) -> Vec3 {
-    if depth > 0 {
-        if let Some(hit) = world.hit(&ray, EPSILON, MAX_HIT_DISTANCE) {
-            if let Some(material) = &hit.material {
-                material.shade(world, lights, sky, &hit.ray, &hit, depth)
-            } else {
-                color::color(0, 0, 0)
-            }
+fn trace_ray<T: Hittable>(
+    ray: &Ray,
+    world: &T,
+    lights: &[Light],
+    sky: &Sky,
+    context: &ShadingContext,
+    depth: u32,
+) -> Vec3 {
+    stats::record(stats::Counter::SecondaryRays);
+    trace_ray_within(
+        ray,
+        world,
+        lights,
+        sky,
+        context,
+        depth,
+        EPSILON,
+        MAX_HIT_DISTANCE,
+    )
+}
+
+/// Given a ray, figure out what color it sees, only considering geometry
+/// between `min_distance` and `max_distance` along the ray. Anything outside
+/// that range is ignored, like with near and far clipping planes. Rays which
+/// bounce off the first hit are traced with the default range. The color is
+/// weighted by the ray's throughput, see `Ray::attenuation`. After the
+/// context's `russian_roulette_bounces`, a path is ended with a chance of one
+/// less its throughput, and one which carries on has its throughput divided
+/// by the chance it had, so dim paths stop early and the image is the same
+/// on average.
+///
+/// # Arguments
+/// - `ray: &Ray` - ray along which we are sampling the scene
+/// - `world: &HittableList` - objects that compose our scene
+/// - `lights: &Vec<Light>` - light sources for the scene
+/// - `context: &ShadingContext` - render settings used to shade each hit
+/// - `min_distance: f32` - closest distance along the ray to accept a hit
+/// - `max_distance: f32` - furthest distance along the ray to accept a hit
+///
+/// # Returns
+/// - `Vec3` - the color that this ray contributes to the pixel
+#[allow(clippy::too_many_arguments)]
+fn trace_ray_within<T: Hittable>(
+    ray: &Ray,
+    world: &T,
+    lights: &[Light],
+    sky: &Sky,
+    context: &ShadingContext,
+    depth: u32,
+    min_distance: f32,
+    max_distance: f32,
+) -> Vec3 {
+    if depth == 0 {
+        return color::color_linear(0, 0, 0);
+    }
+    let mut ray = *ray;
+    if DEPTH_LIMIT.saturating_sub(depth) >= context.russian_roulette_bounces {
+        // a path split by wavelength carries only its own channel's light
+        let survival = match ray.channel {
+            Some(channel) => ray.attenuation[channel],
+            None => glm::comp_max(&ray.attenuation),
+        }
+        .min(1.0);
+        if rng().gen::<f32>() >= survival {
+            return color::color_linear(0, 0, 0);
+        }
+        ray.attenuation /= survival;
+    }
+    if let Some(hit) = world.hit(&ray, min_distance, max_distance) {
+        if let Some(material) = &hit.material {
+            material.shade(world, lights, sky, context, &hit.ray, &hit, depth)
         } else {
-            // if we hit nothing, give the sky's color
-            sky(ray)
+            ray.weigh(material::missing_material_color(context))
         }
     } else {
-        color::color(0, 0, 0)
+        // if we hit nothing, give the sky's color
+        ray.weigh(sky.color(&ray))
     }
 }
 
@@ -147,29 +805,28 @@ fn trace_ray<T: Hittable>(ray: &Ray, world: &T, lights: &[Light], sky: &Sky, dep
 /// https://graphics.pixar.com/library/MultiJitteredSampling/paper.pdf
 ///
 /// # Arguments
-/// - `jitter_boxes: &mut [[[f32; 2]; SAMPLES_LEVEL]; SAMPLES_LEVEL]` - the
-///     array of sample locations arranged on the NxN grid. This is a mutable
-///     reference, so values are edited in place.
+/// - `jitter_boxes: &mut Vec<Vec<(f32, f32)>>` - the array of sample
+///   locations arranged on the NxN grid. This is a mutable reference, so
+///   values are edited in place.
 ///
 /// # Returns
-/// - `&[[[f32; 2]; SAMPLES_LEVEL]; SAMPLES_LEVEL]` - the reference to the
-///     sample locations to return ownership to the main loop
-fn shuffle_jittered_sampling(
-    jitter_boxes: &mut [[(f32, f32); SAMPLES_LEVEL]; SAMPLES_LEVEL],
-) -> &[[(f32, f32); SAMPLES_LEVEL]; SAMPLES_LEVEL] {
-    for j in 0..SAMPLES_LEVEL {
-        for i in 0..SAMPLES_LEVEL {
-            let k: usize = ((j as f32 + rng().gen::<f32>() * (SAMPLES_LEVEL - j) as f32) as usize)
-                .min(SAMPLES_LEVEL - 1);
+/// - `&Vec<Vec<(f32, f32)>>` - the reference to the sample locations to
+///   return ownership to the main loop
+// the shuffles swap entries between rows and columns, which takes indices
+#[allow(clippy::needless_range_loop)]
+fn shuffle_jittered_sampling(jitter_boxes: &mut Vec<Vec<(f32, f32)>>) -> &Vec<Vec<(f32, f32)>> {
+    let n = jitter_boxes.len();
+    for j in 0..n {
+        for i in 0..n {
+            let k: usize = ((j as f32 + rng().gen::<f32>() * (n - j) as f32) as usize).min(n - 1);
             let temp = jitter_boxes[j][i].0;
             jitter_boxes[j][i].0 = jitter_boxes[k][i].0;
             jitter_boxes[k][i].0 = temp;
         }
     }
-    for i in 0..SAMPLES_LEVEL {
-        for j in 0..SAMPLES_LEVEL {
-            let k: usize = ((i as f32 + rng().gen::<f32>() * (SAMPLES_LEVEL - i) as f32) as usize)
-                .min(SAMPLES_LEVEL - 1);
+    for i in 0..n {
+        for j in 0..n {
+            let k: usize = ((i as f32 + rng().gen::<f32>() * (n - i) as f32) as usize).min(n - 1);
             let temp = jitter_boxes[j][i].1;
             jitter_boxes[j][i].1 = jitter_boxes[j][k].1;
             jitter_boxes[j][k].0 = temp;
@@ -177,3 +834,322 @@ fn shuffle_jittered_sampling(
     }
     jitter_boxes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::perspective_camera::PerspectiveCamera;
+    use hittable::aabb::AABB;
+    use hittable::hittable_list::HittableList;
+    use material::lambertian::Lambertian;
+    use material::MaterialType;
+    use std::convert::TryInto;
+
+    #[test]
+    fn materialless_hit_shows_the_missing_material_color() {
+        // a bare AABB's hit record has no material
+        let world = AABB {
+            minimum_point: glm::vec3(-1.0, -1.0, -3.0),
+            maximum_point: glm::vec3(1.0, 1.0, -1.0),
+        };
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0), None);
+        let sky = Sky::uniform(glm::vec3(0.5, 0.5, 0.5));
+        for (flag, expected) in [
+            ("magenta", glm::vec3(1.0, 0.0, 1.0)),
+            ("black", glm::vec3(0.0, 0.0, 0.0)),
+        ] {
+            let args = vec![String::from("--missing-material"), String::from(flag)];
+            let context = RenderSettings::from_args(args.into_iter())
+                .unwrap()
+                .shading_context();
+            let color = trace_ray(&ray, &world, &[], &sky, &context, DEPTH_LIMIT);
+            assert_eq!(color, expected, "--missing-material {}", flag);
+        }
+    }
+
+    #[test]
+    fn negative_light_darkens_without_negative_pixels() {
+        let mut world = HittableList::new();
+        world.add_plane(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(0.8, 0.8, 0.8),
+                force_front_face: false,
+            }),
+        );
+        // looking straight down at the floor under the light
+        let camera = PerspectiveCamera::new(
+            glm::vec3(0.0, 5.0, 0.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, -1.0),
+            60.0,
+            1.0,
+        );
+        let light = || Light::point(glm::vec3(0.0, 2.0, 0.0), 4.0);
+        let negative = Light::point(glm::vec3(1.5, 0.5, 0.0), -6.0);
+        let sky = Sky::uniform(glm::vec3(0.0, 0.0, 0.0));
+        let settings = RenderSettings {
+            image_width: 32,
+            image_height: 32,
+            aa_level: 1,
+            ..RenderSettings::default()
+        };
+        let lit = render(&world, &camera, &[light()], &sky, &settings).color;
+        let darkened = render(&world, &camera, &[light(), negative], &sky, &settings).color;
+
+        // the pixel whose center sees the floor nearest a point
+        let pixel_over = |point: Vec3| {
+            (0..32 * 32)
+                .min_by(|a, b| {
+                    let floor_distance = |index: &u32| {
+                        let (u, v) =
+                            camera::pixel_to_camera(index % 32, index / 32, (0.5, 0.5), 32, 32);
+                        let ray = camera.get_ray(u, v);
+                        glm::distance(&ray.at(-ray.origin.y / ray.direction.y), &point)
+                    };
+                    floor_distance(a).partial_cmp(&floor_distance(b)).unwrap()
+                })
+                .unwrap() as usize
+        };
+        let near = pixel_over(glm::vec3(1.5, 0.0, 0.0));
+        let far = pixel_over(glm::vec3(-1.5, 0.0, 0.0));
+        assert!(lit[near].x > 0.1);
+        assert_eq!(darkened[near], glm::vec3(0.0, 0.0, 0.0));
+        assert!(darkened[far].x < lit[far].x && darkened[far].x > 0.5 * lit[far].x);
+        assert!(darkened
+            .iter()
+            .all(|color| color.iter().all(|channel| *channel >= 0.0)));
+    }
+
+    #[test]
+    fn complementary_row_bands_make_up_the_full_render() {
+        let (width, height) = (40, 30);
+        for name in ["shadow_catcher", "simple_primitives"] {
+            let settings = RenderSettings {
+                image_width: width,
+                image_height: height,
+                aa_level: 1,
+                max_reflection_depth: 2,
+                max_refraction_depth: 2,
+                ..RenderSettings::default()
+            };
+            let (world, camera, lights, sky, _) =
+                scenes::by_name(name, width, height, settings.bvh).unwrap();
+            let full = render(&world, &camera, &lights, &sky, &settings);
+            for band in [0..13, 13..30] {
+                let strip = render(
+                    &world,
+                    &camera,
+                    &lights,
+                    &sky,
+                    &RenderSettings {
+                        rows: Some((band.start, band.end)),
+                        ..settings.clone()
+                    },
+                );
+                for y in 0..height {
+                    for x in 0..width {
+                        let index = (y * width + x) as usize;
+                        if band.contains(&y) {
+                            assert_eq!(
+                                strip.color[index], full.color[index],
+                                "{} ({}, {})",
+                                name, x, y
+                            );
+                            assert_eq!(strip.alpha[index], full.alpha[index]);
+                            assert_eq!(strip.depth[index].to_bits(), full.depth[index].to_bits());
+                        } else {
+                            assert_eq!(strip.color[index], glm::vec3(0.0, 0.0, 0.0));
+                            assert_eq!(strip.alpha[index], 0.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read back an uncompressed scanline OpenEXR file of 32 bit floats,
+    /// giving its channel names in header order, its data window, and the
+    /// values of each channel in row-major order from the top left.
+    fn read_exr(bytes: &[u8]) -> (Vec<String>, [i32; 4], Vec<Vec<f32>>) {
+        let int = |at: usize| {
+            i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        let string = |at: usize| {
+            let end = at + bytes[at..].iter().position(|&byte| byte == 0).unwrap();
+            (String::from_utf8(bytes[at..end].to_vec()).unwrap(), end + 1)
+        };
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        assert_eq!(int(4), 2);
+        let (mut names, mut window, mut compression) = (Vec::new(), None, None);
+        let mut at = 8;
+        loop {
+            let (name, next) = string(at);
+            if name.is_empty() {
+                at = next;
+                break;
+            }
+            let (type_name, next) = string(next);
+            let size = int(next) as usize;
+            let value = next + 4;
+            match name.as_str() {
+                "channels" => {
+                    assert_eq!(type_name, "chlist");
+                    let mut channel = value;
+                    while bytes[channel] != 0 {
+                        let (channel_name, next) = string(channel);
+                        assert_eq!(int(next), 2, "{} is not a float channel", channel_name);
+                        names.push(channel_name);
+                        channel = next + 16;
+                    }
+                    assert_eq!(channel + 1, value + size);
+                }
+                "dataWindow" => {
+                    assert_eq!(type_name, "box2i");
+                    window = Some([int(value), int(value + 4), int(value + 8), int(value + 12)]);
+                }
+                "compression" => compression = Some(bytes[value]),
+                _ => (),
+            }
+            at = value + size;
+        }
+        assert_eq!(compression, Some(0));
+        let window = window.unwrap();
+        let (width, height) = (
+            (window[2] - window[0] + 1) as usize,
+            (window[3] - window[1] + 1) as usize,
+        );
+        let mut values = vec![Vec::new(); names.len()];
+        for y in 0..height {
+            let offset = u64::from_le_bytes(bytes[at + 8 * y..at + 8 * y + 8].try_into().unwrap());
+            let block = offset as usize;
+            assert_eq!(int(block), window[1] + y as i32);
+            assert_eq!(int(block + 4) as usize, names.len() * width * 4);
+            for (channel, channel_values) in values.iter_mut().enumerate() {
+                for x in 0..width {
+                    let sample = block + 8 + (channel * width + x) * 4;
+                    channel_values.push(f32::from_le_bytes(
+                        bytes[sample..sample + 4].try_into().unwrap(),
+                    ));
+                }
+            }
+        }
+        (names, window, values)
+    }
+
+    #[test]
+    fn exr_layers_read_back_with_their_names_and_values() {
+        let (width, height) = (3, 2);
+        let pixels = (width * height) as usize;
+        let value = |index: usize, offset: f32| index as f32 + offset;
+        let buffers = RenderBuffers {
+            color: (0..pixels)
+                .map(|i| glm::vec3(value(i, 0.1), value(i, 0.2), value(i, 0.3)))
+                .collect(),
+            albedo: (0..pixels)
+                .map(|i| glm::vec3(value(i, 0.4), value(i, 0.5), value(i, 0.6)))
+                .collect(),
+            normal: (0..pixels)
+                .map(|i| glm::vec3(-value(i, 0.1), -value(i, 0.2), -value(i, 0.3)))
+                .collect(),
+            depth: (0..pixels).map(|i| value(i, 10.0)).collect(),
+            caustic: (0..pixels).map(|i| value(i, 0.75)).collect(),
+            alpha: (0..pixels).map(|i| value(i, 0.25)).collect(),
+        };
+        let path = std::env::temp_dir().join(format!("ray_tracer_aov_{}.exr", std::process::id()));
+        let filename = path.to_str().unwrap();
+        for alpha in [false, true] {
+            let settings = RenderSettings {
+                image_width: width,
+                image_height: height,
+                alpha,
+                ..RenderSettings::default()
+            };
+            write_exr_aov(filename, &buffers, &settings).unwrap();
+            let (names, window, values) = read_exr(&std::fs::read(&path).unwrap());
+            let mut expected = vec![
+                "B",
+                "G",
+                "R",
+                "albedo.B",
+                "albedo.G",
+                "albedo.R",
+                "caustic.Y",
+                "depth.Z",
+                "normal.X",
+                "normal.Y",
+                "normal.Z",
+            ];
+            if alpha {
+                expected.insert(0, "A");
+            }
+            assert_eq!(names, expected);
+            assert_eq!(window, [0, 0, width as i32 - 1, height as i32 - 1]);
+
+            let channel = |name: &str| &values[names.iter().position(|n| n == name).unwrap()];
+            // the last pixel, at the bottom right, checks the rows and
+            // columns are both in order
+            let last = pixels - 1;
+            assert_eq!(channel("R")[last], buffers.color[last].x);
+            assert_eq!(channel("G")[last], buffers.color[last].y);
+            assert_eq!(channel("B")[last], buffers.color[last].z);
+            assert_eq!(channel("albedo.R")[last], buffers.albedo[last].x);
+            assert_eq!(channel("albedo.B")[last], buffers.albedo[last].z);
+            assert_eq!(channel("normal.X")[last], buffers.normal[last].x);
+            assert_eq!(channel("normal.Z")[last], buffers.normal[last].z);
+            assert_eq!(channel("depth.Z")[last], buffers.depth[last]);
+            assert_eq!(channel("caustic.Y")[last], buffers.caustic[last]);
+            if alpha {
+                assert_eq!(channel("A")[last], buffers.alpha[last]);
+            }
+            // and every value of a layer is where it belongs
+            let green: Vec<f32> = buffers.albedo.iter().map(|albedo| albedo.y).collect();
+            assert_eq!(channel("albedo.G"), &green);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn area_light_edge_pixel_blends_the_light_and_the_sky() {
+        // zoom in from the scene's camera on the middle of the panel
+        // light's near edge, which then crosses the center of the middle
+        // pixel, cutting it in half whatever its slope on the image
+        let (width, height) = (9, 9);
+        let (world, _, lights, sky, _) = scenes::rectangle_light_example(width, height);
+        let camera = PerspectiveCamera::new(
+            glm::vec3(-1.0, 0.2, 2.0),
+            glm::vec3(4.0, 2.0, -2.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+        );
+        let settings = RenderSettings {
+            image_width: width,
+            image_height: height,
+            aa_level: 8,
+            ..RenderSettings::default()
+        };
+        let buffers = render(&world, &camera, &lights, &sky, &settings);
+        let column: Vec<f32> = (0..height)
+            .map(|y| buffers.color[(y * width + width / 2) as usize].x)
+            .collect();
+        let (top, middle, bottom) = (column[0], column[4], column[8]);
+        let (light, background) = (top.max(bottom), top.min(bottom));
+        // away from the edge every sample sees the same thing, the light's
+        // full emission on one side
+        assert!((light - 5.0).abs() < 1.0e-4, "{:?}", column);
+        assert!(background < 1.0, "{:?}", column);
+        for (y, value) in column.iter().enumerate().filter(|(y, _)| *y != 4) {
+            assert!(
+                *value == light || (value - background).abs() < 1.0e-3,
+                "row {}",
+                y
+            );
+        }
+        // the edge pixel is a blend of both rather than a hard step to
+        // either side, in proportion to how much of it the light covers
+        let coverage = (middle - background) / (light - background);
+        assert!((coverage - 0.5).abs() < 0.05, "{:?}", column);
+    }
+}
@@ -1,36 +1,116 @@
 pub mod diffuse_light;
+pub mod isotropic;
 pub mod lambertian;
 pub mod metal;
+pub mod phong;
+pub mod shadow_catcher;
+pub mod subsurface;
+pub mod textured;
 pub mod transparent;
+pub mod triplanar;
+pub mod vertex_color;
+pub mod water;
+pub mod wireframe;
 
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::light::Light;
 use crate::material::diffuse_light::DiffuseLight;
+use crate::material::isotropic::Isotropic;
 use crate::material::lambertian::Lambertian;
 use crate::material::metal::Metal;
+use crate::material::phong::Phong;
+use crate::material::shadow_catcher::ShadowCatcher;
+use crate::material::subsurface::Subsurface;
+use crate::material::textured::Textured;
 use crate::material::transparent::Transparent;
+use crate::material::triplanar::Triplanar;
+use crate::material::vertex_color::VertexColor;
+use crate::material::water::Water;
+use crate::material::wireframe::Wireframe;
 use crate::ray::Ray;
 use crate::scenes::Sky;
 use glm::Vec3;
+use std::sync::Once;
+
+/// Guards the warning printed the first time a hit has no material
+static MISSING_WARNING: Once = Once::new();
+
+/// Color seen along a ray which hit geometry without a material. A warning
+/// is printed the first time this happens.
+///
+/// # Arguments
+/// - `context: &ShadingContext` - holds the color to show
+///
+/// # Returns
+/// - `Vec3` - the context's `missing_material_color`
+pub fn missing_material_color(context: &ShadingContext) -> Vec3 {
+    MISSING_WARNING.call_once(|| eprintln!("warning: a ray hit geometry with no material"));
+    context.missing_material_color
+}
+
+/// Render settings which change how hits are shaded. They are the same for
+/// every path of a render, and are passed down each path along with the
+/// lights and sky.
+#[derive(Clone, Copy)]
+pub struct ShadingContext {
+    /// Number of shadow rays cast toward each light per surface hit, at
+    /// least 1
+    pub light_samples_per_hit: usize,
+    /// How much diffuse bounce light is darkened near other surfaces, like
+    /// in creases and under objects resting on the ground. From 0, which
+    /// turns it off, to 1, which makes fully occluded points black apart
+    /// from direct light
+    pub ambient_occlusion_strength: f32,
+    /// Distance within which surfaces count as occluders
+    pub ambient_occlusion_radius: f32,
+    /// Color seen on hits on geometry without a material, magenta to make
+    /// the geometry easy to spot or black to hide it
+    pub missing_material_color: Vec3,
+    /// Number of bounces after which paths may be ended by Russian
+    /// roulette, see `crate::trace_ray_within`, or `u32::MAX` to follow
+    /// every path to the depth limit
+    pub russian_roulette_bounces: u32,
+}
+
+impl Default for ShadingContext {
+    /// One shadow ray per light, no ambient occlusion, black where a
+    /// material is missing, and Russian roulette after five bounces.
+    fn default() -> ShadingContext {
+        ShadingContext {
+            light_samples_per_hit: 1,
+            ambient_occlusion_strength: 0.0,
+            ambient_occlusion_radius: 0.5,
+            missing_material_color: glm::vec3(0.0, 0.0, 0.0),
+            russian_roulette_bounces: 5,
+        }
+    }
+}
 
 /// Material trait
 pub trait Material {
     /// Determine where the next ray goes after a hit depending on this
-    /// material.
+    /// material, and the light found along the path. Light emitted or
+    /// sampled at the hit is weighted by the incoming ray's throughput,
+    /// see `Ray::weigh`. Rays continued with `Ray::continue_path` carry the
+    /// bounce's weight in their own throughput, so what they trace is
+    /// returned as it is.
     ///
     /// # Arguments
     /// - self reference
+    /// - `context` - render settings which change how the hit is shaded
     /// - `incoming_ray` - ray which has just hit this material
     /// - `hit_record` - specification of the hit which just ocurred
     ///
     /// # Returns
-    /// - optional `Ray`, or none if the ray was absorbed
+    /// - `Vec3` - the light reaching the pixel along the incoming ray
+    #[allow(clippy::too_many_arguments)]
     fn shade<T: Hittable>(
         &self,
         world: &T,
         lights: &[Light],
         sky: &Sky,
+        context: &ShadingContext,
         incoming_ray: &Ray,
         hit_record: &HitRecord,
         depth: u32,
@@ -45,6 +125,47 @@ pub trait Material {
     /// - `Vec3` - the RGB color
     fn color(&self) -> Vec3;
 
+    /// Retrieve the color of the material at a particular hit. This is the
+    /// base color unless the material varies over its surface.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit_record` - the hit to find the color at
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color_at(&self, _hit_record: &HitRecord) -> Vec3 {
+        self.color()
+    }
+
+    /// Materials with an opacity mask can cut holes in a surface. A hit on a
+    /// hole is treated as a miss, so the ray carries on unobstructed.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `texture_coordinates: (f32, f32)` - surface coordinates of the hit
+    ///
+    /// # Returns
+    /// - `bool` - true if there is no surface at these coordinates
+    fn cuts_out(&self, _texture_coordinates: (f32, f32)) -> bool {
+        false
+    }
+
+    /// Thin surfaces, like cloth or paper, are often modelled as a single
+    /// sheet with inconsistent winding, so their interpolated normals can't
+    /// be trusted to say which side was hit. Materials which force the front
+    /// face treat every hit as a front face hit and shade with the face's
+    /// own normal, turned toward the incoming ray.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `bool` - true if hits should always count as front face hits
+    fn force_front_face(&self) -> bool {
+        false
+    }
+
     // /// For emissive materials, they may overload this method to emit light
     // /// from their surface.
     // ///
@@ -64,6 +185,15 @@ pub enum MaterialType {
     Metal(Metal),
     Transparent(Transparent),
     DiffuseLight(DiffuseLight),
+    Textured(Textured),
+    Wireframe(Wireframe),
+    VertexColor(VertexColor),
+    ShadowCatcher(ShadowCatcher),
+    Water(Water),
+    Triplanar(Triplanar),
+    Subsurface(Subsurface),
+    Phong(Phong),
+    Isotropic(Isotropic),
 }
 
 impl Material for MaterialType {
@@ -72,32 +202,62 @@ impl Material for MaterialType {
     ///
     /// # Arguments
     /// - self reference
+    /// - `context` - render settings which change how the hit is shaded
     /// - `incoming_ray` - ray which has just hit this material
     /// - `hit_record` - specification of the hit which just ocurred
     ///
     /// # Returns
     /// - optional `Ray`, or none if the ray was absorbed
+    #[allow(clippy::too_many_arguments)]
     fn shade<T: Hittable>(
         &self,
         world: &T,
         lights: &[Light],
         sky: &Sky,
+        context: &ShadingContext,
         incoming_ray: &Ray,
         hit_record: &HitRecord,
         depth: u32,
     ) -> Vec3 {
         match *self {
             MaterialType::Lambertian(ref material) => {
-                material.shade(world, lights, sky, incoming_ray, hit_record, depth)
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
             }
             MaterialType::Metal(ref material) => {
-                material.shade(world, lights, sky, incoming_ray, hit_record, depth)
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
             }
             MaterialType::Transparent(ref material) => {
-                material.shade(world, lights, sky, incoming_ray, hit_record, depth)
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
             }
             MaterialType::DiffuseLight(ref material) => {
-                material.shade(world, lights, sky, incoming_ray, hit_record, depth)
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Textured(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Wireframe(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::VertexColor(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::ShadowCatcher(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Water(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Triplanar(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Subsurface(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Phong(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+            }
+            MaterialType::Isotropic(ref material) => {
+                material.shade(world, lights, sky, context, incoming_ray, hit_record, depth)
             }
         }
     }
@@ -115,6 +275,64 @@ impl Material for MaterialType {
             MaterialType::Metal(ref material) => material.color(),
             MaterialType::Transparent(ref material) => material.color(),
             MaterialType::DiffuseLight(ref material) => material.color(),
+            MaterialType::Textured(ref material) => material.color(),
+            MaterialType::Wireframe(ref material) => material.color(),
+            MaterialType::VertexColor(ref material) => material.color(),
+            MaterialType::ShadowCatcher(ref material) => material.color(),
+            MaterialType::Water(ref material) => material.color(),
+            MaterialType::Triplanar(ref material) => material.color(),
+            MaterialType::Subsurface(ref material) => material.color(),
+            MaterialType::Phong(ref material) => material.color(),
+            MaterialType::Isotropic(ref material) => material.color(),
+        }
+    }
+
+    /// Retrieve the color of the material at a particular hit.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit_record` - the hit to find the color at
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color_at(&self, hit_record: &HitRecord) -> Vec3 {
+        match *self {
+            MaterialType::Textured(ref material) => material.color_at(hit_record),
+            MaterialType::Wireframe(ref material) => material.color_at(hit_record),
+            MaterialType::VertexColor(ref material) => material.color_at(hit_record),
+            MaterialType::Triplanar(ref material) => material.color_at(hit_record),
+            _ => self.color(),
+        }
+    }
+
+    /// Check the material's opacity mask at the given coordinates.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `texture_coordinates: (f32, f32)` - surface coordinates of the hit
+    ///
+    /// # Returns
+    /// - `bool` - true if there is no surface at these coordinates
+    fn cuts_out(&self, texture_coordinates: (f32, f32)) -> bool {
+        match *self {
+            MaterialType::Textured(ref material) => material.cuts_out(texture_coordinates),
+            _ => false,
+        }
+    }
+
+    /// Check whether the material treats every hit as a front face hit.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `bool` - true if hits should always count as front face hits
+    fn force_front_face(&self) -> bool {
+        match *self {
+            MaterialType::Lambertian(ref material) => material.force_front_face(),
+            MaterialType::Textured(ref material) => material.force_front_face(),
+            MaterialType::DiffuseLight(ref material) => material.force_front_face(),
+            _ => false,
         }
     }
 }
@@ -1,32 +1,197 @@
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
+use crate::light;
 use crate::light::Light;
 use crate::material::Material;
+use crate::material::ShadingContext;
 use crate::ray::Ray;
 use crate::scenes::Sky;
 use glm::Vec3;
 
+/// Represent an emissive material
 #[derive(Clone, Copy)]
 pub struct DiffuseLight {
+    /// Color emitted from the surface
     pub color: Vec3,
+    /// Exponent of the angular falloff. Emission is scaled by
+    /// `max(0, dot(normal, -ray.direction))^falloff`, so `0.0` emits equally
+    /// in all directions from both faces, `1.0` is a Lambertian emitter, and
+    /// larger values narrow the emission into a cone around the normal.
+    pub falloff: f32,
+    /// Emit the falloff cone from whichever face the ray sees, instead of
+    /// only from the front face, see `Material::force_front_face`
+    pub force_front_face: bool,
 }
 
 impl Material for DiffuseLight {
-    /// To shade a light, just return the color it emits
+    /// To shade a light, return the color it emits toward the incoming ray.
+    /// If a diffuse bounce found a surface which is also sampled through the
+    /// scene's light list, the emission is weighted by multiple importance
+    /// sampling, so the two estimates of the same light add up to one.
     fn shade<T: Hittable>(
         &self,
         _world: &T,
-        _lights: &[Light],
+        lights: &[Light],
         _sky: &Sky,
-        _incoming_ray: &Ray,
-        _hit_record: &HitRecord,
+        _context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
         _depth: u32,
     ) -> Vec3 {
-        self.color
+        if self.falloff == 0.0 {
+            return incoming_ray.weigh(match incoming_ray.scatter_pdf {
+                Some(scatter_pdf) => {
+                    let light_pdf = lights
+                        .iter()
+                        .find_map(|light| light.emitter_pdf(incoming_ray, hit_record))
+                        .unwrap_or(0.0);
+                    self.color * light::power_heuristic(scatter_pdf, light_pdf)
+                }
+                None => self.color,
+            });
+        }
+        let cos_theta =
+            glm::normalize_dot(&self.emission_normal(hit_record), &-incoming_ray.direction)
+                .max(0.0);
+        incoming_ray.weigh(self.color * cos_theta.powf(self.falloff))
     }
 
     /// Retrieve the base color of the material.
     fn color(&self) -> Vec3 {
         self.color
     }
+
+    fn force_front_face(&self) -> bool {
+        self.force_front_face
+    }
+}
+
+impl DiffuseLight {
+    /// Create a light which emits equally in all directions from both faces.
+    /// Each channel of the color which is negative or not a number is
+    /// replaced with zero, with a warning, since a single one would spread
+    /// through every pixel that sees the light or anything it lights.
+    ///
+    /// # Arguments
+    /// - `color: Vec3` - color emitted from the surface
+    ///
+    /// # Returns
+    /// - the light, emitting a finite, non-negative color
+    pub fn new(color: Vec3) -> DiffuseLight {
+        let sanitized = color.map(|channel| {
+            if channel.is_finite() && channel > 0.0 {
+                channel
+            } else {
+                0.0
+            }
+        });
+        if sanitized != color {
+            eprintln!(
+                "warning: light color ({}, {}, {}) has negative or non-finite channels, which emit nothing",
+                color.x, color.y, color.z
+            );
+        }
+        DiffuseLight {
+            color: sanitized,
+            falloff: 0.0,
+            force_front_face: false,
+        }
+    }
+
+    /// Narrow the emission into a cone around the normal, see `falloff`.
+    /// A negative or non-finite exponent is replaced with zero, with a
+    /// warning, since it would make the emission infinite at grazing angles.
+    ///
+    /// # Arguments
+    /// - `falloff: f32` - exponent of the angular falloff
+    ///
+    /// # Returns
+    /// - the light with the falloff
+    pub fn with_falloff(self, falloff: f32) -> DiffuseLight {
+        let sanitized = if falloff.is_finite() && falloff > 0.0 {
+            falloff
+        } else {
+            if falloff != 0.0 {
+                eprintln!(
+                    "warning: light falloff {} is negative or non-finite, emitting evenly instead",
+                    falloff
+                );
+            }
+            0.0
+        };
+        DiffuseLight {
+            falloff: sanitized,
+            ..self
+        }
+    }
+
+    /// Create a white light which emits a total power from each face,
+    /// spread evenly over its area. Lights of any size made with the same
+    /// power light distant objects equally, where a fixed `color` would make
+    /// a bigger light brighter.
+    ///
+    /// # Arguments
+    /// - `watts: f32` - power emitted from each face
+    /// - `area: f32` - area of the surface the material is on
+    ///
+    /// # Returns
+    /// - the light, emitting equally in all directions
+    pub fn from_power(watts: f32, area: f32) -> DiffuseLight {
+        // a face with constant radiance L over area A emits pi * L * A
+        let radiance = watts / (std::f32::consts::PI * area);
+        DiffuseLight::new(glm::vec3(radiance, radiance, radiance))
+    }
+
+    /// Normal about which the falloff cone is centered.
+    fn emission_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        if self.force_front_face {
+            hit_record.normal()
+        } else {
+            hit_record.outward_normal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::MaterialType;
+
+    #[test]
+    fn new_replaces_negative_and_non_finite_emission() {
+        let light = DiffuseLight::new(glm::vec3(f32::NAN, 1.0, 2.0));
+        assert_eq!(light.color, glm::vec3(0.0, 1.0, 2.0));
+        let light = DiffuseLight::new(glm::vec3(-5.0, f32::INFINITY, 3.0));
+        assert_eq!(light.color, glm::vec3(0.0, 0.0, 3.0));
+        assert_eq!(light.with_falloff(-1.0).falloff, 0.0);
+        assert_eq!(light.with_falloff(f32::NAN).falloff, 0.0);
+        assert_eq!(light.with_falloff(4.0).falloff, 4.0);
+
+        // so the light shades finite and non-negative from head on to grazing
+        let world = HittableList::new();
+        let sky = Sky::uniform(glm::vec3(0.0, 0.0, 0.0));
+        let context = ShadingContext::default();
+        for falloff in [f32::NAN, -1.0, 1.0] {
+            let material = MaterialType::DiffuseLight(
+                DiffuseLight::new(glm::vec3(-1.0, f32::NAN, 2.0)).with_falloff(falloff),
+            );
+            for step in 0..100 {
+                let angle = step as f32 / 99.0 * std::f32::consts::FRAC_PI_2;
+                let direction = glm::vec3(angle.sin(), 0.0, -angle.cos());
+                let ray = Ray::new(-direction, direction, None);
+                let normal = glm::vec3(0.0, 0.0, 1.0);
+                let hit = HitRecord::new(&ray, 1.0, normal, normal, Some(&material));
+                let color = material.shade(&world, &[], &sky, &context, &ray, &hit, 1);
+                assert!(
+                    color
+                        .iter()
+                        .all(|channel| channel.is_finite() && *channel >= 0.0),
+                    "{:?} at falloff {}",
+                    color,
+                    falloff
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,125 @@
+use super::super::trace_ray;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random;
+use crate::random::rng;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+use rand::Rng;
+
+/// Represent the scattering of a participating medium like fog or smoke,
+/// for the hits a `crate::hittable::medium::ConstantMedium` makes inside
+/// its volume. Light is scattered with the Henyey-Greenstein phase
+/// function, whose `asymmetry`, often called `g`, is the mean cosine
+/// between the incoming and scattered directions. Zero scatters the same in
+/// every direction, positive values scatter forward like fog around a
+/// headlight, and negative values back toward the light like some smoke.
+///
+/// The phase function is sampled exactly, so each bounce is weighted by the
+/// albedo alone, and lights are sampled directly through it.
+#[derive(Clone, Copy)]
+pub struct Isotropic {
+    /// Fraction of each channel scattered at each hit, the rest absorbed
+    pub albedo: Vec3,
+    /// Mean cosine of the scattering angle, strictly between -1 and 1
+    pub asymmetry: f32,
+}
+
+/// Methods for the material trait
+impl Material for Isotropic {
+    /// Shade a scattering event inside a medium by sampling the lights
+    /// through the phase function and tracing one scattered ray picked from
+    /// it.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just scattered in the medium
+    /// - `hit_record` - specification of where it scattered
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        // the phase function scatters through every side of the point, so
+        // the cosine from the record's normal plays no part
+        let response = |direction: &Vec3, _cosine: f32| {
+            let phase = self.pdf(&incoming_ray.direction, direction);
+            (glm::vec3(phase, phase, phase), phase)
+        };
+        let direct_light = lights
+            .iter()
+            .fold(glm::vec3(0.0, 0.0, 0.0), |total, light| {
+                total
+                    + light.reflected_radiance(
+                        hit_record,
+                        world,
+                        context.light_samples_per_hit,
+                        &response,
+                    )
+            })
+            + sky.direct_reflected_radiance(hit_record, world, &response);
+
+        let (direction, scatter_pdf) = self.scatter(&incoming_ray.direction);
+        let scattered_ray = incoming_ray
+            .continue_path(hit_record.hit_point, direction, Some(self.albedo))
+            .with_scatter_pdf(scatter_pdf);
+        trace_ray(&scattered_ray, world, lights, sky, context, depth - 1)
+            + incoming_ray.weigh(glm::matrix_comp_mult(&self.albedo, &direct_light))
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Methods specific to isotropic materials
+impl Isotropic {
+    /// Pick the direction light travelling along `incoming` scatters in,
+    /// from the Henyey-Greenstein phase function.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming: &Vec3` - unit direction the light was travelling in
+    ///
+    /// # Returns
+    /// - `(Vec3, f32)` - the unit scattered direction, and the solid angle
+    ///   density it was picked with, for weighing it against light sampling
+    pub fn scatter(&self, incoming: &Vec3) -> (Vec3, f32) {
+        let direction =
+            random::henyey_greenstein_direction(incoming, self.asymmetry, rng().gen(), rng().gen());
+        (direction, self.pdf(incoming, &direction))
+    }
+
+    /// Density with which `scatter` picks a direction, per unit solid
+    /// angle, which is also the phase function's value there.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming: &Vec3` - unit direction the light was travelling in
+    /// - `scattered: &Vec3` - direction it scatters in
+    ///
+    /// # Returns
+    /// - `f32` - the density of the scattered direction
+    pub fn pdf(&self, incoming: &Vec3, scattered: &Vec3) -> f32 {
+        random::henyey_greenstein_pdf(glm::normalize_dot(incoming, scattered), self.asymmetry)
+    }
+}
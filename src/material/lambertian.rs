@@ -4,34 +4,79 @@ use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::light::Light;
 use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random::rng;
 use crate::ray::Ray;
 use crate::scenes::Sky;
+use crate::stats;
+use crate::stats::Counter;
 use glm::Vec3;
-use rand::prelude::thread_rng as rng;
 use rand::Rng;
 
+/// Number of short rays cast per hit to estimate ambient occlusion
+const AMBIENT_OCCLUSION_SAMPLES: usize = 4;
+
 /// Represent a Lambertial material with diffuse scattering
 #[derive(Clone, Copy)]
 pub struct Lambertian {
     /// Base albedo of the material
     pub albedo: Vec3,
+    /// Treat every hit as a hit on the front face, with the face's own
+    /// normal turned toward the incoming ray, see `Material::force_front_face`
+    pub force_front_face: bool,
 }
 
 /// Methods specific to Lambertian materials
 impl Lambertian {
-    /// Generate a vector on a Lambertian distribution
+    /// Generate a vector on a Lambertian distribution. Added to a unit
+    /// normal, it gives a cosine distributed direction about the normal.
     ///
     /// # Arguments
     /// None
     ///
     /// # Returns
     /// - `Vec3` - random direction
-    fn random_direction() -> Vec3 {
+    pub fn random_direction() -> Vec3 {
         let theta = rng().gen::<f32>() * 2.0 * std::f32::consts::PI;
         let z = (rng().gen::<f32>() * 2.0) - 1.0;
         let r = (1.0 - z * z).sqrt();
         glm::vec3(r * theta.cos(), r * theta.sin(), z)
     }
+
+    /// Estimate what fraction of the bounce light reaches a hit, by casting
+    /// a few short rays about the normal and counting those which escape.
+    ///
+    /// # Arguments
+    /// - `world: &T` - objects which can occlude the hit
+    /// - `context: &ShadingContext` - the ambient occlusion strength and
+    ///   radius
+    /// - `hit_record` - the hit to find the occlusion at
+    ///
+    /// # Returns
+    /// - `f32` - factor from 0 to 1 to scale the bounce light by, 1 if
+    ///   ambient occlusion is off
+    fn ambient_occlusion<T: Hittable>(
+        world: &T,
+        context: &ShadingContext,
+        hit_record: &HitRecord,
+    ) -> f32 {
+        let strength = context.ambient_occlusion_strength.clamp(0.0, 1.0);
+        if strength == 0.0 {
+            return 1.0;
+        }
+        let radius = context.ambient_occlusion_radius;
+        let normal = hit_record.normal();
+        let occluded = (0..AMBIENT_OCCLUSION_SAMPLES)
+            .filter(|_| {
+                let direction = normal + Lambertian::random_direction();
+                let ray =
+                    Ray::new(hit_record.hit_point, direction, None).with_time(hit_record.ray.time);
+                stats::record(Counter::ShadowRays);
+                world.any_hit(&ray, EPSILON, radius)
+            })
+            .count();
+        1.0 - strength * occluded as f32 / AMBIENT_OCCLUSION_SAMPLES as f32
+    }
 }
 
 /// Methods for the material trait
@@ -51,7 +96,8 @@ impl Material for Lambertian {
         world: &T,
         lights: &[Light],
         sky: &Sky,
-        _incoming_ray: &Ray,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
         hit_record: &HitRecord,
         depth: u32,
     ) -> Vec3 {
@@ -63,10 +109,32 @@ impl Material for Lambertian {
             scatter_direction = hit_record.normal()
         }
 
-        let scattered_ray = Ray::new(hit_record.hit_point, scatter_direction, Some(self.albedo));
-        let scattered_color = trace_ray(&scattered_ray, world, lights, sky, depth - 1);
+        // the scattered directions are cosine distributed about the normal
+        let scatter_pdf = glm::normalize_dot(&hit_record.normal(), &scatter_direction).max(0.0)
+            / std::f32::consts::PI;
+        let occlusion = Lambertian::ambient_occlusion(world, context, hit_record);
+        let scattered_ray = incoming_ray
+            .continue_path(
+                hit_record.hit_point,
+                scatter_direction,
+                Some(self.albedo * occlusion),
+            )
+            .with_scatter_pdf(scatter_pdf);
+        let scattered_color = trace_ray(&scattered_ray, world, lights, sky, context, depth - 1);
 
-        glm::matrix_comp_mult(&self.albedo, &scattered_color)
+        // lights in the scene's light list, and the sun of a daylight sky,
+        // are sampled directly, weighted against the scattered ray hitting
+        // the same light
+        let direct_light = lights
+            .iter()
+            .fold(glm::vec3(0.0, 0.0, 0.0), |total, light| {
+                total
+                    + light.irradiance(hit_record, world, context.light_samples_per_hit)
+                        / std::f32::consts::PI
+            })
+            + sky.direct_irradiance(hit_record, world) / std::f32::consts::PI;
+
+        scattered_color + incoming_ray.weigh(glm::matrix_comp_mult(&self.albedo, &direct_light))
     }
 
     /// Retrieve the base color of the material.
@@ -79,4 +147,8 @@ impl Material for Lambertian {
     fn color(&self) -> Vec3 {
         self.albedo
     }
+
+    fn force_front_face(&self) -> bool {
+        self.force_front_face
+    }
 }
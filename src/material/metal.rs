@@ -4,15 +4,27 @@ use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::light::Light;
 use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random::rng;
 use crate::ray::Ray;
 use crate::scenes::Sky;
 use glm::Vec3;
+use rand::Rng;
 
 /// Represent a metal material with reflection
 #[derive(Clone, Copy)]
 pub struct Metal {
     /// Base albedo of the material
     pub albedo: Vec3,
+    /// Scale reflections with Schlick's Fresnel approximation, so the metal
+    /// is tinted by `albedo` when seen head on and turns white toward
+    /// grazing angles. Without it, reflections are untinted mirrors.
+    pub fresnel: bool,
+    /// Spread of the surface's microscopic slopes along its tangent and
+    /// across it, for brushed metal whose highlights stretch in one
+    /// direction. The tangent follows the u texture coordinate on meshes.
+    /// `(0.0, 0.0)` is a perfect mirror.
+    pub roughness: (f32, f32),
 }
 
 /// Methods for the material trait
@@ -32,17 +44,27 @@ impl Material for Metal {
         world: &T,
         lights: &[Light],
         sky: &Sky,
+        context: &ShadingContext,
         incoming_ray: &Ray,
         hit_record: &HitRecord,
         depth: u32,
     ) -> Vec3 {
-        let reflected_direction = glm::reflect_vec(&incoming_ray.direction, &hit_record.normal());
+        let reflected_direction =
+            glm::reflect_vec(&incoming_ray.direction, &self.facet_normal(hit_record));
         if glm::dot(&reflected_direction, &hit_record.normal()) > 0.0 {
-            let reflected_ray =
-                Ray::new(hit_record.hit_point, reflected_direction, Some(self.albedo));
-            trace_ray(&reflected_ray, world, lights, sky, depth - 1)
+            let weight = if self.fresnel {
+                let cos_theta =
+                    glm::normalize_dot(&-incoming_ray.direction, &hit_record.normal()).max(0.0);
+                Some(self.reflectance(cos_theta))
+            } else {
+                None
+            };
+            let reflected_ray = incoming_ray
+                .continue_path(hit_record.hit_point, reflected_direction, weight)
+                .after_specular();
+            trace_ray(&reflected_ray, world, lights, sky, context, depth - 1)
         } else {
-            color::color(0, 0, 0)
+            color::color_linear(0, 0, 0)
         }
     }
 
@@ -57,3 +79,62 @@ impl Material for Metal {
         self.albedo
     }
 }
+
+/// Methods specific to metal materials
+impl Metal {
+    /// Schlick's approximation of the Fresnel reflectance, using the albedo
+    /// as the reflectance at normal incidence.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `cos_theta: f32` - cosine of the angle between the view and normal
+    ///
+    /// # Returns
+    /// - `Vec3` - the fraction of each channel which is reflected
+    fn reflectance(&self, cos_theta: f32) -> Vec3 {
+        let white = glm::vec3(1.0, 1.0, 1.0);
+        self.albedo + (white - self.albedo) * (1.0 - cos_theta).powi(5)
+    }
+
+    /// Pick the normal of the microscopic facet which reflects the ray,
+    /// tilting the surface normal by random slopes scaled by the roughness
+    /// along the tangent and across it.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit_record` - the hit to find the facet at
+    ///
+    /// # Returns
+    /// - `Vec3` - the unit facet normal, the surface normal for mirrors
+    fn facet_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        let normal = hit_record.normal();
+        if self.roughness == (0.0, 0.0) {
+            return normal;
+        }
+
+        // surfaces without a tangent are brushed in an arbitrary direction
+        let guess = hit_record.tangent.unwrap_or_else(|| {
+            if normal.x.abs() < 0.9 {
+                glm::vec3(1.0, 0.0, 0.0)
+            } else {
+                glm::vec3(0.0, 1.0, 0.0)
+            }
+        });
+        let mut tangent = guess - normal * glm::dot(&guess, &normal);
+        if glm::length(&tangent) < 1e-6 {
+            tangent = glm::cross(&normal, &glm::vec3(0.0, 0.0, 1.0));
+        }
+        let tangent = glm::normalize(&tangent);
+        let bitangent = glm::cross(&normal, &tangent);
+
+        // normally distributed slopes, by the Box-Muller transform
+        let radius = (-2.0 * (1.0 - rng().gen::<f32>()).ln()).sqrt();
+        let angle = rng().gen::<f32>() * 2.0 * std::f32::consts::PI;
+        let slopes = (radius * angle.cos(), radius * angle.sin());
+        glm::normalize(
+            &(normal
+                + slopes.0 * self.roughness.0 * tangent
+                + slopes.1 * self.roughness.1 * bitangent),
+        )
+    }
+}
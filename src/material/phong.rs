@@ -0,0 +1,174 @@
+use super::super::trace_ray;
+use super::super::EPSILON;
+use crate::color;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random;
+use crate::random::rng;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+use rand::Rng;
+
+/// Represent a glossy material with the classic Phong lobe, for surfaces
+/// between a diffuse Lambertian and a mirror like `Metal`, such as plastic
+/// or varnished wood. Light is reflected diffusely by `diffuse`, and into a
+/// highlight about the mirror direction by `specular`, falling off with the
+/// cosine from the mirror direction raised to `exponent`. The lobe is
+/// normalized, so `diffuse + specular` at most 1 in every channel reflects
+/// no more light than arrives, whatever the exponent.
+///
+/// Each hit follows one bounce, picked from the diffuse or the specular
+/// lobe in proportion to their luminances, and lights are sampled directly
+/// through the full lobe, so highlights of small lights converge quickly.
+#[derive(Clone, Copy)]
+pub struct Phong {
+    /// Fraction of each channel reflected diffusely
+    pub diffuse: Vec3,
+    /// Fraction of each channel reflected into the glossy highlight
+    pub specular: Vec3,
+    /// Power of the cosine from the mirror direction, at least 0. Small
+    /// exponents spread the highlight over the surface, and large ones
+    /// draw it tight toward a mirror.
+    pub exponent: f32,
+}
+
+/// Methods for the material trait
+impl Material for Phong {
+    /// Shade a hit by sampling the lights through the Phong lobe and
+    /// tracing one bounce from the diffuse or specular lobe.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let normal = hit_record.normal();
+        let mirror = glm::normalize(&glm::reflect_vec(&incoming_ray.direction, &normal));
+        let specular_chance = match self.specular_chance() {
+            Some(specular_chance) => specular_chance,
+            None => return glm::vec3(0.0, 0.0, 0.0),
+        };
+        let response = |direction: &Vec3, cosine: f32| {
+            self.response(&mirror, specular_chance, direction, cosine)
+        };
+        let direct_light = lights
+            .iter()
+            .fold(glm::vec3(0.0, 0.0, 0.0), |total, light| {
+                total
+                    + light.reflected_radiance(
+                        hit_record,
+                        world,
+                        context.light_samples_per_hit,
+                        &response,
+                    )
+            })
+            + sky.direct_reflected_radiance(hit_record, world, &response);
+        let direct_light = incoming_ray.weigh(direct_light);
+
+        let direction = if rng().gen::<f32>() < specular_chance {
+            random::cosine_power_direction(&mirror, self.exponent, rng().gen(), rng().gen())
+        } else {
+            let direction = normal + Lambertian::random_direction();
+            if glm::length2(&direction) < EPSILON * EPSILON {
+                normal
+            } else {
+                direction
+            }
+        };
+        // the specular lobe reaches below the surface toward grazing angles,
+        // and far out in a tight lobe its density can round to zero
+        let cosine = glm::normalize_dot(&normal, &direction);
+        let (reflected, scatter_pdf) = response(&direction, cosine);
+        if cosine <= 0.0 || scatter_pdf <= 0.0 {
+            return direct_light;
+        }
+        let weight = reflected / scatter_pdf;
+        let bounce = incoming_ray
+            .continue_path(hit_record.hit_point, direction, Some(weight))
+            .with_scatter_pdf(scatter_pdf);
+        direct_light + trace_ray(&bounce, world, lights, sky, context, depth - 1)
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.diffuse
+    }
+}
+
+/// Methods specific to Phong materials
+impl Phong {
+    /// Chance of a bounce being picked from the specular lobe rather than
+    /// the diffuse one, its share of the two lobes' luminances.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - the chance, or `None` if the material reflects no light at all
+    fn specular_chance(&self) -> Option<f32> {
+        let diffuse = color::luminance(&self.diffuse).max(0.0);
+        let specular = color::luminance(&self.specular).max(0.0);
+        if diffuse + specular > 0.0 {
+            Some(specular / (diffuse + specular))
+        } else {
+            None
+        }
+    }
+
+    /// How the surface reflects light arriving from a direction, see
+    /// `light::SurfaceResponse`: the BRDF times the cosine, and the density
+    /// with which a bounce picks the direction from either lobe.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `mirror: &Vec3` - unit mirror direction of the incoming ray
+    /// - `specular_chance: f32` - chance of a bounce from the specular lobe
+    /// - `direction: &Vec3` - direction the light arrives from
+    /// - `cosine: f32` - cosine between the direction and the normal
+    ///
+    /// # Returns
+    /// - `(Vec3, f32)` - the reflected fraction of each channel, and the
+    ///   bounces' density
+    fn response(
+        &self,
+        mirror: &Vec3,
+        specular_chance: f32,
+        direction: &Vec3,
+        cosine: f32,
+    ) -> (Vec3, f32) {
+        let lobe_pdf =
+            random::cosine_power_pdf(glm::normalize_dot(mirror, direction), self.exponent);
+        let diffuse_pdf = cosine / std::f32::consts::PI;
+        // the normalized lobe is (n + 2) / (2 pi) times the cosine power,
+        // where its density is (n + 1) / (2 pi) times it
+        let specular_brdf = lobe_pdf * (self.exponent + 2.0) / (self.exponent + 1.0);
+        let brdf = self.diffuse / std::f32::consts::PI + self.specular * specular_brdf;
+        (
+            brdf * cosine,
+            (1.0 - specular_chance) * diffuse_pdf + specular_chance * lobe_pdf,
+        )
+    }
+}
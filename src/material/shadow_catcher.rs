@@ -0,0 +1,108 @@
+use super::super::trace_ray;
+use crate::color;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+
+/// Represent an invisible surface which only shows the shadows falling on
+/// it, for compositing rendered objects into a photo. Rays pass straight
+/// through it to whatever is behind, darkened where the surface is hidden
+/// from the scene's lights. Rendered with `--alpha`, camera rays which see
+/// the background through it leave the pixel transparent except for the
+/// shadow, see `ShadowCatcher::shadow`.
+#[derive(Clone, Copy)]
+pub struct ShadowCatcher {
+    /// How dark a point hidden from every light becomes, from 0 for no
+    /// shadow to 1 for black
+    pub shadow_opacity: f32,
+}
+
+/// Methods specific to shadow catchers
+impl ShadowCatcher {
+    /// Estimate how much of the light from the scene's lights is blocked at
+    /// a hit, with the same shadow rays diffuse surfaces cast. Light from a
+    /// daylight sky's sun isn't counted.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `world: &T` - objects which can cast shadows
+    /// - `lights: &[Light]` - light sources for the scene
+    /// - `samples: usize` - number of shadow rays to cast toward each light
+    /// - `hit_record` - the hit on the shadow catcher
+    ///
+    /// # Returns
+    /// - `f32` - opacity of the shadow, from 0 where the hit is fully lit
+    ///   to `shadow_opacity` where every light is blocked
+    pub fn shadow<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        samples: usize,
+        hit_record: &HitRecord,
+    ) -> f32 {
+        let (lit, unshadowed) = lights.iter().fold(
+            (glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 0.0)),
+            |(lit, unshadowed), light| {
+                let (light_lit, light_unshadowed) =
+                    light.shadowed_irradiance(hit_record, world, samples);
+                (lit + light_lit, unshadowed + light_unshadowed)
+            },
+        );
+        let unshadowed = color::luminance(&unshadowed);
+        if unshadowed <= 0.0 {
+            // no light reaches this side at all, so there's nothing to block
+            return 0.0;
+        }
+        let blocked = 1.0 - color::luminance(&lit) / unshadowed;
+        self.shadow_opacity * blocked.clamp(0.0, 1.0)
+    }
+}
+
+/// Methods for the material trait
+impl Material for ShadowCatcher {
+    /// Pass the incoming ray through the surface and darken what it sees
+    /// behind by the shadow at the hit.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let shadow = self.shadow(world, lights, context.light_samples_per_hit, hit_record);
+        let passed_ray = incoming_ray.continue_path(
+            hit_record.hit_point,
+            incoming_ray.direction,
+            Some(glm::vec3(1.0, 1.0, 1.0) * (1.0 - shadow)),
+        );
+        trace_ray(&passed_ray, world, lights, sky, context, depth - 1)
+    }
+
+    /// Retrieve the base color of the material, which is black since the
+    /// surface has none of its own.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        glm::vec3(0.0, 0.0, 0.0)
+    }
+}
@@ -0,0 +1,147 @@
+use super::super::trace_ray;
+use super::super::EPSILON;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random::rng;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use crate::stats;
+use crate::stats::Counter;
+use glm::Vec3;
+use rand::Rng;
+
+/// Represent a translucent material like wax, skin or marble, where light
+/// enters the surface, scatters around inside and leaves somewhere else, a
+/// cheap stand-in for a BSSRDF. Light crosses the surface diffusely both
+/// ways, and inside it takes a random walk of straight steps with
+/// exponentially distributed lengths, scattering in a random direction
+/// after each, until it reaches the surface again. Thin parts let through
+/// light from behind, and the more the light scatters on its way the more
+/// deeply it is tinted.
+///
+/// The walk leaves through whatever surface it reaches first, so objects
+/// inside the material, or overlapping it, end the walk as if they were its
+/// boundary. The surface should be closed, or walks which leave through a
+/// hole wander until they run out of scattering events.
+#[derive(Clone, Copy)]
+pub struct Subsurface {
+    /// Fraction of each channel kept at every scattering event inside
+    pub albedo: Vec3,
+    /// Average distance light travels inside between scattering events.
+    /// Shorter distances keep the light near where it entered, so the
+    /// material looks more opaque.
+    pub mean_free_path: f32,
+    /// Most scattering events in one walk. Light which hasn't left by then
+    /// is taken as absorbed, which darkens thick parts.
+    pub max_scatter_events: u32,
+}
+
+/// Methods for the material trait
+impl Material for Subsurface {
+    /// Shade a hit by following the light into the material, along a random
+    /// walk to where it leaves, and finding the light arriving there.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        // light enters cosine distributed about the inward normal
+        let inward = -hit_record.normal();
+        let mut direction = inward + Lambertian::random_direction();
+        if glm::length2(&direction) < EPSILON * EPSILON {
+            direction = inward;
+        }
+        let mut ray = incoming_ray.continue_path(hit_record.hit_point, direction, None);
+        for _ in 0..self.max_scatter_events {
+            let step = -(1.0 - rng().gen::<f32>()).ln() * self.mean_free_path;
+            stats::record(Counter::SecondaryRays);
+            if let Some(exit) = world.hit(&ray, EPSILON, step) {
+                return Subsurface::light_leaving(world, lights, sky, context, &ray, &exit, depth);
+            }
+            // scatter in a random direction where the step ends, darkened
+            // by the albedo in the walk's throughput
+            ray = ray.continue_path(
+                ray.at(step),
+                Lambertian::random_direction(),
+                Some(self.albedo),
+            );
+        }
+        glm::vec3(0.0, 0.0, 0.0)
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Methods specific to subsurface materials
+impl Subsurface {
+    /// Find the light which a walk carries out of the material where it
+    /// reaches the surface, from the light arriving there from outside. The
+    /// surface passes it on diffusely, like a Lambertian surface facing out,
+    /// so the lights are sampled directly and one bounce is traced outward.
+    ///
+    /// # Arguments
+    /// - `world: &T` - objects that compose our scene
+    /// - `lights: &[Light]` - light sources for the scene
+    /// - `sky: &Sky` - background of the scene
+    /// - `ray: &Ray` - the last step of the walk
+    /// - `exit: &HitRecord` - where the walk reaches the surface
+    /// - `depth: u32` - recursion depth left
+    ///
+    /// # Returns
+    /// - `Vec3` - the light carried back along the walk
+    fn light_leaving<T: Hittable>(
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        ray: &Ray,
+        exit: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        // lit from outside, with the normal facing out
+        let outside = exit.seen_from_other_side();
+        let normal = outside.normal();
+        let mut direction = normal + Lambertian::random_direction();
+        if glm::length2(&direction) < EPSILON * EPSILON {
+            direction = normal;
+        }
+        let scatter_pdf = glm::normalize_dot(&normal, &direction).max(0.0) / std::f32::consts::PI;
+        let bounce = ray
+            .continue_path(outside.hit_point, direction, None)
+            .with_scatter_pdf(scatter_pdf);
+        let direct_light = lights
+            .iter()
+            .fold(glm::vec3(0.0, 0.0, 0.0), |total, light| {
+                total + light.irradiance(&outside, world, context.light_samples_per_hit)
+            })
+            + sky.direct_irradiance(&outside, world);
+        trace_ray(&bounce, world, lights, sky, context, depth - 1)
+            + ray.weigh(direct_light / std::f32::consts::PI)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use crate::texture::ImageTexture;
+use glm::Vec3;
+
+/// Represent a diffuse material whose albedo comes from an image texture.
+/// The texture's alpha channel cuts holes in the surface, which is how
+/// foliage cards and fences are usually made.
+#[derive(Clone, Copy)]
+pub struct Textured {
+    /// Image providing the albedo and alpha of the surface
+    pub texture: ImageTexture,
+    /// Texels with alpha below this value are treated as holes
+    pub alpha_threshold: f32,
+    /// Treat every hit as a hit on the front face, with the face's own
+    /// normal turned toward the incoming ray, see `Material::force_front_face`
+    pub force_front_face: bool,
+}
+
+/// Methods for the material trait
+impl Material for Textured {
+    /// Shade the hit as a Lambertian surface with the texture's color at
+    /// the hit point.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let albedo = self.color_at(hit_record);
+        Lambertian {
+            albedo,
+            force_front_face: false,
+        }
+        .shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+    }
+
+    /// Retrieve the base color of the material. A texture has no single
+    /// color, so this is the color at the texture's origin.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.texture.color((0.0, 0.0))
+    }
+
+    /// Sample the texture at the hit.
+    fn color_at(&self, hit_record: &HitRecord) -> Vec3 {
+        self.texture.color(hit_record.texture_coordinates)
+    }
+
+    /// Holes in the texture are wherever alpha falls below the threshold.
+    fn cuts_out(&self, texture_coordinates: (f32, f32)) -> bool {
+        self.texture.alpha(texture_coordinates) < self.alpha_threshold
+    }
+
+    fn force_front_face(&self) -> bool {
+        self.force_front_face
+    }
+}
@@ -1,11 +1,45 @@
 use super::super::trace_ray;
+use super::super::DEPTH_LIMIT;
+use crate::color;
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::light::Light;
 use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::random;
+use crate::random::rng;
 use crate::ray::Ray;
 use crate::scenes::Sky;
 use glm::Vec3;
+use rand::Rng;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Wavelengths in micrometres which the red, green and blue channels of a
+/// dispersed path are refracted at
+const CHANNEL_WAVELENGTHS: [f32; 3] = [0.65, 0.55, 0.45];
+/// Wavelengths in micrometres of the Fraunhofer d, F and C lines, which
+/// the refractive index and Abbe number of a glass are given at
+const D_LINE: f32 = 0.5876;
+const F_LINE: f32 = 0.4861;
+const C_LINE: f32 = 0.6563;
+
+/// Least probability a reflect bias can lower the chance of reflecting or
+/// refracting to, when both carry light, so it can't starve one of samples
+/// or weight it out of all proportion
+const MIN_BRANCH_PROBABILITY: f32 = 0.05;
+
+/// Number of shaded colors which weren't finite and were replaced
+static NON_FINITE_COLORS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of times transparent shading produced an infinite or NaN color,
+/// which was replaced so it didn't spread to the rest of the pixel.
+///
+/// # Returns
+/// - `u64` - count of replaced colors since the program started
+pub fn non_finite_count() -> u64 {
+    NON_FINITE_COLORS.load(Ordering::Relaxed)
+}
 
 /// Represent a transparent material with reflection, refraction, and absorption
 #[derive(Clone, Copy)]
@@ -16,8 +50,38 @@ pub struct Transparent {
     pub reflectance: f32,
     /// proportion of light refracted
     pub transmittance: f32,
-    /// Refractive index specific to this material
+    /// Refractive index specific to this material, at the d line for
+    /// dispersive materials
     pub refractive_index: f32,
+    /// Abbe number of a dispersive material, lower for stronger dispersion,
+    /// or `None` to refract every color alike. A path through a dispersive
+    /// material is split into one path per color channel, each refracted at
+    /// its own wavelength.
+    pub abbe_number: Option<f32>,
+    /// Width of the GGX distribution of the surface's microscopic facets,
+    /// for frosted glass. Each hit reflects and refracts off one facet
+    /// picked at random, which blurs both what is seen through the glass
+    /// and what it reflects. `0.0` is perfectly smooth glass.
+    pub roughness: f32,
+    /// `None` to trace both the reflection and the refraction at each hit,
+    /// weighted by `reflectance` and `transmittance`. Otherwise each hit
+    /// follows one of them, weighted by Schlick's approximation of the
+    /// Fresnel reflectance, and this, from 0 to 1, blends the chance of
+    /// reflecting from the Fresnel reflectance toward the share of
+    /// `reflectance` in `reflectance + transmittance`. The weights divide by
+    /// the chance, so the bias only moves samples between the two, and the
+    /// image converges to the same Fresnel result, less noisy where the
+    /// favored side matters more. With `None`, see `branch_depth`.
+    pub reflect_bias: Option<f32>,
+    /// `None` to trace both the reflection and the refraction at every hit
+    /// when `reflect_bias` is `None`, which doubles the rays with each
+    /// bounce through the glass. Otherwise, once a path has bounced this
+    /// many times, only the one of the two which carries more light is
+    /// traced, and the other is left out like a branch which has used up its
+    /// depth budget. Paths then stop branching at deep bounces, where each
+    /// branch adds little, so the glass is a little darker there in return
+    /// for far fewer rays.
+    pub branch_depth: Option<u32>,
 }
 
 /// Methods for the material trait
@@ -36,23 +100,158 @@ impl Material for Transparent {
         world: &T,
         lights: &[Light],
         sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let abbe_number = match self.abbe_number {
+            Some(abbe_number) => abbe_number,
+            None => {
+                return self.shade_at_one_index(
+                    world,
+                    lights,
+                    sky,
+                    context,
+                    incoming_ray,
+                    hit_record,
+                    depth,
+                )
+            }
+        };
+        let channel_glass = |channel: usize| Transparent {
+            refractive_index: self.channel_index(abbe_number, channel),
+            abbe_number: None,
+            ..*self
+        };
+        match incoming_ray.channel {
+            // the path was already split, so it keeps to its wavelength
+            Some(channel) => channel_glass(channel).shade_at_one_index(
+                world,
+                lights,
+                sky,
+                context,
+                incoming_ray,
+                hit_record,
+                depth,
+            ),
+            None => {
+                let mut color = color::color_linear(0, 0, 0);
+                for channel in 0..3 {
+                    let channel_ray = incoming_ray.with_channel(channel);
+                    color[channel] = channel_glass(channel).shade_at_one_index(
+                        world,
+                        lights,
+                        sky,
+                        context,
+                        &channel_ray,
+                        hit_record,
+                        depth,
+                    )[channel];
+                }
+                color
+            }
+        }
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Methods specific to transparent materials
+impl Transparent {
+    /// Shade a hit, refracting every color channel at `refractive_index`.
+    #[allow(clippy::too_many_arguments)]
+    fn shade_at_one_index<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
         incoming_ray: &Ray,
         hit_record: &HitRecord,
         depth: u32,
     ) -> Vec3 {
-        // compute reflected light
+        // rough glass reflects and refracts off a facet instead of the surface
+        let facet_hit;
+        let hit_record = if self.roughness > 0.0 {
+            facet_hit = hit_record.with_outward_normal(self.facet_normal(hit_record, incoming_ray));
+            &facet_hit
+        } else {
+            hit_record
+        };
+        if self.reflect_bias.is_some() {
+            return self.shade_one_branch(
+                world,
+                lights,
+                sky,
+                context,
+                incoming_ray,
+                hit_record,
+                depth,
+            );
+        }
+
+        // past the branch depth, only the branch carrying more light is
+        // traced where both could be
+        let total_internal_reflection =
+            Transparent::total_internal_reflection(hit_record, incoming_ray, self.refractive_index);
+        let (skip_reflection, skip_refraction) = if self.past_branch_depth(depth)
+            && incoming_ray.reflection_depth > 0
+            && incoming_ray.refraction_depth > 0
+            && !total_internal_reflection
+        {
+            let refraction_dominates = self.refraction_dominates(hit_record, incoming_ray);
+            (refraction_dominates, !refraction_dominates)
+        } else {
+            (false, false)
+        };
+
+        // compute reflected light, unless the path has used up its reflections
         let reflected_direction = glm::reflect_vec(&incoming_ray.direction, &hit_record.normal());
-        let reflected_ray = Ray::new(hit_record.hit_point, reflected_direction, Some(self.albedo));
+        // divided by the cosine's size like the transmittance, since inside
+        // the glass the reflection leaves against the outward normal
         let reflectance = self.reflectance * self.albedo
-            / glm::dot(&hit_record.outward_normal, &reflected_direction);
-        let reflected_color = trace_ray(&reflected_ray, world, lights, sky, depth - 1);
+            / glm::dot(&hit_record.outward_normal, &reflected_direction).abs();
+        let reflected_weight =
+            reflectance * glm::dot(&hit_record.outward_normal, &reflected_direction).abs();
+        let reflected_color = if incoming_ray.reflection_depth > 0 && !skip_reflection {
+            // totally reflected light isn't scaled
+            let weight = if total_internal_reflection {
+                None
+            } else {
+                Some(reflected_weight)
+            };
+            let reflected_ray = incoming_ray
+                .continue_path(hit_record.hit_point, reflected_direction, weight)
+                .with_depth_budgets(
+                    incoming_ray.reflection_depth - 1,
+                    incoming_ray.refraction_depth,
+                )
+                .after_specular()
+                .escape_stall();
+            trace_ray(&reflected_ray, world, lights, sky, context, depth - 1)
+        } else {
+            color::color_linear(0, 0, 0)
+        };
 
         // check for total internal reflection
-        if Transparent::total_internal_reflection(hit_record, incoming_ray, self.refractive_index) {
+        if total_internal_reflection {
             return reflected_color;
         }
 
-        // compute refracted light
+        // compute refracted light, unless the path has used up its refractions
+        if incoming_ray.refraction_depth == 0 || skip_refraction {
+            return Transparent::finite_or(reflected_color, color::color_linear(0, 0, 0));
+        }
         let mut normal = hit_record.outward_normal;
         let mut eta = self.refractive_index;
         let incoming_direction = -incoming_ray.direction;
@@ -67,35 +266,198 @@ impl Material for Transparent {
         let cos_theta_2 = (1.0 - (1.0 - cos_theta_i * cos_theta_i) / (eta * eta)).sqrt();
         let transmitted_direction =
             -incoming_direction / eta - (cos_theta_2 - cos_theta_i / eta) * normal;
-        let transmitted_ray = Ray::new(
-            hit_record.hit_point,
-            transmitted_direction,
-            Some(self.albedo),
-        );
         let transmittance = self.transmittance / (eta * eta) * self.albedo
             / glm::dot(&hit_record.outward_normal, &transmitted_direction).abs();
-        let transmitted_color = trace_ray(&transmitted_ray, world, lights, sky, depth - 1);
+        let transmitted_weight =
+            transmittance * glm::dot(&hit_record.outward_normal, &transmitted_direction).abs();
+        let transmitted_ray = incoming_ray
+            .continue_path(
+                hit_record.hit_point,
+                transmitted_direction,
+                Some(transmitted_weight),
+            )
+            .with_depth_budgets(
+                incoming_ray.reflection_depth,
+                incoming_ray.refraction_depth - 1,
+            )
+            .after_specular()
+            .escape_stall();
+        let transmitted_color = trace_ray(&transmitted_ray, world, lights, sky, context, depth - 1);
 
-        glm::matrix_comp_mult(&reflectance, &reflected_color)
-            * glm::dot(&hit_record.outward_normal, &reflected_direction).abs()
-            + glm::matrix_comp_mult(&transmittance, &transmitted_color)
-                * glm::dot(&hit_record.outward_normal, &transmitted_direction).abs()
+        // the weights divide by cosines which vanish at grazing angles
+        Transparent::finite_or(reflected_color + transmitted_color, reflected_color)
     }
 
-    /// Retrieve the base color of the material.
+    /// Shade a hit by following either the reflection or the refraction, see
+    /// `reflect_bias`.
     ///
     /// # Arguments
     /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit, on the facet for rough
+    ///   glass
     ///
     /// # Returns
-    /// - `Vec3` - the RGB color
-    fn color(&self) -> Vec3 {
-        self.albedo
+    /// - `Vec3` - the color seen along the incoming ray
+    #[allow(clippy::too_many_arguments)]
+    fn shade_one_branch<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let mut normal = hit_record.outward_normal;
+        let mut eta = self.refractive_index;
+        let incoming_direction = -incoming_ray.direction;
+        let mut cos_theta_i = glm::dot(&normal, &incoming_direction);
+        let entering = cos_theta_i >= 0.0;
+        if !entering {
+            cos_theta_i = -cos_theta_i;
+            normal = -normal;
+            eta = 1.0 / eta;
+        }
+        let sin_squared_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+        let cos_theta_t = (1.0 - sin_squared_t).max(0.0).sqrt();
+        let fresnel = if sin_squared_t > 1.0 {
+            1.0
+        } else {
+            Transparent::schlick(
+                self.refractive_index,
+                if entering { cos_theta_i } else { cos_theta_t },
+            )
+        };
+        let probability = self.reflect_probability(fresnel, self.reflect_bias.unwrap_or(0.0));
+
+        let (direction, weight, reflection_depth, refraction_depth) =
+            if rng().gen::<f32>() < probability {
+                if incoming_ray.reflection_depth == 0 {
+                    return color::color_linear(0, 0, 0);
+                }
+                (
+                    glm::reflect_vec(&incoming_ray.direction, &normal),
+                    self.albedo * (fresnel / probability),
+                    incoming_ray.reflection_depth - 1,
+                    incoming_ray.refraction_depth,
+                )
+            } else {
+                if incoming_ray.refraction_depth == 0 {
+                    return color::color_linear(0, 0, 0);
+                }
+                (
+                    -incoming_direction / eta - (cos_theta_t - cos_theta_i / eta) * normal,
+                    self.albedo * ((1.0 - fresnel) / (1.0 - probability) / (eta * eta)),
+                    incoming_ray.reflection_depth,
+                    incoming_ray.refraction_depth - 1,
+                )
+            };
+        let ray = incoming_ray
+            .continue_path(hit_record.hit_point, direction, Some(weight))
+            .with_depth_budgets(reflection_depth, refraction_depth)
+            .after_specular()
+            .escape_stall();
+        Transparent::finite_or(
+            trace_ray(&ray, world, lights, sky, context, depth - 1),
+            color::color_linear(0, 0, 0),
+        )
+    }
+
+    /// Chance of following the reflection at a hit with some Fresnel
+    /// reflectance, blended toward the share of `reflectance` by the bias.
+    /// The bias can't take it within `MIN_BRANCH_PROBABILITY` of 0 or 1,
+    /// and with no bias it is the Fresnel reflectance itself.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `fresnel: f32` - Fresnel reflectance at the hit
+    /// - `bias: f32` - the reflect bias, from 0 for the Fresnel reflectance
+    ///   to 1 for the share of `reflectance`
+    ///
+    /// # Returns
+    /// - `f32` - the chance of reflecting
+    fn reflect_probability(&self, fresnel: f32, bias: f32) -> f32 {
+        if fresnel <= 0.0 || fresnel >= 1.0 {
+            return fresnel.clamp(0.0, 1.0);
+        }
+        let total = self.reflectance + self.transmittance;
+        let share = if total > 0.0 {
+            self.reflectance / total
+        } else {
+            fresnel
+        };
+        let bias = bias.clamp(0.0, 1.0);
+        ((1.0 - bias) * fresnel + bias * share).clamp(
+            fresnel.min(MIN_BRANCH_PROBABILITY),
+            fresnel.max(1.0 - MIN_BRANCH_PROBABILITY),
+        )
+    }
+
+    /// Schlick's approximation of the Fresnel reflectance of a boundary with
+    /// air.
+    ///
+    /// # Arguments
+    /// - `refractive_index: f32` - refractive index of the material
+    /// - `cosine: f32` - cosine of the angle to the normal on the air side
+    ///
+    /// # Returns
+    /// - `f32` - the share of light reflected
+    fn schlick(refractive_index: f32, cosine: f32) -> f32 {
+        let r0 = ((refractive_index - 1.0) / (refractive_index + 1.0)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+
+    /// Refractive index at the wavelength of a color channel, from Cauchy's
+    /// equation n = A + B / λ² fitted to the index at the d line and the
+    /// Abbe number (n_d - 1) / (n_F - n_C).
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `abbe_number: f32` - Abbe number of the material
+    /// - `channel: usize` - index of the channel, 0 for red to 2 for blue
+    ///
+    /// # Returns
+    /// - `f32` - the refractive index for that channel
+    fn channel_index(&self, abbe_number: f32, channel: usize) -> f32 {
+        let b = (self.refractive_index - 1.0) / (abbe_number * (F_LINE.powi(-2) - C_LINE.powi(-2)));
+        let a = self.refractive_index - b * D_LINE.powi(-2);
+        a + b * CHANNEL_WAVELENGTHS[channel].powi(-2)
+    }
+
+    /// Pick the normal of the microscopic facet a ray meets, from the GGX
+    /// distribution of facet normals about the outward normal, whose
+    /// slopes have a long tail so even smooth-looking frost scatters some
+    /// light far from the mirror and refraction directions.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit_record` - the hit to find the facet at
+    /// - `incoming_ray` - ray which has just hit this material
+    ///
+    /// # Returns
+    /// - `Vec3` - the unit facet normal, on the same side as the outward
+    ///   normal. The outward normal itself is kept at grazing angles where
+    ///   the ray would meet the facet from its other side, since that would
+    ///   swap which side of the surface is inside.
+    fn facet_normal(&self, hit_record: &HitRecord, incoming_ray: &Ray) -> Vec3 {
+        let outward = glm::normalize(&hit_record.outward_normal);
+        let alpha = self.roughness;
+        // tan^2 of the angle from the normal, by inverting the GGX
+        // distribution's cumulative density
+        let u = rng().gen::<f32>();
+        let tan_squared = alpha * alpha * u / (1.0 - u);
+        let cos_theta = 1.0 / (1.0 + tan_squared).sqrt();
+        let facet = random::cone_direction(&outward, 1.0 - cos_theta, 1.0, rng().gen());
+        let side = |normal: &Vec3| glm::dot(normal, &incoming_ray.direction) < 0.0;
+        if side(&facet) == side(&outward) {
+            facet
+        } else {
+            outward
+        }
     }
-}
 
-/// Methods specific to transparent materials
-impl Transparent {
     /// Test for total internal reflection.
     fn total_internal_reflection(
         hit_record: &HitRecord,
@@ -112,4 +474,233 @@ impl Transparent {
 
         1.0 - (1.0 - cos_theta_i * cos_theta_i) / (eta * eta) < 0.0
     }
+
+    /// Whether a path has bounced enough times to trace only one of the
+    /// reflection and refraction, see `branch_depth`.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `depth: u32` - recursion depth left, which starts at `DEPTH_LIMIT`
+    ///   and drops by one with each bounce
+    ///
+    /// # Returns
+    /// - `bool` - true if only one branch should be traced
+    fn past_branch_depth(&self, depth: u32) -> bool {
+        self.branch_depth
+            .is_some_and(|branch_depth| DEPTH_LIMIT.saturating_sub(depth) >= branch_depth)
+    }
+
+    /// Whether a hit's refraction carries more light than its reflection.
+    /// Both are scaled by the albedo, with `reflectance` for the reflection
+    /// and `transmittance` over the square of the relative refractive index
+    /// for the refraction, so only those factors are compared.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit_record` - the hit
+    /// - `incoming_ray` - ray which has just hit this material
+    ///
+    /// # Returns
+    /// - `bool` - true if the refraction carries more light
+    fn refraction_dominates(&self, hit_record: &HitRecord, incoming_ray: &Ray) -> bool {
+        let eta = if glm::dot(&hit_record.outward_normal, &-incoming_ray.direction) < 0.0 {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+        self.transmittance / (eta * eta) > self.reflectance
+    }
+
+    /// Replace a color which isn't finite, counting the replacement.
+    ///
+    /// # Arguments
+    /// - `color: Vec3` - the shaded color
+    /// - `fallback: Vec3` - color to use instead, or black if it isn't
+    ///   finite either
+    ///
+    /// # Returns
+    /// - `Vec3` - a finite color
+    fn finite_or(color: Vec3, fallback: Vec3) -> Vec3 {
+        if color.iter().all(|channel| channel.is_finite()) {
+            return color;
+        }
+        NON_FINITE_COLORS.fetch_add(1, Ordering::Relaxed);
+        if fallback.iter().all(|channel| channel.is_finite()) {
+            fallback
+        } else {
+            color::color_linear(0, 0, 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::MaterialType;
+
+    /// Smooth glass which traces both branches at every hit.
+    fn glass(refractive_index: f32) -> Transparent {
+        Transparent {
+            albedo: glm::vec3(1.0, 1.0, 1.0),
+            reflectance: 0.1,
+            transmittance: 0.9,
+            refractive_index,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
+        }
+    }
+
+    #[test]
+    fn finite_or_replaces_non_finite_colors() {
+        let finite = glm::vec3(0.25, 0.5, 1.0);
+        let nan = glm::vec3(f32::NAN, 0.0, 0.0);
+        let infinite = glm::vec3(0.0, f32::INFINITY, 0.0);
+        assert_eq!(Transparent::finite_or(finite, nan), finite);
+        let before = non_finite_count();
+        assert_eq!(Transparent::finite_or(nan, finite), finite);
+        assert_eq!(
+            Transparent::finite_or(infinite, nan),
+            glm::vec3(0.0, 0.0, 0.0)
+        );
+        assert!(non_finite_count() >= before + 2);
+    }
+
+    #[test]
+    fn grazing_exit_shades_a_finite_color() {
+        // a ray running exactly along the surface, so the cosines the
+        // weights divide by are zero
+        let material = MaterialType::Transparent(glass(1.0));
+        let ray = Ray::new(glm::vec3(-1.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), None);
+        let hit = HitRecord::new(
+            &ray,
+            1.0,
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            Some(&material),
+        );
+        let world = HittableList::new();
+        let sky = Sky::uniform(glm::vec3(1.0, 1.0, 1.0));
+        let color = glass(1.0).shade(&world, &[], &sky, &ShadingContext::default(), &ray, &hit, 4);
+        assert!(
+            color.iter().all(|channel| channel.is_finite()),
+            "{:?}",
+            color
+        );
+    }
+
+    #[test]
+    fn unbiased_reflect_probability_is_the_fresnel_reflectance() {
+        let glass = glass(1.5);
+        let opaque_mirror = Transparent {
+            reflectance: 1.0,
+            transmittance: 0.0,
+            ..glass
+        };
+        for step in 0..=20 {
+            let fresnel = Transparent::schlick(1.5, step as f32 / 20.0);
+            assert_eq!(glass.reflect_probability(fresnel, 0.0), fresnel);
+            // a full bias follows the share of reflectance, but a bias can't
+            // take the chance within 5% of never or always reflecting
+            if fresnel < 1.0 {
+                assert!((glass.reflect_probability(fresnel, 1.0) - 0.1).abs() < 1e-6);
+            }
+            assert_eq!(
+                opaque_mirror.reflect_probability(fresnel, 1.0),
+                fresnel.max(1.0 - MIN_BRANCH_PROBABILITY)
+            );
+        }
+        // total internal reflection and no reflection at all are kept
+        assert_eq!(glass.reflect_probability(1.0, 1.0), 1.0);
+        assert_eq!(glass.reflect_probability(0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn internal_reflection_adds_its_share_of_light() {
+        let material = MaterialType::Transparent(glass(1.5));
+        let mut world = HittableList::new();
+        world.add_sphere(glm::vec3(0.0, 0.0, 0.0), 1.0, material);
+        let sky = Sky::uniform(glm::vec3(1.0, 1.0, 1.0));
+        let context = ShadingContext::default();
+        // from the center, so every hit is head on from inside the glass
+        let trace = |reflections: u32| {
+            let ray = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), None)
+                .with_depth_budgets(reflections, 1);
+            trace_ray(&ray, &world, &[], &sky, &context, DEPTH_LIMIT)
+        };
+        // the reflection crosses back to the far side and leaves there, so
+        // it adds the light of the way straight out, scaled by reflectance
+        let straight_out = trace(0);
+        let with_reflection = trace(1);
+        assert!(straight_out.x > 0.0);
+        assert!(
+            glm::distance(&with_reflection, &(1.1 * straight_out)) < 1e-5,
+            "{:?} with a reflection, {:?} without",
+            with_reflection,
+            straight_out
+        );
+    }
+
+    /// Paths which have escaped into `counting_sky` so far
+    static ESCAPED_PATHS: AtomicU64 = AtomicU64::new(0);
+
+    /// A sky which brightens upward and counts the paths seeing it.
+    fn counting_sky(ray: &Ray) -> Vec3 {
+        ESCAPED_PATHS.fetch_add(1, Ordering::Relaxed);
+        let up = 0.5 * (ray.direction.y + 1.0);
+        glm::vec3(0.2 + 0.8 * up, 0.3 + 0.5 * up, 0.9)
+    }
+
+    #[test]
+    fn branch_depth_cuts_the_rays_traced_through_nested_glass() {
+        let sky = Sky::Custom(counting_sky);
+        // Russian roulette would end the dim branches itself
+        let context = ShadingContext {
+            russian_roulette_bounces: u32::MAX,
+            ..ShadingContext::default()
+        };
+        // average color seen through three nested glass spheres, and the
+        // number of paths traced for it
+        let trace = |branch_depth: Option<u32>| {
+            let material = MaterialType::Transparent(Transparent {
+                branch_depth,
+                ..glass(1.5)
+            });
+            let mut world = HittableList::new();
+            for radius in [1.0, 0.7, 0.4] {
+                world.add_sphere(glm::vec3(0.0, 0.0, 0.0), radius, material);
+            }
+            ESCAPED_PATHS.store(0, Ordering::Relaxed);
+            let mut total = glm::vec3(0.0, 0.0, 0.0);
+            for i in 0..5 {
+                for j in 0..4 {
+                    let target = glm::vec3(i as f32 * 0.2 - 0.4, j as f32 * 0.2 - 0.3, 0.0);
+                    let origin = glm::vec3(0.0, 0.0, 5.0);
+                    let ray = Ray::new(origin, target - origin, None).with_depth_budgets(6, 6);
+                    total += trace_ray(&ray, &world, &[], &sky, &context, DEPTH_LIMIT);
+                }
+            }
+            (total / 20.0, ESCAPED_PATHS.load(Ordering::Relaxed))
+        };
+        let (exact, exact_paths) = trace(None);
+        let (bounded, bounded_paths) = trace(Some(5));
+        assert!(
+            bounded_paths * 5 < exact_paths,
+            "{} paths bounded, {} exact",
+            bounded_paths,
+            exact_paths
+        );
+        // the branches left out carry little of the light, and since every
+        // branch adds light, leaving some out can only darken the image
+        for channel in 0..3 {
+            assert!(
+                bounded[channel] <= exact[channel] && bounded[channel] > 0.97 * exact[channel],
+                "{:?} bounded, {:?} exact",
+                bounded,
+                exact
+            );
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use crate::texture::TriplanarTexture;
+use glm::Vec3;
+
+/// Represent a diffuse material whose albedo is an image projected onto the
+/// surface along the three axes, see `TriplanarTexture`. Unlike `Textured`,
+/// it needs no texture coordinates, so it works on any mesh.
+#[derive(Clone, Copy)]
+pub struct Triplanar {
+    /// Projected image providing the albedo of the surface
+    pub texture: TriplanarTexture,
+}
+
+/// Methods for the material trait
+impl Material for Triplanar {
+    /// Shade the hit as a Lambertian surface with the projected texture's
+    /// color at the hit point.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let albedo = self.color_at(hit_record);
+        Lambertian {
+            albedo,
+            force_front_face: false,
+        }
+        .shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+    }
+
+    /// Retrieve the base color of the material. A texture has no single
+    /// color, so this is the color at the origin of an upward facing
+    /// surface.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.texture
+            .color(&glm::vec3(0.0, 0.0, 0.0), &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    /// Sample the projections at the hit point, blended by the outward
+    /// normal so both sides of a surface show the same texture.
+    fn color_at(&self, hit_record: &HitRecord) -> Vec3 {
+        self.texture
+            .color(&hit_record.hit_point, &hit_record.outward_normal)
+    }
+}
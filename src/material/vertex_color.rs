@@ -0,0 +1,69 @@
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+
+/// Represent a diffuse material colored by the vertex colors of the mesh it
+/// is on, blended smoothly across each triangle. Surfaces without vertex
+/// colors are filled with the base color.
+#[derive(Clone, Copy)]
+pub struct VertexColor {
+    /// Color of surfaces which have no vertex colors
+    pub albedo: Vec3,
+}
+
+/// Methods for the material trait
+impl Material for VertexColor {
+    /// Shade the hit as a Lambertian surface with the interpolated vertex
+    /// color.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let albedo = self.color_at(hit_record);
+        Lambertian {
+            albedo,
+            force_front_face: false,
+        }
+        .shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+
+    /// Weigh the triangle's vertex colors by the barycentric coordinates of
+    /// the hit.
+    fn color_at(&self, hit_record: &HitRecord) -> Vec3 {
+        match (hit_record.vertex_colors, hit_record.barycentric_coordinates) {
+            (Some([c0, c1, c2]), Some(weights)) => weights.x * c0 + weights.y * c1 + weights.z * c2,
+            _ => self.albedo,
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::super::trace_ray;
+use crate::color;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+
+/// Directions in radians, on the surface's tangent plane, of the sine waves
+/// summed into the ripples, spread out so they don't line up into stripes
+const WAVE_DIRECTIONS: [f32; 3] = [0.3, 2.2, 4.1];
+/// Wavelength of each wave relative to the material's ripple wavelength
+const WAVE_LENGTHS: [f32; 3] = [1.0, 0.67, 0.43];
+/// Height of each wave relative to the material's ripple height
+const WAVE_HEIGHTS: [f32; 3] = [1.0, 0.55, 0.3];
+/// Phase of each wave in radians, so the crests don't all meet at the origin
+const WAVE_PHASES: [f32; 3] = [0.0, 1.7, 4.4];
+
+/// Represent a water surface, which reflects and refracts like a dielectric
+/// with the share of each set by Fresnel's equations: mostly see-through
+/// when looked into from above and mirror-like toward grazing angles. The
+/// surface is rippled by tilting its normal with a few sine waves over the
+/// hit point, so flat geometry shimmers without any extra triangles.
+#[derive(Clone, Copy)]
+pub struct Water {
+    /// Tint of the light refracted through the surface
+    pub albedo: Vec3,
+    /// Refractive index of the water, about 1.33
+    pub refractive_index: f32,
+    /// Height of the largest ripple, 0 for a still surface
+    pub ripple_height: f32,
+    /// Distance between the crests of the largest ripple
+    pub ripple_wavelength: f32,
+}
+
+/// Methods for the material trait
+impl Material for Water {
+    /// Shade a hit to this material, tracing both the reflection and the
+    /// refraction off the rippled surface and weighting them by Schlick's
+    /// approximation of the Fresnel reflectance.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let outward = glm::normalize(&hit_record.outward_normal);
+        let entering = glm::dot(&outward, &incoming_ray.direction) < 0.0;
+        // the normal on the side the ray comes from, and the ratio of the
+        // refractive indices it crosses from and into
+        let (normal, eta) = if entering {
+            (
+                self.ripple_normal(&outward, &hit_record.hit_point),
+                1.0 / self.refractive_index,
+            )
+        } else {
+            (
+                -self.ripple_normal(&outward, &hit_record.hit_point),
+                self.refractive_index,
+            )
+        };
+        let mut cos_i = -glm::dot(&normal, &incoming_ray.direction);
+        // a steep ripple seen at a grazing angle can face away from the ray,
+        // so the flat surface's normal is used there instead
+        let normal = if cos_i > 0.0 {
+            normal
+        } else {
+            let flat = if entering { outward } else { -outward };
+            cos_i = -glm::dot(&flat, &incoming_ray.direction);
+            flat
+        };
+
+        let sin_squared_t = eta * eta * (1.0 - cos_i * cos_i);
+        let total_internal_reflection = sin_squared_t > 1.0;
+        let reflectance = if total_internal_reflection {
+            1.0
+        } else {
+            // Schlick's approximation uses the angle on the side of the
+            // lower index, which is the refracted angle when leaving water
+            let cos_t = (1.0 - sin_squared_t).sqrt();
+            let cosine = if entering { cos_i } else { cos_t };
+            let r0 = ((self.refractive_index - 1.0) / (self.refractive_index + 1.0)).powi(2);
+            r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+        };
+
+        let mut color = color::color_linear(0, 0, 0);
+        if incoming_ray.reflection_depth > 0 {
+            let reflected_direction = glm::reflect_vec(&incoming_ray.direction, &normal);
+            let weight = glm::vec3(reflectance, reflectance, reflectance);
+            let reflected_ray = incoming_ray
+                .continue_path(hit_record.hit_point, reflected_direction, Some(weight))
+                .with_depth_budgets(
+                    incoming_ray.reflection_depth - 1,
+                    incoming_ray.refraction_depth,
+                )
+                .after_specular()
+                .escape_stall();
+            color += trace_ray(&reflected_ray, world, lights, sky, context, depth - 1);
+        }
+        if !total_internal_reflection && incoming_ray.refraction_depth > 0 {
+            let cos_t = (1.0 - sin_squared_t).sqrt();
+            let transmitted_direction =
+                eta * incoming_ray.direction + (eta * cos_i - cos_t) * normal;
+            let weight = self.albedo * (1.0 - reflectance);
+            let transmitted_ray = incoming_ray
+                .continue_path(hit_record.hit_point, transmitted_direction, Some(weight))
+                .with_depth_budgets(
+                    incoming_ray.reflection_depth,
+                    incoming_ray.refraction_depth - 1,
+                )
+                .after_specular()
+                .escape_stall();
+            color += trace_ray(&transmitted_ray, world, lights, sky, context, depth - 1);
+        }
+        color
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Methods specific to water
+impl Water {
+    /// Tilt a surface normal by the slope of the ripples at a point. The
+    /// ripples are a sum of sine waves running across the tangent plane,
+    /// and the normal leans against the gradient of their height.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `normal: &Vec3` - unit normal of the flat surface
+    /// - `point: &Vec3` - point on the surface
+    ///
+    /// # Returns
+    /// - `Vec3` - the unit normal of the rippled surface, on the same side
+    fn ripple_normal(&self, normal: &Vec3, point: &Vec3) -> Vec3 {
+        if self.ripple_height == 0.0 {
+            return *normal;
+        }
+        let guess = if normal.x.abs() < 0.9 {
+            glm::vec3(1.0, 0.0, 0.0)
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        };
+        let tangent = glm::normalize(&glm::cross(&guess, normal));
+        let bitangent = glm::cross(normal, &tangent);
+        let (s, t) = (glm::dot(point, &tangent), glm::dot(point, &bitangent));
+
+        // d/dx of h sin(k x + phase) is h k cos(k x + phase)
+        let mut slope = (0.0, 0.0);
+        for wave in 0..WAVE_DIRECTIONS.len() {
+            let (sin, cos) = WAVE_DIRECTIONS[wave].sin_cos();
+            let k = 2.0 * std::f32::consts::PI / (self.ripple_wavelength * WAVE_LENGTHS[wave]);
+            let height = self.ripple_height * WAVE_HEIGHTS[wave];
+            let along = k * (cos * s + sin * t) + WAVE_PHASES[wave];
+            let rate = height * k * along.cos();
+            slope.0 += rate * cos;
+            slope.1 += rate * sin;
+        }
+        glm::normalize(&(normal - slope.0 * tangent - slope.1 * bitangent))
+    }
+}
@@ -0,0 +1,73 @@
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::material::lambertian::Lambertian;
+use crate::material::Material;
+use crate::material::ShadingContext;
+use crate::ray::Ray;
+use crate::scenes::Sky;
+use glm::Vec3;
+
+/// Represent a diffuse material which draws the edges of triangles in a
+/// second color, for showing the structure of a mesh. Surfaces which aren't
+/// triangles are filled with the base color.
+#[derive(Clone, Copy)]
+pub struct Wireframe {
+    /// Color of the inside of each triangle
+    pub albedo: Vec3,
+    /// Color of the edges
+    pub edge_color: Vec3,
+    /// Width of the edges, as the barycentric coordinate below which a point
+    /// counts as on an edge
+    pub edge_width: f32,
+}
+
+/// Methods for the material trait
+impl Material for Wireframe {
+    /// Shade the hit as a Lambertian surface colored by whether the hit is
+    /// near an edge of its triangle.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `incoming_ray` - ray which has just hit this material
+    /// - `hit_record` - specification of the hit which just ocurred
+    ///
+    /// # Returns
+    /// - `Vec3` - the color seen along the incoming ray
+    fn shade<T: Hittable>(
+        &self,
+        world: &T,
+        lights: &[Light],
+        sky: &Sky,
+        context: &ShadingContext,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        depth: u32,
+    ) -> Vec3 {
+        let albedo = self.color_at(hit_record);
+        Lambertian {
+            albedo,
+            force_front_face: false,
+        }
+        .shade(world, lights, sky, context, incoming_ray, hit_record, depth)
+    }
+
+    /// Retrieve the base color of the material.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color
+    fn color(&self) -> Vec3 {
+        self.albedo
+    }
+
+    /// A hit is on an edge when any of its barycentric coordinates is small.
+    fn color_at(&self, hit_record: &HitRecord) -> Vec3 {
+        match hit_record.barycentric_coordinates {
+            Some(weights) if glm::comp_min(&weights) < self.edge_width => self.edge_color,
+            _ => self.albedo,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use glm::Vec3;
+
+/// Parameters for the edge-aware denoiser.
+pub struct DenoiseParameters {
+    /// Half-width of the square filter window in pixels
+    pub radius: usize,
+    /// Standard deviation of the spatial falloff in pixels
+    pub spatial_sigma: f32,
+    /// Standard deviation of the color difference between pixels, or `None`
+    /// to leave color out. Noise itself is a large color difference, so it
+    /// is left out by default and the feature buffers alone find the edges.
+    pub color_sigma: Option<f32>,
+    /// Standard deviation of the albedo difference between pixels
+    pub albedo_sigma: f32,
+    /// Standard deviation of the normal difference between pixels
+    pub normal_sigma: f32,
+}
+
+impl Default for DenoiseParameters {
+    fn default() -> DenoiseParameters {
+        DenoiseParameters {
+            radius: 3,
+            spatial_sigma: 2.0,
+            color_sigma: None,
+            albedo_sigma: 0.1,
+            normal_sigma: 0.2,
+        }
+    }
+}
+
+/// Denoise a linear color buffer with a joint bilateral filter. Each pixel
+/// becomes a weighted average of its neighbors, where the weights fall off
+/// with distance in the image and with differences in color, albedo, and
+/// normal. Neighbors across an albedo or geometry edge get almost no
+/// weight, so those edges stay sharp while flat regions are smoothed.
+///
+/// All buffers are row-major with `width * height` entries, and the color
+/// should be averaged (not summed) over samples and not yet tone mapped.
+///
+/// # Arguments
+/// - `color_buffer: &[Vec3]` - the noisy linear color
+/// - `albedo_buffer: &[Vec3]` - albedo feature buffer
+/// - `normal_buffer: &[Vec3]` - normal feature buffer
+/// - `width: usize` - width of the image in pixels
+/// - `height: usize` - height of the image in pixels
+/// - `parameters: &DenoiseParameters` - filter settings
+///
+/// # Returns
+/// - `Vec<Vec3>` - the filtered color buffer
+pub fn denoise(
+    color_buffer: &[Vec3],
+    albedo_buffer: &[Vec3],
+    normal_buffer: &[Vec3],
+    width: usize,
+    height: usize,
+    parameters: &DenoiseParameters,
+) -> Vec<Vec3> {
+    let radius = parameters.radius as isize;
+    let spatial = -0.5 / parameters.spatial_sigma.powi(2);
+    // leaving the color term out, rather than giving it an infinite sigma,
+    // keeps a pixel with an infinite color from making 0 * inf = NaN
+    let color = parameters.color_sigma.map(|sigma| -0.5 / sigma.powi(2));
+    let albedo = -0.5 / parameters.albedo_sigma.powi(2);
+    let normal = -0.5 / parameters.normal_sigma.powi(2);
+
+    let mut filtered = Vec::with_capacity(color_buffer.len());
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let center = (y as usize) * width + x as usize;
+            let mut total = glm::vec3(0.0, 0.0, 0.0);
+            let mut total_weight = 0.0;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                        continue;
+                    }
+                    let neighbor = (ny as usize) * width + nx as usize;
+                    let mut exponent = spatial * (dx * dx + dy * dy) as f32
+                        + albedo * glm::distance2(&albedo_buffer[center], &albedo_buffer[neighbor])
+                        + normal * glm::distance2(&normal_buffer[center], &normal_buffer[neighbor]);
+                    if let Some(color) = color {
+                        exponent +=
+                            color * glm::distance2(&color_buffer[center], &color_buffer[neighbor]);
+                    }
+                    let weight = exponent.exp();
+                    total += weight * color_buffer[neighbor];
+                    total_weight += weight;
+                }
+            }
+            // the center pixel always has weight 1, so this never divides by zero
+            filtered.push(total / total_weight);
+        }
+    }
+    filtered
+}
+
+/// Scale a linear color buffer in place by an exposure and a white balance,
+/// before it is tone mapped.
+///
+/// # Arguments
+/// - `color_buffer: &mut [Vec3]` - the linear color
+/// - `exposure: f32` - stops to brighten by, so each stop doubles the color
+///   and zero leaves it alone
+/// - `white_balance: Vec3` - gain for each channel, (1, 1, 1) to leave it
+///   alone
+pub fn expose(color_buffer: &mut [Vec3], exposure: f32, white_balance: Vec3) {
+    let gain = exposure.exp2() * white_balance;
+    for color in color_buffer.iter_mut() {
+        *color = glm::matrix_comp_mult(color, &gain);
+    }
+}
+
+/// Mirror a row-major buffer top to bottom in place, so its first row
+/// becomes its last.
+///
+/// # Arguments
+/// - `buffer: &mut [T]` - the buffer, a whole number of rows long
+/// - `width: usize` - number of entries in each row
+pub fn flip_rows<T>(buffer: &mut [T], width: usize) {
+    let height = buffer.len() / width.max(1);
+    for y in 0..height / 2 {
+        let (top, bottom) = buffer.split_at_mut((height - 1 - y) * width);
+        top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denoise_keeps_infinite_pixels_from_spreading_nan() {
+        let (width, height) = (9, 9);
+        let mut color = vec![glm::vec3(0.5, 0.5, 0.5); width * height];
+        color[4 * width + 4] = glm::vec3(f32::INFINITY, 0.5, 0.5);
+        let albedo = vec![glm::vec3(0.8, 0.8, 0.8); width * height];
+        let normal = vec![glm::vec3(0.0, 1.0, 0.0); width * height];
+        let filtered = denoise(
+            &color,
+            &albedo,
+            &normal,
+            width,
+            height,
+            &DenoiseParameters::default(),
+        );
+        assert!(filtered
+            .iter()
+            .all(|pixel| pixel.iter().all(|channel| !channel.is_nan())));
+        // pixels out of the window's reach are untouched
+        assert_eq!(filtered[0], glm::vec3(0.5, 0.5, 0.5));
+        assert_eq!(filtered[4 * width + 4].y, 0.5);
+    }
+
+    #[test]
+    fn denoise_color_sigma_keeps_color_edges() {
+        // a black half and a white half, with the same albedo and normals
+        let (width, height) = (8, 4);
+        let color: Vec<Vec3> = (0..width * height)
+            .map(|index| {
+                if index % width < width / 2 {
+                    glm::vec3(0.0, 0.0, 0.0)
+                } else {
+                    glm::vec3(1.0, 1.0, 1.0)
+                }
+            })
+            .collect();
+        let albedo = vec![glm::vec3(0.8, 0.8, 0.8); width * height];
+        let normal = vec![glm::vec3(0.0, 1.0, 0.0); width * height];
+        let filter = |color_sigma| {
+            let parameters = DenoiseParameters {
+                color_sigma,
+                ..DenoiseParameters::default()
+            };
+            denoise(&color, &albedo, &normal, width, height, &parameters)
+        };
+        let edge = width / 2 - 1;
+        assert!(filter(None)[edge].x > 0.1);
+        assert!(filter(Some(0.1))[edge].x < 1.0e-6);
+    }
+
+    #[test]
+    fn expose_doubles_per_stop_and_neutral_balance_is_a_no_op() {
+        let original = vec![glm::vec3(0.1, 0.25, 0.5), glm::vec3(2.0, 0.0, 1.0)];
+        let mut brighter = original.clone();
+        expose(&mut brighter, 1.0, glm::vec3(1.0, 1.0, 1.0));
+        for (bright, color) in brighter.iter().zip(original.iter()) {
+            assert_eq!(*bright, color * 2.0);
+        }
+        let mut neutral = original.clone();
+        expose(&mut neutral, 0.0, glm::vec3(1.0, 1.0, 1.0));
+        assert_eq!(neutral, original);
+        // the balance scales each channel on its own
+        let mut warm = original.clone();
+        expose(&mut warm, 0.0, glm::vec3(2.0, 1.0, 0.5));
+        assert_eq!(warm[0], glm::vec3(0.2, 0.25, 0.25));
+    }
+
+    #[test]
+    fn flip_rows_reverses_the_rows_of_even_and_odd_heights() {
+        let width = 3;
+        for height in [1, 4, 5] {
+            let mut buffer: Vec<usize> = (0..width * height).collect();
+            flip_rows(&mut buffer, width);
+            for y in 0..height {
+                let row: Vec<usize> = ((height - 1 - y) * width..(height - y) * width).collect();
+                assert_eq!(
+                    buffer[y * width..(y + 1) * width],
+                    row[..],
+                    "height {}",
+                    height
+                );
+            }
+            // flipping twice gives back the buffer
+            flip_rows(&mut buffer, width);
+            assert_eq!(buffer, (0..width * height).collect::<Vec<usize>>());
+        }
+    }
+}
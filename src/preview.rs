@@ -0,0 +1,90 @@
+use crate::color;
+use glm::Vec3;
+use image::RgbImage;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Image of the pixels finished so far, written to disk every so often
+/// while a render runs so its composition can be judged early. Pixels which
+/// haven't been traced yet are black.
+pub struct Preview {
+    /// File name the preview is written to
+    filename: String,
+    /// Time to wait between writes
+    interval: Duration,
+    /// Finished pixels, already gamma adjusted and clamped
+    image: Mutex<RgbImage>,
+    /// When the preview was last written, or the render started
+    last_write: Mutex<Instant>,
+    /// Whether rows are written bottom first, to match a flipped image
+    flip_y: bool,
+}
+
+impl Preview {
+    /// Create an empty preview.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - file name to write the preview to
+    /// - `image_width: u32` - width of the image in pixels
+    /// - `image_height: u32` - height of the image in pixels
+    /// - `interval: f32` - seconds to wait between writes
+    ///
+    /// # Returns
+    /// - `Preview` - the preview, with every pixel black
+    pub fn new(filename: &str, image_width: u32, image_height: u32, interval: f32) -> Preview {
+        Preview {
+            filename: String::from(filename),
+            interval: Duration::from_secs_f32(interval),
+            image: Mutex::new(RgbImage::new(image_width, image_height)),
+            last_write: Mutex::new(Instant::now()),
+            flip_y: false,
+        }
+    }
+
+    /// Write the preview's rows bottom first, like the finished image with
+    /// `--flip-y`.
+    ///
+    /// # Arguments
+    /// - `flip_y: bool` - whether to mirror the preview top to bottom
+    ///
+    /// # Returns
+    /// - `Preview` - the preview
+    pub fn with_flip_y(mut self, flip_y: bool) -> Preview {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Record a finished pixel, and write the preview if it is due. Safe to
+    /// call from any thread; if another thread is already writing the
+    /// preview, this one carries on rendering.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `x: u32` - column of the pixel
+    /// - `y: u32` - row of the pixel
+    /// - `pixel_color: &Vec3` - linear color of the pixel
+    /// - `sample_count: usize` - number of samples accumulated into the color
+    pub fn update(&self, x: u32, y: u32, pixel_color: &Vec3, sample_count: usize) {
+        let rgb = color::vec3_to_rgb(pixel_color, sample_count);
+        {
+            let mut image = self.image.lock().unwrap();
+            let row = if self.flip_y {
+                image.height() - 1 - y
+            } else {
+                y
+            };
+            image.put_pixel(x, row, rgb);
+        }
+
+        if let Ok(mut last_write) = self.last_write.try_lock() {
+            if last_write.elapsed() >= self.interval {
+                let snapshot = self.image.lock().unwrap().clone();
+                if let Err(error) = snapshot.save(&self.filename) {
+                    eprintln!("warning: couldn't write preview: {}", error);
+                }
+                *last_write = Instant::now();
+            }
+        }
+    }
+}
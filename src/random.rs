@@ -0,0 +1,287 @@
+use glm::Vec3;
+use rand::RngCore;
+use std::cell::Cell;
+
+thread_local! {
+    /// State of the generator on this thread
+    static STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Handle to this thread's random number generator. The generator is
+/// reseeded from the pixel coordinates before each pixel is traced, so every
+/// pixel sees the same random numbers no matter which thread traces it or in
+/// what order, and renders are reproducible for a given seed.
+pub struct PixelRng;
+
+/// Get a handle to this thread's random number generator.
+pub fn rng() -> PixelRng {
+    PixelRng
+}
+
+/// Reseed this thread's generator for the pixel about to be traced.
+///
+/// # Arguments
+/// - `x: u32` - horizontal pixel coordinate
+/// - `y: u32` - vertical pixel coordinate
+/// - `seed: u64` - seed of the whole render
+pub fn seed_pixel(x: u32, y: u32, seed: u64) {
+    let pixel = ((x as u64) << 32) | y as u64;
+    STATE.with(|state| state.set(mix(mix(seed) ^ pixel)));
+}
+
+/// Reseed this thread's generator for one pass of a progressive render
+/// over the pixel, so each pass sees different random numbers but the
+/// same passes give the same image.
+///
+/// # Arguments
+/// - `x: u32` - horizontal pixel coordinate
+/// - `y: u32` - vertical pixel coordinate
+/// - `seed: u64` - seed of the whole render
+/// - `pass: u64` - number of passes already taken over the image
+pub fn seed_pixel_pass(x: u32, y: u32, seed: u64, pass: u64) {
+    seed_pixel(x, y, mix(seed ^ mix(pass)));
+}
+
+/// Pick a direction uniformly over a cone, which has the same density
+/// `1 / (2 pi (1 - cos theta_max))` in every direction inside it.
+///
+/// # Arguments
+/// - `axis: &Vec3` - unit vector along the middle of the cone
+/// - `one_minus_cos_theta_max: f32` - one minus the cosine of the cone's
+///   half angle, which keeps its precision for narrow cones
+/// - `u: f32` - random number in [0, 1) choosing the angle from the axis
+/// - `v: f32` - random number in [0, 1) choosing the angle about the axis
+///
+/// # Returns
+/// - `Vec3` - unit direction inside the cone
+pub fn cone_direction(axis: &Vec3, one_minus_cos_theta_max: f32, u: f32, v: f32) -> Vec3 {
+    // any two directions perpendicular to the axis and each other
+    let helper = if axis.x.abs() > 0.9 {
+        glm::vec3(0.0, 1.0, 0.0)
+    } else {
+        glm::vec3(1.0, 0.0, 0.0)
+    };
+    let side = glm::normalize(&glm::cross(axis, &helper));
+    let other_side = glm::cross(axis, &side);
+
+    let cos_theta = 1.0 - u * one_minus_cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * v;
+    cos_theta * axis + sin_theta * (phi.cos() * side + phi.sin() * other_side)
+}
+
+/// Pick a scattering direction from the Henyey-Greenstein phase function,
+/// which models how participating media like fog and smoke scatter light.
+/// The asymmetry `g` is the mean cosine between the incoming and scattered
+/// directions: positive values scatter forward, negative values back
+/// toward the light, and zero scatters the same in every direction. See
+/// `henyey_greenstein_pdf` for the density.
+///
+/// # Arguments
+/// - `forward: &Vec3` - unit direction the light was travelling in
+/// - `g: f32` - asymmetry, strictly between -1 and 1
+/// - `u: f32` - random number in [0, 1) choosing the angle from `forward`
+/// - `v: f32` - random number in [0, 1) choosing the angle about `forward`
+///
+/// # Returns
+/// - `Vec3` - unit direction the light is scattered in
+pub fn henyey_greenstein_direction(forward: &Vec3, g: f32, u: f32, v: f32) -> Vec3 {
+    let cos_theta = if g.abs() < 1.0e-3 {
+        // the inverted distribution divides by g, so use the isotropic
+        // one it approaches
+        1.0 - 2.0 * u
+    } else {
+        let ratio = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        ((1.0 + g * g - ratio * ratio) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    // the edge of a cone as wide as the scattering angle
+    cone_direction(forward, 1.0 - cos_theta, 1.0, v)
+}
+
+/// Density with which `henyey_greenstein_direction` picks a direction, per
+/// unit solid angle, which is the value of the phase function itself. It
+/// is `1 / (4 pi)` everywhere for `g = 0`.
+///
+/// # Arguments
+/// - `cos_theta: f32` - cosine between the incoming and scattered directions
+/// - `g: f32` - asymmetry, strictly between -1 and 1
+///
+/// # Returns
+/// - `f32` - the density of the scattered direction
+pub fn henyey_greenstein_pdf(cos_theta: f32, g: f32) -> f32 {
+    (1.0 - g * g) / (4.0 * std::f32::consts::PI * (1.0 + g * g - 2.0 * g * cos_theta).powf(1.5))
+}
+
+/// Pick a direction from a cosine-power lobe about an axis, with density
+/// proportional to the cosine from the axis raised to `exponent`, for
+/// glossy reflections about the mirror direction. Larger exponents draw the
+/// lobe tighter. See `cosine_power_pdf` for the density.
+///
+/// # Arguments
+/// - `axis: &Vec3` - unit direction the lobe is centered on
+/// - `exponent: f32` - power of the cosine, at least 0
+/// - `u: f32` - random number in [0, 1) choosing the angle from `axis`
+/// - `v: f32` - random number in [0, 1) choosing the angle about `axis`
+///
+/// # Returns
+/// - `Vec3` - unit direction within the lobe
+pub fn cosine_power_direction(axis: &Vec3, exponent: f32, u: f32, v: f32) -> Vec3 {
+    // 1 - u keeps the cosine above 0, so the direction never turns away
+    let cos_theta = (1.0 - u).powf(1.0 / (exponent + 1.0));
+    cone_direction(axis, 1.0 - cos_theta, 1.0, v)
+}
+
+/// Density with which `cosine_power_direction` picks a direction, per unit
+/// solid angle, zero outside the hemisphere about the axis.
+///
+/// # Arguments
+/// - `cos_theta: f32` - cosine between the axis and the direction
+/// - `exponent: f32` - power of the cosine, at least 0
+///
+/// # Returns
+/// - `f32` - the density of the direction
+pub fn cosine_power_pdf(cos_theta: f32, exponent: f32) -> f32 {
+    (exponent + 1.0) / (2.0 * std::f32::consts::PI) * cos_theta.max(0.0).powf(exponent)
+}
+
+/// The output function of the splitmix64 generator, which scrambles every
+/// bit of its input into every bit of the result.
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The generator is splitmix64, which is small, fast, and only needs a
+/// single word of state.
+impl RngCore for PixelRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        STATE.with(|state| {
+            let next = state.get().wrapping_add(0x9e3779b97f4a7c15);
+            state.set(next);
+            mix(next)
+        })
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use rayon::prelude::*;
+
+    /// The first few numbers a pixel sees after being seeded.
+    fn pixel_samples(x: u32, y: u32) -> Vec<u64> {
+        seed_pixel(x, y, 7);
+        (0..4).map(|_| rng().gen::<u64>()).collect()
+    }
+
+    #[test]
+    fn pixel_samples_do_not_depend_on_thread_count_or_order() {
+        let pixels: Vec<(u32, u32)> = (0..16).flat_map(|x| (0..16).map(move |y| (x, y))).collect();
+        let in_order: Vec<Vec<u64>> = pixels.iter().map(|&(x, y)| pixel_samples(x, y)).collect();
+        for threads in [1, 2, 5] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            // trace the pixels backward, so each thread's generator carries
+            // state left by other pixels into the next
+            let backward: Vec<Vec<u64>> = pool.install(|| {
+                pixels
+                    .par_iter()
+                    .rev()
+                    .map(|&(x, y)| pixel_samples(x, y))
+                    .collect()
+            });
+            let backward: Vec<Vec<u64>> = backward.into_iter().rev().collect();
+            assert!(backward == in_order, "{} threads", threads);
+        }
+        // different pixels see different numbers
+        assert_ne!(in_order[0], in_order[1]);
+    }
+
+    /// Fraction of scattering cosines from `henyey_greenstein_direction`
+    /// in each of ten equal bins from -1 to 1, and their mean.
+    fn scattering_cosines(g: f32, samples: usize) -> ([f32; 10], f32) {
+        let forward = glm::normalize(&glm::vec3(1.0, 2.0, -0.5));
+        let mut bins = [0.0; 10];
+        let mut total = 0.0;
+        for _ in 0..samples {
+            let direction = henyey_greenstein_direction(&forward, g, rng().gen(), rng().gen());
+            assert!((glm::length(&direction) - 1.0).abs() < 1.0e-4);
+            let cosine = glm::dot(&forward, &direction);
+            bins[(((cosine + 1.0) * 5.0) as usize).min(9)] += 1.0 / samples as f32;
+            total += cosine;
+        }
+        (bins, total / samples as f32)
+    }
+
+    #[test]
+    fn isotropic_scattering_matches_uniform_sphere_sampling() {
+        // cosines of uniform directions on the sphere are uniform in
+        // [-1, 1], so each bin holds a tenth of them
+        let (bins, mean) = scattering_cosines(0.0, 200_000);
+        for (bin, &fraction) in bins.iter().enumerate() {
+            assert!(
+                (fraction - 0.1).abs() < 0.004,
+                "bin {} holds {}",
+                bin,
+                fraction
+            );
+        }
+        assert!(mean.abs() < 0.01, "mean cosine {}", mean);
+        // and the density is the uniform one
+        assert!((henyey_greenstein_pdf(0.3, 0.0) - 0.25 / std::f32::consts::PI).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn scattering_follows_the_phase_function() {
+        for g in [0.6, -0.4] {
+            let (bins, mean) = scattering_cosines(g, 200_000);
+            // the mean cosine of the phase function is g
+            assert!((mean - g).abs() < 0.01, "g {} mean cosine {}", g, mean);
+            for (bin, &fraction) in bins.iter().enumerate() {
+                // integrate the density over the bin's band of directions
+                let steps = 100;
+                let expected: f32 = (0..steps)
+                    .map(|step| {
+                        let cosine = -1.0 + 0.2 * (bin as f32 + (step as f32 + 0.5) / steps as f32);
+                        henyey_greenstein_pdf(cosine, g) * 2.0 * std::f32::consts::PI * 0.2
+                            / steps as f32
+                    })
+                    .sum();
+                assert!(
+                    (fraction - expected).abs() < 0.005,
+                    "g {} bin {} holds {}, expected {}",
+                    g,
+                    bin,
+                    fraction,
+                    expected
+                );
+            }
+        }
+        // positive asymmetry scatters forward, into the last bins
+        let (forward_bins, _) = scattering_cosines(0.6, 20_000);
+        let (backward_bins, _) = scattering_cosines(-0.4, 20_000);
+        assert!(forward_bins[9] > 0.3 && forward_bins[0] < 0.02);
+        assert!(backward_bins[0] > backward_bins[9]);
+    }
+}
@@ -1,5 +1,17 @@
 use glm::Vec3;
 
+/// Paths which move less than this between hits may be caught between
+/// coincident surfaces
+const STALL_DISTANCE: f32 = 0.0001;
+/// Number of hits in a row, each within `STALL_DISTANCE` of the last, after
+/// which a path through transparent surfaces is nudged forward
+const MAX_STALLED_HITS: u32 = 3;
+/// Distance a stalled path is moved along its new direction
+const STALL_NUDGE: f32 = 0.001;
+
+/// Number of rays traced together by packet traversals, see `RayPacket`
+pub const PACKET_WIDTH: usize = 4;
+
 /// Represent a ray with an origin and direction.
 #[derive(Clone, Copy)]
 pub struct Ray {
@@ -7,8 +19,43 @@ pub struct Ray {
     pub origin: Vec3,
     /// direction along with the ray point, normalized
     pub direction: Vec3,
-    /// color carried along this ray
-    pub attenuation: Option<Vec3>,
+    /// Throughput of the path up to this ray: the product of the weights
+    /// of every bounce before it, (1, 1, 1) for a new path. Light found
+    /// along this ray, emitted by what it hits or by the sky it escapes
+    /// to, reaches the pixel scaled by this, see `weigh`. Materials hand
+    /// each bounce's weight to `continue_path` and return what they trace
+    /// along the new ray as it is, so every weight is applied once, where
+    /// the light is found, and a loop following the path one ray at a time
+    /// would add up the same light.
+    pub attenuation: Vec3,
+    /// Componentwise reciprocal of the direction, cached for slab tests
+    pub inverse_direction: Vec3,
+    /// Whether each component of the direction is negative
+    pub direction_is_negative: [bool; 3],
+    /// Number of specular reflections still allowed along this ray's path
+    pub reflection_depth: u32,
+    /// Number of refractions still allowed along this ray's path
+    pub refraction_depth: u32,
+    /// Solid angle density with which a diffuse bounce chose this ray's
+    /// direction, used to weigh emission it hits against light sampling.
+    /// `None` for camera rays and specular bounces.
+    pub scatter_pdf: Option<f32>,
+    /// Number of hits in a row along this ray's path which were each within
+    /// `STALL_DISTANCE` of the one before
+    pub stalled_hits: u32,
+    /// Color channel this path carries alone after a dispersive material
+    /// split it by wavelength, or `None` while it carries all three
+    pub channel: Option<usize>,
+    /// Moment in the camera's frame interval, from 0 to 1, at which this
+    /// ray's path sees the scene. Moving objects are placed where they are
+    /// at this time, see `crate::camera::Shutter`.
+    pub time: f32,
+    /// Whether this ray's path has been through a specular reflection or
+    /// refraction, so a diffuse surface it reaches is seen in a mirror or
+    /// through glass. Those surfaces are sharp but wrongly placed compared
+    /// to the geometry, which denoisers need to know, see
+    /// `crate::aov::Features::caustic`.
+    pub caustic: bool,
 }
 
 /// Methods for the ray struct
@@ -25,12 +72,379 @@ impl Ray {
         self.origin + t * self.direction
     }
 
-    /// Create a new ray. The direction is normalized in this process.
+    /// Create a new ray. The direction is normalized in this process, and its
+    /// reciprocal is cached for bounding box tests. The ray starts a new
+    /// path, so its reflection and refraction budgets are unlimited apart
+    /// from the overall depth limit. `attenuation` is the throughput of the
+    /// path so far, or `None` for (1, 1, 1).
     pub fn new(origin: Vec3, direction: Vec3, attenuation: Option<Vec3>) -> Ray {
+        let direction = glm::normalize(&direction);
         Ray {
             origin,
-            direction: glm::normalize(&direction),
-            attenuation,
+            direction,
+            attenuation: attenuation.unwrap_or_else(|| glm::vec3(1.0, 1.0, 1.0)),
+            inverse_direction: glm::vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z),
+            direction_is_negative: [direction.x < 0.0, direction.y < 0.0, direction.z < 0.0],
+            reflection_depth: u32::MAX,
+            refraction_depth: u32::MAX,
+            scatter_pdf: None,
+            stalled_hits: 0,
+            channel: None,
+            time: 0.0,
+            caustic: false,
+        }
+    }
+
+    /// Scale light found along this ray by the throughput of its path,
+    /// giving what it adds to the pixel.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `light: Vec3` - light emitted toward the ray's origin
+    ///
+    /// # Returns
+    /// - `Vec3` - the light as it reaches the pixel
+    pub fn weigh(&self, light: Vec3) -> Vec3 {
+        glm::matrix_comp_mult(&self.attenuation, &light)
+    }
+
+    /// Limit the number of reflections and refractions along this ray's path.
+    ///
+    /// # Arguments
+    /// - `reflection_depth: u32` - specular reflections allowed
+    /// - `refraction_depth: u32` - refractions allowed
+    ///
+    /// # Returns
+    /// - the ray with the new budgets
+    pub fn with_depth_budgets(mut self, reflection_depth: u32, refraction_depth: u32) -> Ray {
+        self.reflection_depth = reflection_depth;
+        self.refraction_depth = refraction_depth;
+        self
+    }
+
+    /// Record the density with which a diffuse bounce chose this ray.
+    ///
+    /// # Arguments
+    /// - `scatter_pdf: f32` - solid angle density of the direction
+    ///
+    /// # Returns
+    /// - the ray with the density recorded
+    pub fn with_scatter_pdf(mut self, scatter_pdf: f32) -> Ray {
+        self.scatter_pdf = Some(scatter_pdf);
+        self
+    }
+
+    /// Make this ray's path carry one color channel from here on.
+    ///
+    /// # Arguments
+    /// - `channel: usize` - index of the channel, 0 for red to 2 for blue
+    ///
+    /// # Returns
+    /// - the ray with the channel recorded
+    pub fn with_channel(mut self, channel: usize) -> Ray {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Set the time at which this ray sees the scene.
+    ///
+    /// # Arguments
+    /// - `time: f32` - moment in the frame interval, from 0 to 1
+    ///
+    /// # Returns
+    /// - the ray with the time recorded
+    pub fn with_time(mut self, time: f32) -> Ray {
+        self.time = time;
+        self
+    }
+
+    /// Mark this ray's path as having been through a specular bounce, for
+    /// rays leaving mirrors and glass.
+    ///
+    /// # Returns
+    /// - the ray with its caustic flag set
+    pub fn after_specular(mut self) -> Ray {
+        self.caustic = true;
+        self
+    }
+
+    /// Create a ray which continues this ray's path, keeping its remaining
+    /// reflection and refraction budgets, its color channel, time and
+    /// caustic flag, and counting how many hits in a row the path has
+    /// barely moved for.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `origin: Vec3` - origin of the new ray
+    /// - `direction: Vec3` - direction of the new ray, normalized here
+    /// - `weight: Option<Vec3>` - factor the bounce scales the light found
+    ///   along the new ray by, or `None` if it isn't scaled. The new ray's
+    ///   attenuation is this ray's times the weight.
+    ///
+    /// # Returns
+    /// - the continuing `Ray`
+    pub fn continue_path(&self, origin: Vec3, direction: Vec3, weight: Option<Vec3>) -> Ray {
+        let attenuation = match weight {
+            Some(weight) => glm::matrix_comp_mult(&self.attenuation, &weight),
+            None => self.attenuation,
+        };
+        let mut ray = Ray::new(origin, direction, Some(attenuation))
+            .with_depth_budgets(self.reflection_depth, self.refraction_depth);
+        ray.channel = self.channel;
+        ray.time = self.time;
+        ray.caustic = self.caustic;
+        if glm::distance(&origin, &self.origin) < STALL_DISTANCE {
+            ray.stalled_hits = self.stalled_hits + 1;
+        }
+        ray
+    }
+
+    /// Touching or coincident transparent surfaces can catch a path
+    /// bouncing between them at one point until its budgets run out, which
+    /// shows up as dark speckles. Nudge the ray forward along its direction
+    /// if its path has barely moved for several hits.
+    ///
+    /// # Arguments
+    /// - self, which is consumed
+    ///
+    /// # Returns
+    /// - the ray, moved past the surfaces it was caught on if it was stalled
+    pub fn escape_stall(mut self) -> Ray {
+        if self.stalled_hits >= MAX_STALLED_HITS {
+            self.origin += STALL_NUDGE * self.direction;
+            self.stalled_hits = 0;
+        }
+        self
+    }
+}
+
+/// Up to `PACKET_WIDTH` rays traced through a BVH together, with the parts
+/// of each ray the slab test reads laid out lane by lane, so one box can be
+/// tested against every ray with the same operation on each lane. Rays
+/// close together in direction and origin, like the camera rays of
+/// neighboring pixels, mostly visit the same boxes, so they share most of
+/// the traversal. See `crate::hittable::Hittable::hit_packet`.
+pub struct RayPacket<'a> {
+    /// The rays, one for each lane in use
+    pub rays: &'a [Ray],
+    /// Each component of the origins, lane by lane
+    pub origin: [[f32; PACKET_WIDTH]; 3],
+    /// Each component of the cached inverse directions, lane by lane
+    pub inverse_direction: [[f32; PACKET_WIDTH]; 3],
+    /// Whether each component of the directions is negative, lane by lane
+    pub direction_is_negative: [[bool; PACKET_WIDTH]; 3],
+}
+
+impl<'a> RayPacket<'a> {
+    /// Gather rays into a packet. Lanes past the last ray are zeroed and
+    /// never report hits.
+    ///
+    /// # Arguments
+    /// - `rays: &[Ray]` - at most `PACKET_WIDTH` rays
+    ///
+    /// # Returns
+    /// - `RayPacket` - the packet
+    pub fn new(rays: &'a [Ray]) -> RayPacket<'a> {
+        assert!(
+            rays.len() <= PACKET_WIDTH,
+            "a packet holds at most {} rays",
+            PACKET_WIDTH
+        );
+        let mut packet = RayPacket {
+            rays,
+            origin: [[0.0; PACKET_WIDTH]; 3],
+            inverse_direction: [[0.0; PACKET_WIDTH]; 3],
+            direction_is_negative: [[false; PACKET_WIDTH]; 3],
+        };
+        for (lane, ray) in rays.iter().enumerate() {
+            for dimension in 0..3 {
+                packet.origin[dimension][lane] = ray.origin[dimension];
+                packet.inverse_direction[dimension][lane] = ray.inverse_direction[dimension];
+                packet.direction_is_negative[dimension][lane] =
+                    ray.direction_is_negative[dimension];
+            }
         }
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::transparent::Transparent;
+    use crate::material::MaterialType;
+    use crate::material::ShadingContext;
+    use crate::scenes::Sky;
+    use crate::trace_ray;
+    use crate::DEPTH_LIMIT;
+
+    #[test]
+    fn path_caught_between_coincident_glass_is_nudged_free() {
+        // two glass surfaces in the same place: every hit starts the next
+        // ray where the last one started
+        let point = glm::vec3(0.0, 0.5, 0.5);
+        let forward = glm::vec3(0.0, 0.0, -1.0);
+        let mut ray = Ray::new(point, forward, None);
+        for hit in 1..MAX_STALLED_HITS {
+            ray = ray.continue_path(point, forward, None).escape_stall();
+            assert_eq!(ray.stalled_hits, hit);
+            assert_eq!(ray.origin, point);
+        }
+        ray = ray.continue_path(point, forward, None).escape_stall();
+        assert_eq!(ray.stalled_hits, 0);
+        assert!(glm::distance(&ray.origin, &(point + STALL_NUDGE * forward)) < 1e-7);
+
+        // a path which moves between hits is never nudged
+        let mut ray = Ray::new(point, forward, None);
+        for hit in 1..=2 * MAX_STALLED_HITS {
+            let origin = point + 0.01 * hit as f32 * forward;
+            ray = ray.continue_path(origin, forward, None).escape_stall();
+            assert_eq!(ray.stalled_hits, 0);
+            assert_eq!(ray.origin, origin);
+        }
+    }
+
+    #[test]
+    fn continue_path_multiplies_bounce_weights_into_the_throughput() {
+        let half = glm::vec3(0.5, 0.5, 0.5);
+        let mut ray = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), None);
+        assert_eq!(ray.attenuation, glm::vec3(1.0, 1.0, 1.0));
+        for bounce in 1..=10 {
+            let origin = glm::vec3(0.0, bounce as f32, 0.0);
+            ray = ray.continue_path(origin, glm::vec3(0.0, 1.0, 0.0), Some(half));
+            let expected = 0.5f32.powi(bounce);
+            assert!((ray.attenuation - glm::vec3(expected, expected, expected)).amax() < 1e-9);
+        }
+        // an unweighted bounce, like total internal reflection, keeps it
+        let kept = ray.continue_path(glm::vec3(0.0, 20.0, 0.0), glm::vec3(0.0, 1.0, 0.0), None);
+        assert_eq!(kept.attenuation, ray.attenuation);
+    }
+
+    #[test]
+    fn bounces_of_half_albedo_weigh_the_sky_by_powers_of_a_half() {
+        let sky = Sky::uniform(glm::vec3(1.0, 1.0, 1.0));
+        let context = ShadingContext::default();
+        // glass which lets half of the light straight through, and reflects none
+        let pane = MaterialType::Transparent(Transparent {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            reflectance: 0.0,
+            transmittance: 1.0,
+            refractive_index: 1.0,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
+        });
+        let floor = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let world = |panes: usize, with_floor: bool| {
+            let mut world = HittableList::new();
+            for pane_index in 1..=panes {
+                let height = glm::vec3(0.0, pane_index as f32, 0.0);
+                world.add_plane(height, glm::vec3(0.0, 1.0, 0.0), pane);
+            }
+            if with_floor {
+                world.add_plane(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), floor);
+            }
+            world
+        };
+        // looking down through the panes, and never following reflections
+        let ray = Ray::new(glm::vec3(0.0, 10.0, 0.0), glm::vec3(0.3, -1.0, 0.2), None)
+            .with_depth_budgets(0, u32::MAX);
+        let expect = |world: &HittableList, bounces: i32| {
+            let expected = 0.5f32.powi(bounces);
+            for _ in 0..20 {
+                let color = trace_ray(&ray, world, &[], &sky, &context, DEPTH_LIMIT);
+                assert!(
+                    (color - glm::vec3(expected, expected, expected)).amax() < 1e-6 * expected,
+                    "{:?} after {} bounces",
+                    color,
+                    bounces
+                );
+            }
+        };
+        // straight through to the sky below
+        for panes in 1..=4 {
+            expect(&world(panes, false), panes as i32);
+        }
+        // down to the floor, which sends every path back up through the
+        // panes, so each is crossed twice
+        expect(&world(0, true), 1);
+        expect(&world(1, true), 3);
+
+        // past five bounces, Russian roulette ends most paths, and those
+        // which carry on are brightened to make up for them
+        let deep = world(5, false);
+        let samples = 30_000;
+        let mut total = glm::vec3(0.0, 0.0, 0.0);
+        let mut ended = 0;
+        for _ in 0..samples {
+            let color = trace_ray(&ray, &deep, &[], &sky, &context, DEPTH_LIMIT);
+            ended += (color.x == 0.0) as usize;
+            total += color;
+        }
+        let average = total / samples as f32;
+        let expected = 0.5f32.powi(5);
+        assert!(ended > samples * 9 / 10, "{} of {} ended", ended, samples);
+        assert!(
+            (average - glm::vec3(expected, expected, expected)).amax() < 0.15 * expected,
+            "{:?} on average",
+            average
+        );
+    }
+
+    #[test]
+    fn only_paths_through_glass_are_marked_specular() {
+        // a sky which is red to rays marked specular and green to the rest
+        fn caustic_sky(ray: &Ray) -> Vec3 {
+            if ray.caustic {
+                glm::vec3(1.0, 0.0, 0.0)
+            } else {
+                glm::vec3(0.0, 1.0, 0.0)
+            }
+        }
+        let sky = Sky::Custom(caustic_sky);
+        let context = ShadingContext::default();
+        let mut diffuse = HittableList::new();
+        diffuse.add_sphere(
+            glm::vec3(0.0, 0.0, -3.0),
+            1.0,
+            MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(0.5, 0.5, 0.5),
+                force_front_face: false,
+            }),
+        );
+        let mut glass = HittableList::new();
+        glass.add_sphere(
+            glm::vec3(0.0, 0.0, -3.0),
+            1.0,
+            MaterialType::Transparent(Transparent {
+                albedo: glm::vec3(1.0, 1.0, 1.0),
+                reflectance: 0.1,
+                transmittance: 0.9,
+                refractive_index: 1.5,
+                abbe_number: None,
+                roughness: 0.0,
+                reflect_bias: None,
+                branch_depth: None,
+            }),
+        );
+        let ray = Ray::new(glm::vec3(0.0, 0.3, 0.0), glm::vec3(0.0, 0.0, -1.0), None);
+        for _ in 0..20 {
+            let bounced = trace_ray(&ray, &diffuse, &[], &sky, &context, DEPTH_LIMIT);
+            assert!(bounced.x == 0.0 && bounced.y > 0.0, "{:?}", bounced);
+        }
+        let through = trace_ray(&ray, &glass, &[], &sky, &context, DEPTH_LIMIT);
+        assert!(through.x > 0.0 && through.y == 0.0, "{:?}", through);
+
+        // and the mark stays for the rest of the path
+        let marked = ray.after_specular();
+        let origin = glm::vec3(0.0, 0.0, -1.0);
+        assert!(marked.continue_path(origin, ray.direction, None).caustic);
+        assert!(!ray.continue_path(origin, ray.direction, None).caustic);
     }
 }
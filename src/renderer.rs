@@ -0,0 +1,431 @@
+use super::trace_ray_within;
+use super::DEPTH_LIMIT;
+use crate::camera;
+use crate::camera::perspective_camera::PerspectiveCamera;
+use crate::camera::Camera;
+use crate::color;
+use crate::hittable::hittable_list::HittableList;
+use crate::light::Light;
+use crate::post;
+use crate::random;
+use crate::random::rng;
+use crate::scenes::Scene;
+use crate::scenes::Sky;
+use crate::settings::AdaptiveMetric;
+use crate::settings::RenderSettings;
+use crate::stats;
+use crate::wireframe::WireframeOverlay;
+use glm::Vec3;
+use image::ImageFormat;
+use image::RgbImage;
+use rand::Rng;
+use rayon::prelude::*;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Passes every pixel takes before adaptive sampling may stop sampling it,
+/// so its noise is estimated from enough samples to be trusted
+const MIN_ADAPTIVE_SAMPLES: usize = 16;
+
+/// Running sums of the samples taken in one pixel.
+#[derive(Clone, Copy)]
+struct PixelSums {
+    /// Sum of the linear colors of the samples
+    color: Vec3,
+    /// Sum of the squares of each channel of the samples
+    color_squared: Vec3,
+    /// Sum of the squares of the luminances of the samples
+    luminance_squared: f32,
+    /// Number of samples taken
+    sample_count: usize,
+}
+
+impl PixelSums {
+    /// Sums of no samples.
+    fn new() -> PixelSums {
+        PixelSums {
+            color: glm::vec3(0.0, 0.0, 0.0),
+            color_squared: glm::vec3(0.0, 0.0, 0.0),
+            luminance_squared: 0.0,
+            sample_count: 0,
+        }
+    }
+
+    /// Add one sample to the sums.
+    fn add(&mut self, color: &Vec3) {
+        self.color += color;
+        self.color_squared += glm::matrix_comp_mult(color, color);
+        self.luminance_squared += color::luminance(color).powi(2);
+        self.sample_count += 1;
+    }
+
+    /// Average color of the samples, black if there are none or if lights
+    /// with negative weights took it below black.
+    fn mean(&self) -> Vec3 {
+        glm::max(&(self.color / self.sample_count.max(1) as f32), 0.0)
+    }
+
+    /// Whether the noise in the pixel's average is below a threshold, so
+    /// more samples wouldn't visibly change it. The noise is the standard
+    /// error of the mean, estimated from the spread of the samples.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `metric: AdaptiveMetric` - how to measure the noise
+    /// - `threshold: f32` - noise below which the pixel is done
+    ///
+    /// # Returns
+    /// - `bool` - true if the pixel needs no more samples
+    fn has_converged(&self, metric: AdaptiveMetric, threshold: f32) -> bool {
+        if self.sample_count < MIN_ADAPTIVE_SAMPLES {
+            return false;
+        }
+        let count = self.sample_count as f32;
+        // variance of the mean of samples with these sums
+        let variance_of_mean = |sum: f32, sum_squared: f32| {
+            (sum_squared - sum * sum / count).max(0.0) / (count - 1.0) / count
+        };
+        match metric {
+            AdaptiveMetric::Rgb => (0..3).all(|channel| {
+                variance_of_mean(self.color[channel], self.color_squared[channel]).sqrt()
+                    <= threshold
+            }),
+            AdaptiveMetric::Luminance => {
+                let luminance = color::luminance(&self.color);
+                variance_of_mean(luminance, self.luminance_squared).sqrt()
+                    <= threshold * luminance / count
+            }
+        }
+    }
+}
+
+/// A scene together with the light gathered from it so far, for rendering
+/// progressively: each pass adds one sample to every pixel, and the image
+/// can be looked at between passes. Unlike `render`, there is no fixed
+/// sample count to reach, so a viewer can keep refining for as long as it
+/// is left open.
+pub struct Renderer {
+    /// Objects that compose the scene
+    world: HittableList,
+    /// Camera to generate rays from
+    camera: PerspectiveCamera,
+    /// Light sources for the scene
+    lights: Vec<Light>,
+    /// Background of the scene
+    sky: Sky,
+    /// Image size, depth budgets, clipping, seed and tone mapping
+    settings: RenderSettings,
+    /// Sums of the samples taken in each pixel, row-major
+    accumulated: Vec<PixelSums>,
+    /// Number of passes taken, which is the number of samples in each pixel
+    pass_count: usize,
+    /// Moment after which no more pixels are sampled, from `max_time`
+    deadline: Option<Instant>,
+}
+
+impl Renderer {
+    /// Create a renderer for a scene with no samples taken yet.
+    ///
+    /// # Arguments
+    /// - `scene: Scene` - the scene to render, which the renderer keeps
+    /// - `settings: &RenderSettings` - image size, depth budgets, clipping,
+    ///   seed and tone mapping. The anti-aliasing and path counts are not
+    ///   used, since every pass takes one sample per pixel.
+    ///
+    /// # Returns
+    /// - `Renderer` - the renderer, with every pixel black
+    pub fn new(scene: Scene, settings: &RenderSettings) -> Renderer {
+        // the settings' exposure is used, with the scene's already filled in
+        let (world, camera, lights, sky, _) = scene;
+        let pixel_count = (settings.image_width * settings.image_height) as usize;
+        Renderer {
+            world,
+            camera,
+            lights,
+            sky,
+            settings: settings.clone(),
+            accumulated: vec![PixelSums::new(); pixel_count],
+            pass_count: 0,
+            deadline: settings
+                .max_time
+                .map(|seconds| Instant::now() + Duration::from_secs_f32(seconds)),
+        }
+    }
+
+    /// Trace one path through every pixel, at a random position inside it,
+    /// and add its color to the pixel's sum. With adaptive sampling, pixels
+    /// which have converged are skipped, and once the time budget runs out
+    /// the rest of the pass is skipped, leaving those pixels a sample short.
+    ///
+    /// # Arguments
+    /// - mutable self reference
+    pub fn add_sample_pass(&mut self) {
+        let image_width = self.settings.image_width;
+        let pass = self.pass_count as u64;
+        let world = &self.world;
+        let camera = &self.camera;
+        let lights = &self.lights;
+        let sky = &self.sky;
+        let settings = &self.settings;
+        let deadline = self.deadline;
+        let min_distance = settings.camera_min_distance(world);
+        let context = settings.shading_context();
+        let wireframe = settings.wireframe.map(|line_width| {
+            WireframeOverlay::new(line_width, image_width, settings.image_height)
+        });
+        self.accumulated
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, sums)| {
+                if settings.adaptive.is_some_and(|threshold| {
+                    sums.has_converged(settings.adaptive_metric, threshold)
+                }) || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    return;
+                }
+                let x = index as u32 % image_width;
+                let y = index as u32 / image_width;
+                random::seed_pixel_pass(x, y, settings.seed, pass);
+                let (u, v) = camera::pixel_to_camera(
+                    x,
+                    y,
+                    (rng().gen::<f32>(), rng().gen::<f32>()),
+                    image_width,
+                    settings.image_height,
+                );
+                if wireframe.as_ref().is_some_and(|wireframe| {
+                    wireframe.covers(world, camera, u, v, min_distance, settings.max_distance)
+                }) {
+                    // lines are black
+                    sums.add(&glm::vec3(0.0, 0.0, 0.0));
+                    return;
+                }
+                let ray = camera.get_ray(u, v).with_depth_budgets(
+                    settings.max_reflection_depth,
+                    settings.max_refraction_depth,
+                );
+                stats::record(stats::Counter::PrimaryRays);
+                sums.add(&trace_ray_within(
+                    &ray,
+                    world,
+                    lights,
+                    sky,
+                    &context,
+                    DEPTH_LIMIT,
+                    min_distance,
+                    settings.max_distance,
+                ));
+            });
+        self.pass_count += 1;
+    }
+
+    /// Number of passes taken so far, which is the number of samples in
+    /// each pixel.
+    pub fn pass_count(&self) -> usize {
+        self.pass_count
+    }
+
+    /// Whether adaptive sampling has stopped sampling every pixel, so more
+    /// passes wouldn't change the image. Always false without adaptive
+    /// sampling.
+    pub fn is_converged(&self) -> bool {
+        self.settings.adaptive.is_some_and(|threshold| {
+            self.accumulated
+                .iter()
+                .all(|sums| sums.has_converged(self.settings.adaptive_metric, threshold))
+        })
+    }
+
+    /// Whether the time budget given by `max_time` has run out, so passes
+    /// won't sample any more pixels. Always false without one.
+    pub fn is_out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Number of samples taken in each pixel so far, row-major. Every pixel
+    /// has one per pass unless adaptive sampling stopped sampling it.
+    pub fn sample_counts(&self) -> Vec<usize> {
+        self.accumulated
+            .iter()
+            .map(|sums| sums.sample_count)
+            .collect()
+    }
+
+    /// Average the samples taken so far, apply the exposure and white
+    /// balance, and tone map them into an 8 bit image.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `RgbImage` - the image, black if no passes have been taken
+    pub fn tonemapped_image(&self) -> RgbImage {
+        let image_width = self.settings.image_width;
+        let mut color_buffer: Vec<Vec3> = self.accumulated.iter().map(PixelSums::mean).collect();
+        let [red, green, blue] = self.settings.white_balance;
+        post::expose(
+            &mut color_buffer,
+            self.settings.exposure.unwrap_or(0.0),
+            glm::vec3(red, green, blue),
+        );
+        if self.settings.flip_y {
+            post::flip_rows(&mut color_buffer, image_width as usize);
+        }
+        let mut img = RgbImage::new(image_width, self.settings.image_height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let color = &color_buffer[(y * image_width + x) as usize];
+            *pixel = if self.settings.dither {
+                color::vec3_to_rgb_dithered(color, 1, x, y)
+            } else {
+                color::vec3_to_rgb(color, 1)
+            };
+        }
+        img
+    }
+}
+
+/// Render a scene in passes of one sample per pixel, writing the image to
+/// the output file after each pass so it can be watched as it refines.
+///
+/// # Arguments
+/// - `scene: Scene` - the scene to render
+/// - `settings: &RenderSettings` - image size, output name, and tone mapping
+/// - `pass_count: usize` - number of passes to take
+pub fn render_progressive(scene: Scene, settings: &RenderSettings, pass_count: usize) {
+    let mut renderer = Renderer::new(scene, settings);
+    while renderer.pass_count() < pass_count {
+        renderer.add_sample_pass();
+        println!("pass {} of {}", renderer.pass_count(), pass_count);
+        save_whole(&renderer.tonemapped_image(), &settings.output).unwrap();
+        if renderer.is_converged() {
+            println!("every pixel has converged");
+            break;
+        }
+        if renderer.is_out_of_time() {
+            let sample_counts = renderer.sample_counts();
+            let sample_total: usize = sample_counts.iter().sum();
+            println!(
+                "out of time, stopped with {:.1} samples per pixel",
+                sample_total as f32 / sample_counts.len().max(1) as f32
+            );
+            break;
+        }
+    }
+}
+
+/// Save an image by writing it beside the file and then renaming it into
+/// place, so the file always holds a whole image even if the program is
+/// stopped partway through writing it.
+///
+/// # Arguments
+/// - `img: &RgbImage` - the image to save
+/// - `filename: &str` - file to save it to, whose extension sets the format
+///
+/// # Returns
+/// - `Result<(), String>` - a message describing what went wrong, if anything
+fn save_whole(img: &RgbImage, filename: &str) -> Result<(), String> {
+    let format = ImageFormat::from_path(filename).map_err(|error| error.to_string())?;
+    let partial = format!("{}.partial", filename);
+    img.save_with_format(&partial, format)
+        .map_err(|error| error.to_string())?;
+    std::fs::rename(&partial, filename).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::MaterialType;
+
+    /// A bright and a dark flat panel filling the left and right halves of
+    /// the image, under a sky which varies with direction, so the bright
+    /// panel's noise is ten times the dark one's in absolute terms.
+    fn bright_and_dark_panels() -> Scene {
+        let panel = |albedo: f32| {
+            MaterialType::Lambertian(Lambertian {
+                albedo: glm::vec3(albedo, albedo, albedo),
+                force_front_face: false,
+            })
+        };
+        let mut world = HittableList::new();
+        world.add_rectangle(
+            [
+                glm::vec3(-10.0, -10.0, -1.0),
+                glm::vec3(0.0, -10.0, -1.0),
+                glm::vec3(0.0, 10.0, -1.0),
+                glm::vec3(-10.0, 10.0, -1.0),
+            ],
+            panel(0.8),
+        );
+        world.add_rectangle(
+            [
+                glm::vec3(0.0, -10.0, -1.0),
+                glm::vec3(10.0, -10.0, -1.0),
+                glm::vec3(10.0, 10.0, -1.0),
+                glm::vec3(0.0, 10.0, -1.0),
+            ],
+            panel(0.08),
+        );
+        let camera = PerspectiveCamera::new(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, -1.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            60.0,
+            2.0,
+        );
+        let sky = Sky::Gradient {
+            bottom: glm::vec3(0.0, 0.0, 0.0),
+            top: glm::vec3(1.0, 1.0, 1.0),
+            axis: glm::vec3(0.0, 1.0, 0.0),
+        };
+        (world, camera, Vec::new(), sky, None)
+    }
+
+    /// Render the panels adaptively until every pixel has converged, and
+    /// return the largest sample count on each panel.
+    fn panel_sample_counts(metric: AdaptiveMetric, threshold: f32) -> (usize, usize) {
+        let settings = RenderSettings {
+            image_width: 4,
+            image_height: 2,
+            adaptive: Some(threshold),
+            adaptive_metric: metric,
+            ..RenderSettings::default()
+        };
+        let mut renderer = Renderer::new(bright_and_dark_panels(), &settings);
+        while !renderer.is_converged() && renderer.pass_count() < 20000 {
+            renderer.add_sample_pass();
+        }
+        assert!(renderer.is_converged());
+        let counts = renderer.sample_counts();
+        let most = |columns: std::ops::Range<usize>| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| columns.contains(&(index % 4)))
+                .map(|(_, count)| *count)
+                .max()
+                .unwrap()
+        };
+        (most(0..2), most(2..4))
+    }
+
+    #[test]
+    fn luminance_metric_converges_bright_and_dark_panels_alike() {
+        let (bright, dark) = panel_sample_counts(AdaptiveMetric::Luminance, 0.05);
+        assert!(
+            bright.max(dark) < 2 * bright.min(dark),
+            "bright {} and dark {} samples",
+            bright,
+            dark
+        );
+        // absolute noise leaves the dark panel done long before the bright
+        let (bright, dark) = panel_sample_counts(AdaptiveMetric::Rgb, 0.01);
+        assert!(
+            bright > 10 * dark,
+            "bright {} and dark {} samples",
+            bright,
+            dark
+        );
+    }
+}
@@ -1,73 +1,573 @@
 use crate::camera::perspective_camera::PerspectiveCamera;
+use crate::camera::Shutter;
+use crate::camera::ShutterProfile;
 use crate::color;
+use crate::daylight::Daylight;
+use crate::hit_record::HitRecord;
+use crate::hittable::bvh::BvhSettings;
 use crate::hittable::hittable_list::HittableList;
+use crate::hittable::medium::ConstantMedium;
+use crate::hittable::mesh::ImportTransform;
 use crate::hittable::mesh::Mesh;
-use crate::hittable::plane::Plane;
-use crate::hittable::rectangle::Rectangle;
+use crate::hittable::mesh::UpAxis;
 use crate::hittable::sphere::Sphere;
-use crate::hittable::triangle::Triangle;
+use crate::hittable::Hittable;
 use crate::hittable::HittableItem;
 use crate::light::Light;
+use crate::light::SurfaceResponse;
 use crate::material::diffuse_light::DiffuseLight;
+use crate::material::isotropic::Isotropic;
 use crate::material::lambertian::Lambertian;
 use crate::material::metal::Metal;
+use crate::material::phong::Phong;
+use crate::material::shadow_catcher::ShadowCatcher;
+use crate::material::subsurface::Subsurface;
+use crate::material::textured::Textured;
 use crate::material::transparent::Transparent;
+use crate::material::triplanar::Triplanar;
+use crate::material::vertex_color::VertexColor;
+use crate::material::water::Water;
+use crate::material::wireframe::Wireframe;
 use crate::material::MaterialType;
 use crate::ray::Ray;
+use crate::texture::ColorSpace;
+use crate::texture::EnvironmentMap;
+use crate::texture::ImageTexture;
+use crate::texture::TriplanarTexture;
 use glm::Vec3;
-use image::GenericImageView;
-use image::Pixel;
 
-/// A sky takes a &Ray and return the color of the skybox in that ray's
-/// direction.
-pub type Sky = fn(&Ray) -> Vec3;
-pub type SkyBox = fn(&Ray, &image::DynamicImage) -> Vec3;
+/// Background of a scene, which gives the color seen by rays which escape
+/// without hitting anything.
+#[derive(Clone, Copy)]
+pub enum Sky {
+    /// A blend from one color to another along an axis: rays along `axis`
+    /// see `top`, rays against it see `bottom`, and rays across it see an
+    /// even mix of the two, see `gradient_parameter`. The axis needn't be
+    /// normalized.
+    Gradient {
+        /// Color seen against the axis
+        bottom: Vec3,
+        /// Color seen along the axis
+        top: Vec3,
+        /// Direction the blend runs toward `top` in
+        axis: Vec3,
+    },
+    /// Takes a &Ray and returns the color of the sky in that ray's
+    /// direction, for skies which aren't a gradient along an axis
+    Custom(fn(&Ray) -> Vec3),
+    /// An image of the surroundings, looked up by the ray's direction
+    Environment(EnvironmentMap),
+    /// A daytime sky whose sun lights the scene
+    Daylight(Daylight),
+}
+
+impl Sky {
+    /// A sky of one color in every direction.
+    ///
+    /// # Arguments
+    /// - `color: Vec3` - color of the sky
+    ///
+    /// # Returns
+    /// - the sky, a gradient from the color to itself
+    pub fn uniform(color: Vec3) -> Sky {
+        Sky::Gradient {
+            bottom: color,
+            top: color,
+            axis: glm::vec3(0.0, 1.0, 0.0),
+        }
+    }
+
+    /// Color of the sky seen along a ray.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `ray: &Ray` - ray which escaped the scene
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color of the sky
+    pub fn color(&self, ray: &Ray) -> Vec3 {
+        match self {
+            Sky::Gradient { bottom, top, axis } => {
+                let t = gradient_parameter(glm::dot(&ray.direction, &glm::normalize(axis)));
+                bottom * (1.0 - t) + top * t
+            }
+            Sky::Custom(sky) => sky(ray),
+            Sky::Environment(environment) => environment.color(&ray.direction),
+            Sky::Daylight(daylight) => daylight.radiance(ray),
+        }
+    }
+
+    /// Light arriving at a diffuse hit straight from a bright part of the
+    /// sky which is sampled directly, like the sun of a daylight sky, cosine
+    /// weighted by the surface normal. Zero for skies with nothing sampled.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    ///
+    /// # Returns
+    /// - `Vec3` - irradiance at the hit point
+    pub fn direct_irradiance(&self, hit: &HitRecord, world: &dyn Hittable) -> Vec3 {
+        match self {
+            Sky::Daylight(daylight) => daylight.direct_irradiance(hit, world),
+            _ => glm::vec3(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Light from a directly sampled part of the sky which a hit reflects
+    /// toward the viewer, for surfaces which aren't Lambertian, see
+    /// `Light::reflected_radiance`. Zero for skies with nothing sampled.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `hit: &HitRecord` - the surface hit being lit
+    /// - `world: &dyn Hittable` - objects which can cast shadows
+    /// - `response: SurfaceResponse` - how the surface reflects the light
+    ///
+    /// # Returns
+    /// - `Vec3` - radiance reflected toward the viewer
+    pub fn direct_reflected_radiance(
+        &self,
+        hit: &HitRecord,
+        world: &dyn Hittable,
+        response: SurfaceResponse,
+    ) -> Vec3 {
+        match self {
+            Sky::Daylight(daylight) => daylight.direct_reflected_radiance(hit, world, response),
+            _ => glm::vec3(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Turn an environment sky, a daylight sky's sun, or a gradient sky's
+    /// axis about the y axis. Custom skies are computed from the ray
+    /// directly and are left as they are.
+    ///
+    /// # Arguments
+    /// - `degrees: f32` - angle to turn by, counterclockwise seen from above
+    ///
+    /// # Returns
+    /// - the rotated sky
+    pub fn with_yaw(self, degrees: f32) -> Sky {
+        match self {
+            Sky::Environment(environment) => Sky::Environment(environment.with_yaw(degrees)),
+            Sky::Daylight(daylight) => Sky::Daylight(daylight.with_yaw(degrees)),
+            Sky::Gradient { bottom, top, axis } => {
+                // whole turns are dropped first, like for the other skies
+                let angle = degrees.rem_euclid(360.0).to_radians();
+                let yaw = glm::mat4_to_mat3(&glm::rotation(angle, &glm::vec3(0.0, 1.0, 0.0)));
+                Sky::Gradient {
+                    bottom,
+                    top,
+                    axis: yaw * axis,
+                }
+            }
+            custom => custom,
+        }
+    }
+}
+
+/// Remap a component of a normalized ray direction from [-1, 1] to a [0, 1]
+/// blend factor for gradient skies, so the blend weights are never negative.
+///
+/// # Arguments
+/// - `component: f32` - one component of a normalized direction
+///
+/// # Returns
+/// - `f32` - the blend factor
+pub fn gradient_parameter(component: f32) -> f32 {
+    (0.5 * (component + 1.0)).clamp(0.0, 1.0)
+}
+
+/// Everything needed to render a scene: the objects, the camera looking at
+/// them, the lights, the sky behind them, and the exposure in stops the
+/// scene is meant to be seen at, if it isn't 0. The exposure is used when
+/// `--exposure` isn't given.
+pub type Scene = (
+    HittableList,
+    PerspectiveCamera,
+    Vec<Light>,
+    Sky,
+    Option<f32>,
+);
+
+/// A row of glossy spheres under a square panel light, with the Phong
+/// exponent growing from left to right, see `Phong`. The highlight starts
+/// as a broad sheen over the leftmost sphere and tightens into a small,
+/// bright reflection of the panel on the rightmost.
+pub fn phong_spheres(image_width: u32, image_height: u32) -> Scene {
+    let white = color::color_linear(255, 255, 255);
+    let panel_radiance = 8.0;
+
+    let mut world = HittableList::new();
+    for (index, exponent) in [1.0, 8.0, 40.0, 200.0, 1000.0].iter().enumerate() {
+        world.add_sphere(
+            glm::vec3(index as f32 * 1.2 - 2.4, 0.5, 0.0),
+            0.5,
+            MaterialType::Phong(Phong {
+                diffuse: 0.5 * color::color_linear(60, 110, 230),
+                specular: glm::vec3(0.45, 0.45, 0.45),
+                exponent: *exponent,
+            }),
+        );
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(150, 150, 150),
+            force_front_face: false,
+        }),
+    );
+    // a panel above and in front, seen in the spheres and sampled directly
+    world.add_rectangle(
+        [
+            glm::vec3(-0.5, 3.0, 1.5),
+            glm::vec3(0.5, 3.0, 1.5),
+            glm::vec3(0.5, 3.0, 2.5),
+            glm::vec3(-0.5, 3.0, 2.5),
+        ],
+        MaterialType::DiffuseLight(DiffuseLight::new(panel_radiance * white)),
+    );
+
+    let lights = vec![Light {
+        position: glm::vec3(-0.5, 3.0, 1.5),
+        weight: panel_radiance,
+        edges: [glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0)],
+        sphere: None,
+        color: white,
+        emitter_in_world: true,
+    }];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.2, 6.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.5, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let dim_sky = Sky::uniform(0.05 * color::color_linear(200, 210, 255));
+
+    (world, camera, lights, dim_sky, None)
+}
+
+/// Spheres on a ground plane in a bank of fog, lit by a small glowing
+/// sphere inside it, for `ConstantMedium` and its `Isotropic` phase
+/// function. The fog scatters forward, so it glows brightest between the
+/// camera and the lamp, and the spheres cast shafts of shadow through it.
+pub fn foggy_lamp(image_width: u32, image_height: u32) -> Scene {
+    let lamp_radiance = 60.0;
+    let lamp_color = color::color_linear(255, 220, 170);
+    let lamp = Sphere {
+        center: glm::vec3(0.0, 1.6, -1.5),
+        radius: 0.25,
+        material: MaterialType::DiffuseLight(DiffuseLight::new(lamp_radiance * lamp_color)),
+        velocity: glm::vec3(0.0, 0.0, 0.0),
+    };
+
+    let mut world = HittableList::new();
+    world.add_sphere(lamp.center, lamp.radius, lamp.material);
+    for (center, albedo) in [
+        (glm::vec3(-1.0, 0.5, -0.5), color::color_linear(200, 80, 70)),
+        (glm::vec3(0.9, 0.4, -0.2), color::color_linear(70, 120, 200)),
+    ] {
+        world.add_sphere(
+            center,
+            center.y,
+            MaterialType::Lambertian(Lambertian {
+                albedo,
+                force_front_face: false,
+            }),
+        );
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(180, 180, 180),
+            force_front_face: false,
+        }),
+    );
+    // the fog fills a big sphere around the lamp and the spheres, and its
+    // lower half is hidden below the ground
+    let fog_boundary = HittableItem::Sphere(Sphere {
+        center: glm::vec3(0.0, 0.0, -1.0),
+        radius: 4.0,
+        material: MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.0, 0.0, 0.0),
+            force_front_face: false,
+        }),
+        velocity: glm::vec3(0.0, 0.0, 0.0),
+    });
+    world.add(HittableItem::ConstantMedium(ConstantMedium::new(
+        fog_boundary,
+        0.15,
+        Isotropic {
+            albedo: glm::vec3(0.9, 0.9, 0.9),
+            asymmetry: 0.6,
+        },
+    )));
+
+    let lights = vec![Light::sphere(lamp, lamp_radiance, lamp_color)];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.2, 5.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.9, -1.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let night_sky = Sky::uniform(0.02 * color::color_linear(90, 120, 200));
+
+    (world, camera, lights, night_sky, None)
+}
 
-pub fn colorful_shadows(
+/// A cow in the afternoon sun, loaded from a binary PLY file, the format
+/// scanned models often come in. The file has no normals, so the cow is
+/// shaded with the smooth normals `Mesh::create_ply` averages for it.
+pub fn ply_cow(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create_ply(
+        "assets/cow.ply",
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 170, 140),
+            force_front_face: false,
+        }),
+        bvh,
+    );
+    // the cow's hooves rest on the ground
+    let ground_height = mesh.bounding_box().unwrap().minimum_point.y;
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    world.add_plane(
+        glm::vec3(0.0, ground_height, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(120, 140, 110),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(1.2, 0.4, 3.0);
+    let camera_lookat: Vec3 = glm::vec3(0.15, -0.1, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    // sun from the front right, so the cow's shadow falls behind it
+    let sky = Daylight::new(glm::vec3(0.5, 0.7, 0.6), 3.0);
+
+    (world, camera, Vec::new(), Sky::Daylight(sky), None)
+}
+
+/// Names accepted by `by_name`, in the order they are listed to the user.
+pub const SCENE_NAMES: [&str; 37] = [
+    "colorful_shadows",
+    "infinite_mirror_hallway",
+    "simple_primitives",
+    "rectangle_light_example",
+    "teapot_caustic",
+    "above_right_dragon",
+    "hollow_glass_bubble",
+    "spotlight_mesh",
+    "foliage_card",
+    "tilt_shift_tower",
+    "welded_cube",
+    "area_light_penumbra",
+    "glass_pane_stack",
+    "fresnel_metal_spheres",
+    "wireframe_sphere",
+    "vertex_color_torus",
+    "environment_spheres",
+    "mixed_winding_cloth",
+    "contact_shadow_sphere",
+    "obj_directory",
+    "equal_power_lights",
+    "brushed_cylinder",
+    "coincident_glass",
+    "z_up_house",
+    "concave_polygons",
+    "prism_rainbow",
+    "sphere_sun",
+    "daylight_spheres",
+    "frosted_glass",
+    "shadow_catcher",
+    "rippled_water",
+    "rock_dragon",
+    "rolling_shutter",
+    "wax_spheres",
+    "phong_spheres",
+    "foggy_lamp",
+    "ply_cow",
+];
+
+/// Errors from selecting or configuring a scene.
+#[derive(Debug)]
+pub enum SceneError {
+    /// No scene has the requested name
+    UnknownScene(String),
+    /// The image dimensions can't be used to build a camera
+    InvalidDimensions(u32, u32),
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::UnknownScene(name) => write!(
+                f,
+                "unknown scene `{}`, available scenes are: {}",
+                name,
+                SCENE_NAMES.join(", ")
+            ),
+            SceneError::InvalidDimensions(width, height) => {
+                write!(f, "invalid image size {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Build one of the scenes in this module from its function name.
+///
+/// # Arguments
+/// - `name: &str` - name of the scene, one of `SCENE_NAMES`
+/// - `image_width: u32` - width of the image in pixels
+/// - `image_height: u32` - height of the image in pixels
+/// - `bvh: BvhSettings` - how to build the BVHs of the scene's meshes
+///
+/// # Returns
+/// - `Result<Scene, SceneError>` - the scene, or why it couldn't be built
+pub fn by_name(
+    name: &str,
     image_width: u32,
     image_height: u32,
-) -> (HittableList, PerspectiveCamera, Vec<Light>, Sky) {
+    bvh: BvhSettings,
+) -> Result<Scene, SceneError> {
+    if image_width == 0 || image_height == 0 {
+        return Err(SceneError::InvalidDimensions(image_width, image_height));
+    }
+    let scene = match name {
+        "colorful_shadows" => colorful_shadows(image_width, image_height),
+        "infinite_mirror_hallway" => infinite_mirror_hallway(image_width, image_height),
+        "simple_primitives" => simple_primitives(image_width, image_height),
+        "rectangle_light_example" => rectangle_light_example(image_width, image_height),
+        "teapot_caustic" => teapot_caustic(image_width, image_height, bvh),
+        "above_right_dragon" => above_right_dragon(image_width, image_height, bvh),
+        "hollow_glass_bubble" => hollow_glass_bubble(image_width, image_height),
+        "spotlight_mesh" => spotlight_mesh(image_width, image_height, bvh),
+        "foliage_card" => foliage_card(image_width, image_height),
+        "tilt_shift_tower" => tilt_shift_tower(image_width, image_height),
+        "welded_cube" => welded_cube(image_width, image_height, bvh),
+        "area_light_penumbra" => area_light_penumbra(image_width, image_height),
+        "glass_pane_stack" => glass_pane_stack(image_width, image_height),
+        "fresnel_metal_spheres" => fresnel_metal_spheres(image_width, image_height),
+        "wireframe_sphere" => wireframe_sphere(image_width, image_height, bvh),
+        "vertex_color_torus" => vertex_color_torus(image_width, image_height, bvh),
+        "environment_spheres" => environment_spheres(image_width, image_height),
+        "mixed_winding_cloth" => mixed_winding_cloth(image_width, image_height, bvh),
+        "contact_shadow_sphere" => contact_shadow_sphere(image_width, image_height),
+        "obj_directory" => obj_directory(image_width, image_height, bvh),
+        "equal_power_lights" => equal_power_lights(image_width, image_height),
+        "brushed_cylinder" => brushed_cylinder(image_width, image_height, bvh),
+        "coincident_glass" => coincident_glass(image_width, image_height),
+        "z_up_house" => z_up_house(image_width, image_height, bvh),
+        "concave_polygons" => concave_polygons(image_width, image_height),
+        "prism_rainbow" => prism_rainbow(image_width, image_height),
+        "sphere_sun" => sphere_sun(image_width, image_height),
+        "daylight_spheres" => daylight_spheres(image_width, image_height),
+        "frosted_glass" => frosted_glass(image_width, image_height),
+        "shadow_catcher" => shadow_catcher(image_width, image_height),
+        "rippled_water" => rippled_water(image_width, image_height),
+        "rock_dragon" => rock_dragon(image_width, image_height, bvh),
+        "rolling_shutter" => rolling_shutter(image_width, image_height),
+        "wax_spheres" => wax_spheres(image_width, image_height),
+        "phong_spheres" => phong_spheres(image_width, image_height),
+        "foggy_lamp" => foggy_lamp(image_width, image_height),
+        "ply_cow" => ply_cow(image_width, image_height, bvh),
+        _ => return Err(SceneError::UnknownScene(String::from(name))),
+    };
+    Ok(scene)
+}
+
+pub fn colorful_shadows(image_width: u32, image_height: u32) -> Scene {
     // configure object colors
-    let white = color::color(255, 255, 255);
+    let white = color::color_linear(255, 255, 255);
 
     // create world and populate it
     let mut world = HittableList::new();
     // ground plane
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(0.0, 0.0, 0.0),
-        normal: glm::vec3(0.0, 1.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian { albedo: white }),
-    }));
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
     // back plane
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(0.0, 0.0, -6.001),
-        normal: glm::vec3(0.0, 0.0, 1.0),
-        material: MaterialType::Lambertian(Lambertian { albedo: white }),
-    }));
+    world.add_plane(
+        glm::vec3(0.0, 0.0, -6.001),
+        glm::vec3(0.0, 0.0, 1.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
     // left plane
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(-4.0, 0.0, 0.0),
-        normal: glm::vec3(1.0, 0.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian { albedo: white }),
-    }));
+    world.add_plane(
+        glm::vec3(-4.0, 0.0, 0.0),
+        glm::vec3(1.0, 0.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
     // right plane
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(4.0, 0.0, 0.0),
-        normal: glm::vec3(-1.0, 0.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian { albedo: white }),
-    }));
+    world.add_plane(
+        glm::vec3(4.0, 0.0, 0.0),
+        glm::vec3(-1.0, 0.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
     // add an area light
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    world.add_rectangle(
         [
             glm::vec3(-1.0, 1.0, -6.0),
             glm::vec3(1.0, 1.0, -6.0),
             glm::vec3(1.0, 3.0, -6.0),
             glm::vec3(-1.0, 3.0, -6.0),
         ],
-        MaterialType::DiffuseLight(DiffuseLight { color: 8.0 * white }),
-    )));
+        MaterialType::DiffuseLight(DiffuseLight::new(8.0 * white)),
+    );
     // colored glass objects
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    world.add_rectangle(
         [
             glm::vec3(-2.5, 0.0, -1.0),
             glm::vec3(-1.25, 0.0, -1.0),
@@ -75,13 +575,17 @@ pub fn colorful_shadows(
             glm::vec3(-2.5, 1.0, -1.0),
         ],
         MaterialType::Transparent(Transparent {
-            albedo: color::color(0, 255, 255),
+            albedo: color::color_linear(0, 255, 255),
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    )));
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    );
+    world.add_rectangle(
         [
             glm::vec3(-1.75, 0.0, 0.0),
             glm::vec3(-0.5, 0.0, 0.0),
@@ -89,13 +593,17 @@ pub fn colorful_shadows(
             glm::vec3(-1.75, 1.0, 0.0),
         ],
         MaterialType::Transparent(Transparent {
-            albedo: color::color(255, 255, 0),
+            albedo: color::color_linear(255, 255, 0),
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    )));
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    );
+    world.add_rectangle(
         [
             glm::vec3(0.0, 0.0, -1.0),
             glm::vec3(0.5, 0.0, -1.0),
@@ -103,29 +611,40 @@ pub fn colorful_shadows(
             glm::vec3(0.0, 1.0, -1.0),
         ],
         MaterialType::Transparent(Transparent {
-            albedo: color::color(255, 50, 255),
+            albedo: color::color_linear(255, 50, 255),
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    )));
+    );
     // glass sphere
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(-0.25, 0.25, 1.5),
-        radius: 0.25,
-        material: MaterialType::Transparent(Transparent {
+    world.add_sphere(
+        glm::vec3(-0.25, 0.25, 1.5),
+        0.25,
+        MaterialType::Transparent(Transparent {
             albedo: white,
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    }));
+    );
     // diffuse sphere
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(1.0, 0.25, 1.5),
-        radius: 0.25,
-        material: MaterialType::Lambertian(Lambertian { albedo: white }),
-    }));
+    world.add_sphere(
+        glm::vec3(1.0, 0.25, 1.5),
+        0.25,
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
 
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(0.0, 0.75, 5.0);
@@ -141,22 +660,18 @@ pub fn colorful_shadows(
         image_width as f32 / image_height as f32,
     );
 
-    let black_sky = |_ray: &Ray| color::color(0, 0, 0);
+    let black_sky = Sky::uniform(color::color_linear(0, 0, 0));
 
-    (world, camera, Vec::new(), black_sky)
+    (world, camera, Vec::new(), black_sky, None)
 }
 
-pub fn infinite_mirror_hallway(
-    image_width: u32,
-    image_height: u32,
-) -> (HittableList, PerspectiveCamera, Vec<Light>, Sky) {
-    let ground_plane_color = color::color(58, 222, 99);
-    let little_ball_color = color::color(0, 255, 0);
+pub fn infinite_mirror_hallway(image_width: u32, image_height: u32) -> Scene {
+    let little_ball_color = color::color_linear(0, 255, 0);
 
     // create world
     let mut world = HittableList::new();
     // add rectangular mirror on either side
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    world.add_rectangle(
         [
             glm::vec3(-2.0, 2.0, 0.0),
             glm::vec3(-2.0, 2.0, -100.0),
@@ -164,10 +679,12 @@ pub fn infinite_mirror_hallway(
             glm::vec3(-2.0, 0.0, 0.0),
         ],
         MaterialType::Metal(Metal {
-            albedo: color::color(255, 255, 255),
+            albedo: color::color_linear(255, 255, 255),
+            fresnel: false,
+            roughness: (0.0, 0.0),
         }),
-    )));
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    );
+    world.add_rectangle(
         [
             glm::vec3(2.0, 2.0, 0.0),
             glm::vec3(2.0, 2.0, -100.0),
@@ -175,25 +692,30 @@ pub fn infinite_mirror_hallway(
             glm::vec3(2.0, 0.0, 0.0),
         ],
         MaterialType::Metal(Metal {
-            albedo: color::color(255, 255, 255),
+            albedo: color::color_linear(255, 255, 255),
+            fresnel: false,
+            roughness: (0.0, 0.0),
         }),
-    )));
+    );
     // little ball
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(0.0, 1.0, -20.0),
-        radius: 0.5,
-        material: MaterialType::Lambertian(Lambertian {
+    world.add_sphere(
+        glm::vec3(0.0, 1.0, -20.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
             albedo: little_ball_color,
+            force_front_face: false,
         }),
-    }));
+    );
     // big ball
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(0.0, 10.0, -15.0),
-        radius: 5.0,
-        material: MaterialType::Metal(Metal {
-            albedo: color::color(255, 255, 255),
+    world.add_sphere(
+        glm::vec3(0.0, 10.0, -15.0),
+        5.0,
+        MaterialType::Metal(Metal {
+            albedo: color::color_linear(255, 255, 255),
+            fresnel: false,
+            roughness: (0.0, 0.0),
         }),
-    }));
+    );
 
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(0.0, 1.0, 1.0);
@@ -209,63 +731,68 @@ pub fn infinite_mirror_hallway(
         image_width as f32 / image_height as f32,
     );
 
-    let sunset_sky_gradient = |ray: &Ray| {
-        let t = ray.direction.x;
-        0.5 * color::color(245, 64, 64) * (1.0 - t) + 1.5 * color::color(255, 201, 34) * t
+    let sunset_sky_gradient = Sky::Gradient {
+        bottom: 0.5 * color::color_linear(245, 64, 64),
+        top: 1.5 * color::color_linear(255, 201, 34),
+        axis: glm::vec3(1.0, 0.0, 0.0),
     };
 
-    (world, camera, Vec::new(), sunset_sky_gradient)
+    (world, camera, Vec::new(), sunset_sky_gradient, None)
 }
 
 /// Simple scene with a ground plane, two spheres, and a triangle.
 ///
 /// # Returns
 /// - The scene as a boxed hittable.
-pub fn simple_primitives(
-    image_width: u32,
-    image_height: u32,
-) -> (HittableList, PerspectiveCamera, Vec<Light>, Sky) {
+pub fn simple_primitives(image_width: u32, image_height: u32) -> Scene {
     // configure object colors
-    let ground_plane_color = color::color(58, 222, 99);
-    let little_ball_color = color::color(194, 90, 250);
-    let white = color::color(255, 255, 255);
-    let ground_ball_color = color::color(242, 78, 190);
-    let triangle_color = color::color(242, 181, 75);
+    let ground_plane_color = color::color_linear(58, 222, 99);
+    let little_ball_color = color::color_linear(194, 90, 250);
+    let white = color::color_linear(255, 255, 255);
+    let ground_ball_color = color::color_linear(242, 78, 190);
+    let triangle_color = color::color_linear(242, 181, 75);
 
     // create world and populate it
     let mut world = HittableList::new();
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(0.2, 0.4, -1.0),
-        radius: 0.5,
-        material: MaterialType::Transparent(Transparent {
+    world.add_sphere(
+        glm::vec3(0.2, 0.4, -1.0),
+        0.5,
+        MaterialType::Transparent(Transparent {
             albedo: white,
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    }));
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(-0.5, 1.0, -2.0),
-        radius: 0.6,
-        material: MaterialType::Lambertian(Lambertian {
+    );
+    world.add_sphere(
+        glm::vec3(-0.5, 1.0, -2.0),
+        0.6,
+        MaterialType::Lambertian(Lambertian {
             albedo: triangle_color,
+            force_front_face: false,
         }),
-    }));
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(0.0, -5.5, -3.0),
-        radius: 5.0,
-        material: MaterialType::Lambertian(Lambertian {
+    );
+    world.add_sphere(
+        glm::vec3(0.0, -5.5, -3.0),
+        5.0,
+        MaterialType::Lambertian(Lambertian {
             albedo: ground_ball_color,
+            force_front_face: false,
         }),
-    }));
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(3.0, -2.0, -7.0),
-        radius: 2.0,
-        material: MaterialType::Lambertian(Lambertian {
+    );
+    world.add_sphere(
+        glm::vec3(3.0, -2.0, -7.0),
+        2.0,
+        MaterialType::Lambertian(Lambertian {
             albedo: little_ball_color,
+            force_front_face: false,
         }),
-    }));
-    world.add(HittableItem::Triangle(Triangle::new(
+    );
+    world.add_triangle(
         [
             glm::vec3(0.5, -0.5, -1.0),
             glm::vec3(-0.5, 0.75, -2.5),
@@ -273,15 +800,18 @@ pub fn simple_primitives(
         ],
         MaterialType::Metal(Metal {
             albedo: triangle_color,
+            fresnel: false,
+            roughness: (0.0, 0.0),
         }),
-    )));
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(0.0, -1.0, 0.0),
-        normal: glm::vec3(0.0, 1.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian {
+    );
+    world.add_plane(
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
             albedo: ground_plane_color,
+            force_front_face: false,
         }),
-    }));
+    );
 
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(-1.0, 0.2, 4.0);
@@ -297,79 +827,74 @@ pub fn simple_primitives(
         image_width as f32 / image_height as f32,
     );
 
-    let sunset_sky_gradient = |ray: &Ray| {
-        let t = ray.direction.x;
-        0.5 * color::color(245, 64, 64) * (1.0 - t) + 1.5 * color::color(255, 201, 34) * t
+    let sunset_sky_gradient = Sky::Gradient {
+        bottom: 0.5 * color::color_linear(245, 64, 64),
+        top: 1.5 * color::color_linear(255, 201, 34),
+        axis: glm::vec3(1.0, 0.0, 0.0),
     };
 
-    // TODO: currently, this architecture doesn't support skyboxes
-    // // load the skybox
-    // let sky = |ray: &Ray| {
-    //     let sky_image = image::open("assets/outside.jpg").unwrap();
-    //     let (sky_width, sky_height) = sky_image.dimensions();
-    //     let horizontal_angle = (-ray.direction.z / ray.direction.x).atan();
-    //     let vertical_angle = (ray.direction.y / -ray.direction.z).atan();
-    //     let u = horizontal_angle / std::f32::consts::PI;
-    //     let v = vertical_angle / std::f32::consts::PI;
-    //     let x = (u * (sky_width as f32 / 2.0) + (sky_width as f32 / 2.0)) as u32;
-    //     let y = (v * (sky_height as f32 / 2.0) + (sky_height as f32 / 2.0)) as u32;
-    //     let image_pixel = sky_image.get_pixel(x, y);
-    //     let pixel = image_pixel.channels();
-    //     glm::vec3(
-    //         pixel[0] as f32 / 255.0,
-    //         pixel[1] as f32 / 255.0,
-    //         pixel[2] as f32 / 255.0,
-    //     )
-    // };
-
-    (world, camera, Vec::new(), sunset_sky_gradient)
-}
-
-pub fn rectangle_light_example(
-    image_width: u32,
-    image_height: u32,
-) -> (HittableList, PerspectiveCamera, Vec<Light>, Sky) {
+    // for an image of the surroundings as the sky, see `Sky::Environment`
+
+    (world, camera, Vec::new(), sunset_sky_gradient, None)
+}
+
+pub fn rectangle_light_example(image_width: u32, image_height: u32) -> Scene {
     // configure object colors
-    let ground_plane_color = color::color(58, 222, 99);
-    let little_ball_color = color::color(194, 90, 250);
-    let white = color::color(255, 255, 255);
+    let ground_plane_color = color::color_linear(58, 222, 99);
+    let little_ball_color = color::color_linear(194, 90, 250);
+    let white = color::color_linear(255, 255, 255);
 
     // create world and populate it
     let mut world = HittableList::new();
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(2.0, 0.5, -3.5),
-        radius: 0.5,
-        material: MaterialType::Lambertian(Lambertian {
+    world.add_sphere(
+        glm::vec3(2.0, 0.5, -3.5),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
             albedo: little_ball_color,
+            force_front_face: false,
         }),
-    }));
-    world.add(HittableItem::Sphere(Sphere {
-        center: glm::vec3(4.0, 0.0, -3.0),
-        radius: 0.5,
-        material: MaterialType::Transparent(Transparent {
+    );
+    world.add_sphere(
+        glm::vec3(4.0, 0.0, -3.0),
+        0.5,
+        MaterialType::Transparent(Transparent {
             albedo: white,
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-    }));
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(0.0, -1.0, 0.0),
-        normal: glm::vec3(0.0, 1.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian {
+    );
+    world.add_plane(
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
             albedo: ground_plane_color,
+            force_front_face: false,
         }),
-    }));
-    // add an area light
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    );
+    // add an area light, which is also sampled directly from the light list
+    world.add_rectangle(
         [
             glm::vec3(3.0, 2.0, -2.0),
             glm::vec3(5.0, 2.0, -2.0),
             glm::vec3(5.0, 2.0, -4.0),
             glm::vec3(3.0, 2.0, -4.0),
         ],
-        MaterialType::DiffuseLight(DiffuseLight { color: 5.0 * white }),
-    )));
+        MaterialType::DiffuseLight(DiffuseLight::new(5.0 * white)),
+    );
+
+    let lights = vec![Light {
+        position: glm::vec3(3.0, 2.0, -2.0),
+        weight: 5.0,
+        edges: [glm::vec3(2.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -2.0)],
+        sphere: None,
+        color: white,
+        emitter_in_world: true,
+    }];
 
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(-1.0, 0.2, 2.0);
@@ -385,18 +910,17 @@ pub fn rectangle_light_example(
         image_width as f32 / image_height as f32,
     );
 
-    let sunset_sky_gradient = |ray: &Ray| {
-        let t = ray.direction.x;
-        0.1 * (0.5 * color::color(245, 64, 64) * (1.0 - t) + 1.5 * color::color(255, 201, 34) * t)
+    let sunset_sky_gradient = Sky::Gradient {
+        bottom: 0.05 * color::color_linear(245, 64, 64),
+        top: 0.15 * color::color_linear(255, 201, 34),
+        axis: glm::vec3(1.0, 0.0, 0.0),
     };
 
-    (world, camera, Vec::new(), sunset_sky_gradient)
+    // the panel light is dim, so the scene is seen a stop brighter
+    (world, camera, lights, sunset_sky_gradient, Some(1.0))
 }
 
-pub fn teapot_caustic(
-    image_width: u32,
-    image_height: u32,
-) -> (HittableList, PerspectiveCamera, Vec<Light>, Sky) {
+pub fn teapot_caustic(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(5.0, 2.0, 20.0);
     let camera_lookat: Vec3 = glm::vec3(0.0, 1.5, 0.0);
@@ -414,53 +938,58 @@ pub fn teapot_caustic(
     let mesh = Mesh::create(
         "assets/teapot.obj",
         MaterialType::Transparent(Transparent {
-            albedo: color::color(255, 255, 255),
+            albedo: color::color_linear(255, 255, 255),
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
         // MaterialType::Lambertian(Lambertian {
-        //     albedo: color::color(128, 128, 128),
+        //     albedo: color::color_linear(128, 128, 128),
+        //     force_front_face: false,
         // }),
-        32,
+        bvh,
+        None,
+        ImportTransform::default(),
     );
 
     let mut world = HittableList::new();
     // teapot
-    world.add(HittableItem::Mesh(mesh));
+    world.add_mesh(mesh);
     // ground plane
-    world.add(HittableItem::Plane(Plane {
-        center: glm::vec3(0.0, -1.0, 0.0),
-        normal: glm::vec3(0.0, 1.0, 0.0),
-        material: MaterialType::Lambertian(Lambertian {
-            albedo: color::color(128, 128, 128),
+    world.add_plane(
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(128, 128, 128),
+            force_front_face: false,
         }),
-    }));
+    );
     // area light
-    world.add(HittableItem::Rectangle(Rectangle::new(
+    world.add_rectangle(
         [
             glm::vec3(-3.0, 5.0, -3.0),
             glm::vec3(3.0, 5.0, -3.0),
             glm::vec3(3.0, 5.0, 3.0),
             glm::vec3(-3.0, 5.0, 3.0),
         ],
-        MaterialType::DiffuseLight(DiffuseLight {
-            color: 5.0 * color::color(255, 255, 255),
-        }),
-    )));
+        MaterialType::DiffuseLight(DiffuseLight::new(5.0 * color::color_linear(255, 255, 255))),
+    );
 
-    let sunset_sky_gradient = |ray: &Ray| {
-        let t = ray.direction.x;
-        0.1 * (0.5 * color::color(245, 64, 64) * (1.0 - t) + 1.5 * color::color(255, 201, 34) * t)
+    let sunset_sky_gradient = Sky::Gradient {
+        bottom: 0.05 * color::color_linear(245, 64, 64),
+        top: 0.15 * color::color_linear(255, 201, 34),
+        axis: glm::vec3(1.0, 0.0, 0.0),
     };
 
-    (world, camera, Vec::new(), sunset_sky_gradient)
+    // the dim caustics read better a stop brighter
+    (world, camera, Vec::new(), sunset_sky_gradient, Some(1.0))
 }
 
-pub fn above_right_dragon(
-    image_width: u32,
-    image_height: u32,
-) -> (Mesh, PerspectiveCamera, Vec<Light>, Sky) {
+pub fn above_right_dragon(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
     // configure camera position
     let camera_origin: Vec3 = glm::vec3(3.0, 3.0, 3.0);
     let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
@@ -478,18 +1007,1730 @@ pub fn above_right_dragon(
     let mesh = Mesh::create(
         "assets/dragon.obj",
         MaterialType::Transparent(Transparent {
-            albedo: color::color(255, 255, 255),
+            albedo: color::color_linear(255, 255, 255),
             reflectance: 0.1,
             transmittance: 0.9,
             refractive_index: 1.3,
+            abbe_number: None,
+            roughness: 0.0,
+            reflect_bias: None,
+            branch_depth: None,
         }),
-        32,
+        bvh,
+        None,
+        ImportTransform::default(),
     );
 
     let gentle_red_gradient_sky = |ray: &Ray| {
+        // squaring keeps the blend in [0, 1], so this needs no remapping
         let t = ray.direction.x;
-        color::color(245, 64, 64) * (1.0 - t * t) + 1.5 * color::color(255, 255, 255) * t * t
+        color::color_linear(245, 64, 64) * (1.0 - t * t)
+            + 1.5 * color::color_linear(255, 255, 255) * t * t
+    };
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+
+    // the bright sky and red-tinted dragon wash out without a stop less
+    (
+        world,
+        camera,
+        Vec::new(),
+        Sky::Custom(gentle_red_gradient_sky),
+        Some(-1.0),
+    )
+}
+
+/// A hollow glass bubble: a glass sphere with a slightly smaller,
+/// negative-radius sphere inside it, so the inner surface refracts back out
+/// of the glass instead of into a solid ball.
+pub fn hollow_glass_bubble(image_width: u32, image_height: u32) -> Scene {
+    let glass = MaterialType::Transparent(Transparent {
+        albedo: color::color_linear(255, 255, 255),
+        reflectance: 0.1,
+        transmittance: 0.9,
+        refractive_index: 1.5,
+        abbe_number: None,
+        roughness: 0.0,
+        reflect_bias: None,
+        branch_depth: None,
+    });
+
+    let mut world = HittableList::new();
+    // outer surface of the shell
+    world.add_sphere(glm::vec3(0.0, 0.5, -1.0), 0.5, glass);
+    // inner surface of the shell, with normals pointing inward
+    world.add_sphere(glm::vec3(0.0, 0.5, -1.0), -0.45, glass);
+    // solid glass ball for comparison
+    world.add_sphere(glm::vec3(1.2, 0.5, -1.0), 0.5, glass);
+    // diffuse ball behind the glass to see the refraction against
+    world.add_sphere(
+        glm::vec3(0.6, 0.5, -3.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(194, 90, 250),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(58, 222, 99),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.6, 0.8, 3.0);
+    let camera_lookat: Vec3 = glm::vec3(0.6, 0.5, -1.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let sunset_sky_gradient = Sky::Gradient {
+        bottom: 0.5 * color::color_linear(245, 64, 64),
+        top: 1.5 * color::color_linear(255, 201, 34),
+        axis: glm::vec3(1.0, 0.0, 0.0),
     };
 
-    (mesh, camera, Vec::new(), gentle_red_gradient_sky)
+    (world, camera, Vec::new(), sunset_sky_gradient, None)
+}
+
+/// A sphere mesh with a directional emitter. With the angular falloff, the
+/// mesh glows brightest where its surface faces the camera head-on and fades
+/// toward its silhouette.
+pub fn spotlight_mesh(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/sphere.obj",
+        MaterialType::DiffuseLight(
+            DiffuseLight::new(4.0 * color::color_linear(255, 230, 180)).with_falloff(4.0),
+        ),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane to catch the light
+    world.add_plane(
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(128, 128, 128),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 0.5, 6.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let black_sky = Sky::uniform(color::color_linear(0, 0, 0));
+
+    (world, camera, Vec::new(), black_sky, None)
+}
+
+/// A leaf card: a textured quad whose alpha channel cuts out the leaf shape,
+/// held above the ground under an area light so it casts a leaf-shaped
+/// shadow.
+pub fn foliage_card(image_width: u32, image_height: u32) -> Scene {
+    let white = color::color_linear(255, 255, 255);
+    let leaf = MaterialType::Textured(Textured {
+        texture: ImageTexture::new("assets/leaf.png", ColorSpace::Srgb),
+        alpha_threshold: 0.5,
+        force_front_face: false,
+    });
+
+    let mut world = HittableList::new();
+    // leaf card, tilted toward the camera
+    world.add_rectangle(
+        [
+            glm::vec3(-0.5, 0.6, 0.3),
+            glm::vec3(0.5, 0.6, 0.3),
+            glm::vec3(0.5, 1.4, -0.3),
+            glm::vec3(-0.5, 1.4, -0.3),
+        ],
+        leaf,
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: white,
+            force_front_face: false,
+        }),
+    );
+    // area light straight above the leaf
+    world.add_rectangle(
+        [
+            glm::vec3(-0.5, 4.0, -0.5),
+            glm::vec3(0.5, 4.0, -0.5),
+            glm::vec3(0.5, 4.0, 0.5),
+            glm::vec3(-0.5, 4.0, 0.5),
+        ],
+        MaterialType::DiffuseLight(DiffuseLight::new(10.0 * white)),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.5, 4.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.5, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let dim_sky = Sky::uniform(0.1 * color::color_linear(200, 220, 255));
+
+    (world, camera, Vec::new(), dim_sky, None)
+}
+
+/// A tall tower seen from near the ground. The camera looks level so the
+/// tower's vertical edges stay parallel, and the sensor is shifted up to
+/// bring the top of the tower into frame instead of tilting the camera.
+pub fn tilt_shift_tower(image_width: u32, image_height: u32) -> Scene {
+    let wall = MaterialType::Lambertian(Lambertian {
+        albedo: color::color_linear(242, 181, 75),
+        force_front_face: false,
+    });
+
+    let mut world = HittableList::new();
+    // four walls of the tower
+    let corners = [
+        glm::vec3(-0.5, 0.0, 0.5),
+        glm::vec3(0.5, 0.0, 0.5),
+        glm::vec3(0.5, 0.0, -0.5),
+        glm::vec3(-0.5, 0.0, -0.5),
+    ];
+    let height = glm::vec3(0.0, 4.0, 0.0);
+    for side in 0..4 {
+        let start = corners[side];
+        let end = corners[(side + 1) % 4];
+        world.add_rectangle([start, end, end + height, start + height], wall);
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(58, 222, 99),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(2.0, 0.5, 6.0);
+    let camera_lookat: Vec3 = glm::vec3(2.0, 0.5, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera, shifted up and left to frame the whole tower
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        45.0,
+        image_width as f32 / image_height as f32,
+    )
+    .with_sensor_shift(-0.3, 0.35);
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// A cube whose faces don't share vertices. Welding merges the split
+/// corners, so the shared normals shade the cube smoothly instead of as six
+/// flat faces. Pass `None` for the weld epsilon to compare.
+pub fn welded_cube(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/split_cube.obj",
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(194, 90, 250),
+            force_front_face: false,
+        }),
+        bvh,
+        Some(0.0001),
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, -0.5, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(128, 128, 128),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(2.0, 1.5, 3.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// A sphere on the ground under a square area light from the scene's light
+/// list. The light is sampled directly at every diffuse hit, so the soft
+/// shadow gets smoother as `--light-samples` goes up, e.g. compare
+/// `--light-samples 1` with `--light-samples 16` at the same `--aa`.
+pub fn area_light_penumbra(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(0.0, 1.0, 0.0),
+        1.0,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(194, 90, 250),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // a 2x2 light above and to the left of the sphere
+    let lights = vec![Light {
+        position: glm::vec3(-3.0, 4.0, -1.0),
+        weight: 16.0,
+        edges: [glm::vec3(2.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 2.0)],
+        sphere: None,
+        color: color::color_linear(255, 244, 229),
+        emitter_in_world: false,
+    }];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.5, 7.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.8, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let black_sky = Sky::uniform(color::color_linear(0, 0, 0));
+
+    (world, camera, lights, black_sky, None)
+}
+
+/// A stack of glass panes in front of a sphere. Seeing the sphere takes two
+/// refractions per pane, while the reflections between panes only add faint
+/// ghosts, so a render like `--refraction-depth 16 --reflection-depth 1`
+/// keeps the view through all five panes but stops the reflected rays early.
+pub fn glass_pane_stack(image_width: u32, image_height: u32) -> Scene {
+    let glass = MaterialType::Transparent(Transparent {
+        albedo: color::color_linear(235, 250, 245),
+        reflectance: 0.1,
+        transmittance: 0.9,
+        refractive_index: 1.5,
+        abbe_number: None,
+        roughness: 0.0,
+        reflect_bias: None,
+        branch_depth: None,
+    });
+
+    let mut world = HittableList::new();
+    for pane in 0..5 {
+        let front = 1.0 - pane as f32 * 0.5;
+        let back = front - 0.05;
+        // the front face faces the camera and the back face faces away
+        world.add_rectangle(
+            [
+                glm::vec3(-1.0, 0.0, front),
+                glm::vec3(1.0, 0.0, front),
+                glm::vec3(1.0, 1.5, front),
+                glm::vec3(-1.0, 1.5, front),
+            ],
+            glass,
+        );
+        world.add_rectangle(
+            [
+                glm::vec3(-1.0, 0.0, back),
+                glm::vec3(-1.0, 1.5, back),
+                glm::vec3(1.0, 1.5, back),
+                glm::vec3(1.0, 0.0, back),
+            ],
+            glass,
+        );
+    }
+    world.add_sphere(
+        glm::vec3(0.0, 0.6, -2.5),
+        0.6,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(250, 90, 60),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(128, 128, 128),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.8, 1.2, 4.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.6, -1.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// Two gold spheres, the left one with the legacy mirror reflection and the
+/// right one with Fresnel reflection. The right sphere is tinted gold where
+/// it faces the camera and brightens to a white rim toward its silhouette.
+pub fn fresnel_metal_spheres(image_width: u32, image_height: u32) -> Scene {
+    let gold = color::color_linear(255, 195, 86);
+
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(-1.1, 1.0, 0.0),
+        1.0,
+        MaterialType::Metal(Metal {
+            albedo: gold,
+            fresnel: false,
+            roughness: (0.0, 0.0),
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(1.1, 1.0, 0.0),
+        1.0,
+        MaterialType::Metal(Metal {
+            albedo: gold,
+            fresnel: true,
+            roughness: (0.0, 0.0),
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(90, 90, 90),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.5, 6.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.9, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// The sphere mesh drawn with a wireframe material, which outlines every
+/// triangle using the barycentric coordinates of the hits.
+pub fn wireframe_sphere(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/sphere.obj",
+        MaterialType::Wireframe(Wireframe {
+            albedo: color::color_linear(230, 230, 230),
+            edge_color: color::color_linear(20, 60, 200),
+            edge_width: 0.04,
+        }),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.0, 5.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// A torus whose OBJ gives every vertex a color, shaded with `VertexColor`
+/// over a ground plane. The colors blend smoothly across each triangle, so
+/// the hue changes gradually around the ring instead of in flat bands.
+pub fn vertex_color_torus(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    // the torus's colors change hue around its ring, one color per vertex
+    let mesh = Mesh::create(
+        "assets/color_torus.obj",
+        MaterialType::VertexColor(VertexColor {
+            albedo: color::color_linear(230, 230, 230),
+        }),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, -0.4, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.5, 4.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 20.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+pub fn environment_spheres(image_width: u32, image_height: u32) -> Scene {
+    // diffuse spheres lit only by the environment, so turning it with
+    // `--sky-rotation` turns the lighting too
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(-1.2, 0.0, 0.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(230, 230, 230),
+            force_front_face: false,
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(0.0, 0.0, 0.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(230, 120, 90),
+            force_front_face: false,
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(1.2, 0.0, 0.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(90, 140, 230),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 0.3, 4.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let sky = Sky::Environment(EnvironmentMap::new("assets/outside.jpg"));
+
+    (world, camera, Vec::new(), sky, None)
+}
+
+/// A wavy cloth sheet whose triangles are wound randomly either way. Its
+/// smooth normals partly cancel, so it is shaded with the face normals by
+/// forcing the front face.
+pub fn mixed_winding_cloth(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/mixed_winding_cloth.obj",
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(220, 80, 80),
+            force_front_face: true,
+        }),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, -0.5, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.6, 2.6);
+    let camera_lookat: Vec3 = glm::vec3(0.0, -0.1, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        45.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(1.5, 3.0, 2.0), 12.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+/// A sphere resting just above the ground under an overcast sky, for
+/// comparing renders with and without `--ao-strength`.
+pub fn contact_shadow_sphere(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(0.0, 0.52, 0.0),
+        0.5,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.2, 3.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.4, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let overcast_sky = Sky::Gradient {
+        bottom: color::color_linear(200, 200, 200),
+        top: color::color_linear(255, 255, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), overcast_sky, None)
+}
+
+/// Every OBJ file in `assets/obj_directory`, each in its own color, on a
+/// ground plane.
+pub fn obj_directory(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    // pick each file's color from its name
+    let material = |path: &std::path::Path| {
+        let albedo = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some("cube") => color::color_linear(230, 90, 80),
+            Some("pyramid") => color::color_linear(90, 200, 110),
+            Some("octahedron") => color::color_linear(90, 130, 230),
+            _ => color::color_linear(200, 200, 200),
+        };
+        MaterialType::Lambertian(Lambertian {
+            albedo,
+            force_front_face: false,
+        })
+    };
+
+    let mut world = HittableList::new();
+    if let Err(error) = world.add_obj_directory("assets/obj_directory", material, bvh, true) {
+        eprintln!("warning: couldn't read assets/obj_directory: {}", error);
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.0, 4.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.4, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 20.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+/// A small and a large square light with the same power, each above a
+/// sphere on a ground plane. Both halves of the image come out equally bright.
+pub fn equal_power_lights(image_width: u32, image_height: u32) -> Scene {
+    let gray = MaterialType::Lambertian(Lambertian {
+        albedo: color::color_linear(200, 200, 200),
+        force_front_face: false,
+    });
+
+    let mut world = HittableList::new();
+    let mut lights = Vec::new();
+    // ground plane
+    world.add_plane(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), gray);
+    for &(x, size) in [(-2.0f32, 0.4f32), (2.0, 1.6)].iter() {
+        world.add_sphere(glm::vec3(x, 0.5, 0.0), 0.5, gray);
+        let half = size / 2.0;
+        let light = DiffuseLight::from_power(60.0, size * size);
+        world.add_rectangle(
+            [
+                glm::vec3(x - half, 3.0, -half),
+                glm::vec3(x + half, 3.0, -half),
+                glm::vec3(x + half, 3.0, half),
+                glm::vec3(x - half, 3.0, half),
+            ],
+            MaterialType::DiffuseLight(light),
+        );
+        lights.push(Light {
+            position: glm::vec3(x - half, 3.0, -half),
+            weight: light.color.x,
+            edges: [glm::vec3(size, 0.0, 0.0), glm::vec3(0.0, 0.0, size)],
+            sphere: None,
+            color: glm::vec3(1.0, 1.0, 1.0),
+            emitter_in_world: true,
+        });
+    }
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.0, 7.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.8, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let black_sky = Sky::uniform(glm::vec3(0.0, 0.0, 0.0));
+
+    (world, camera, lights, black_sky, None)
+}
+
+/// A brushed metal cylinder whose u texture coordinate runs around it, so the
+/// reflection of a narrow light stretches around the surface.
+pub fn brushed_cylinder(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/brushed_cylinder.obj",
+        MaterialType::Metal(Metal {
+            albedo: color::color_linear(230, 230, 235),
+            fresnel: true,
+            roughness: (0.4, 0.02),
+        }),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(120, 120, 120),
+            force_front_face: false,
+        }),
+    );
+    // tall, narrow light behind the camera
+    world.add_rectangle(
+        [
+            glm::vec3(-0.95, -0.8, 6.0),
+            glm::vec3(-0.55, -0.8, 6.0),
+            glm::vec3(-0.55, 2.5, 6.0),
+            glm::vec3(-0.95, 2.5, 6.0),
+        ],
+        MaterialType::DiffuseLight(DiffuseLight::new(12.0 * color::color_linear(255, 255, 255))),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(1.5, 1.0, 4.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 10.0)];
+
+    let dim_sky = Sky::Gradient {
+        bottom: 0.2 * color::color_linear(255, 255, 255),
+        top: 0.2 * color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, dim_sky, None)
+}
+
+/// Two glass spheres in exactly the same place. Paths can get caught
+/// bouncing between the shared surfaces, see `Ray::escape_stall`.
+pub fn coincident_glass(image_width: u32, image_height: u32) -> Scene {
+    let glass = MaterialType::Transparent(Transparent {
+        albedo: color::color_linear(255, 255, 255),
+        reflectance: 0.1,
+        transmittance: 0.9,
+        refractive_index: 1.5,
+        abbe_number: None,
+        roughness: 0.0,
+        reflect_bias: None,
+        branch_depth: None,
+    });
+
+    let mut world = HittableList::new();
+    world.add_sphere(glm::vec3(0.0, 0.5, 0.0), 0.5, glass);
+    world.add_sphere(glm::vec3(0.0, 0.5, 0.0), 0.5, glass);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.2, 3.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.5, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 20.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+/// A house modelled with Z up, rotated upright as it is loaded so its roof
+/// points up.
+pub fn z_up_house(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/z_up_house.obj",
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(220, 120, 80),
+            force_front_face: false,
+        }),
+        bvh,
+        None,
+        ImportTransform {
+            up_axis: UpAxis::Z,
+            flip_x: false,
+            normalize: false,
+        },
+    );
+
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(2.0, 1.8, 3.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.7, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 20.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+/// An L and a five pointed star standing on the ground, flat concave
+/// polygons split into triangles by ear clipping.
+pub fn concave_polygons(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_polygon(
+        &[
+            glm::vec3(-2.0, 0.0, 0.0),
+            glm::vec3(-0.6, 0.0, 0.0),
+            glm::vec3(-0.6, 0.5, 0.0),
+            glm::vec3(-1.5, 0.5, 0.0),
+            glm::vec3(-1.5, 2.0, 0.0),
+            glm::vec3(-2.0, 2.0, 0.0),
+        ],
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(60, 120, 220),
+            force_front_face: false,
+        }),
+    );
+    let star: Vec<Vec3> = (0..10)
+        .map(|index| {
+            let angle = std::f32::consts::FRAC_PI_2 + index as f32 * std::f32::consts::PI / 5.0;
+            let radius = if index % 2 == 0 { 1.0 } else { 0.4 };
+            glm::vec3(1.0 + radius * angle.cos(), 1.0 + radius * angle.sin(), 0.0)
+        })
+        .collect();
+    world.add_polygon(
+        &star,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(230, 190, 40),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.5, 5.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let lights = vec![Light::point(glm::vec3(2.0, 4.0, 3.0), 20.0)];
+
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(128, 178, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, blue_sky, None)
+}
+
+/// A beam of white light from a slit in a dark wall, split into a spectrum
+/// on the far wall by a strongly dispersive glass prism. The beam is only
+/// found by diffuse bounces from the wall, so it needs many samples.
+pub fn prism_rainbow(image_width: u32, image_height: u32) -> Scene {
+    let black = MaterialType::Lambertian(Lambertian {
+        albedo: color::color_linear(0, 0, 0),
+        force_front_face: false,
+    });
+    let white = MaterialType::Lambertian(Lambertian {
+        albedo: color::color_linear(230, 230, 230),
+        force_front_face: false,
+    });
+
+    let mut world = HittableList::new();
+    // prism with its apex down, so the beam is bent up onto the wall
+    let cross_section = [
+        glm::vec3(-0.5, 2.0, 0.0),
+        glm::vec3(0.0, 2.0 - 0.75f32.sqrt(), 0.0),
+        glm::vec3(0.5, 2.0, 0.0),
+    ];
+    let front = glm::vec3(0.0, 0.0, 0.6);
+    let glass = MaterialType::Transparent(Transparent {
+        albedo: color::color_linear(255, 255, 255),
+        reflectance: 0.05,
+        transmittance: 0.95,
+        refractive_index: 1.6,
+        abbe_number: Some(8.0),
+        roughness: 0.0,
+        reflect_bias: None,
+        branch_depth: None,
+    });
+    world.add_polygon(&cross_section.map(|corner| corner + front), glass);
+    world.add_polygon(
+        &[
+            cross_section[0] - front,
+            cross_section[2] - front,
+            cross_section[1] - front,
+        ],
+        glass,
+    );
+    for edge in 0..3 {
+        let start = cross_section[edge];
+        let end = cross_section[(edge + 1) % 3];
+        world.add_polygon(
+            &[start + front, start - front, end - front, end + front],
+            glass,
+        );
+    }
+    // dark wall with a slit for the beam, and the light behind it
+    world.add_rectangle(
+        [
+            glm::vec3(-2.0, 0.0, 3.0),
+            glm::vec3(-2.0, 0.0, -3.0),
+            glm::vec3(-2.0, 2.27, -3.0),
+            glm::vec3(-2.0, 2.27, 3.0),
+        ],
+        black,
+    );
+    world.add_rectangle(
+        [
+            glm::vec3(-2.0, 2.36, 3.0),
+            glm::vec3(-2.0, 2.36, -3.0),
+            glm::vec3(-2.0, 6.0, -3.0),
+            glm::vec3(-2.0, 6.0, 3.0),
+        ],
+        black,
+    );
+    world.add_rectangle(
+        [
+            glm::vec3(-5.0, 3.4, 0.5),
+            glm::vec3(-5.0, 3.4, -0.5),
+            glm::vec3(-5.0, 3.8, -0.5),
+            glm::vec3(-5.0, 3.8, 0.5),
+        ],
+        MaterialType::DiffuseLight(DiffuseLight::new(
+            400.0 * color::color_linear(255, 255, 255),
+        )),
+    );
+    // wall the spectrum lands on, and the floor
+    world.add_plane(glm::vec3(3.0, 0.0, 0.0), glm::vec3(-1.0, 0.0, 0.0), white);
+    world.add_plane(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0), white);
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(-1.0, 2.2, 5.5);
+    let camera_lookat: Vec3 = glm::vec3(1.2, 2.1, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        45.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    // just enough light to make out the room around the beam
+    let dim_sky = Sky::uniform(0.02 * color::color_linear(128, 150, 200));
+
+    (world, camera, Vec::new(), dim_sky, None)
+}
+
+/// Spheres on a ground plane lit by a small glowing sphere, which is
+/// sampled directly as a light so its soft shadows are smooth.
+pub fn sphere_sun(image_width: u32, image_height: u32) -> Scene {
+    let sun_radiance = 40.0;
+    let sun_color = color::color_linear(255, 236, 200);
+    let sun = Sphere {
+        center: glm::vec3(-2.0, 4.0, -1.0),
+        radius: 0.5,
+        material: MaterialType::DiffuseLight(DiffuseLight::new(sun_radiance * sun_color)),
+        velocity: glm::vec3(0.0, 0.0, 0.0),
+    };
+
+    let mut world = HittableList::new();
+    world.add_sphere(sun.center, sun.radius, sun.material);
+    world.add_sphere(
+        glm::vec3(0.0, 0.7, 0.0),
+        0.7,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 80, 70),
+            force_front_face: false,
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(1.4, 0.4, 0.8),
+        0.4,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(70, 120, 200),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 200, 200),
+            force_front_face: false,
+        }),
+    );
+
+    let lights = vec![Light::sphere(sun, sun_radiance, sun_color)];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.5, 2.0, 6.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.8, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let night_sky = Sky::Gradient {
+        bottom: 0.05 * color::color_linear(255, 255, 255),
+        top: 0.05 * color::color_linear(90, 120, 200),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, night_sky, None)
+}
+
+/// Spheres on open ground in the afternoon, lit only by a daylight sky and
+/// its sun. The mirror sphere shows the sky's gradient and the ground below
+/// the horizon.
+pub fn daylight_spheres(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(-1.2, 0.7, 0.0),
+        0.7,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(210, 70, 60),
+            force_front_face: false,
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(0.4, 0.6, -0.6),
+        0.6,
+        MaterialType::Metal(Metal {
+            albedo: color::color_linear(230, 230, 230),
+            fresnel: false,
+            roughness: (0.0, 0.0),
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(1.6, 0.4, 0.6),
+        0.4,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(230, 230, 230),
+            force_front_face: false,
+        }),
+    );
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(190, 170, 140),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.2, 5.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.9, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        45.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    // afternoon sun behind and to the left, on a clear day
+    let sky = Daylight::new(glm::vec3(-0.6, 0.5, -0.6), 3.0);
+
+    (world, camera, Vec::new(), Sky::Daylight(sky), None)
+}
+
+/// Three glass spheres in front of a striped wall, growing rougher from
+/// left to right. Clear glass shows the stripes sharp and flipped, and the
+/// rougher spheres blur them more, along with their reflections of the sky.
+/// Every glass hit traces both a reflection and a refraction, so budgets
+/// like `--reflection-depth 2 --refraction-depth 8` keep renders quick.
+pub fn frosted_glass(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    for (index, &roughness) in [0.0, 0.05, 0.2].iter().enumerate() {
+        world.add_sphere(
+            glm::vec3(index as f32 * 1.3 - 1.3, 0.6, 0.0),
+            0.6,
+            MaterialType::Transparent(Transparent {
+                albedo: color::color_linear(255, 255, 255),
+                reflectance: 0.05,
+                transmittance: 0.95,
+                refractive_index: 1.5,
+                abbe_number: None,
+                roughness,
+                reflect_bias: None,
+                branch_depth: None,
+            }),
+        );
+    }
+    // vertical stripes on a wall behind the spheres
+    let stripe_width = 0.15;
+    for stripe in 0..40 {
+        let left = stripe as f32 * stripe_width - 3.0;
+        let albedo = if stripe % 2 == 0 {
+            color::color_linear(230, 230, 230)
+        } else {
+            color::color_linear(40, 60, 160)
+        };
+        world.add_rectangle(
+            [
+                glm::vec3(left, 0.0, -1.5),
+                glm::vec3(left + stripe_width, 0.0, -1.5),
+                glm::vec3(left + stripe_width, 3.0, -1.5),
+                glm::vec3(left, 3.0, -1.5),
+            ],
+            MaterialType::Lambertian(Lambertian {
+                albedo,
+                force_front_face: false,
+            }),
+        );
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(150, 150, 150),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.0, 4.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.6, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let light = Light::point(glm::vec3(1.0, 4.0, 4.0), 20.0);
+    let sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(150, 180, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, vec![light], sky, None)
+}
+
+/// A sphere resting on an invisible floor which only shows the soft shadow
+/// cast on it by an area light, for compositing onto a photo. Without
+/// `--alpha` the shadow darkens the sky seen through the floor, and with it
+/// the background is transparent apart from the shadow.
+pub fn shadow_catcher(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_sphere(
+        glm::vec3(0.0, 1.0, 0.0),
+        1.0,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(230, 120, 60),
+            force_front_face: false,
+        }),
+    );
+    // the floor, seen only where it is in shadow. It stops well short of
+    // the horizon, since shadow rays from points thousands of units away
+    // are too imprecise to trust.
+    world.add_rectangle(
+        [
+            glm::vec3(-20.0, 0.0, 20.0),
+            glm::vec3(20.0, 0.0, 20.0),
+            glm::vec3(20.0, 0.0, -20.0),
+            glm::vec3(-20.0, 0.0, -20.0),
+        ],
+        MaterialType::ShadowCatcher(ShadowCatcher {
+            shadow_opacity: 0.8,
+        }),
+    );
+
+    // a 2x2 light above and to the left of the sphere
+    let lights = vec![Light {
+        position: glm::vec3(-3.0, 4.0, -1.0),
+        weight: 16.0,
+        edges: [glm::vec3(2.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 2.0)],
+        sphere: None,
+        color: color::color_linear(255, 244, 229),
+        emitter_in_world: false,
+    }];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.5, 7.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.8, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        35.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(150, 180, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, lights, sky, None)
+}
+
+/// A rippled pool of water under a blue sky, with a sphere resting on
+/// the sandy floor beneath it. Looking down into the water the sphere and
+/// floor are seen refracted and wobbling through the ripples, and further
+/// off the surface turns mirror-like and reflects the sky. Every water hit
+/// traces both a reflection and a refraction, so budgets like
+/// `--reflection-depth 2 --refraction-depth 4` keep renders quick.
+pub fn rippled_water(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Water(Water {
+            albedo: color::color_linear(215, 240, 235),
+            refractive_index: 1.33,
+            ripple_height: 0.01,
+            ripple_wavelength: 0.6,
+        }),
+    );
+    world.add_sphere(
+        glm::vec3(0.3, -0.7, -0.5),
+        0.8,
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(220, 80, 60),
+            force_front_face: false,
+        }),
+    );
+    // floor of the pool
+    world.add_plane(
+        glm::vec3(0.0, -1.5, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(200, 180, 130),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 2.0, 4.5);
+    let camera_lookat: Vec3 = glm::vec3(0.0, -0.6, -0.5);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        50.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    // a soft sky with no sun, since light reaching the pool floor is only
+    // found by diffuse bounces refracted back out, which turn a small
+    // bright sun into speckles
+    let blue_sky = Sky::Gradient {
+        bottom: color::color_linear(255, 255, 255),
+        top: color::color_linear(90, 150, 255),
+        axis: glm::vec3(0.0, 1.0, 0.0),
+    };
+
+    (world, camera, Vec::new(), blue_sky, None)
+}
+
+/// The dragon mesh, which has no texture coordinates, textured with rock by
+/// projecting the image along each axis, see `TriplanarTexture`. The body's
+/// sides and back face the x and y axes, where a single projection shows.
+pub fn rock_dragon(image_width: u32, image_height: u32, bvh: BvhSettings) -> Scene {
+    let mesh = Mesh::create(
+        "assets/dragon.obj",
+        MaterialType::Triplanar(Triplanar {
+            texture: TriplanarTexture::new("assets/rock.png", 0.6),
+        }),
+        bvh,
+        None,
+        ImportTransform::default(),
+    );
+    let mut world = HittableList::new();
+    world.add_mesh(mesh);
+    // ground plane under the dragon's feet
+    world.add_plane(
+        glm::vec3(0.0, -0.55, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(110, 100, 85),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(2.6, 0.9, 1.8);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.1, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    // midday sun from over the camera's shoulder
+    let sky = Daylight::new(glm::vec3(0.5, 0.8, 0.4), 3.0);
+
+    // the midday sun washes out the texture without a stop less
+    (world, camera, Vec::new(), Sky::Daylight(sky), Some(-1.0))
+}
+
+/// A ball racing past a row of posts, seen through a rolling shutter which
+/// exposes the rows of the image one after another. The ball has moved on
+/// by the time the lower rows are exposed, so it leans over into a slanted
+/// smear while the still posts stay upright. `--rolling-shutter 0` exposes
+/// every row at once instead, for plain motion blur.
+pub fn rolling_shutter(image_width: u32, image_height: u32) -> Scene {
+    let mut world = HittableList::new();
+    world.add(HittableItem::Sphere(Sphere {
+        center: glm::vec3(-1.4, 0.8, 0.0),
+        radius: 0.5,
+        material: MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(220, 60, 50),
+            force_front_face: false,
+        }),
+        velocity: glm::vec3(2.8, 0.0, 0.0),
+    }));
+    // thin posts behind the ball's path
+    for index in 0..5 {
+        let x = index as f32 - 2.0;
+        world.add_rectangle(
+            [
+                glm::vec3(x - 0.05, 0.0, -1.2),
+                glm::vec3(x + 0.05, 0.0, -1.2),
+                glm::vec3(x + 0.05, 2.0, -1.2),
+                glm::vec3(x - 0.05, 2.0, -1.2),
+            ],
+            MaterialType::Lambertian(Lambertian {
+                albedo: color::color_linear(230, 230, 230),
+                force_front_face: false,
+            }),
+        );
+    }
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(120, 130, 110),
+            force_front_face: false,
+        }),
+    );
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.0, 1.0, 5.0);
+    let camera_lookat: Vec3 = glm::vec3(0.0, 0.9, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera whose shutter takes most of the frame to read out
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    )
+    .with_shutter(Shutter {
+        readout: 0.8,
+        profile: ShutterProfile::Box,
+    });
+
+    let sky = Daylight::new(glm::vec3(0.4, 0.7, 0.6), 3.0);
+
+    (world, camera, Vec::new(), Sky::Daylight(sky), None)
+}
+
+/// Wax spheres lit from behind by a spherical light, with only a dim sky in
+/// front, see `Subsurface`. Light walking through the thin rims of the
+/// spheres, and through the whole of the smallest, makes them glow, while
+/// their middles stay dark and deeply tinted.
+pub fn wax_spheres(image_width: u32, image_height: u32) -> Scene {
+    let light_radiance = 60.0;
+    let light_color = color::color_linear(255, 226, 180);
+    let lamp = Sphere {
+        center: glm::vec3(0.4, 0.7, -2.0),
+        radius: 0.3,
+        material: MaterialType::DiffuseLight(DiffuseLight::new(light_radiance * light_color)),
+        velocity: glm::vec3(0.0, 0.0, 0.0),
+    };
+    let wax = |mean_free_path: f32| {
+        MaterialType::Subsurface(Subsurface {
+            albedo: glm::vec3(0.995, 0.98, 0.94),
+            mean_free_path,
+            max_scatter_events: 256,
+        })
+    };
+
+    let mut world = HittableList::new();
+    world.add_sphere(lamp.center, lamp.radius, lamp.material);
+    world.add_sphere(glm::vec3(-1.3, 0.8, -0.3), 0.8, wax(0.05));
+    world.add_sphere(glm::vec3(0.4, 0.55, 0.0), 0.55, wax(0.05));
+    world.add_sphere(glm::vec3(1.5, 0.25, 0.4), 0.25, wax(0.05));
+    // ground plane
+    world.add_plane(
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        MaterialType::Lambertian(Lambertian {
+            albedo: color::color_linear(90, 80, 75),
+            force_front_face: false,
+        }),
+    );
+
+    let lights = vec![Light::sphere(lamp, light_radiance, light_color)];
+
+    // configure camera position
+    let camera_origin: Vec3 = glm::vec3(0.4, 1.3, 5.0);
+    let camera_lookat: Vec3 = glm::vec3(0.3, 0.6, 0.0);
+    let camera_up: Vec3 = glm::vec3(0.0, 1.0, 0.0);
+
+    // create a camera
+    let camera = PerspectiveCamera::new(
+        camera_origin,
+        camera_lookat,
+        camera_up,
+        40.0,
+        image_width as f32 / image_height as f32,
+    );
+
+    let dim_sky = Sky::uniform(0.03 * color::color_linear(200, 210, 255));
+
+    (world, camera, lights, dim_sky, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_parameter_maps_extreme_directions_to_the_ends() {
+        assert_eq!(gradient_parameter(-1.0), 0.0);
+        assert_eq!(gradient_parameter(0.0), 0.5);
+        assert_eq!(gradient_parameter(1.0), 1.0);
+        // a normalized direction can round a little past unit length
+        assert_eq!(gradient_parameter(1.0 + 1e-6), 1.0);
+        assert_eq!(gradient_parameter(-1.0 - 1e-6), 0.0);
+    }
+
+    #[test]
+    fn gradient_sky_shows_top_along_its_axis_and_bottom_against_it() {
+        let (bottom, top) = (glm::vec3(0.8, 0.6, 0.2), glm::vec3(0.1, 0.3, 0.9));
+        let sky = Sky::Gradient {
+            bottom,
+            top,
+            // not normalized as given
+            axis: glm::vec3(0.0, 2.0, 0.0),
+        };
+        let looking =
+            |direction: Vec3| sky.color(&Ray::new(glm::vec3(0.0, 0.0, 0.0), direction, None));
+        assert_eq!(looking(glm::vec3(0.0, 1.0, 0.0)), top);
+        assert_eq!(looking(glm::vec3(0.0, -1.0, 0.0)), bottom);
+        assert!(glm::distance(&looking(glm::vec3(1.0, 0.0, 0.0)), &((bottom + top) / 2.0)) < 1e-6);
+
+        // a quarter turn counterclockwise from above takes +x to -z
+        let tilted = Sky::Gradient {
+            bottom,
+            top,
+            axis: glm::vec3(1.0, 0.0, 0.0),
+        }
+        .with_yaw(90.0);
+        let toward =
+            |direction: Vec3| tilted.color(&Ray::new(glm::vec3(0.0, 0.0, 0.0), direction, None));
+        assert!(glm::distance(&toward(glm::vec3(0.0, 0.0, -1.0)), &top) < 1e-6);
+        assert!(glm::distance(&toward(glm::vec3(0.0, 0.0, 1.0)), &bottom) < 1e-6);
+    }
 }
@@ -0,0 +1,845 @@
+use super::DEPTH_LIMIT;
+use super::EPSILON;
+use super::IMAGE_HEIGHT;
+use super::IMAGE_WIDTH;
+use super::MAX_HIT_DISTANCE;
+use super::SAMPLES_LEVEL;
+use crate::camera::Handedness;
+use crate::camera::ShutterProfile;
+use crate::hittable::bvh::BvhSettings;
+use crate::hittable::Hittable;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::MaterialType;
+use crate::material::ShadingContext;
+use crate::stereo::StereoLayout;
+
+/// Diagnostic images which can be rendered instead of the shaded image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DebugPass {
+    /// Color each pixel by the ID of the object its center ray hits first
+    ObjectId,
+    /// Shade the first surface each camera ray hits with only the direct
+    /// light from the scene's lights and sun, as if it were white and
+    /// diffuse, see `aov::trace_direct_lighting`
+    Lighting,
+    /// Shade the first surface each camera ray hits with only the light
+    /// from the sky which reaches it unblocked, as if it were white and
+    /// diffuse, see `aov::trace_sky_occlusion`
+    SkyOcclusion,
+}
+
+/// Measures of how noisy a pixel still is, which adaptive sampling compares
+/// against its threshold to decide whether to stop sampling the pixel.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AdaptiveMetric {
+    /// Standard error of the mean of the noisiest channel, in linear color
+    /// units. Bright pixels are noisier in these units, so they take more
+    /// samples than dark ones with the same relative noise.
+    Rgb,
+    /// Standard error of the mean relative luminance, as a fraction of the
+    /// mean luminance, so dark and bright pixels stop at the same relative
+    /// noise and the color channels count by how bright they look
+    Luminance,
+}
+
+/// Options for a render which can be overridden from the command line.
+#[derive(Clone)]
+pub struct RenderSettings {
+    /// Width of the output image in pixels
+    pub image_width: u32,
+    /// Height of the output image in pixels
+    pub image_height: u32,
+    /// Number of sub-pixel positions along each axis of the multi-jittered
+    /// grid, so each pixel is sampled at `aa_level * aa_level` positions
+    pub aa_level: usize,
+    /// Number of paths traced through each sub-pixel position
+    pub path_samples: usize,
+    /// Number of specular reflections allowed along each path
+    pub max_reflection_depth: u32,
+    /// Number of refractions allowed along each path
+    pub max_refraction_depth: u32,
+    /// Number of shadow rays cast toward each area light per diffuse hit
+    pub light_samples_per_hit: usize,
+    /// Strength of the ambient occlusion at diffuse hits, zero for none
+    pub ao_strength: f32,
+    /// Distance within which surfaces occlude diffuse hits
+    pub ao_radius: f32,
+    /// Closest distance along a camera ray at which geometry is visible, or
+    /// `None` to scale it with the size of the scene, see
+    /// `camera_min_distance`
+    pub min_distance: Option<f32>,
+    /// Furthest distance along a camera ray at which geometry is visible
+    pub max_distance: f32,
+    /// Seed for the random numbers, the same seed gives the same image
+    pub seed: u64,
+    /// How to build the BVHs of the scene's meshes
+    pub bvh: BvhSettings,
+    /// Names of the scenes to render, see `scenes::SCENE_NAMES`
+    pub scenes: Vec<String>,
+    /// glTF or binary glTF file to render instead of a built-in scene
+    pub gltf: Option<String>,
+    /// Material for every triangle of the glTF file in place of its own
+    pub material_override: Option<MaterialType>,
+    /// File name of the rendered image, when there is only one scene.
+    /// Batches of scenes are each written to `<scene>.png`.
+    pub output: String,
+    /// Stops to brighten the linear color by before tone mapping, or `None`
+    /// for the exposure suggested by the scene
+    pub exposure: Option<f32>,
+    /// Gain of the red, green and blue channels before tone mapping
+    pub white_balance: [f32; 3],
+    /// Bits per channel of the rendered image, 8 or 16
+    pub bit_depth: u8,
+    /// Dither 8 bit images to break up banding
+    pub dither: bool,
+    /// Mirror the finished image top to bottom before saving it, so its
+    /// first row is the bottom of the picture
+    pub flip_y: bool,
+    /// Also write the albedo and normal feature buffers for denoising
+    pub aov: bool,
+    /// Smooth the image with an edge-aware filter guided by the feature
+    /// buffers before tone mapping
+    pub denoise: bool,
+    /// Also write the color and every feature buffer as layers of one
+    /// OpenEXR file with this name
+    pub exr_aov: Option<String>,
+    /// Angle in degrees to turn an environment sky about the vertical axis
+    pub sky_rotation: f32,
+    /// Readout of the camera's shutter in place of the scene's, 0 for a
+    /// global shutter, see `crate::camera::Shutter::readout`
+    pub shutter_readout: Option<f32>,
+    /// Profile of the camera's shutter in place of the scene's
+    pub shutter_profile: Option<ShutterProfile>,
+    /// Which way the horizontal axis of the image runs, in place of the
+    /// scene camera's
+    pub handedness: Option<Handedness>,
+    /// Render both eyes of a stereo pair and compose them this way, each eye
+    /// at the image size
+    pub stereo: Option<StereoLayout>,
+    /// Distance between the eyes of a stereo render, in scene units
+    pub ipd: f32,
+    /// Print counts of rays and geometry when the render finishes
+    pub stats: bool,
+    /// Print a report on each mesh in the scene before rendering, see
+    /// `crate::hittable::mesh::Mesh::validate`
+    pub verbose: bool,
+    /// Write the pixels finished so far to the output file every this many
+    /// seconds while rendering
+    pub preview_interval: Option<f32>,
+    /// Show hits on geometry without a material in magenta instead of
+    /// black. On by default in debug builds.
+    pub highlight_missing_material: bool,
+    /// Render this many frames orbiting the scene instead of a single image
+    pub turntable: Option<usize>,
+    /// File name of the turntable animation
+    pub gif: String,
+    /// Render a diagnostic image instead of the shaded image
+    pub debug: Option<DebugPass>,
+    /// Draw the edges of triangles over the shaded image in black lines this
+    /// many pixels wide
+    pub wireframe: Option<f32>,
+    /// Render this many passes of one sample per pixel instead of the
+    /// anti-aliasing grid, rewriting the output after each pass
+    pub progressive: Option<usize>,
+    /// Stop taking passes over the pixels of a progressive render whose
+    /// noise, measured by `adaptive_metric`, is below this threshold
+    pub adaptive: Option<f32>,
+    /// How the noise of each pixel is measured for `adaptive`
+    pub adaptive_metric: AdaptiveMetric,
+    /// Seconds a progressive render may take before it stops sampling and
+    /// keeps what it has
+    pub max_time: Option<f32>,
+    /// Write the image with an alpha channel and a transparent background,
+    /// keeping only the shadows on shadow catchers over it
+    pub alpha: bool,
+    /// Render only the image rows from the first up to but not including
+    /// the second, counted from the top, leaving the rest transparent, so a
+    /// render can be split into strips across machines
+    pub rows: Option<(u32, u32)>,
+    /// Instead of rendering, check the hash of a small render of every
+    /// built-in scene against those stored in this file, see
+    /// `golden::check`
+    pub golden: Option<String>,
+    /// Rewrite the `golden` file with new hashes instead of checking them
+    pub update_golden: bool,
+}
+
+impl Default for RenderSettings {
+    /// The defaults reproduce the constants at the top of `main.rs`.
+    fn default() -> RenderSettings {
+        RenderSettings {
+            image_width: IMAGE_WIDTH,
+            image_height: IMAGE_HEIGHT,
+            aa_level: SAMPLES_LEVEL,
+            path_samples: 1,
+            max_reflection_depth: DEPTH_LIMIT,
+            max_refraction_depth: DEPTH_LIMIT,
+            light_samples_per_hit: 1,
+            ao_strength: 0.0,
+            ao_radius: 0.5,
+            min_distance: None,
+            max_distance: MAX_HIT_DISTANCE,
+            seed: 0,
+            bvh: BvhSettings::default(),
+            scenes: vec![String::from("infinite_mirror_hallway")],
+            gltf: None,
+            material_override: None,
+            output: String::from("out.png"),
+            exposure: None,
+            white_balance: [1.0, 1.0, 1.0],
+            bit_depth: 8,
+            dither: false,
+            flip_y: false,
+            aov: false,
+            denoise: false,
+            exr_aov: None,
+            stats: false,
+            verbose: false,
+            preview_interval: None,
+            highlight_missing_material: cfg!(debug_assertions),
+            sky_rotation: 0.0,
+            shutter_readout: None,
+            shutter_profile: None,
+            handedness: None,
+            stereo: None,
+            ipd: 0.065,
+            turntable: None,
+            gif: String::from("turntable.gif"),
+            debug: None,
+            wireframe: None,
+            progressive: None,
+            adaptive: None,
+            adaptive_metric: AdaptiveMetric::Rgb,
+            max_time: None,
+            alpha: false,
+            rows: None,
+            golden: None,
+            update_golden: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Build settings from command line arguments, starting from the
+    /// defaults. The program name should already be stripped from `args`.
+    ///
+    /// # Arguments
+    /// - `args` - iterator over the command line arguments
+    ///
+    /// # Returns
+    /// - the parsed settings, or a message describing the bad argument
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<RenderSettings, String> {
+        let mut settings = RenderSettings::default();
+        // flags which would overwrite each other's files in a batch
+        let mut scenes_given = false;
+        let mut single_scene_flag = None;
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--width" => settings.image_width = parse_value(&flag, args.next())?,
+                "--height" => settings.image_height = parse_value(&flag, args.next())?,
+                "--aa" => settings.aa_level = parse_value(&flag, args.next())?,
+                "--paths" => settings.path_samples = parse_value(&flag, args.next())?,
+                "--reflection-depth" => {
+                    settings.max_reflection_depth = parse_value(&flag, args.next())?
+                }
+                "--refraction-depth" => {
+                    settings.max_refraction_depth = parse_value(&flag, args.next())?
+                }
+                "--light-samples" => {
+                    settings.light_samples_per_hit = parse_value(&flag, args.next())?
+                }
+                "--ao-strength" => settings.ao_strength = parse_value(&flag, args.next())?,
+                "--ao-radius" => settings.ao_radius = parse_value(&flag, args.next())?,
+                "--clip-near" => settings.min_distance = Some(parse_value(&flag, args.next())?),
+                "--clip-far" => settings.max_distance = parse_value(&flag, args.next())?,
+                "--seed" => settings.seed = parse_value(&flag, args.next())?,
+                "--bvh-leaf" => settings.bvh.leaf_max = parse_value(&flag, args.next())?,
+                "--bvh-bins" => settings.bvh.bins = parse_value(&flag, args.next())?,
+                "--scene" => {
+                    // the first `--scene` replaces the default, later ones add to it
+                    if !scenes_given {
+                        settings.scenes.clear();
+                        scenes_given = true;
+                    }
+                    let names: String = parse_value(&flag, args.next())?;
+                    settings.scenes.extend(
+                        names
+                            .split(',')
+                            .filter(|name| !name.is_empty())
+                            .map(String::from),
+                    );
+                }
+                "--gltf" => settings.gltf = Some(parse_value(&flag, args.next())?),
+                "--material-override" => {
+                    settings.material_override =
+                        Some(match parse_value::<String>(&flag, args.next())?.as_str() {
+                            "white" => MaterialType::Lambertian(Lambertian {
+                                albedo: glm::vec3(0.8, 0.8, 0.8),
+                                force_front_face: false,
+                            }),
+                            "chrome" => MaterialType::Metal(Metal {
+                                albedo: glm::vec3(0.9, 0.9, 0.9),
+                                fresnel: false,
+                                roughness: (0.0, 0.0),
+                            }),
+                            _ => {
+                                return Err(String::from(
+                                    "--material-override must be white or chrome",
+                                ))
+                            }
+                        })
+                }
+                "--output" => {
+                    settings.output = parse_value(&flag, args.next())?;
+                    single_scene_flag = Some(flag);
+                }
+                "--exposure" => settings.exposure = Some(parse_value(&flag, args.next())?),
+                "--white-balance" => {
+                    for gain in settings.white_balance.iter_mut() {
+                        *gain = parse_value(&flag, args.next())?;
+                    }
+                }
+                "--bit-depth" => settings.bit_depth = parse_value(&flag, args.next())?,
+                "--dither" => settings.dither = true,
+                "--flip-y" => settings.flip_y = true,
+                "--aov" => {
+                    settings.aov = true;
+                    single_scene_flag = Some(flag);
+                }
+                "--denoise" => settings.denoise = true,
+                "--stats" => settings.stats = true,
+                "--verbose" => settings.verbose = true,
+                "--preview-interval" => {
+                    settings.preview_interval = Some(parse_value(&flag, args.next())?)
+                }
+                "--missing-material" => {
+                    settings.highlight_missing_material =
+                        match parse_value::<String>(&flag, args.next())?.as_str() {
+                            "magenta" => true,
+                            "black" => false,
+                            _ => {
+                                return Err(String::from(
+                                    "--missing-material must be magenta or black",
+                                ))
+                            }
+                        }
+                }
+                "--debug" => {
+                    settings.debug = match parse_value::<String>(&flag, args.next())?.as_str() {
+                        "object-id" => Some(DebugPass::ObjectId),
+                        "lighting" => Some(DebugPass::Lighting),
+                        _ => return Err(String::from("--debug must be object-id or lighting")),
+                    }
+                }
+                "--sky-occlusion" => settings.debug = Some(DebugPass::SkyOcclusion),
+                "--wireframe" => settings.wireframe = Some(parse_value(&flag, args.next())?),
+                "--sky-rotation" => settings.sky_rotation = parse_value(&flag, args.next())?,
+                "--rolling-shutter" => {
+                    settings.shutter_readout = Some(parse_value(&flag, args.next())?)
+                }
+                "--handedness" => {
+                    settings.handedness = match parse_value::<String>(&flag, args.next())?.as_str()
+                    {
+                        "right" => Some(Handedness::Right),
+                        "left" => Some(Handedness::Left),
+                        _ => return Err(String::from("--handedness must be right or left")),
+                    }
+                }
+                "--stereo" => {
+                    settings.stereo = match parse_value::<String>(&flag, args.next())?.as_str() {
+                        "side-by-side" => Some(StereoLayout::SideBySide),
+                        "anaglyph" => Some(StereoLayout::Anaglyph),
+                        _ => return Err(String::from("--stereo must be side-by-side or anaglyph")),
+                    }
+                }
+                "--ipd" => settings.ipd = parse_value(&flag, args.next())?,
+                "--shutter-profile" => {
+                    settings.shutter_profile = match parse_value::<String>(&flag, args.next())?
+                        .as_str()
+                    {
+                        "box" => Some(ShutterProfile::Box),
+                        "gaussian" => Some(ShutterProfile::Gaussian),
+                        _ => return Err(String::from("--shutter-profile must be box or gaussian")),
+                    }
+                }
+                "--exr-aov" => {
+                    settings.exr_aov = Some(parse_value(&flag, args.next())?);
+                    single_scene_flag = Some(flag);
+                }
+                "--turntable" => {
+                    settings.turntable = Some(parse_value(&flag, args.next())?);
+                    single_scene_flag = Some(flag);
+                }
+                "--gif" => settings.gif = parse_value(&flag, args.next())?,
+                "--progressive" => settings.progressive = Some(parse_value(&flag, args.next())?),
+                "--adaptive" => settings.adaptive = Some(parse_value(&flag, args.next())?),
+                "--max-time" => settings.max_time = Some(parse_value(&flag, args.next())?),
+                "--adaptive-metric" => {
+                    settings.adaptive_metric = match parse_value::<String>(&flag, args.next())?
+                        .as_str()
+                    {
+                        "rgb" => AdaptiveMetric::Rgb,
+                        "luminance" => AdaptiveMetric::Luminance,
+                        _ => {
+                            return Err(String::from("--adaptive-metric must be rgb or luminance"))
+                        }
+                    }
+                }
+                "--alpha" => settings.alpha = true,
+                "--rows" => {
+                    let band: String = parse_value(&flag, args.next())?;
+                    let (start, end) = band
+                        .split_once(':')
+                        .ok_or_else(|| format!("invalid value `{}` for `{}`", band, flag))?;
+                    settings.rows = Some((
+                        parse_value(&flag, Some(String::from(start)))?,
+                        parse_value(&flag, Some(String::from(end)))?,
+                    ));
+                }
+                "--golden" => settings.golden = Some(parse_value(&flag, args.next())?),
+                "--update-golden" => settings.update_golden = true,
+                _ => return Err(format!("unknown argument `{}`", flag)),
+            }
+        }
+        if settings.gltf.is_some() && scenes_given {
+            return Err(String::from("--gltf can't be used with --scene"));
+        }
+        if settings.material_override.is_some() && settings.gltf.is_none() {
+            return Err(String::from("--material-override needs --gltf"));
+        }
+        if settings.scenes.is_empty() {
+            return Err(String::from("--scene needs at least one scene name"));
+        }
+        if let (Some(flag), true) = (&single_scene_flag, settings.scenes.len() > 1) {
+            return Err(format!("`{}` can only be used with one scene", flag));
+        }
+        if settings.aa_level == 0
+            || settings.path_samples == 0
+            || settings.light_samples_per_hit == 0
+        {
+            return Err(String::from(
+                "--aa, --paths, and --light-samples must be at least 1",
+            ));
+        }
+        if !(0.0..=1.0).contains(&settings.ao_strength) || settings.ao_radius <= 0.0 {
+            return Err(String::from(
+                "--ao-strength must be from 0 to 1 and --ao-radius above 0",
+            ));
+        }
+        if settings
+            .exposure
+            .is_some_and(|exposure| !exposure.is_finite())
+            || settings
+                .white_balance
+                .iter()
+                .any(|gain| !gain.is_finite() || *gain < 0.0)
+        {
+            return Err(String::from(
+                "--exposure must be finite and --white-balance gains at least 0",
+            ));
+        }
+        if settings.bvh.leaf_max == 0 || settings.bvh.bins == 1 {
+            return Err(String::from(
+                "--bvh-leaf must be at least 1 and --bvh-bins 0 or at least 2",
+            ));
+        }
+        if settings.bit_depth != 8 && settings.bit_depth != 16 {
+            return Err(String::from("--bit-depth must be 8 or 16"));
+        }
+        if settings
+            .preview_interval
+            .is_some_and(|interval| interval.is_nan() || interval <= 0.0)
+        {
+            return Err(String::from("--preview-interval must be above 0"));
+        }
+        if settings
+            .wireframe
+            .is_some_and(|width| width.is_nan() || width <= 0.0)
+        {
+            return Err(String::from("--wireframe must be above 0"));
+        }
+        if settings
+            .shutter_readout
+            .is_some_and(|readout| !(0.0..=1.0).contains(&readout))
+        {
+            return Err(String::from("--rolling-shutter must be from 0 to 1"));
+        }
+        if !(settings.ipd.is_finite() && settings.ipd > 0.0) {
+            return Err(String::from("--ipd must be above 0"));
+        }
+        if settings.stereo.is_some()
+            && (settings.turntable.is_some()
+                || settings.progressive.is_some()
+                || settings.debug.is_some()
+                || settings.alpha
+                || settings.aov
+                || settings.exr_aov.is_some()
+                || settings.bit_depth == 16)
+        {
+            return Err(String::from(
+                "--stereo can't be used with --turntable, --progressive, --debug, --sky-occlusion, --alpha, --aov, --exr-aov or --bit-depth 16",
+            ));
+        }
+        if settings.update_golden && settings.golden.is_none() {
+            return Err(String::from("--update-golden needs --golden"));
+        }
+        if settings.turntable == Some(0) {
+            return Err(String::from("--turntable needs at least 1 frame"));
+        }
+        if settings.progressive == Some(0) {
+            return Err(String::from("--progressive needs at least 1 pass"));
+        }
+        if settings.progressive.is_some()
+            && (settings.turntable.is_some() || settings.needs_features())
+        {
+            return Err(String::from(
+                "--progressive can't be used with --turntable, --aov, --denoise or --exr-aov",
+            ));
+        }
+        if settings
+            .adaptive
+            .is_some_and(|threshold| threshold.is_nan() || threshold <= 0.0)
+        {
+            return Err(String::from("--adaptive must be above 0"));
+        }
+        if settings.adaptive.is_some() && settings.progressive.is_none() {
+            return Err(String::from("--adaptive needs --progressive"));
+        }
+        if settings
+            .max_time
+            .is_some_and(|seconds| !(seconds.is_finite() && seconds > 0.0))
+        {
+            return Err(String::from("--max-time must be above 0"));
+        }
+        if settings.max_time.is_some() && settings.progressive.is_none() {
+            return Err(String::from("--max-time needs --progressive"));
+        }
+        if settings.debug.is_some()
+            && (settings.turntable.is_some() || settings.progressive.is_some())
+        {
+            return Err(String::from(
+                "--debug and --sky-occlusion can't be used with --turntable or --progressive",
+            ));
+        }
+        if settings.alpha
+            && (settings.turntable.is_some()
+                || settings.progressive.is_some()
+                || settings.debug.is_some())
+        {
+            return Err(String::from(
+                "--alpha can't be used with --turntable, --progressive, --debug or --sky-occlusion",
+            ));
+        }
+        if settings
+            .rows
+            .is_some_and(|(start, end)| start >= end || end > settings.image_height)
+        {
+            return Err(String::from(
+                "--rows must be start:end with start below end and end at most --height",
+            ));
+        }
+        // the denoiser reads pixels across the edges of the band, and the
+        // other modes write images of their own
+        if settings.rows.is_some()
+            && (settings.denoise
+                || settings.turntable.is_some()
+                || settings.progressive.is_some()
+                || settings.stereo.is_some()
+                || settings.debug == Some(DebugPass::ObjectId))
+        {
+            return Err(String::from(
+                "--rows can't be used with --denoise, --turntable, --progressive, --stereo or --debug object-id",
+            ));
+        }
+        // NaN compares false against everything, so it would pass the
+        // check that near is less than far
+        if settings
+            .min_distance
+            .is_some_and(|near| !(near.is_finite() && near >= 0.0))
+            || settings.max_distance.is_nan()
+            || settings.max_distance <= 0.0
+        {
+            return Err(String::from(
+                "--clip-near must be finite and at least 0, and --clip-far above 0",
+            ));
+        }
+        if settings
+            .min_distance
+            .is_some_and(|near| near >= settings.max_distance)
+        {
+            return Err(String::from("--clip-near must be less than --clip-far"));
+        }
+        Ok(settings)
+    }
+
+    /// Closest distance along a camera ray at which geometry is visible.
+    /// Unless `--clip-near` gave one, this is `EPSILON` times the length of
+    /// the diagonal of the scene's bounding box, so it stays the same
+    /// fraction of the scene at any size. A fixed distance would hide the
+    /// nearest surface of a scene a few thousandths of a unit across, and be
+    /// smaller than the rounding error in one thousands of units across.
+    /// Scenes with no finite size, such as a lone infinite plane, use
+    /// `EPSILON` as it is.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `world: &dyn Hittable` - the scene the camera rays are traced into
+    ///
+    /// # Returns
+    /// - `f32` - the distance to start camera rays' hit searches at
+    pub fn camera_min_distance(&self, world: &dyn Hittable) -> f32 {
+        if let Some(near) = self.min_distance {
+            return near;
+        }
+        let scale = world
+            .bounding_box()
+            .map(|bounds| glm::distance(&bounds.minimum_point, &bounds.maximum_point))
+            .filter(|diagonal| diagonal.is_finite() && *diagonal > 0.0)
+            .unwrap_or(1.0);
+        EPSILON * scale
+    }
+
+    /// Settings which change how hits are shaded, to pass down every path.
+    ///
+    /// # Arguments
+    /// - self reference
+    ///
+    /// # Returns
+    /// - `ShadingContext` - the shading settings for this render
+    pub fn shading_context(&self) -> ShadingContext {
+        ShadingContext {
+            light_samples_per_hit: self.light_samples_per_hit,
+            ambient_occlusion_strength: self.ao_strength,
+            ambient_occlusion_radius: self.ao_radius,
+            missing_material_color: if self.highlight_missing_material {
+                glm::vec3(1.0, 0.0, 1.0)
+            } else {
+                glm::vec3(0.0, 0.0, 0.0)
+            },
+            ..ShadingContext::default()
+        }
+    }
+
+    /// Rows of the image to render, counted from the top: those given by
+    /// `rows`, or every row.
+    pub fn rendered_rows(&self) -> std::ops::Range<u32> {
+        match self.rows {
+            Some((start, end)) => start..end,
+            None => 0..self.image_height,
+        }
+    }
+
+    /// Whether the image is written with an alpha channel, for `alpha` or
+    /// for the transparent rows outside a band given by `rows`.
+    pub fn writes_alpha(&self) -> bool {
+        self.alpha || self.rows.is_some()
+    }
+
+    /// Whether the feature buffers have to be rendered along with the color.
+    pub fn needs_features(&self) -> bool {
+        self.aov || self.denoise || self.exr_aov.is_some()
+    }
+
+    /// File name to write a scene's image to.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `scene: &str` - name of the scene
+    ///
+    /// # Returns
+    /// - `String` - `output` for a single scene, or `<scene>.png` in a batch
+    pub fn output_for(&self, scene: &str) -> String {
+        if self.scenes.len() > 1 {
+            format!("{}.png", scene)
+        } else {
+            self.output.clone()
+        }
+    }
+
+    /// Total number of samples accumulated into each pixel.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.aa_level * self.aa_level * self.path_samples
+    }
+}
+
+/// Usage text printed when the arguments can't be parsed.
+pub const USAGE: &str = "usage: ray_tracer [options]
+    --width <pixels>        width of the output image
+    --height <pixels>       height of the output image
+    --aa <n>                anti-aliasing grid size, n x n positions per pixel
+    --paths <n>             paths traced per anti-aliasing position
+    --reflection-depth <n>  reflections off glass allowed along each path
+    --refraction-depth <n>  refractions through glass allowed along each path
+    --light-samples <n>     shadow rays toward each area light per diffuse hit
+    --ao-strength <s>       darken bounce light near other surfaces, 0 (off) to 1
+    --ao-radius <dist>      reach of the ambient occlusion, defaults to 0.5
+    --clip-near <dist>      hide geometry closer than this to the camera
+                            defaults to a sliver of the scene's size
+    --clip-far <dist>       hide geometry further than this from the camera,
+                            defaults to no limit
+    --seed <n>              seed for the random numbers, defaults to 0
+    --bvh-leaf <n>          most triangles in a BVH leaf, defaults to 32
+    --bvh-bins <n>          bins to choose BVH splits over by surface area,
+                            defaults to 0 (split at the mean centroid)
+    --scene <names>         scenes to render, comma separated or repeated, each
+                            written to <name>.png if there are several;
+                            defaults to infinite_mirror_hallway
+    --gltf <file>           render a .gltf or .glb file instead of a built-in scene
+    --material-override <m> give every triangle of the --gltf file a white diffuse
+                            or chrome mirror material instead of its own
+    --output <file>         name of the rendered image
+    --exposure <stops>      brighten the image, each stop doubling the light;
+                            defaults to the scene's own exposure, usually 0
+    --white-balance <r g b> gain of each color channel, defaults to 1 1 1
+    --bit-depth <bits>      bits per channel of the image, 8 (default) or 16
+    --dither                dither 8 bit images to hide banding in gradients
+    --flip-y                write the bottom row of the image first, for tools
+                            expecting OpenGL's origin rather than the top left
+    --alpha                 write an alpha channel, leaving the background
+                            transparent apart from shadows on shadow catchers
+    --rows <start:end>      render only rows start up to end, counted from the
+                            top, leaving the rest transparent, for compositing
+                            strips rendered on several machines
+    --aov                   also write albedo.png, normal.png and caustic.png
+                            denoiser guides
+    --denoise               smooth the image with the built-in edge-aware filter
+    --exr-aov <file>        also write color, albedo, normal and depth to one EXR
+    --sky-rotation <deg>    turn an environment sky about the vertical axis
+    --handedness <h>        right (default) puts +x on the right looking down -z
+                            with +y up, left mirrors the image
+    --stereo <layout>       render both eyes of a stereo pair, side-by-side in an
+                            image twice as wide or as a red/cyan anaglyph
+    --ipd <dist>            distance between the stereo eyes, defaults to 0.065
+    --rolling-shutter <r>   expose the rows top to bottom over this fraction of
+                            the shutter time, 0 for a global shutter; opens the
+                            shutter for scenes without one
+    --shutter-profile <p>   weight each row's exposure with a box (default) or
+                            gaussian profile
+    --stats                 print ray counts, geometry size and time at the end
+    --verbose               print triangle counts, holes and other problems found
+                            in each mesh of the scene before rendering
+    --preview-interval <s>  write the finished pixels to the output every s seconds
+    --debug <pass>          render a diagnostic pass instead of the shaded image:
+                            object-id colors each object by its ID, lighting
+                            shows only the direct light on white surfaces
+    --sky-occlusion         render only the sky light reaching white surfaces
+                            unblocked, a quick sky-lit preview without bounces
+    --wireframe <pixels>    draw triangle edges over the image in lines this wide
+    --missing-material <c>  color of hits with no material, magenta or black
+                            (defaults to magenta in debug builds)
+    --turntable <frames>    render an orbit around the scene instead of one image
+    --gif <file>            name of the turntable animation, defaults to turntable.gif
+    --progressive <passes>  take this many passes of one sample per pixel instead
+                            of the --aa grid, rewriting the output after each
+    --adaptive <threshold>  stop sampling pixels of a progressive render once
+                            their noise is below the threshold
+    --adaptive-metric <m>   noise measure for --adaptive, rgb (default) for the
+                            noisiest channel or luminance for relative luminance
+    --max-time <seconds>    stop a progressive render once it has taken this long
+                            and write what it has
+    --golden <file>         instead of rendering, check small renders of every
+                            scene against the image hashes stored in the file
+    --update-golden         rewrite the --golden file with the current hashes";
+
+/// Parse the value following a flag.
+///
+/// # Arguments
+/// - `flag` - the flag the value belongs to, for error messages
+/// - `value` - the next argument, if there was one
+///
+/// # Returns
+/// - the parsed value, or a message describing what went wrong
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for `{}`", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::lambertian::Lambertian;
+    use crate::ray::Ray;
+
+    /// Parse settings from flags written out as one string.
+    fn parse(flags: &str) -> Result<RenderSettings, String> {
+        RenderSettings::from_args(flags.split_whitespace().map(String::from))
+    }
+
+    /// Gray diffuse material for test geometry.
+    fn gray() -> MaterialType {
+        MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        })
+    }
+
+    #[test]
+    fn clip_distances_must_be_ordered_finite_and_not_negative() {
+        assert!(parse("--clip-near 1 --clip-far 2").is_ok());
+        assert!(parse("--clip-near 0").is_ok());
+        assert!(parse("--clip-near 2 --clip-far 1").is_err());
+        assert!(parse("--clip-near nan").is_err());
+        assert!(parse("--clip-near inf").is_err());
+        assert!(parse("--clip-near -1").is_err());
+        assert!(parse("--clip-far nan").is_err());
+        assert!(parse("--clip-far -1").is_err());
+        assert!(parse("--clip-far 0").is_err());
+    }
+
+    #[test]
+    fn clip_distances_hide_geometry_outside_them() {
+        // spheres whose near sides are 1, 3 and 5 units along the ray
+        let mut world = HittableList::new();
+        for z in [1.5f32, 3.5, 5.5].iter() {
+            world.add_sphere(glm::vec3(0.0, 0.0, -z), 0.5, gray());
+        }
+        let ray = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0), None);
+        let first_hit = |flags: &str| {
+            let settings = parse(flags).unwrap();
+            world
+                .hit(
+                    &ray,
+                    settings.camera_min_distance(&world),
+                    settings.max_distance,
+                )
+                .map(|hit| hit.distance)
+        };
+        assert!((first_hit("").unwrap() - 1.0).abs() < 1.0e-4);
+        assert!((first_hit("--clip-near 2").unwrap() - 3.0).abs() < 1.0e-4);
+        // the far side of the first sphere is still in front of the second
+        assert!((first_hit("--clip-near 1.2").unwrap() - 2.0).abs() < 1.0e-4);
+        assert!((first_hit("--clip-near 2 --clip-far 4").unwrap() - 3.0).abs() < 1.0e-4);
+        assert_eq!(first_hit("--clip-near 2 --clip-far 2.5"), None);
+        assert_eq!(first_hit("--clip-far 0.5"), None);
+    }
+
+    #[test]
+    fn camera_min_distance_finds_the_nearest_surface_at_any_scale() {
+        let settings = parse("").unwrap();
+        for scale in [0.001f32, 1000.0].iter() {
+            // a sphere of radius `scale`, with the camera a five hundredth
+            // of that in front of it
+            let mut world = HittableList::new();
+            world.add_sphere(glm::vec3(0.0, 0.0, -2.0 * scale), *scale, gray());
+            let gap = 0.002 * scale;
+            let ray = Ray::new(
+                glm::vec3(0.0, 0.0, -scale + gap),
+                glm::vec3(0.0, 0.0, -1.0),
+                None,
+            );
+            let min_distance = settings.camera_min_distance(&world);
+            assert!(min_distance < gap);
+            let hit = world.hit(&ray, min_distance, f32::INFINITY).unwrap();
+            assert!((hit.distance - gap).abs() < 1e-3 * gap, "scale {}", scale);
+            if gap < EPSILON {
+                // a fixed distance skips the near side, and sees the far one
+                let fixed = world.hit(&ray, EPSILON, f32::INFINITY).unwrap();
+                assert!(fixed.distance > *scale);
+            }
+        }
+    }
+}
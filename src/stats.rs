@@ -0,0 +1,201 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Whether counters are being recorded. Off unless `--stats` is given, so a
+/// normal render only pays for one relaxed load per counted event.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Totals for each kind of counted event, indexed by `Counter`
+static COUNTS: [AtomicU64; 4] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Time each of rayon's worker threads spent rendering pixels, indexed by
+/// the thread's index in its pool, `None` for threads which rendered none
+static THREAD_TIMES: Mutex<Vec<Option<ThreadTime>>> = Mutex::new(Vec::new());
+
+/// Time one worker thread spent on pixels during a render
+#[derive(Clone, Copy)]
+struct ThreadTime {
+    /// When the thread started its first pixel
+    first_start: Instant,
+    /// When the thread finished its last pixel
+    last_finish: Instant,
+    /// Total time spent on pixels
+    busy: Duration,
+}
+
+/// Kinds of events counted during a render
+#[derive(Clone, Copy)]
+pub enum Counter {
+    /// Rays traced from the camera
+    PrimaryRays,
+    /// Rays traced after bouncing off or through a surface
+    SecondaryRays,
+    /// Rays testing whether a light or nearby surface is visible from a hit
+    ShadowRays,
+    /// Ray-triangle intersection tests
+    TriangleTests,
+}
+
+/// Numbers describing the scene's geometry, gathered once before rendering.
+pub struct GeometryCounts {
+    /// Triangles in meshes, rectangles and on their own
+    pub triangles: usize,
+    /// Interior and leaf nodes of every BVH
+    pub bvh_nodes: usize,
+}
+
+/// Start recording counters.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Set every counter back to zero, so each scene of a batch is counted on
+/// its own.
+pub fn reset() {
+    for count in COUNTS.iter() {
+        count.store(0, Ordering::Relaxed);
+    }
+    THREAD_TIMES.lock().unwrap().clear();
+}
+
+/// Count one event, if counters are being recorded. Safe to call from any
+/// thread.
+///
+/// # Arguments
+/// - `counter: Counter` - kind of event which happened
+#[inline]
+pub fn record(counter: Counter) {
+    if ENABLED.load(Ordering::Relaxed) {
+        COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Start timing a pixel, if counters are being recorded.
+///
+/// # Returns
+/// - `Option<Instant>` - when the pixel was started, to pass to
+///   `record_pixel_time` once it is finished, or `None` if not recording
+#[inline]
+pub fn start_pixel() -> Option<Instant> {
+    if ENABLED.load(Ordering::Relaxed) {
+        Some(Instant::now())
+    } else {
+        None
+    }
+}
+
+/// Add the time a pixel took to the busy time of the thread which rendered
+/// it, so the report can show how evenly the work was shared out and how
+/// long threads sat idle at the end of the render waiting for the others.
+///
+/// # Arguments
+/// - `started: Option<Instant>` - what `start_pixel` returned for the pixel
+pub fn record_pixel_time(started: Option<Instant>) {
+    let started = match started {
+        Some(started) => started,
+        None => return,
+    };
+    let finished = Instant::now();
+    let thread = rayon::current_thread_index().unwrap_or(0);
+    let mut thread_times = THREAD_TIMES.lock().unwrap();
+    if thread_times.len() <= thread {
+        thread_times.resize(thread + 1, None);
+    }
+    let time = thread_times[thread].get_or_insert(ThreadTime {
+        first_start: started,
+        last_finish: finished,
+        busy: Duration::ZERO,
+    });
+    time.last_finish = finished;
+    time.busy += finished - started;
+}
+
+/// How well the pixels were shared out between the threads which rendered
+/// any, from the times recorded by `record_pixel_time`.
+///
+/// # Returns
+/// - Optional tuple of
+///     - `usize` - number of threads which rendered pixels
+///     - `f64` - their busy time as a fraction of the time from the first
+///       pixel starting to the last finishing, times the thread count
+///     - `Duration` - longest any thread sat idle at the end, after its
+///       last pixel, while others were still rendering
+fn thread_utilization() -> Option<(usize, f64, Duration)> {
+    let thread_times = THREAD_TIMES.lock().unwrap();
+    let used: Vec<&ThreadTime> = thread_times.iter().flatten().collect();
+    let start = used.iter().map(|time| time.first_start).min()?;
+    let end = used.iter().map(|time| time.last_finish).max()?;
+    let span = (end - start).as_secs_f64() * used.len() as f64;
+    let busy: f64 = used.iter().map(|time| time.busy.as_secs_f64()).sum();
+    let longest_tail = used
+        .iter()
+        .map(|time| end - time.last_finish)
+        .max()
+        .unwrap_or_default();
+    Some((
+        used.len(),
+        if span > 0.0 { busy / span } else { 1.0 },
+        longest_tail,
+    ))
+}
+
+/// Current total of a counter.
+fn count(counter: Counter) -> u64 {
+    COUNTS[counter as usize].load(Ordering::Relaxed)
+}
+
+/// Format a summary of the counters and scene for printing after a render.
+///
+/// # Arguments
+/// - `geometry: &GeometryCounts` - size of the scene
+/// - `elapsed: Duration` - wall-clock time the render took
+///
+/// # Returns
+/// - `String` - the report, one statistic per line
+pub fn report(geometry: &GeometryCounts, elapsed: Duration) -> String {
+    let primary = count(Counter::PrimaryRays);
+    let secondary = count(Counter::SecondaryRays);
+    let shadow = count(Counter::ShadowRays);
+    let average_bounces = if primary > 0 {
+        secondary as f64 / primary as f64
+    } else {
+        0.0
+    };
+    let seconds = elapsed.as_secs_f64();
+    let total = primary + secondary + shadow;
+    let (threads, utilization, longest_tail) =
+        thread_utilization().unwrap_or((0, 0.0, Duration::ZERO));
+    [
+        String::from("render statistics:"),
+        format!("    total rays       {:>14}", total),
+        format!("    primary rays     {:>14}", primary),
+        format!("    secondary rays   {:>14}", secondary),
+        format!("    shadow rays      {:>14}", shadow),
+        format!("    average bounces  {:>14.3}", average_bounces),
+        format!("    triangle tests   {:>14}", count(Counter::TriangleTests)),
+        format!("    triangles        {:>14}", geometry.triangles),
+        format!("    bvh nodes        {:>14}", geometry.bvh_nodes),
+        format!("    wall-clock time  {:>13.3}s", seconds),
+        format!("    render threads   {:>14}", threads),
+        format!("    thread busy time {:>13.1}%", 100.0 * utilization),
+        format!("    longest idle end {:>13.3}s", longest_tail.as_secs_f64()),
+        format!(
+            "    rays per second  {:>14.0}",
+            if seconds > 0.0 {
+                total as f64 / seconds
+            } else {
+                0.0
+            }
+        ),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,70 @@
+use super::render;
+use super::tonemapped_image;
+use crate::camera::perspective_camera::PerspectiveCamera;
+use crate::camera::Eye;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::scenes::Sky;
+use crate::settings::RenderSettings;
+use image::RgbImage;
+
+/// Ways of putting the two eyes' images of a stereo render into one image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    /// The left eye's image beside the right eye's, in an image twice as
+    /// wide, for VR headsets and parallel viewing
+    SideBySide,
+    /// The red channel of the left eye's image with the green and blue of
+    /// the right eye's, for red/cyan glasses. Strongly colored surfaces lose
+    /// depth, since only one eye sees them.
+    Anaglyph,
+}
+
+/// Render a scene from both eyes of a stereo pair around the camera and
+/// compose the two images into one.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &PerspectiveCamera` - camera halfway between the eyes
+/// - `lights: &[Light]` - light sources for the scene
+/// - `sky: &Sky` - background of the scene
+/// - `settings: &RenderSettings` - image size of each eye, sample counts,
+///   and the interpupillary distance
+/// - `layout: StereoLayout` - how to compose the two images
+///
+/// # Returns
+/// - `RgbImage` - the composed image
+pub fn render_stereo<T: Hittable + Sync>(
+    world: &T,
+    camera: &PerspectiveCamera,
+    lights: &[Light],
+    sky: &Sky,
+    settings: &RenderSettings,
+    layout: StereoLayout,
+) -> RgbImage {
+    let [left, right] = [Eye::Left, Eye::Right].map(|eye| {
+        let eye_camera = camera.stereo_eye(eye, settings.ipd);
+        tonemapped_image(&render(world, &eye_camera, lights, sky, settings), settings)
+    });
+    let (width, height) = left.dimensions();
+    match layout {
+        StereoLayout::SideBySide => {
+            let mut img = RgbImage::new(2 * width, height);
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                *pixel = if x < width {
+                    *left.get_pixel(x, y)
+                } else {
+                    *right.get_pixel(x - width, y)
+                };
+            }
+            img
+        }
+        StereoLayout::Anaglyph => {
+            let mut img = right;
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                pixel[0] = left.get_pixel(x, y)[0];
+            }
+            img
+        }
+    }
+}
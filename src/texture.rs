@@ -0,0 +1,312 @@
+use crate::color;
+use glm::Vec3;
+
+/// How the color channels of an image texture are stored.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// Colors encoded with the sRGB gamma, like albedo textures painted or
+    /// photographed for display, which are decoded to linear color
+    Srgb,
+    /// Linear values, like normal or roughness maps, which are used as
+    /// stored
+    Linear,
+}
+
+/// An image which can be sampled by texture coordinates.
+///
+/// Materials are `Copy`, so the pixels are loaded once and leaked to get a
+/// `'static` reference which every copy of the material can share. Textures
+/// live for the whole render anyway.
+#[derive(Clone, Copy)]
+pub struct ImageTexture {
+    /// The loaded image, with an alpha channel
+    image: &'static image::RgbaImage,
+    /// How the color channels are stored. The alpha channel is always
+    /// linear.
+    color_space: ColorSpace,
+}
+
+impl ImageTexture {
+    /// Load a texture from an image file.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - path to the image
+    /// - `color_space: ColorSpace` - how its colors are stored, `Srgb` for
+    ///   colors and `Linear` for data
+    ///
+    /// # Returns
+    /// - the texture, ready to be sampled
+    pub fn new(filename: &str, color_space: ColorSpace) -> ImageTexture {
+        let image = image::open(filename)
+            .unwrap_or_else(|error| panic!("could not load texture {}: {}", filename, error))
+            .into_rgba8();
+        ImageTexture {
+            image: Box::leak(Box::new(image)),
+            color_space,
+        }
+    }
+
+    /// Find the pixel under the given texture coordinates. Coordinates wrap
+    /// around, and `v` runs from the bottom of the image to the top.
+    fn texel(&self, texture_coordinates: (f32, f32)) -> &image::Rgba<u8> {
+        let (width, height) = self.image.dimensions();
+        let u = texture_coordinates.0.rem_euclid(1.0);
+        let v = 1.0 - texture_coordinates.1.rem_euclid(1.0);
+        let x = ((u * width as f32) as u32).min(width - 1);
+        let y = ((v * height as f32) as u32).min(height - 1);
+        self.image.get_pixel(x, y)
+    }
+
+    /// Sample the color of the texture.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `texture_coordinates: (f32, f32)` - the (u, v) coordinates to sample
+    ///
+    /// # Returns
+    /// - `Vec3` - the linear RGB color of the texture
+    pub fn color(&self, texture_coordinates: (f32, f32)) -> Vec3 {
+        let texel = self.texel(texture_coordinates);
+        match self.color_space {
+            ColorSpace::Srgb => color::color_srgb(texel[0], texel[1], texel[2]),
+            ColorSpace::Linear => color::color_linear(texel[0], texel[1], texel[2]),
+        }
+    }
+
+    /// Sample the opacity of the texture.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `texture_coordinates: (f32, f32)` - the (u, v) coordinates to sample
+    ///
+    /// # Returns
+    /// - `f32` - alpha from 0 (fully transparent) to 1 (opaque)
+    pub fn alpha(&self, texture_coordinates: (f32, f32)) -> f32 {
+        self.texel(texture_coordinates)[3] as f32 / 255.0
+    }
+}
+
+/// An image projected onto a surface along the x, y and z axes, for meshes
+/// without texture coordinates like most scanned models. Each point is
+/// sampled in all three projections, which are blended by how squarely
+/// the surface faces each axis, so faces turned toward an axis show a
+/// single undistorted projection and the blend hides where they meet.
+#[derive(Clone, Copy)]
+pub struct TriplanarTexture {
+    /// The image projected along each axis
+    texture: ImageTexture,
+    /// Size in world units of one repeat of the image
+    scale: f32,
+    /// Power the normal's components are raised to for the blend weights.
+    /// 2 blends by the squared components, and higher powers narrow the
+    /// blend toward the edges between faces.
+    sharpness: f32,
+}
+
+impl TriplanarTexture {
+    /// Load a texture to project from an image file, blended by the squared
+    /// components of the normal.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - path to the image, which should tile, with its
+    ///   colors in sRGB
+    /// - `scale: f32` - size in world units of one repeat of the image
+    ///
+    /// # Returns
+    /// - the texture, ready to be sampled
+    pub fn new(filename: &str, scale: f32) -> TriplanarTexture {
+        TriplanarTexture {
+            texture: ImageTexture::new(filename, ColorSpace::Srgb),
+            scale,
+            sharpness: 2.0,
+        }
+    }
+
+    /// Change the power of the normal's components used to blend the
+    /// projections, see `TriplanarTexture::sharpness`.
+    ///
+    /// # Arguments
+    /// - `sharpness: f32` - the power, at least 1
+    ///
+    /// # Returns
+    /// - the texture with the new blend
+    #[allow(dead_code)]
+    pub fn with_sharpness(self, sharpness: f32) -> TriplanarTexture {
+        TriplanarTexture { sharpness, ..self }
+    }
+
+    /// Sample the color of the texture on a surface.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `point: &Vec3` - point on the surface
+    /// - `normal: &Vec3` - unit normal of the surface at the point
+    ///
+    /// # Returns
+    /// - `Vec3` - the blended RGB color of the three projections
+    pub fn color(&self, point: &Vec3, normal: &Vec3) -> Vec3 {
+        let weights = glm::vec3(
+            normal.x.abs().powf(self.sharpness),
+            normal.y.abs().powf(self.sharpness),
+            normal.z.abs().powf(self.sharpness),
+        );
+        let weights = weights / (weights.x + weights.y + weights.z);
+        let p = point / self.scale;
+        // each projection drops the axis it looks along
+        let projections = [(p.z, p.y), (p.x, p.z), (p.x, p.y)];
+        let mut color = glm::vec3(0.0, 0.0, 0.0);
+        for (axis, texture_coordinates) in projections.iter().enumerate() {
+            // a face turned away from an axis takes nothing from its
+            // projection, so the lookup is skipped
+            if weights[axis] > 0.0 {
+                color += weights[axis] * self.texture.color(*texture_coordinates);
+            }
+        }
+        color
+    }
+}
+
+/// An equirectangular (latitude-longitude) image of the surroundings, which
+/// can be turned about the vertical axis to move features like the sun
+/// without editing the image.
+#[derive(Clone, Copy)]
+pub struct EnvironmentMap {
+    /// The image, with longitude across and latitude up
+    texture: ImageTexture,
+    /// Rotation of the environment from its orientation in the image
+    rotation: glm::Mat3,
+}
+
+impl EnvironmentMap {
+    /// Load an environment map from an equirectangular image file. The
+    /// center of the image is seen looking down the -z axis.
+    ///
+    /// # Arguments
+    /// - `filename: &str` - path to the image
+    ///
+    /// # Returns
+    /// - the environment map, unrotated
+    pub fn new(filename: &str) -> EnvironmentMap {
+        EnvironmentMap {
+            // the pixels are taken as linear radiance, as they always have
+            // been, so a map's brightness sets how strongly it lights
+            texture: ImageTexture::new(filename, ColorSpace::Linear),
+            rotation: glm::Mat3::identity(),
+        }
+    }
+
+    /// Turn the environment by an angle about the y axis, counterclockwise
+    /// when seen from above.
+    ///
+    /// # Arguments
+    /// - `degrees: f32` - angle to turn by
+    ///
+    /// # Returns
+    /// - the rotated environment map
+    pub fn with_yaw(self, degrees: f32) -> EnvironmentMap {
+        // whole turns are dropped first so they give exactly the same image
+        let angle = degrees.rem_euclid(360.0).to_radians();
+        let yaw = glm::mat4_to_mat3(&glm::rotation(angle, &glm::vec3(0.0, 1.0, 0.0)));
+        self.with_rotation(yaw)
+    }
+
+    /// Apply any rotation to the environment, after its current rotation.
+    ///
+    /// # Arguments
+    /// - `rotation: glm::Mat3` - orthonormal rotation matrix
+    ///
+    /// # Returns
+    /// - the rotated environment map
+    pub fn with_rotation(self, rotation: glm::Mat3) -> EnvironmentMap {
+        EnvironmentMap {
+            rotation: rotation * self.rotation,
+            ..self
+        }
+    }
+
+    /// Look up the color of the environment in a direction.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `direction: &Vec3` - normalized direction to look in
+    ///
+    /// # Returns
+    /// - `Vec3` - the RGB color seen in that direction
+    pub fn color(&self, direction: &Vec3) -> Vec3 {
+        // undo the rotation to find the direction in the image's orientation
+        let direction = self.rotation.transpose() * direction;
+        let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * std::f32::consts::PI);
+        let v = 0.5 + direction.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI;
+        self.texture.color((u, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mid_gray_decodes_darker_as_srgb_than_as_linear() {
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_mid_gray_{}.png", std::process::id()));
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([128, 128, 128, 128]))
+            .save(&path)
+            .unwrap();
+        let srgb = ImageTexture::new(path.to_str().unwrap(), ColorSpace::Srgb);
+        let linear = ImageTexture::new(path.to_str().unwrap(), ColorSpace::Linear);
+        std::fs::remove_file(&path).unwrap();
+
+        let stored = 128.0 / 255.0;
+        for channel in 0..3 {
+            assert!((srgb.color((0.5, 0.5))[channel] - 0.2159).abs() < 1e-3);
+            assert!((linear.color((0.5, 0.5))[channel] - stored).abs() < 1e-6);
+        }
+        // alpha is linear either way
+        assert!((srgb.alpha((0.5, 0.5)) - stored).abs() < 1e-6);
+        assert!((linear.alpha((0.5, 0.5)) - stored).abs() < 1e-6);
+    }
+
+    #[test]
+    fn higher_sharpness_narrows_the_triplanar_blend() {
+        // black on the left half and white on the right, placed so the x
+        // projection sees black and the y projection white
+        let path = std::env::temp_dir().join(format!(
+            "ray_tracer_triplanar_halves_{}.png",
+            std::process::id()
+        ));
+        let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 0, image::Rgba([255, 255, 255, 255]));
+        image.save(&path).unwrap();
+        let soft = TriplanarTexture::new(path.to_str().unwrap(), 1.0);
+        let sharp = soft.with_sharpness(8.0);
+        std::fs::remove_file(&path).unwrap();
+
+        let point = glm::vec3(0.75, 0.0, 0.25);
+        // how many degrees of the turn from facing x to facing y are
+        // visibly blended between the two projections
+        let blended_angles = |texture: &TriplanarTexture| {
+            (0..=90)
+                .filter(|degrees| {
+                    let angle = (*degrees as f32).to_radians();
+                    let normal = glm::vec3(angle.cos(), angle.sin(), 0.0);
+                    let white = texture.color(&point, &normal).x;
+                    white > 0.05 && white < 0.95
+                })
+                .count()
+        };
+        for texture in [soft, sharp].iter() {
+            let facing_x = texture.color(&point, &glm::vec3(1.0, 0.0, 0.0));
+            let facing_y = texture.color(&point, &glm::vec3(0.0, 1.0, 0.0));
+            let halfway = glm::normalize(&glm::vec3(1.0, 1.0, 0.0));
+            assert!(facing_x.x.abs() < 1e-6 && (facing_y.x - 1.0).abs() < 1e-6);
+            assert!((texture.color(&point, &halfway).x - 0.5).abs() < 1e-4);
+        }
+        let (soft_angles, sharp_angles) = (blended_angles(&soft), blended_angles(&sharp));
+        assert!(
+            sharp_angles * 2 < soft_angles,
+            "{} {}",
+            sharp_angles,
+            soft_angles
+        );
+    }
+}
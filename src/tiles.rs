@@ -0,0 +1,181 @@
+use glm::Vec3;
+use std::ops::Range;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Width and height in pixels of the tiles handed to a tile callback, apart
+/// from those cut short by the right and bottom edges of the image, or by
+/// the edges of the band of rows being rendered
+pub const TILE_SIZE: u32 = 32;
+
+/// A rectangle of pixels in an image, from its top left corner.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileRect {
+    /// Column of the leftmost pixels
+    pub x: u32,
+    /// Row of the top pixels
+    pub y: u32,
+    /// Number of columns
+    pub width: u32,
+    /// Number of rows
+    pub height: u32,
+}
+
+/// Called with each tile of an image once every pixel in it is finished,
+/// and the tile's pixels, row-major. It may be called from any thread.
+pub type TileCallback<'a> = &'a (dyn Fn(TileRect, &[Vec3]) + Sync);
+
+/// One tile's pixels, gathered as they finish.
+struct Tile {
+    /// Where the tile is in the image
+    rect: TileRect,
+    /// Pixels finished so far, row-major, black until finished
+    pixels: Mutex<Vec<Vec3>>,
+    /// Number of pixels still being rendered
+    remaining: AtomicUsize,
+}
+
+/// Gathers finished pixels into tiles and hands each tile to a callback as
+/// soon as its last pixel is finished, so a caller can stream the image to
+/// a viewer while it renders. Pixels are still rendered in any order, so the
+/// tiles finish in no particular order either.
+pub struct TileTracker<'a> {
+    /// Width of the image in pixels
+    image_width: u32,
+    /// First row of the image being rendered
+    first_row: u32,
+    /// Tiles of the rows being rendered, row-major
+    tiles: Vec<Tile>,
+    /// What to do with each finished tile
+    on_tile_complete: TileCallback<'a>,
+}
+
+impl<'a> TileTracker<'a> {
+    /// Split the rows of an image being rendered into tiles with no pixels
+    /// finished. Tiles line up with the top of the image whichever rows are
+    /// rendered, so a band of rows gets the same tiles as a whole image,
+    /// cut short at the band's edges.
+    ///
+    /// # Arguments
+    /// - `image_width: u32` - width of the image in pixels
+    /// - `rows: Range<u32>` - rows being rendered, `0..height` for all of them
+    /// - `on_tile_complete: TileCallback` - what to do with each finished tile
+    ///
+    /// # Returns
+    /// - `TileTracker` - the tracker
+    pub fn new(
+        image_width: u32,
+        rows: Range<u32>,
+        on_tile_complete: TileCallback<'a>,
+    ) -> TileTracker<'a> {
+        let mut tiles = Vec::new();
+        let first_tile_row = rows.start - rows.start % TILE_SIZE;
+        for tile_row in (first_tile_row..rows.end).step_by(TILE_SIZE as usize) {
+            let y = tile_row.max(rows.start);
+            for x in (0..image_width).step_by(TILE_SIZE as usize) {
+                let rect = TileRect {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(image_width - x),
+                    height: (tile_row + TILE_SIZE).min(rows.end) - y,
+                };
+                let pixel_count = (rect.width * rect.height) as usize;
+                tiles.push(Tile {
+                    rect,
+                    pixels: Mutex::new(vec![glm::vec3(0.0, 0.0, 0.0); pixel_count]),
+                    remaining: AtomicUsize::new(pixel_count),
+                });
+            }
+        }
+        TileTracker {
+            image_width,
+            first_row: rows.start,
+            tiles,
+            on_tile_complete,
+        }
+    }
+
+    /// Record a finished pixel, and hand its tile to the callback if it was
+    /// the tile's last. Safe to call from any thread, once for each pixel in
+    /// the rows being rendered.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `x: u32` - column of the pixel
+    /// - `y: u32` - row of the pixel
+    /// - `color: &Vec3` - the pixel's color
+    pub fn finish_pixel(&self, x: u32, y: u32, color: &Vec3) {
+        let tiles_across = self.image_width.div_ceil(TILE_SIZE);
+        let tile_row = y / TILE_SIZE - self.first_row / TILE_SIZE;
+        let tile = &self.tiles[(tile_row * tiles_across + x / TILE_SIZE) as usize];
+        let offset = ((y - tile.rect.y) * tile.rect.width + x - tile.rect.x) as usize;
+        tile.pixels.lock().unwrap()[offset] = *color;
+        // the thread which takes the count to zero is the only one to see
+        // it, so the callback fires once per tile
+        if tile.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+            (self.on_tile_complete)(tile.rect, &tile.pixels.lock().unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+
+    /// A color naming the pixel it belongs to.
+    fn pixel_color(x: u32, y: u32) -> Vec3 {
+        glm::vec3(x as f32, y as f32, 1.0)
+    }
+
+    #[test]
+    fn every_tile_fires_once_and_they_cover_the_rows_exactly() {
+        let (width, height) = (70, 45);
+        for rows in [0..height, 10..45, 40..41] {
+            let finished = Mutex::new(Vec::new());
+            let callback = |rect: TileRect, pixels: &[Vec3]| {
+                finished.lock().unwrap().push((rect, pixels.to_vec()));
+            };
+            let tracker = TileTracker::new(width, rows.clone(), &callback);
+            // pixels finish out of order and on many threads
+            let mut pixels: Vec<(u32, u32)> = rows
+                .clone()
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .collect();
+            pixels.reverse();
+            pixels
+                .par_iter()
+                .for_each(|&(x, y)| tracker.finish_pixel(x, y, &pixel_color(x, y)));
+
+            let tile_count = tracker.tiles.len();
+            drop(tracker);
+            let finished = finished.into_inner().unwrap();
+            assert_eq!(finished.len(), tile_count);
+            let mut covered = vec![0; (width * height) as usize];
+            for (rect, pixels) in finished.iter() {
+                assert!(rect.width <= TILE_SIZE && rect.height <= TILE_SIZE);
+                // each tile's pixels were all finished when it fired
+                for y in rect.y..rect.y + rect.height {
+                    for x in rect.x..rect.x + rect.width {
+                        covered[(y * width + x) as usize] += 1;
+                        let offset = ((y - rect.y) * rect.width + x - rect.x) as usize;
+                        assert_eq!(pixels[offset], pixel_color(x, y));
+                    }
+                }
+            }
+            for y in 0..height {
+                for x in 0..width {
+                    let expected = rows.contains(&y) as usize;
+                    assert_eq!(
+                        covered[(y * width + x) as usize],
+                        expected,
+                        "({}, {})",
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+}
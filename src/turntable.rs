@@ -0,0 +1,130 @@
+use super::render;
+use super::EPSILON;
+use super::MAX_HIT_DISTANCE;
+use crate::camera::perspective_camera::PerspectiveCamera;
+use crate::camera::Camera;
+use crate::color;
+use crate::hittable::Hittable;
+use crate::light::Light;
+use crate::scenes::Sky;
+use crate::settings::RenderSettings;
+use glm::Vec3;
+use image::codecs::gif::GifEncoder;
+use image::codecs::gif::Repeat;
+use image::Delay;
+use image::Frame;
+use image::RgbaImage;
+
+/// Turntable frames are previews, so they are rendered with at most this
+/// anti-aliasing level and a single path per position.
+const TURNTABLE_AA_LEVEL: usize = 4;
+/// Time each frame of the animation is shown for, in milliseconds
+const FRAME_DELAY_MS: u32 = 80;
+
+/// Render frames of the camera orbiting the scene and encode them into an
+/// animated GIF. The camera circles the center of the world's bounding box
+/// at its current height, pulling back if needed so the whole box stays in
+/// view. Worlds without a finite bounding box (only planes, say) are
+/// orbited around whatever is in the middle of the frame.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &mut PerspectiveCamera` - camera to orbit, left at the last frame
+/// - `lights: &[Light]` - light sources for the scene
+/// - `sky: &Sky` - background of the scene
+/// - `settings: &RenderSettings` - image size, output name, and sample counts
+/// - `frame_count: usize` - number of frames in one full orbit
+pub fn render_turntable<T: Hittable + Sync>(
+    world: &T,
+    camera: &mut PerspectiveCamera,
+    lights: &[Light],
+    sky: &Sky,
+    settings: &RenderSettings,
+    frame_count: usize,
+) {
+    let mut frame_settings = settings.clone();
+    frame_settings.aa_level = settings.aa_level.min(TURNTABLE_AA_LEVEL);
+    frame_settings.path_samples = 1;
+    frame_settings.preview_interval = None;
+
+    let vertical_fov = camera.vertical_fov();
+    let aspect_ratio = camera.aspect_ratio();
+    let (center, start) = orbit_start(world, camera, vertical_fov, aspect_ratio);
+    let up = glm::vec3(0.0, 1.0, 0.0);
+
+    let mut frames = Vec::with_capacity(frame_count);
+    for frame in 0..frame_count {
+        println!("frame {} of {}", frame + 1, frame_count);
+        let angle = 2.0 * std::f32::consts::PI * frame as f32 / frame_count as f32;
+        let position = center + glm::rotate_y_vec3(&start, angle);
+        camera.move_camera(position, center, up, vertical_fov, aspect_ratio);
+
+        let buffers = render(world, camera, lights, sky, &frame_settings);
+        let mut img = RgbaImage::new(settings.image_width, settings.image_height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let index = (y * settings.image_width + x) as usize;
+            let rgb = if settings.dither {
+                color::vec3_to_rgb_dithered(&buffers.color[index], 1, x, y)
+            } else {
+                color::vec3_to_rgb(&buffers.color[index], 1)
+            };
+            *pixel = image::Rgba([rgb[0], rgb[1], rgb[2], 255]);
+        }
+        frames.push(Frame::from_parts(
+            img,
+            0,
+            0,
+            Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1),
+        ));
+    }
+
+    let file = std::fs::File::create(&settings.gif).unwrap();
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).unwrap();
+    encoder.encode_frames(frames).unwrap();
+}
+
+/// Find the point to orbit around and the camera's starting offset from it.
+///
+/// # Arguments
+/// - `world: &T` - objects that compose our scene
+/// - `camera: &PerspectiveCamera` - camera at the start of the orbit
+/// - `vertical_fov: f32` - vertical field of view of the camera in degrees
+/// - `aspect_ratio: f32` - aspect ratio of the camera
+///
+/// # Returns
+/// - tuple of
+///     - `Vec3` - center of the orbit
+///     - `Vec3` - offset from the center to the first camera position
+fn orbit_start<T: Hittable>(
+    world: &T,
+    camera: &PerspectiveCamera,
+    vertical_fov: f32,
+    aspect_ratio: f32,
+) -> (Vec3, Vec3) {
+    let bounds = world.bounding_box().filter(|bounds| {
+        bounds.minimum_point.iter().all(|x| x.is_finite())
+            && bounds.maximum_point.iter().all(|x| x.is_finite())
+    });
+    match bounds {
+        Some(bounds) => {
+            let center = 0.5 * (bounds.minimum_point + bounds.maximum_point);
+            let radius = 0.5 * glm::distance(&bounds.minimum_point, &bounds.maximum_point);
+            // the narrower of the two fields of view has to fit the bounding sphere
+            let half_fov = (vertical_fov.to_radians() / 2.0)
+                .min(((vertical_fov.to_radians() / 2.0).tan() * aspect_ratio).atan());
+            let offset = camera.origin - center;
+            let distance = glm::length(&offset).max(radius / half_fov.sin());
+            (center, glm::normalize(&offset) * distance)
+        }
+        None => {
+            // orbit whatever is in the middle of the frame
+            let view = camera.get_ray(0.5, 0.5);
+            let distance = world
+                .hit(&view, EPSILON, MAX_HIT_DISTANCE)
+                .map_or(1.0, |hit| hit.distance);
+            let center = view.at(distance);
+            (center, camera.origin - center)
+        }
+    }
+}
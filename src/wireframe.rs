@@ -0,0 +1,152 @@
+use crate::camera::Camera;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use glm::Vec3;
+
+/// Lines drawn along the edges of every triangle in the image, for checking
+/// the topology of meshes. Unlike the `Wireframe` material, the lines keep
+/// the same width on screen however near or far the triangles are, and they
+/// are drawn over any scene without changing its materials.
+pub struct WireframeOverlay {
+    /// Width of the lines in pixels. Each triangle draws its half of the
+    /// line inside its own edge, so an edge between two triangles gets the
+    /// whole width and an outline edge half of it.
+    pub line_width: f32,
+    /// Distance between neighboring pixels in the camera's `u` coordinate
+    pub pixel_u: f32,
+    /// Distance between neighboring pixels in the camera's `v` coordinate
+    pub pixel_v: f32,
+}
+
+impl WireframeOverlay {
+    /// Create an overlay for an image, where the camera's coordinates run
+    /// from zero at one edge of the image to one at the other, see
+    /// `crate::camera::pixel_to_camera`.
+    ///
+    /// # Arguments
+    /// - `line_width: f32` - width of the lines in pixels
+    /// - `image_width: u32` - width of the image in pixels
+    /// - `image_height: u32` - height of the image in pixels
+    ///
+    /// # Returns
+    /// - `WireframeOverlay` - the overlay
+    pub fn new(line_width: f32, image_width: u32, image_height: u32) -> WireframeOverlay {
+        WireframeOverlay {
+            line_width,
+            pixel_u: 1.0 / image_width as f32,
+            pixel_v: 1.0 / image_height as f32,
+        }
+    }
+
+    /// Whether a line covers the point of the image at camera coordinates
+    /// (u, v). The size of a pixel on the surface seen there is found where
+    /// the rays through the neighboring pixels meet the plane of its
+    /// triangle, and the point is on a line when it is closer to an edge
+    /// than half the line width at that size.
+    ///
+    /// # Arguments
+    /// - self reference
+    /// - `world: &T` - objects that compose our scene
+    /// - `camera: &C` - camera to generate rays from
+    /// - `u: f32` - horizontal camera coordinate of the point
+    /// - `v: f32` - vertical camera coordinate of the point
+    /// - `min_distance: f32` - closest distance along a camera ray to accept a hit
+    /// - `max_distance: f32` - furthest distance along a camera ray to accept a hit
+    ///
+    /// # Returns
+    /// - `bool` - true if the point should be drawn as a line
+    pub fn covers<T: Hittable, C: Camera>(
+        &self,
+        world: &T,
+        camera: &C,
+        u: f32,
+        v: f32,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> bool {
+        let hit = match world.hit(&camera.get_ray(u, v), min_distance, max_distance) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let edge_distances = match hit.edge_distances {
+            Some(edge_distances) => edge_distances,
+            None => return false,
+        };
+        let across = on_plane_of(&hit, &camera.get_ray(u + self.pixel_u, v)) - hit.hit_point;
+        let up = on_plane_of(&hit, &camera.get_ray(u, v + self.pixel_v)) - hit.hit_point;
+        let pixel_size = glm::length(&glm::cross(&across, &up)).sqrt();
+        // a triangle seen edge on has no size to measure lines against
+        pixel_size.is_finite()
+            && glm::comp_min(&edge_distances) < 0.5 * self.line_width * pixel_size
+    }
+}
+
+/// Find where a ray's line meets the plane of the surface hit.
+///
+/// # Arguments
+/// - `hit: &HitRecord` - the hit whose plane to meet
+/// - `ray: &Ray` - the ray to follow
+///
+/// # Returns
+/// - `Vec3` - the point on the plane, with infinite or NaN components if
+///   the ray is parallel to it
+fn on_plane_of(hit: &HitRecord, ray: &Ray) -> Vec3 {
+    let normal = &hit.geometric_normal;
+    let distance =
+        glm::dot(&(hit.hit_point - ray.origin), normal) / glm::dot(&ray.direction, normal);
+    ray.at(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::perspective_camera::PerspectiveCamera;
+    use crate::hittable::hittable_list::HittableList;
+    use crate::material::lambertian::Lambertian;
+    use crate::material::MaterialType;
+
+    #[test]
+    fn overlay_covers_a_triangles_edges_but_not_its_inside() {
+        let corners = [
+            glm::vec3(-1.0, -1.0, -2.0),
+            glm::vec3(1.0, -1.0, -2.0),
+            glm::vec3(0.0, 1.0, -2.0),
+        ];
+        let gray = MaterialType::Lambertian(Lambertian {
+            albedo: glm::vec3(0.5, 0.5, 0.5),
+            force_front_face: false,
+        });
+        let mut world = HittableList::new();
+        world.add_triangle(corners, gray);
+        // a 90 degree view, so the plane of the triangle is 4 units across
+        // the image and a pixel there is 1/16 of a unit
+        let size = 64;
+        let camera = PerspectiveCamera::new(
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, -1.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+        );
+        let overlay = WireframeOverlay::new(2.0, size, size);
+        let covers = |point: Vec3| {
+            let (u, v) = (0.5 + point.x / 4.0, 0.5 + point.y / 4.0);
+            overlay.covers(&world, &camera, u, v, 1e-4, f32::INFINITY)
+        };
+
+        let centroid = (corners[0] + corners[1] + corners[2]) / 3.0;
+        assert!(!covers(centroid));
+        for edge in 0..3 {
+            let midpoint = (corners[edge] + corners[(edge + 1) % 3]) / 2.0;
+            let inward = glm::normalize(&(centroid - midpoint));
+            // half a pixel inside the edge is on its line, which is one
+            // pixel wide on this side
+            assert!(covers(midpoint + inward / 32.0), "edge {}", edge);
+            // five pixels inside isn't
+            assert!(!covers(midpoint + inward * 5.0 / 16.0), "edge {}", edge);
+        }
+        // nothing is drawn off the triangle
+        assert!(!covers(glm::vec3(1.5, 1.5, -2.0)));
+    }
+}